@@ -6,7 +6,7 @@ use concordium_contracts_common::{
 };
 use proc_macro::TokenStream;
 use proc_macro2::{Group, Punct, Spacing, Span};
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 use std::str::FromStr;
 use syn::LitStr;
 
@@ -132,6 +132,108 @@ fn module_ref_worker(str: String, span: Span) -> syn::Result<TokenStream> {
     Ok(quote!(concordium_std::ModuleReference::new(#module_ref)).into())
 }
 
+fn state_struct_worker(item: TokenStream) -> syn::Result<TokenStream> {
+    let ast: syn::DeriveInput = syn::parse(item)?;
+    let fields = match &ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new(
+                ast.ident.span(),
+                "StateStruct can only be derived for structs with named fields",
+            ))
+        }
+    };
+    let name = &ast.ident;
+    let field_idents: Vec<_> = fields.iter().filter_map(|field| field.ident.as_ref()).collect();
+    let field_types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let (_, ty_generics, _) = ast.generics.split_for_impl();
+
+    let mut serial_generics = ast.generics.clone();
+    let serial_where = serial_generics.make_where_clause();
+    for ty in field_types.iter() {
+        serial_where.predicates.push(syn::parse_quote!(#ty: concordium_std::StateStructField));
+    }
+    let (serial_impl_generics, _, serial_where) = serial_generics.split_for_impl();
+
+    let state_ident = format_ident!("__S");
+    let mut deserial_generics = ast.generics.clone();
+    deserial_generics.params.push(syn::parse_quote!(#state_ident: concordium_std::HasStateApi));
+    let deserial_where = deserial_generics.make_where_clause();
+    for ty in field_types.iter() {
+        deserial_where.predicates.push(
+            syn::parse_quote!(#ty: concordium_std::StateStructField<StateApi = #state_ident>),
+        );
+    }
+    let (deserial_impl_generics, _, deserial_where) = deserial_generics.split_for_impl();
+
+    let mut delete_generics = ast.generics.clone();
+    let delete_where = delete_generics.make_where_clause();
+    for ty in field_types.iter() {
+        delete_where.predicates.push(syn::parse_quote!(#ty: concordium_std::Deletable));
+    }
+    let (delete_impl_generics, _, delete_where) = delete_generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #serial_impl_generics concordium_std::Serial for #name #ty_generics #serial_where {
+            fn serial<W: concordium_std::Write>(&self, out: &mut W) -> core::result::Result<(), W::Err> {
+                #(concordium_std::StateStructField::store_field(&self.#field_idents, out)?;)*
+                Ok(())
+            }
+        }
+
+        #[automatically_derived]
+        impl #deserial_impl_generics concordium_std::DeserialWithState<#state_ident> for #name #ty_generics #deserial_where {
+            fn deserial_with_state<R: concordium_std::Read>(state: &#state_ident, source: &mut R) -> concordium_std::ParseResult<Self> {
+                Ok(#name {
+                    #(#field_idents: concordium_std::StateStructField::open_field(state.clone(), source)?,)*
+                })
+            }
+        }
+
+        #[automatically_derived]
+        impl #delete_impl_generics concordium_std::Deletable for #name #ty_generics #delete_where {
+            fn delete(self) {
+                #(concordium_std::Deletable::delete(self.#field_idents);)*
+            }
+        }
+    }
+    .into())
+}
+
+/// Derive macro for a contract state which stores each of its fields under a
+/// separate key in the contract state, instead of serializing the whole state
+/// into the root entry.
+///
+/// The macro can only be used on structs with named fields, where the type of
+/// every field is a `concordium_std::StateField`. Fields are loaded lazily on
+/// first access, and only fields which have been accessed mutably are written
+/// back when the struct is stored. Each field has its own location in the
+/// state, allocated when it is created with `StateBuilder::new_field`, and the
+/// struct is serialized as the locations of its fields.
+///
+/// The macro derives `Serial`, `DeserialWithState` and `Deletable`, which
+/// means that the struct can be used as the state of a contract, and nested
+/// in other state, such as the values of a `StateMap`.
+///
+/// ```ignore
+/// use concordium_std::*;
+/// use concordium_std_derive::StateStruct;
+///
+/// #[derive(StateStruct)]
+/// struct State<S = StateApi> {
+///     owner:   StateField<AccountAddress, S>,
+///     counter: StateField<u64, S>,
+/// }
+/// ```
+#[proc_macro_derive(StateStruct)]
+pub fn state_struct(item: TokenStream) -> TokenStream {
+    unwrap_or_report(state_struct_worker(item))
+}
+
 /// Procedural macro for instantiating account addresses.
 /// Input must be a valid base58-encoding.
 #[proc_macro]
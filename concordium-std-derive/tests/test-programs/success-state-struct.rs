@@ -0,0 +1,38 @@
+//! Ensure that deriving `StateStruct` generates compilable code.
+
+use concordium_std::*;
+use concordium_std_derive::StateStruct;
+
+#[derive(Serialize)]
+struct Config {
+    owner: AccountAddress,
+    fee:   Amount,
+}
+
+#[derive(StateStruct)]
+struct State<S = StateApi> {
+    config:   StateField<Config, S>,
+    counter:  StateField<u64, S>,
+    balances: StateField<StateMap<AccountAddress, Amount, S>, S>,
+    r#type:   StateField<u8, S>,
+}
+
+#[derive(StateStruct)]
+struct ConcreteState {
+    counter: StateField<u64>,
+}
+
+/// A state struct nested in another state struct.
+#[derive(StateStruct)]
+struct Outer<S = StateApi> {
+    inner:  StateField<State<S>, S>,
+    by_key: StateField<StateMap<u8, State<S>, S>, S>,
+}
+
+fn assert_contract_state<T: Serial + DeserialWithState<StateApi> + Deletable>() {}
+
+fn main() {
+    assert_contract_state::<State>();
+    assert_contract_state::<ConcreteState>();
+    assert_contract_state::<Outer>();
+}
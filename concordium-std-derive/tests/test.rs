@@ -18,5 +18,6 @@ fn tests() {
 
     let t = trybuild::TestCases::new();
     t.pass("tests/test-programs/success.rs");
+    t.pass("tests/test-programs/success-state-struct.rs");
     t.compile_fail("tests/test-programs/fail-*.rs");
 }
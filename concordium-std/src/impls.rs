@@ -8,7 +8,8 @@ use crate::{
     mem::{self, MaybeUninit},
     num,
     num::NonZeroU32,
-    prims, state_btree,
+    prims, reentrancy_guard, state_binary_heap, state_btree, state_cached, state_counted,
+    state_quota, state_struct, state_vec, state_versioned,
     traits::*,
     types::*,
     vec::Vec,
@@ -182,6 +183,42 @@ impl From<QueryContractBalanceError> for Reject {
     }
 }
 
+/// Quota exceeded error is i32::MIN + 27.
+impl From<state_quota::QuotaExceededError> for Reject {
+    #[inline(always)]
+    fn from(_: state_quota::QuotaExceededError) -> Self {
+        unsafe { crate::num::NonZeroI32::new_unchecked(i32::MIN + 27).into() }
+    }
+}
+
+/// UnsupportedVersion is i32::MIN + 28,
+/// MalformedState is i32::MIN + 29,
+/// InvalidState is i32::MIN + 30.
+impl From<state_versioned::MigrationError> for Reject {
+    #[inline(always)]
+    fn from(me: state_versioned::MigrationError) -> Self {
+        match me {
+            state_versioned::MigrationError::UnsupportedVersion => unsafe {
+                crate::num::NonZeroI32::new_unchecked(i32::MIN + 28).into()
+            },
+            state_versioned::MigrationError::MalformedState => unsafe {
+                crate::num::NonZeroI32::new_unchecked(i32::MIN + 29).into()
+            },
+            state_versioned::MigrationError::InvalidState => unsafe {
+                crate::num::NonZeroI32::new_unchecked(i32::MIN + 30).into()
+            },
+        }
+    }
+}
+
+/// Reentrancy error is i32::MIN + 31.
+impl From<reentrancy_guard::ReentrancyError> for Reject {
+    #[inline(always)]
+    fn from(_: reentrancy_guard::ReentrancyError) -> Self {
+        unsafe { crate::num::NonZeroI32::new_unchecked(i32::MIN + 31).into() }
+    }
+}
+
 /// Return values are intended to be produced by writing to the
 /// [ExternReturnValue] buffer, either in a high-level interface via
 /// serialization, or in a low-level interface by manually using the [Write]
@@ -570,6 +607,7 @@ where
             key,
             key_bytes,
             state_api,
+            counter: None,
             _lifetime_marker: PhantomData,
         }
     }
@@ -588,6 +626,9 @@ where
         let mut state_entry = self.state_api.create_entry(&self.key_bytes).unwrap_abort();
         value.serial(&mut state_entry).unwrap_abort();
         state_entry.move_to_start(); // Reset cursor.
+        if let Some(counter) = self.counter {
+            *counter += 1;
+        }
         OccupiedEntry {
             key: self.key,
             value,
@@ -775,6 +816,14 @@ where
 /// "allocator"/state builder stores "next location". The values stored at this
 /// location are 64-bit integers.
 const NEXT_ITEM_PREFIX_KEY: [u8; 8] = 0u64.to_le_bytes();
+/// The location at which the version of the layout of the contract state is
+/// stored, see [`VersionedState`](crate::VersionedState). The value stored at
+/// this location is a 32-bit integer.
+const STATE_VERSION_KEY: [u8; 9] = [0, 0, 0, 0, 0, 0, 0, 0, 1];
+/// The location of the lock held by a
+/// [`ReentrancyGuard`](crate::ReentrancyGuard). The entry is empty, and only
+/// exists while the lock is held.
+pub(crate) const REENTRANCY_LOCK_KEY: [u8; 9] = [0, 0, 0, 0, 0, 0, 0, 0, 2];
 /// Initial location to store in [NEXT_ITEM_PREFIX_KEY]. For example, the
 /// initial call to "new_state_box" will allocate the box at this location.
 pub(crate) const INITIAL_NEXT_ITEM_PREFIX: [u8; 8] = 2u64.to_le_bytes();
@@ -1038,13 +1087,55 @@ where
     }
 
     /// Serializes the key and prepends the unique map prefix to it.
-    fn key_with_map_prefix(&self, key: &K) -> Vec<u8> {
+    pub(crate) fn key_with_map_prefix(&self, key: &K) -> Vec<u8> {
         let mut key_with_prefix = self.prefix.to_vec();
         key.serial(&mut key_with_prefix).unwrap_abort();
         key_with_prefix
     }
 }
 
+/// Get the sum of the sizes of the keys and values of all the entries in the
+/// subtree with the given prefix.
+fn subtree_size<S: HasStateApi>(state_api: &S, prefix: &[u8]) -> u64 {
+    match state_api.iterator(prefix) {
+        Ok(mut state_iter) => {
+            let mut size = 0u64;
+            for entry in state_iter.by_ref() {
+                // Unwrapping is safe since the entry exists.
+                size += entry.get_key().len() as u64 + u64::from(entry.size().unwrap_abort());
+            }
+            state_api.clone().delete_iterator(state_iter);
+            size
+        }
+        Err(StateError::SubtreeWithPrefixNotFound) => 0,
+        _ => crate::trap(),
+    }
+}
+
+/// The size helpers are only available for maps with flat values, i.e., values
+/// implementing [Serialize]. Nested state, such as values which are a
+/// [StateBox] or [StateMap], is stored separately, and its size would not be
+/// included.
+impl<K, V, S> StateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serialize,
+{
+    /// Get the number of bytes of the serialized value with the given key in
+    /// the state. Return [None] if there is no value with the given key.
+    pub fn entry_size(&self, key: &K) -> Option<u32> {
+        let k = self.key_with_map_prefix(key);
+        // Unwrapping is safe since the entry exists.
+        self.state_api.lookup_entry(&k).map(|entry| entry.size().unwrap_abort())
+    }
+
+    /// Get the number of bytes used by the map in the state, i.e., the sum of
+    /// the sizes of the keys and values of all entries. This iterates over
+    /// all the entries of the map.
+    pub fn size_in_state(&self) -> u64 { subtree_size(&self.state_api, &self.prefix) }
+}
+
 impl<'a, K, V, S: HasStateApi> Drop for StateMapIter<'a, K, V, S> {
     fn drop(&mut self) {
         // Delete the iterator to unlock the subtree.
@@ -1289,6 +1380,11 @@ where
     /// Returns `true` if the set contains no elements.
     pub fn is_empty(&self) -> bool { self.state_api.lookup_entry(&self.prefix).is_none() }
 
+    /// Get the number of bytes used by the set in the state, i.e., the sum of
+    /// the sizes of all the keys. This iterates over all the elements of the
+    /// set.
+    pub fn size_in_state(&self) -> u64 { subtree_size(&self.state_api, &self.prefix) }
+
     /// Returns `true` if the set contains a value.
     pub fn contains(&self, value: &T) -> bool {
         let key_bytes = self.key_with_set_prefix(value);
@@ -1544,6 +1640,36 @@ where
     }
 }
 
+impl<T, S> StateBox<T, S>
+where
+    T: Serialize,
+    S: HasStateApi,
+{
+    /// Get the number of bytes of the serialized value in the state. If the
+    /// value has been modified in memory, then this is the size of the
+    /// modified value, which will be stored when the box is dropped.
+    ///
+    /// Only available for flat values, i.e., values implementing [Serialize].
+    /// Nested state, such as a [StateMap], is stored separately, and its size
+    /// would not be included.
+    pub fn size_in_state(&self) -> u32 {
+        match unsafe { &*self.inner.get() } {
+            StateBoxInner::Loaded {
+                modified: true,
+                value,
+                ..
+            } => to_bytes(value).len() as u32,
+            StateBoxInner::Loaded {
+                entry,
+                ..
+            } => entry.size().unwrap_abort(),
+            StateBoxInner::Reference {
+                prefix,
+            } => self.state_api.lookup_entry(prefix).unwrap_abort().size().unwrap_abort(),
+        }
+    }
+}
+
 impl<T: Serial, S: HasStateApi> Serial for StateBox<T, S> {
     fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
         out.write_all(self.get_location())
@@ -2333,6 +2459,114 @@ where
         StateSet::open(state_api, prefix)
     }
 
+    /// Create a new empty [`CountedStateMap`](crate::CountedStateMap).
+    pub fn new_counted_map<K, V>(&mut self) -> state_counted::CountedStateMap<K, V, S> {
+        state_counted::CountedStateMap {
+            map: self.new_map(),
+            len: 0,
+        }
+    }
+
+    /// Create a new empty [`CountedStateSet`](crate::CountedStateSet).
+    pub fn new_counted_set<T>(&mut self) -> state_counted::CountedStateSet<T, S> {
+        state_counted::CountedStateSet {
+            set: self.new_set(),
+            len: 0,
+        }
+    }
+
+    /// Create a new empty [`CachedStateMap`](crate::CachedStateMap).
+    pub fn new_cached_map<K, V>(&mut self) -> state_cached::CachedStateMap<K, V, S> {
+        state_cached::CachedStateMap::new(self.new_map())
+    }
+
+    /// Create a new empty [`QuotaStateMap`](crate::QuotaStateMap), where each
+    /// serialized value can be at most `limit` bytes. The values must be
+    /// flat, i.e., not contain nested state.
+    pub fn new_quota_map<K, V: Serialize>(
+        &mut self,
+        limit: u32,
+    ) -> state_quota::QuotaStateMap<K, V, S> {
+        state_quota::QuotaStateMap {
+            map: self.new_map(),
+            limit,
+        }
+    }
+
+    /// Create a [`MigratingStateMap`](crate::MigratingStateMap) for migrating
+    /// the values of `old` from the type `Old` to the type `New`.
+    pub fn new_migrating_map<K, Old, New>(
+        &mut self,
+        old: StateMap<K, Old, S>,
+    ) -> state_versioned::MigratingStateMap<K, Old, New, S> {
+        state_versioned::MigratingStateMap {
+            old,
+            new: self.new_map(),
+        }
+    }
+
+    /// Get the version of the layout of the contract state, see
+    /// [`VersionedState`](crate::VersionedState). This is `0` if no version
+    /// has been stored.
+    pub fn state_version(&self) -> u32 {
+        match self.state_api.lookup_entry(&STATE_VERSION_KEY) {
+            // Unwrapping is safe when using only the high-level API.
+            Some(mut entry) => entry.read_u32().unwrap_abort(),
+            None => 0,
+        }
+    }
+
+    /// Acquire the [`ReentrancyGuard`](crate::ReentrancyGuard) of the contract
+    /// instance, which prevents nested calls into the entrypoints acquiring
+    /// it. Return an error if the guard is already held, i.e., if this is a
+    /// reentrant call into such an entrypoint.
+    pub fn enter_non_reentrant(
+        &mut self,
+    ) -> Result<reentrancy_guard::ReentrancyGuard<S>, reentrancy_guard::ReentrancyError> {
+        if self.state_api.lookup_entry(&REENTRANCY_LOCK_KEY).is_some() {
+            return Err(reentrancy_guard::ReentrancyError);
+        }
+        // Unwrapping is safe since the lock is never part of a locked subtree.
+        self.state_api.create_entry(&REENTRANCY_LOCK_KEY).unwrap_abort();
+        Ok(reentrancy_guard::ReentrancyGuard {
+            state_api: self.state_api.clone(),
+        })
+    }
+
+    /// Store the version of the layout of the contract state, see
+    /// [`VersionedState`](crate::VersionedState). This should be called in the
+    /// init function of contracts with a versioned state.
+    pub fn write_state_version(&mut self, version: u32) {
+        let mut entry = self.state_api.create_entry(&STATE_VERSION_KEY).unwrap_abort();
+        entry.write_u32(version).unwrap_abort();
+    }
+
+    /// Create a new empty [`StateVec`](crate::StateVec).
+    pub fn new_vec<T>(&mut self) -> state_vec::StateVec<T, S> {
+        state_vec::StateVec {
+            elements: self.new_map(),
+            len:      0,
+        }
+    }
+
+    /// Create a new empty [`StateDeque`](crate::StateDeque).
+    pub fn new_deque<T>(&mut self) -> state_vec::StateDeque<T, S> {
+        state_vec::StateDeque {
+            elements: self.new_map(),
+            head:     0,
+            len:      0,
+        }
+    }
+
+    /// Create a new [`StateField`](crate::StateField) for a state struct
+    /// deriving `StateStruct`. The field is allocated its own location in
+    /// the state, and the `value` is written there when the struct is stored.
+    #[must_use]
+    pub fn new_field<T>(&mut self, value: T) -> state_struct::StateField<T, S> {
+        let (state_api, prefix) = self.new_state_container();
+        state_struct::StateField::new(value, state_api, prefix)
+    }
+
     /// Create a new [`StateBox`] and insert the `value` into the state.
     /// This stores the serialized value in the contract state. Thus **if the
     /// `StateBox` is dropped without calling [`delete`](StateBox::delete)
@@ -2403,6 +2637,16 @@ where
 }
 
 impl StateBuilder<StateApi> {
+    /// Create a new empty [`StateBinaryHeap`](crate::StateBinaryHeap).
+    pub fn new_binary_heap<K, V>(&mut self) -> state_binary_heap::StateBinaryHeap<K, V> {
+        state_binary_heap::StateBinaryHeap {
+            slots:     self.new_map(),
+            positions: self.new_map(),
+            values:    self.new_map(),
+            len:       0,
+        }
+    }
+
     /// Create a new empty [`StateBTreeSet`](crate::StateBTreeSet).
     pub fn new_btree_set<K>(&mut self) -> state_btree::StateBTreeSet<K> {
         let (state_api, prefix) = self.new_state_container();
@@ -3170,9 +3414,9 @@ mod tests {
 #[cfg(feature = "internal-wasm-test")]
 mod wasm_test {
     use crate::{
-        claim, claim_eq, concordium_test, to_bytes, Deletable, Deserial, DeserialWithState,
-        EntryRaw, HasStateApi, HasStateEntry, ParseResult, Serial, StateApi, StateBuilder,
-        StateError, StateMap, StateSet, INITIAL_NEXT_ITEM_PREFIX,
+        claim, claim_eq, concordium_test, to_bytes, Cursor, Deletable, Deserial, DeserialWithState,
+        EntryRaw, HasStateApi, HasStateEntry, ParseResult, Serial, StateApi, StateBox,
+        StateBuilder, StateError, StateMap, StateSet, INITIAL_NEXT_ITEM_PREFIX,
     };
 
     const GENERIC_MAP_PREFIX: u64 = 1;
@@ -3474,6 +3718,35 @@ mod wasm_test {
         claim_eq!(*statebox.get(), boxed_value);
     }
 
+    #[concordium_test]
+    fn statebox_size_in_state() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut statebox = state_builder.new_box(String::from("ab"));
+        // 4 bytes for the length of the string.
+        claim_eq!(statebox.size_in_state(), 4 + 2);
+        statebox.get_mut().push_str("cd");
+        claim_eq!(statebox.size_in_state(), 4 + 4, "The modified value should be counted.");
+        let bytes = to_bytes(&statebox);
+        drop(statebox);
+        let statebox: StateBox<String, _> =
+            StateBox::deserial_with_state(&state_builder.state_api, &mut Cursor::new(&bytes))
+                .expect("Deserializing should succeed");
+        claim_eq!(statebox.size_in_state(), 4 + 4, "The stored value should be counted.");
+    }
+
+    #[concordium_test]
+    fn stateset_size_in_state() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut set = state_builder.new_set();
+        claim_eq!(set.size_in_state(), 0);
+        claim!(set.insert(1u8));
+        claim!(set.insert(2u8));
+        // Each element is a key of the 8 byte prefix and the element, with no value.
+        claim_eq!(set.size_in_state(), 2 * 9);
+        claim!(set.remove(&1));
+        claim_eq!(set.size_in_state(), 9);
+    }
+
     #[concordium_test]
     fn a_new_entry_can_not_be_created_under_a_locked_subtree() {
         let expected_value: u64 = 123123123;
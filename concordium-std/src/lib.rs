@@ -39,8 +39,9 @@
 //! [`std`](#std-build-with-the-rust-standard-library),
 //! [`build-schema`](#build-schema-build-for-generating-a-module-schema),
 //! [`wasm-test`](#wasm-test-build-for-testing-in-wasm),
-//! [`crypto-primitives`][crypto-feature], and
-//! [`bump_alloc`](#use-a-custom-allocator)
+//! [`crypto-primitives`][crypto-feature],
+//! [`native`](#native-execute-contracts-natively),
+//! [`bump_alloc`](#use-a-custom-allocator), and
 //! [`debug`](#emit-debug-information)
 //!
 //! [crypto-feature]:
@@ -116,6 +117,17 @@
 //! `crypto-primitives` feature when targeting `wasm32-unknown-unknown`.
 //! The issue arises when compiling the [`secp256k1`](https://docs.rs/secp256k1/latest/secp256k1/) crate.
 //!
+//! ## `native`: Execute contracts natively
+//!
+//! Build with this feature to execute contracts compiled for the host platform
+//! instead of Wasm, with the host functions provided at runtime by a
+//! [`native::NativeHost`]. This is used by the
+//! [concordium-smart-contract-testing](https://docs.rs/concordium-smart-contract-testing)
+//! library to run integration tests natively, which makes it possible to debug
+//! contracts with tools such as `gdb` or `lldb`. The feature has no effect
+//! when targeting Wasm. It enables the `std` and `crypto-primitives`
+//! features.
+//!
 //! ## Use a custom allocator
 //!
 //! Some operations in `concordium-std` need to dynamically allocate memory.
@@ -246,6 +258,11 @@
 //! | [UpgradeError::UnsupportedModuleVersion] | `-2147483624` |
 //! | [QueryAccountBalanceError] | `-2147483623` |
 //! | [QueryContractBalanceError] | `-2147483622` |
+//! | [QuotaExceededError] | `-2147483621` |
+//! | [MigrationError::UnsupportedVersion] | `-2147483620` |
+//! | [MigrationError::MalformedState] | `-2147483619` |
+//! | [MigrationError::InvalidState] | `-2147483618` |
+//! | [ReentrancyError] | `-2147483617` |
 //!
 //! Other error codes may be added in the future and custom error codes should
 //! not use the range `i32::MIN` to `i32::MIN + 100`.
@@ -401,7 +418,9 @@ pub extern crate alloc;
 /// When the `std` feature is enabled this is just [std::process::abort](https://doc.rust-lang.org/std/process/fn.abort.html).
 /// When `std` is not present and the target architecture is `wasm32` this will
 /// simply emit the [unreachable](https://doc.rust-lang.org/core/arch/wasm32/fn.unreachable.html) instruction.
-#[cfg(feature = "std")]
+/// When contracts are executed natively with the `native` feature, this panics
+/// instead, see [`native`].
+#[cfg(all(feature = "std", not(all(feature = "native", not(target_arch = "wasm32")))))]
 pub use std::process::abort as trap;
 #[cfg(all(not(feature = "std"), target_arch = "wasm32"))]
 #[inline(always)]
@@ -409,6 +428,8 @@ pub fn trap() -> ! { core::arch::wasm32::unreachable() }
 #[cfg(all(not(feature = "std"), not(target_arch = "wasm32")))]
 #[inline(always)]
 pub fn trap() -> ! { core::intrinsics::abort() }
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+pub use native::trap;
 
 #[cfg(not(feature = "std"))]
 #[panic_handler]
@@ -460,13 +481,31 @@ pub mod collections {
 
 pub mod constants;
 mod impls;
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+pub mod native;
 pub mod prims;
+mod reentrancy_guard;
+mod state_binary_heap;
 mod state_btree;
+mod state_cached;
+mod state_counted;
+mod state_quota;
+mod state_struct;
+mod state_vec;
+mod state_versioned;
 mod traits;
 mod types;
 pub use concordium_contracts_common::*;
 pub use impls::*;
+pub use reentrancy_guard::*;
+pub use state_binary_heap::*;
 pub use state_btree::*;
+pub use state_cached::*;
+pub use state_counted::*;
+pub use state_quota::*;
+pub use state_struct::*;
+pub use state_vec::*;
+pub use state_versioned::*;
 pub use traits::*;
 pub use types::*;
 
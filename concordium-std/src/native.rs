@@ -0,0 +1,596 @@
+//! Support for executing contracts natively, i.e., compiled for the host
+//! platform instead of Wasm, which makes it possible to debug them with
+//! ordinary tools such as `gdb` or `lldb`.
+//!
+//! This module is only available with the `native` feature on non-Wasm
+//! targets. In that configuration the [host functions](crate::prims) are
+//! forwarded to the [`NativeHost`] registered for the current thread with
+//! [`with_native_host`]. The [concordium-smart-contract-testing](https://docs.rs/concordium-smart-contract-testing)
+//! library provides a host that runs contracts against its simulated chain,
+//! so contracts should rarely need to use this module directly.
+//!
+//! The cryptographic primitives are computed directly by this library, which
+//! is why the feature enables the `crypto-primitives` feature.
+//!
+//! When executed natively, [`trap`](crate::trap) panics instead of aborting,
+//! so that a backtrace of the trap can be obtained. The panic stops the
+//! execution of the contract, which then results in [`Trapped`], see
+//! [`with_native_host`].
+use crate::*;
+use std::{
+    cell::RefCell,
+    panic,
+    sync::{mpsc, Once},
+    thread,
+};
+
+/// The host functions available to a contract executed natively.
+///
+/// The functions correspond to the functions in [`prims`](crate::prims), but
+/// the memory regions are passed as slices, and the values returned via
+/// memory are returned as values. The results use the same encoding as the
+/// functions in [`prims`](crate::prims). A host should panic where the node
+/// would stop the execution of a contract with a runtime error.
+pub trait NativeHost {
+    /// Invoke a host operation, such as a transfer, a contract call or a
+    /// query. See [`prims::invoke`](crate::prims::invoke).
+    fn invoke(&mut self, tag: u32, data: &[u8]) -> u64;
+
+    /// Write to the return value of the contract.
+    fn write_output(&mut self, data: &[u8], offset: u32) -> u32;
+
+    /// Upgrade the module of the contract.
+    fn upgrade(&mut self, module_ref: ModuleReference) -> u64;
+
+    /// The size of the `i`th parameter, or `-1` if it does not exist.
+    fn get_parameter_size(&mut self, i: u32) -> i32;
+
+    /// Read a section of the `i`th parameter into `dest`.
+    fn get_parameter_section(&mut self, i: u32, dest: &mut [u8], offset: u32) -> i32;
+
+    /// Read a section of the serialized policies of the sender into `dest`.
+    fn get_policy_section(&mut self, dest: &mut [u8], offset: u32) -> u32;
+
+    /// Log an event.
+    fn log_event(&mut self, event: &[u8]) -> i32;
+
+    /// Look up an entry in the state.
+    fn state_lookup_entry(&mut self, key: &[u8]) -> u64;
+
+    /// Create an entry in the state.
+    fn state_create_entry(&mut self, key: &[u8]) -> u64;
+
+    /// Delete an entry from the state.
+    fn state_delete_entry(&mut self, key: &[u8]) -> u32;
+
+    /// Delete all entries with the given prefix from the state.
+    fn state_delete_prefix(&mut self, prefix: &[u8]) -> u32;
+
+    /// Create an iterator over the entries with the given prefix.
+    fn state_iterate_prefix(&mut self, prefix: &[u8]) -> u64;
+
+    /// Advance an iterator.
+    fn state_iterator_next(&mut self, iterator: u64) -> u64;
+
+    /// Delete an iterator.
+    fn state_iterator_delete(&mut self, iterator: u64) -> u32;
+
+    /// The size of the key of the entry an iterator points to.
+    fn state_iterator_key_size(&mut self, iterator: u64) -> u32;
+
+    /// Read a section of the key of the entry an iterator points to.
+    fn state_iterator_key_read(&mut self, iterator: u64, dest: &mut [u8], offset: u32) -> u32;
+
+    /// Read a section of an entry into `dest`.
+    fn state_entry_read(&mut self, entry: u64, dest: &mut [u8], offset: u32) -> u32;
+
+    /// Write `src` to a section of an entry.
+    fn state_entry_write(&mut self, entry: u64, src: &[u8], offset: u32) -> u32;
+
+    /// The size of an entry.
+    fn state_entry_size(&mut self, entry: u64) -> u32;
+
+    /// Resize an entry.
+    fn state_entry_resize(&mut self, entry: u64, new_size: u32) -> u32;
+
+    /// The account that initiated the initialization of the contract.
+    fn get_init_origin(&mut self) -> AccountAddress;
+
+    /// The account that initiated the transaction.
+    fn get_receive_invoker(&mut self) -> AccountAddress;
+
+    /// The address of the contract.
+    fn get_receive_self_address(&mut self) -> ContractAddress;
+
+    /// The current balance of the contract.
+    fn get_receive_self_balance(&mut self) -> Amount;
+
+    /// The immediate sender of the message.
+    fn get_receive_sender(&mut self) -> Address;
+
+    /// The owner of the contract.
+    fn get_receive_owner(&mut self) -> AccountAddress;
+
+    /// The name of the entrypoint that was invoked.
+    fn get_receive_entrypoint(&mut self) -> OwnedEntrypointName;
+
+    /// The time of the current block.
+    fn get_slot_time(&mut self) -> Timestamp;
+
+    /// Print a debug message emitted by the contract.
+    fn debug_print(&mut self, message: &str, filename: &str, line: u32, column: u32) {
+        eprintln!("{}:{}:{}: {}", filename, line, column, message);
+    }
+}
+
+/// Whether a panic can unwind out of the entrypoint of a contract executed
+/// with [`with_native_host`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unwind {
+    /// The entrypoint has the `"C-unwind"` ABI. A panic unwinds the thread of
+    /// the contract, which then exits.
+    Allowed,
+    /// The entrypoint has the `"C"` ABI, as the entrypoints generated by
+    /// `#[init]` and `#[receive]`. Since unwinding out of such a function
+    /// aborts the process, the thread of the contract is blocked forever
+    /// instead.
+    Forbidden,
+}
+
+/// The result of executing a contract that trapped, i.e., panicked, or whose
+/// host panicked while handling a host function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trapped;
+
+/// A host function called by a contract, to be run with the host on the
+/// thread that called [`with_native_host`].
+struct HostCall(*mut (dyn FnMut(&mut dyn NativeHost) + 'static));
+
+// Safety: The closure is only called while the contract thread waits for the
+// reply, see `with_current_host`.
+unsafe impl Send for HostCall {}
+
+/// A request from the thread executing a contract to its host.
+enum Request {
+    /// Run a host function.
+    Call(HostCall),
+    /// The contract trapped and its thread is blocked, since it cannot
+    /// unwind.
+    Trap,
+}
+
+/// The reply of the host to a [`Request::Call`].
+enum Reply {
+    /// The host function completed, and the contract can continue.
+    Continue,
+    /// The host function panicked, and the contract must stop.
+    Stop,
+}
+
+/// The channel between the thread executing a contract and its host.
+struct ContractChannel {
+    requests: mpsc::Sender<Request>,
+    replies:  mpsc::Receiver<Reply>,
+    unwind:   Unwind,
+}
+
+thread_local! {
+    /// The channel to the host, if this thread executes a contract.
+    static CONTRACT: RefCell<Option<ContractChannel>> = RefCell::new(None);
+}
+
+/// Run `f`, which executes a contract, with the host functions handled by
+/// `host`.
+///
+/// The contract is executed on a separate thread, whose host functions are
+/// forwarded to `host` on the current thread. If the contract traps or
+/// otherwise panics, or if `host` panics while handling a host function, the
+/// result is [`Trapped`].
+///
+/// How the contract thread stops after a panic in the contract depends on
+/// `unwind`. With [`Unwind::Allowed`], the panic unwinds the thread, which then
+/// exits. With [`Unwind::Forbidden`], the thread is blocked forever, since the
+/// panic cannot unwind out of the entrypoint, and each trap therefore leaks a
+/// thread. The thread is also blocked if `host` panics, since the host
+/// functions cannot be unwound either.
+///
+/// The calls may be nested, e.g., when the host executes another contract
+/// natively in response to a call.
+///
+/// Panics of contracts that cannot unwind are detected with a panic hook, which
+/// is installed on the first call and calls the hook that was set before.
+/// Replacing the panic hook afterwards makes such contracts abort the process
+/// when they panic.
+pub fn with_native_host<R: Send + 'static>(
+    host: &mut dyn NativeHost,
+    unwind: Unwind,
+    f: impl FnOnce() -> R + Send + 'static,
+) -> Result<R, Trapped> {
+    install_panic_hook();
+    let (request_sender, requests) = mpsc::channel();
+    let (reply_sender, replies) = mpsc::channel();
+    let contract = thread::spawn(move || {
+        CONTRACT.with(|contract| {
+            *contract.borrow_mut() = Some(ContractChannel {
+                requests: request_sender,
+                replies,
+                unwind,
+            })
+        });
+        panic::catch_unwind(panic::AssertUnwindSafe(f))
+    });
+    // The requests are disconnected when the contract thread finishes.
+    while let Ok(request) = requests.recv() {
+        match request {
+            Request::Call(HostCall(call)) => {
+                // Safety: The contract thread waits for the reply, so the closure is
+                // alive and not used elsewhere.
+                let call = unsafe { &mut *call };
+                match panic::catch_unwind(panic::AssertUnwindSafe(|| call(host))) {
+                    Ok(()) => {
+                        let _ = reply_sender.send(Reply::Continue);
+                    }
+                    Err(_) => {
+                        let _ = reply_sender.send(Reply::Stop);
+                        return Err(Trapped);
+                    }
+                }
+            }
+            Request::Trap => return Err(Trapped),
+        }
+    }
+    contract.join().expect("The contract thread catches its panics.").map_err(|_| Trapped)
+}
+
+/// Install the panic hook that stops the contract threads that cannot unwind
+/// when they panic, see [`with_native_host`].
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            let requests = CONTRACT
+                .try_with(|contract| {
+                    let contract = contract.try_borrow().ok()?;
+                    let channel = contract.as_ref()?;
+                    (channel.unwind == Unwind::Forbidden).then(|| channel.requests.clone())
+                })
+                .ok()
+                .flatten();
+            if let Some(requests) = requests {
+                let _ = requests.send(Request::Trap);
+                block_forever();
+            }
+        }));
+    });
+}
+
+/// Block the current thread forever.
+fn block_forever() -> ! {
+    loop {
+        thread::park();
+    }
+}
+
+/// Call `f` with the host of the contract executing on this thread.
+fn with_current_host<R>(f: impl FnOnce(&mut dyn NativeHost) -> R) -> R {
+    let mut f = Some(f);
+    let mut result = None;
+    let mut call = |host: &mut dyn NativeHost| {
+        if let Some(f) = f.take() {
+            result = Some(f(host));
+        }
+    };
+    let call: *mut (dyn FnMut(&mut dyn NativeHost) + '_) = &mut call;
+    // Safety: The lifetime is erased so that the closure can be sent to the
+    // host. The host only calls it before replying, and the reply is awaited
+    // below.
+    let call: *mut (dyn FnMut(&mut dyn NativeHost) + 'static) = unsafe { mem::transmute(call) };
+    let reply = CONTRACT.with(|contract| {
+        let contract = contract.borrow();
+        let contract = contract.as_ref().unwrap_or_else(|| {
+            panic!("Host functions are only available to contracts executed with a native host.")
+        });
+        contract.requests.send(Request::Call(HostCall(call))).ok()?;
+        contract.replies.recv().ok()
+    });
+    match reply {
+        Some(Reply::Continue) => result.expect("The host function was called."),
+        // The host stopped the contract.
+        Some(Reply::Stop) | None => block_forever(),
+    }
+}
+
+/// Terminate execution of the contract by panicking, which stops the contract
+/// thread, see [`with_native_host`].
+#[inline(never)]
+pub fn trap() -> ! { panic!("The contract trapped.") }
+
+/// Construct a slice from a pointer and a length passed to a host function.
+///
+/// # Safety
+/// The pointer must be valid for reads of `length` bytes.
+unsafe fn slice<'a>(start: *const u8, length: u32) -> &'a [u8] {
+    if length == 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(start, length as usize)
+    }
+}
+
+/// Construct a mutable slice from a pointer and a length passed to a host
+/// function.
+///
+/// # Safety
+/// The pointer must be valid for writes of `length` bytes.
+unsafe fn slice_mut<'a>(start: *mut u8, length: u32) -> &'a mut [u8] {
+    if length == 0 {
+        &mut []
+    } else {
+        core::slice::from_raw_parts_mut(start, length as usize)
+    }
+}
+
+/// Write the serialization of `value` to `dest`.
+///
+/// # Safety
+/// The pointer must be valid for writes of the serialized size of `value`.
+unsafe fn write_serial(dest: *mut u8, value: &impl Serial) {
+    let bytes = to_bytes(value);
+    dest.copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+}
+
+// The host functions, which are declared in `prims`, forwarded to the current
+// native host. The signatures must match the declarations.
+mod host_functions {
+    use super::*;
+
+    #[no_mangle]
+    unsafe extern "C" fn invoke(tag: u32, start: *const u8, length: u32) -> u64 {
+        with_current_host(|host| host.invoke(tag, slice(start, length)))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn write_output(start: *const u8, length: u32, offset: u32) -> u32 {
+        with_current_host(|host| host.write_output(slice(start, length), offset))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn upgrade(module_ref: *const u8) -> u64 {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(slice(module_ref, 32));
+        with_current_host(|host| host.upgrade(ModuleReference::from(bytes)))
+    }
+
+    #[no_mangle]
+    extern "C" fn get_parameter_size(i: u32) -> i32 {
+        with_current_host(|host| host.get_parameter_size(i))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_parameter_section(
+        i: u32,
+        param_bytes: *mut u8,
+        length: u32,
+        offset: u32,
+    ) -> i32 {
+        with_current_host(|host| {
+            host.get_parameter_section(i, slice_mut(param_bytes, length), offset)
+        })
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_policy_section(
+        policy_bytes: *mut u8,
+        length: u32,
+        offset: u32,
+    ) -> u32 {
+        with_current_host(|host| host.get_policy_section(slice_mut(policy_bytes, length), offset))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn log_event(start: *const u8, length: u32) -> i32 {
+        with_current_host(|host| host.log_event(slice(start, length)))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_lookup_entry(key_start: *const u8, key_length: u32) -> u64 {
+        with_current_host(|host| host.state_lookup_entry(slice(key_start, key_length)))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_create_entry(key_start: *const u8, key_length: u32) -> u64 {
+        with_current_host(|host| host.state_create_entry(slice(key_start, key_length)))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_delete_entry(key_start: *const u8, key_length: u32) -> u32 {
+        with_current_host(|host| host.state_delete_entry(slice(key_start, key_length)))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_delete_prefix(key_start: *const u8, key_length: u32) -> u32 {
+        with_current_host(|host| host.state_delete_prefix(slice(key_start, key_length)))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_iterate_prefix(prefix_start: *const u8, prefix_length: u32) -> u64 {
+        with_current_host(|host| host.state_iterate_prefix(slice(prefix_start, prefix_length)))
+    }
+
+    #[no_mangle]
+    extern "C" fn state_iterator_next(iterator: u64) -> u64 {
+        with_current_host(|host| host.state_iterator_next(iterator))
+    }
+
+    #[no_mangle]
+    extern "C" fn state_iterator_delete(iterator: u64) -> u32 {
+        with_current_host(|host| host.state_iterator_delete(iterator))
+    }
+
+    #[no_mangle]
+    extern "C" fn state_iterator_key_size(iterator: u64) -> u32 {
+        with_current_host(|host| host.state_iterator_key_size(iterator))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_iterator_key_read(
+        iterator: u64,
+        start: *mut u8,
+        length: u32,
+        offset: u32,
+    ) -> u32 {
+        with_current_host(|host| {
+            host.state_iterator_key_read(iterator, slice_mut(start, length), offset)
+        })
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_entry_read(
+        entry: u64,
+        start: *mut u8,
+        length: u32,
+        offset: u32,
+    ) -> u32 {
+        with_current_host(|host| host.state_entry_read(entry, slice_mut(start, length), offset))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn state_entry_write(
+        entry: u64,
+        start: *const u8,
+        length: u32,
+        offset: u32,
+    ) -> u32 {
+        with_current_host(|host| host.state_entry_write(entry, slice(start, length), offset))
+    }
+
+    #[no_mangle]
+    extern "C" fn state_entry_size(entry: u64) -> u32 {
+        with_current_host(|host| host.state_entry_size(entry))
+    }
+
+    #[no_mangle]
+    extern "C" fn state_entry_resize(entry: u64, new_size: u32) -> u32 {
+        with_current_host(|host| host.state_entry_resize(entry, new_size))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_init_origin(start: *mut u8) {
+        write_serial(start, &with_current_host(|host| host.get_init_origin()))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_receive_invoker(start: *mut u8) {
+        write_serial(start, &with_current_host(|host| host.get_receive_invoker()))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_receive_self_address(start: *mut u8) {
+        write_serial(start, &with_current_host(|host| host.get_receive_self_address()))
+    }
+
+    #[no_mangle]
+    extern "C" fn get_receive_self_balance() -> u64 {
+        with_current_host(|host| host.get_receive_self_balance()).micro_ccd
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_receive_sender(start: *mut u8) {
+        write_serial(start, &with_current_host(|host| host.get_receive_sender()))
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_receive_owner(start: *mut u8) {
+        write_serial(start, &with_current_host(|host| host.get_receive_owner()))
+    }
+
+    #[no_mangle]
+    extern "C" fn get_receive_entrypoint_size() -> u32 {
+        with_current_host(|host| host.get_receive_entrypoint()).as_entrypoint_name().size()
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn get_receive_entrypoint(start: *mut u8) {
+        let name = with_current_host(|host| host.get_receive_entrypoint());
+        let bytes = name.as_entrypoint_name().to_string().into_bytes();
+        start.copy_from_nonoverlapping(bytes.as_ptr(), bytes.len());
+    }
+
+    #[no_mangle]
+    extern "C" fn get_slot_time() -> u64 {
+        with_current_host(|host| host.get_slot_time()).timestamp_millis()
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn verify_ed25519_signature(
+        public_key: *const u8,
+        signature: *const u8,
+        message: *const u8,
+        message_len: u32,
+    ) -> i32 {
+        let signature = ed25519_zebra::Signature::try_from(slice(signature, 64));
+        let public_key = ed25519_zebra::VerificationKey::try_from(slice(public_key, 32));
+        match (signature, public_key) {
+            (Ok(ref signature), Ok(public_key)) => {
+                public_key.verify(signature, slice(message, message_len)).is_ok() as i32
+            }
+            _ => 0,
+        }
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn verify_ecdsa_secp256k1_signature(
+        public_key: *const u8,
+        signature: *const u8,
+        message_hash: *const u8,
+    ) -> i32 {
+        let signature = secp256k1::ecdsa::Signature::from_compact(slice(signature, 64));
+        let public_key = secp256k1::PublicKey::from_slice(slice(public_key, 33));
+        let message_hash = secp256k1::Message::from_slice(slice(message_hash, 32));
+        match (signature, public_key, message_hash) {
+            (Ok(ref signature), Ok(public_key), Ok(message_hash)) => {
+                let verifier = secp256k1::Secp256k1::verification_only();
+                verifier.verify_ecdsa(&message_hash, signature, &public_key).is_ok() as i32
+            }
+            _ => 0,
+        }
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn hash_sha2_256(data: *const u8, data_len: u32, output: *mut u8) {
+        use sha2::Digest;
+        let hash: [u8; 32] = sha2::Sha256::digest(slice(data, data_len)).into();
+        output.copy_from_nonoverlapping(hash.as_ptr(), 32);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn hash_sha3_256(data: *const u8, data_len: u32, output: *mut u8) {
+        use sha3::Digest;
+        let hash: [u8; 32] = sha3::Sha3_256::digest(slice(data, data_len)).into();
+        output.copy_from_nonoverlapping(hash.as_ptr(), 32);
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn hash_keccak_256(data: *const u8, data_len: u32, output: *mut u8) {
+        use sha3::Digest;
+        let hash: [u8; 32] = sha3::Keccak256::digest(slice(data, data_len)).into();
+        output.copy_from_nonoverlapping(hash.as_ptr(), 32);
+    }
+
+    #[cfg(feature = "debug")]
+    #[no_mangle]
+    unsafe extern "C" fn debug_print(
+        msg_start: *const u8,
+        msg_length: u32,
+        filename_start: *const u8,
+        filename_length: u32,
+        line: u32,
+        column: u32,
+    ) {
+        let message = String::from_utf8_lossy(slice(msg_start, msg_length));
+        let filename = String::from_utf8_lossy(slice(filename_start, filename_length));
+        with_current_host(|host| host.debug_print(&message, &filename, line, column))
+    }
+}
@@ -312,7 +312,9 @@ extern "C" {
 
 // For every external function, we must provide a dummy function.
 // This is necessary to compile to x86_64 during unit tests on Windows and OSX.
-#[cfg(not(target_arch = "wasm32"))]
+// With the `native` feature, the functions are instead provided by the
+// `native` module.
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "native")))]
 mod host_dummy_functions {
     #[no_mangle]
     fn invoke(_tag: u32, _start: *const u8, _length: u32) -> u64 {
@@ -0,0 +1,88 @@
+use crate::{HasStateApi, StateApi, REENTRANCY_LOCK_KEY};
+
+/// A guard preventing reentrant calls into the entrypoints of a contract
+/// instance.
+///
+/// The guard is acquired using
+/// [`enter_non_reentrant`](crate::StateBuilder::enter_non_reentrant), which
+/// stores a lock in the contract state. While the lock is held, acquiring it
+/// again fails with a [`ReentrancyError`]. The lock is released when the guard
+/// is dropped.
+///
+/// Since the contract state is stored before invoking another contract, the
+/// lock is visible to calls back into the same instance. Acquiring the guard
+/// at the beginning of each entrypoint that should not be reentered therefore
+/// makes nested calls into any of these entrypoints reject, while other
+/// entrypoints, such as views, can still be called. If the entrypoint rejects,
+/// the lock is rolled back along with the rest of the state.
+///
+/// ```ignore
+/// # use concordium_std::*;
+/// #[receive(contract = "wallet", name = "withdraw", parameter = "Amount", mutable)]
+/// fn withdraw(ctx: &ReceiveContext, host: &mut Host<State>) -> ReceiveResult<()> {
+///     // Rejects with `ReentrancyError` if called while `withdraw` is executing.
+///     let _guard = host.state_builder().enter_non_reentrant()?;
+///     let amount: Amount = ctx.parameter_cursor().get()?;
+///     // ... update the balance and call the receiver, which cannot call
+///     // `withdraw` again before this call finishes.
+///     Ok(())
+/// }
+/// ```
+///
+/// ### **Caution**
+///
+/// The guard must be kept alive for the duration of the entrypoint, so it must
+/// be bound to a named variable, e.g., `_guard`, and not to `_`, which drops
+/// the guard immediately.
+#[must_use = "the lock is released when the guard is dropped"]
+#[derive(Debug)]
+pub struct ReentrancyGuard<S = StateApi> {
+    pub(crate) state_api: S,
+}
+
+/// Error for acquiring a [`ReentrancyGuard`] while the lock is already held,
+/// i.e., for a reentrant call into a guarded entrypoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReentrancyError;
+
+impl<S: HasStateApi> Drop for ReentrancyGuard<S> {
+    fn drop(&mut self) {
+        if let Some(entry) = self.state_api.lookup_entry(&REENTRANCY_LOCK_KEY) {
+            // The lock is never part of a locked subtree, so deleting it cannot fail.
+            let _ = self.state_api.delete_entry(entry);
+        }
+    }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_reentrancy_guard {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, StateApi, StateBuilder};
+
+    /// Test that the guard can only be acquired once at a time.
+    #[concordium_test]
+    fn test_reentrancy_guard() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let guard = state_builder.enter_non_reentrant();
+        claim!(guard.is_ok());
+        claim_eq!(state_builder.enter_non_reentrant().err(), Some(ReentrancyError));
+        drop(guard);
+        let guard = state_builder.enter_non_reentrant();
+        claim!(guard.is_ok(), "The lock should be released when the guard is dropped.");
+    }
+
+    /// Test that the guard is visible through a separate state API, as for a
+    /// nested call into the contract.
+    #[concordium_test]
+    fn test_reentrancy_guard_shared() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let _guard = state_builder.enter_non_reentrant();
+        let mut nested = StateBuilder::open(StateApi::open());
+        claim!(nested.enter_non_reentrant().is_err());
+        // The lock does not interfere with allocating new collections.
+        let mut map = state_builder.new_map();
+        claim!(map.insert(1u8, 1u8).is_none());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::{
+    Deletable, DeserialWithState, Get, ParseResult, Read, Serial, Serialize, StateApi, StateMap,
+    StateRef, UnwrapAbort, Write,
+};
+
+/// A priority queue based on a [binary heap](https://en.wikipedia.org/wiki/Binary_heap),
+/// where each slot of the heap is stored separately in the low-level key-value
+/// store.
+///
+/// The heap is a _min-heap_ of unique keys, each associated with a value,
+/// meaning [`peek`](Self::peek) and [`pop`](Self::pop) return the entry with
+/// the smallest key, e.g., the earliest deadline. To allow several entries with
+/// the same priority, make the key unique by including an identifier, e.g.,
+/// `(Timestamp, u64)`.
+///
+/// | Operation                                      | Performance       |
+/// |------------------------------------------------|-------------------|
+/// | [`peek`](Self::peek)                           | O(k)              |
+/// | [`get`](Self::get)                             | O(k)              |
+/// | [`push`](Self::push)                           | O(k * log(n))     |
+/// | [`pop`](Self::pop)                             | O(k * log(n))     |
+/// | [`remove_by_key`](Self::remove_by_key)         | O(k * log(n))     |
+///
+/// Where `k` is the byte size of the serialized keys and `n` is the number of
+/// entries in the heap. Each step of restoring the heap ordering reads the key
+/// of a parent or of two children and writes two entries in the state, so
+/// the energy cost is dominated by the `log(n)` factor for large heaps. The
+/// value is only read or written once per operation.
+///
+/// ## Storage
+///
+/// The heap is stored like a [`StateBTreeMap`](crate::StateBTreeMap): the
+/// ordering is kept in entries of its own, one per slot, and the values are
+/// kept in a separate [`StateMap`] from key to value, so that restoring the
+/// ordering never reads or writes a value. Unlike the nodes of a
+/// [`StateBTreeSet`](crate::StateBTreeSet), which hold up to `2 * M - 1` keys
+/// and are allocated by ID, the slots are addressed by their index, since the
+/// parent and children of slot `i` are found by arithmetic on `i`. Each slot
+/// holds a single key, so a swap writes two small entries instead of two whole
+/// nodes. A third map from key to slot allows
+/// [`remove_by_key`](Self::remove_by_key) to find the slot of a key without
+/// searching the heap.
+///
+/// ## Type parameters
+///
+/// The heap `StateBinaryHeap<K, V>` is parametrized by the types:
+/// - `K`: Keys used as priorities in the heap. Most operations on the heap
+///   require this to implement [`Serialize`](crate::Serialize) and [`Ord`].
+///   Keys cannot contain references to the low-level state, such as types
+///   containing [`StateBox`](crate::StateBox).
+/// - `V`: Values stored in the heap. Most operations on the heap require this
+///   to implement [`Serial`](crate::Serial) and
+///   [`DeserialWithState<StateApi>`](crate::DeserialWithState).
+///
+/// ## Usage
+///
+/// New heaps can be constructed using the
+/// [`new_binary_heap`](crate::StateBuilder::new_binary_heap) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// let mut expiries = state_builder.new_binary_heap();
+/// let _ = expiries.push(Timestamp::from_timestamp_millis(2000), "b".to_string());
+/// let _ = expiries.push(Timestamp::from_timestamp_millis(1000), "a".to_string());
+/// assert_eq!(expiries.pop(), Some((Timestamp::from_timestamp_millis(1000), "a".to_string())));
+/// ```
+///
+/// ### **Caution**
+///
+/// `StateBinaryHeap`s must be explicitly deleted when they are no longer
+/// needed, otherwise they will remain in the contract's state, albeit
+/// unreachable.
+pub struct StateBinaryHeap<K, V> {
+    /// The keys in heap order indexed by their slot, such that the key in slot
+    /// `i` is smaller than the keys in slots `2i + 1` and `2i + 2`.
+    pub(crate) slots:     StateMap<u32, K, StateApi>,
+    /// The slot of each key, for removing by key.
+    pub(crate) positions: StateMap<K, u32, StateApi>,
+    /// The value associated with each key.
+    pub(crate) values:    StateMap<K, V, StateApi>,
+    /// The number of entries in the heap.
+    pub(crate) len:       u32,
+}
+
+impl<K, V> StateBinaryHeap<K, V>
+where
+    K: Serialize + Ord,
+    V: Serial + DeserialWithState<StateApi>,
+{
+    /// Push a key with an associated value into the heap.
+    /// If the key is already in the heap, the value is replaced and the old
+    /// value is returned.
+    ///
+    /// *Caution*: If `Option<V>` is to be deleted and contains a data structure
+    /// prefixed with `State` (such as [StateBox](crate::StateBox) or
+    /// [StateMap](crate::StateMap)), then it is important to call
+    /// [`Deletable::delete`](crate::Deletable::delete) on the value returned
+    /// when you're finished with it. Otherwise, it will remain in the
+    /// contract state.
+    #[must_use]
+    pub fn push(&mut self, key: K, value: V) -> Option<V> {
+        let old_value = self.values.insert_borrowed(&key, value);
+        if old_value.is_none() {
+            // Trap if the length would overflow.
+            let slot = self.len;
+            self.len = self.len.checked_add(1).unwrap_abort();
+            self.sift_up(slot, key);
+        }
+        old_value
+    }
+
+    /// Returns references to the smallest key in the heap and its value, if
+    /// any.
+    pub fn peek(&self) -> Option<(StateRef<K>, StateRef<V>)> {
+        let key = self.slots.get(&0)?;
+        // Unwrap is safe, otherwise the slots and the values have inconsistencies.
+        let value = self.values.get(&key).unwrap_abort();
+        Some((key, value))
+    }
+
+    /// Remove the smallest key and its value from the heap and return them,
+    /// if any.
+    ///
+    /// *Caution*: If `V` contains a data structure prefixed with `State`, then
+    /// it is important to call [`Deletable::delete`](crate::Deletable::delete)
+    /// on the value returned.
+    #[must_use]
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(self.remove_slot(0))
+    }
+
+    /// Remove a key from the heap, returning its value if the key was in the
+    /// heap.
+    ///
+    /// *Caution*: If `V` contains a data structure prefixed with `State`, then
+    /// it is important to call [`Deletable::delete`](crate::Deletable::delete)
+    /// on the value returned.
+    #[must_use]
+    pub fn remove_by_key(&mut self, key: &K) -> Option<V> {
+        let slot = self.positions.get(key)?.value;
+        let (_, value) = self.remove_slot(slot);
+        Some(value)
+    }
+
+    /// Get a reference to the value associated with the key.
+    pub fn get(&self, key: &K) -> Option<StateRef<V>> {
+        if self.len == 0 {
+            None
+        } else {
+            self.values.get(key)
+        }
+    }
+
+    /// Returns `true` if the heap contains the key.
+    pub fn contains_key(&self, key: &K) -> bool { self.get_slot_of(key).is_some() }
+
+    /// Clears the heap, removing all entries.
+    /// This also includes values pointed at, if `V`, for example, is a
+    /// [StateBox](crate::StateBox). **If applicable use
+    /// [`clear_flat`](Self::clear_flat) instead.**
+    pub fn clear(&mut self)
+    where
+        V: Deletable, {
+        self.values.clear();
+        self.slots.clear_flat();
+        self.positions.clear_flat();
+        self.len = 0;
+    }
+
+    /// Clears the heap, removing all entries.
+    /// **This should be used over [`clear`](Self::clear) if it is
+    /// applicable.** It avoids recursive deletion of values since the
+    /// values are required to be _flat_.
+    pub fn clear_flat(&mut self)
+    where
+        V: Serialize, {
+        self.values.clear_flat();
+        self.slots.clear_flat();
+        self.positions.clear_flat();
+        self.len = 0;
+    }
+
+    /// Internal function for looking up the slot of a key.
+    fn get_slot_of(&self, key: &K) -> Option<u32> {
+        if self.len == 0 {
+            None
+        } else {
+            self.positions.get(key).map(|slot| slot.value)
+        }
+    }
+
+    /// Internal function for looking up the key in a slot.
+    /// This assumes the slot is in use and traps if this is not the case.
+    fn get_key(&self, slot: u32) -> K { self.slots.get(&slot).unwrap_abort().value }
+
+    /// Internal function for storing a key in a slot.
+    fn place(&mut self, slot: u32, key: K) {
+        let _ = self.positions.insert_borrowed(&key, slot);
+        let _ = self.slots.insert(slot, key);
+    }
+
+    /// Internal function for removing the entry in a slot, which must be in
+    /// use. The last key is moved to the slot and then moved up or down to
+    /// restore the heap ordering.
+    fn remove_slot(&mut self, slot: u32) -> (K, V) {
+        self.len -= 1;
+        let last_key = self.slots.remove_and_get(&self.len).unwrap_abort();
+        let key = if slot == self.len {
+            last_key
+        } else {
+            let key = self.get_key(slot);
+            let moves_up = slot > 0 && last_key < self.get_key((slot - 1) / 2);
+            if moves_up {
+                self.sift_up(slot, last_key);
+            } else {
+                self.sift_down(slot, last_key);
+            }
+            key
+        };
+        let _ = self.positions.remove_and_get(&key);
+        // Unwrap is safe, otherwise the slots and the values have inconsistencies.
+        let value = self.values.remove_and_get(&key).unwrap_abort();
+        (key, value)
+    }
+
+    /// Internal function for placing a key in a slot, and moving it up towards
+    /// the root while it is smaller than its parent.
+    fn sift_up(&mut self, mut slot: u32, key: K) {
+        while slot > 0 {
+            let parent = (slot - 1) / 2;
+            let parent_key = self.get_key(parent);
+            if key >= parent_key {
+                break;
+            }
+            self.place(slot, parent_key);
+            slot = parent;
+        }
+        self.place(slot, key);
+    }
+
+    /// Internal function for placing a key in a slot, and moving it down
+    /// towards the leaves while it is larger than its smallest child.
+    fn sift_down(&mut self, mut slot: u32, key: K) {
+        loop {
+            // Computed in `u64` to avoid overflowing for large heaps.
+            let left = 2 * u64::from(slot) + 1;
+            if left >= u64::from(self.len) {
+                break;
+            }
+            let left = left as u32;
+            let right = left + 1;
+            let (child, child_key) = {
+                let left_key = self.get_key(left);
+                if right < self.len {
+                    let right_key = self.get_key(right);
+                    if right_key < left_key {
+                        (right, right_key)
+                    } else {
+                        (left, left_key)
+                    }
+                } else {
+                    (left, left_key)
+                }
+            };
+            if key <= child_key {
+                break;
+            }
+            self.place(slot, child_key);
+            slot = child;
+        }
+        self.place(slot, key);
+    }
+}
+
+impl<K, V> StateBinaryHeap<K, V> {
+    /// Return the number of entries in the heap.
+    #[inline(always)]
+    pub fn len(&self) -> u32 { self.len }
+
+    /// Returns `true` if the heap contains no entries.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+impl<K, V> Serial for StateBinaryHeap<K, V> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.slots.serial(out)?;
+        self.positions.serial(out)?;
+        self.values.serial(out)?;
+        self.len.serial(out)
+    }
+}
+
+impl<K, V> DeserialWithState<StateApi> for StateBinaryHeap<K, V> {
+    fn deserial_with_state<R: Read>(state: &StateApi, source: &mut R) -> ParseResult<Self> {
+        let slots = StateMap::deserial_with_state(state, source)?;
+        let positions = StateMap::deserial_with_state(state, source)?;
+        let values = StateMap::deserial_with_state(state, source)?;
+        let len = source.get()?;
+        Ok(Self {
+            slots,
+            positions,
+            values,
+            len,
+        })
+    }
+}
+
+impl<K, V> Deletable for StateBinaryHeap<K, V>
+where
+    K: Serialize + Ord,
+    V: Serial + DeserialWithState<StateApi> + Deletable,
+{
+    fn delete(mut self) { self.clear(); }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_binary_heap {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, vec::Vec, StateBuilder};
+
+    /// Check that every key is smaller than its children, and that the
+    /// positions match the slots.
+    fn check_heap<K: Serialize + Ord, V: Serial + DeserialWithState<StateApi>>(
+        heap: &StateBinaryHeap<K, V>,
+    ) -> bool {
+        (0..heap.len()).all(|slot| {
+            let key = heap.get_key(slot);
+            let ordered = slot == 0 || heap.get_key((slot - 1) / 2) <= key;
+            ordered && heap.get_slot_of(&key) == Some(slot)
+        })
+    }
+
+    /// Test popping the entries in increasing order of the keys.
+    #[concordium_test]
+    fn test_binary_heap_pop_in_order() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut heap = state_builder.new_binary_heap();
+        for key in [5u32, 3, 8, 1, 9, 2, 7, 4, 6, 0] {
+            claim!(heap.push(key, key * 10).is_none());
+            claim!(check_heap(&heap));
+        }
+        claim_eq!(heap.len(), 10);
+        claim_eq!(heap.peek().map(|(k, v)| (*k, *v)), Some((0, 0)));
+        let popped: Vec<(u32, u32)> = (0..10).filter_map(|_| heap.pop()).collect();
+        claim_eq!(popped, (0..10).map(|k| (k, k * 10)).collect::<Vec<_>>());
+        claim!(heap.is_empty());
+        claim!(heap.pop().is_none());
+    }
+
+    /// Test pushing a key which is already in the heap replaces the value.
+    #[concordium_test]
+    fn test_binary_heap_push_present_key() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut heap = state_builder.new_binary_heap();
+        claim!(heap.push(1u32, 10u32).is_none());
+        claim_eq!(heap.push(1u32, 11u32), Some(10));
+        claim_eq!(heap.len(), 1);
+        claim_eq!(heap.get(&1).as_deref(), Some(&11));
+    }
+
+    /// Test removing keys in the middle of the heap, which can move the last
+    /// key both up and down.
+    #[concordium_test]
+    fn test_binary_heap_remove_by_key() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut heap = state_builder.new_binary_heap();
+        for key in [0u32, 10, 1, 11, 12, 2, 3] {
+            let _ = heap.push(key, ());
+        }
+        claim_eq!(heap.remove_by_key(&11), Some(()));
+        claim!(check_heap(&heap));
+        claim_eq!(heap.remove_by_key(&11), None);
+        claim_eq!(heap.remove_by_key(&0), Some(()));
+        claim!(check_heap(&heap));
+        claim!(!heap.contains_key(&0));
+        claim!(heap.contains_key(&12));
+        let popped: Vec<u32> = (0..5).filter_map(|_| heap.pop()).map(|(k, _)| k).collect();
+        claim_eq!(popped, [1, 2, 3, 10, 12]);
+    }
+}
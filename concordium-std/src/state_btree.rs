@@ -1,7 +1,15 @@
 use crate::{
-    self as concordium_std, cmp::Ordering, marker::PhantomData, mem, prims, vec::Vec, Deletable,
-    Deserial, DeserialWithState, Get, HasStateApi, ParseResult, Read, Serial, Serialize, StateApi,
-    StateItemPrefix, StateMap, StateRef, StateRefMut, UnwrapAbort, Write, STATE_ITEM_PREFIX_SIZE,
+    self as concordium_std,
+    cmp::Ordering,
+    iter::Rev,
+    marker::PhantomData,
+    mem,
+    ops::{Bound, RangeBounds},
+    prims,
+    vec::Vec,
+    Deletable, Deserial, DeserialWithState, Entry, Get, HasStateApi, OccupiedEntry, ParseResult,
+    Read, Serial, Serialize, StateApi, StateItemPrefix, StateMap, StateRef, StateRefMut,
+    UnwrapAbort, VacantEntry, Write, STATE_ITEM_PREFIX_SIZE,
 };
 
 /// An ordered map based on [B-Tree](https://en.wikipedia.org/wiki/B-tree), where
@@ -36,9 +44,11 @@ use crate::{
 /// - `M`: A `const usize` determining the _minimum degree_ of the B-tree.
 ///   _Must_ be a value of `2` or above for the tree to work. This can be used
 ///   to tweak the height of the tree vs size of each node in the tree. The
-///   default is set to 8, which seems to perform well on benchmarks. These
-///   benchmarks ran operations on a collection of 1000 elements, some using
-///   keys of 4 bytes others 16 bytes.
+///   default is 8. Which degree performs best depends on the size of the keys
+///   and on the operations used, and can be measured for a contract with the
+///   `Benchmark` and `ScalingCurve` of the
+///   [concordium-smart-contract-testing](https://docs.rs/concordium-smart-contract-testing)
+///   library.
 ///
 /// ## Usage
 ///
@@ -192,6 +202,30 @@ impl<const M: usize, K, V> StateBTreeMap<K, V, M> {
         }
     }
 
+    /// Get an entry for the given key, for looking up, inserting or modifying
+    /// the value in-place.
+    ///
+    /// ```no_run
+    /// # use concordium_std::*;
+    /// # let mut state_builder = StateBuilder::open(StateApi::open());
+    /// let mut stakes: StateBTreeMap<u32, u64> = state_builder.new_btree_map();
+    /// *stakes.entry(7).or_insert(0) += 100;
+    /// stakes.entry(7).and_modify(|stake| *stake /= 2);
+    /// assert_eq!(stakes.get(&7).as_deref(), Some(&50));
+    /// ```
+    pub fn entry(&mut self, key: K) -> StateBTreeMapEntry<'_, K, V, M>
+    where
+        K: Serialize + Ord,
+        V: Serial + DeserialWithState<StateApi>, {
+        match self.key_value.entry(key) {
+            Entry::Vacant(entry) => StateBTreeMapEntry::Vacant(StateBTreeMapVacantEntry {
+                entry,
+                key_order: &mut self.key_order,
+            }),
+            Entry::Occupied(entry) => StateBTreeMapEntry::Occupied(entry),
+        }
+    }
+
     /// Returns `true` if the map contains a value for the specified key.
     #[inline(always)]
     pub fn contains_key(&self, key: &K) -> bool
@@ -272,6 +306,84 @@ impl<const M: usize, K, V> StateBTreeMap<K, V, M> {
         }
     }
 
+    /// Create an iterator over the entries of [`StateBTreeMap`] with keys
+    /// within the range. Ordered by `K` ascending and empty if the start of
+    /// the range is larger than the end.
+    ///
+    /// The iterator is double-ended, see also
+    /// [`range_rev`](Self::range_rev).
+    ///
+    /// ```no_run
+    /// # use concordium_std::*;
+    /// # let mut state_builder = StateBuilder::open(StateApi::open());
+    /// let mut map = state_builder.new_btree_map();
+    /// for n in 0u32..100 {
+    ///     let _ = map.insert(n, n * 2);
+    /// }
+    /// // The next 5 entries after key 42.
+    /// let page: Vec<(u32, u32)> = map
+    ///     .range((ops::Bound::Excluded(42), ops::Bound::Unbounded))
+    ///     .take(5)
+    ///     .map(|(k, v)| (*k, *v))
+    ///     .collect();
+    /// assert_eq!(page, [(43, 86), (44, 88), (45, 90), (46, 92), (47, 94)]);
+    /// ```
+    #[inline(always)]
+    pub fn range<R>(&self, range: R) -> StateBTreeMapIter<K, V, M>
+    where
+        K: Serialize + Ord,
+        R: RangeBounds<K>, {
+        StateBTreeMapIter {
+            key_iter: self.key_order.range(range),
+            map:      &self.key_value,
+        }
+    }
+
+    /// Create an iterator over the entries of [`StateBTreeMap`] with keys
+    /// within the range, ordered by `K` descending.
+    /// This is the same as reversing the iterator from [`range`](Self::range).
+    #[inline(always)]
+    pub fn range_rev<R>(&self, range: R) -> Rev<StateBTreeMapIter<K, V, M>>
+    where
+        K: Serialize + Ord,
+        R: RangeBounds<K>, {
+        self.range(range).rev()
+    }
+
+    /// Like [`iter`](Self::iter), but allows modifying the values during
+    /// iteration. Ordered by `K` ascending.
+    pub fn iter_mut(&mut self) -> StateBTreeMapIterMut<K, V, M> {
+        StateBTreeMapIterMut {
+            key_iter: self.key_order.iter(),
+            map:      &mut self.key_value,
+        }
+    }
+
+    /// Retain only the entries for which the predicate returns `true`, visiting
+    /// the entries ordered by `K` ascending. The predicate may also modify the
+    /// values which are retained.
+    ///
+    /// The removed values are deleted from the state, including the values
+    /// pointed at, if `V`, for example, is a [StateBox](crate::StateBox).
+    /// The keys of the removed entries are collected during iteration and
+    /// removed afterwards, since the tree cannot be modified while it is
+    /// being iterated.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        K: Serialize + Ord,
+        V: Serial + DeserialWithState<StateApi> + Deletable,
+        F: FnMut(&K, &mut V) -> bool, {
+        let mut removed = Vec::new();
+        for (key, mut value) in self.iter_mut() {
+            if !f(&key, &mut value) {
+                removed.push(key.value);
+            }
+        }
+        for key in removed {
+            self.remove(&key);
+        }
+    }
+
     /// Clears the map, removing all key-value pairs.
     /// This also includes values pointed at, if `V`, for example, is a
     /// [StateBox](crate::StateBox). **If applicable use
@@ -325,9 +437,11 @@ impl<const M: usize, K, V> StateBTreeMap<K, V, M> {
 /// - `M`: A `const usize` determining the _minimum degree_ of the B-tree.
 ///   _Must_ be a value of `2` or above for the tree to work. This can be used
 ///   to tweak the height of the tree vs size of each node in the tree. The
-///   default is set to 8, which seems to perform well on benchmarks. These
-///   benchmarks ran operations on a collection of 1000 elements, some using
-///   keys of 4 bytes others 16 bytes.
+///   default is 8. Which degree performs best depends on the size of the keys
+///   and on the operations used, and can be measured for a contract with the
+///   `Benchmark` and `ScalingCurve` of the
+///   [concordium-smart-contract-testing](https://docs.rs/concordium-smart-contract-testing)
+///   library.
 ///
 /// ## Usage
 ///
@@ -479,14 +593,76 @@ impl<const M: usize, K> StateBTreeSet<K, M> {
     /// returns elements in increasing order.
     pub fn iter(&self) -> StateBTreeSetIter<K, M> {
         StateBTreeSetIter {
-            length:            self.len.try_into().unwrap_abort(),
-            next_node:         self.root,
-            depth_first_stack: Vec::new(),
-            tree:              self,
-            _marker_lifetime:  Default::default(),
+            length:           self.len.try_into().unwrap_abort(),
+            exact_length:     true,
+            front:            Cursor::new(self.root),
+            back:             Cursor::new(self.root),
+            front_last:       None,
+            back_last:        None,
+            front_stop:       None,
+            back_stop:        None,
+            tree:             self,
+            _marker_lifetime: Default::default(),
         }
     }
 
+    /// Get an iterator over the elements of the `StateBTreeSet` within the
+    /// range. The iterator returns elements in increasing order and is
+    /// empty if the start of the range is larger than the end.
+    ///
+    /// Only the nodes on the path to the bounds of the range and the nodes
+    /// holding the returned elements are looked up. The iterator is
+    /// double-ended, see also [`range_rev`](Self::range_rev).
+    ///
+    /// ```no_run
+    /// # use concordium_std::*;
+    /// # let mut state_builder = StateBuilder::open(StateApi::open());
+    /// let mut set = state_builder.new_btree_set();
+    /// for n in 0u32..100 {
+    ///     set.insert(n);
+    /// }
+    /// let page: Vec<u32> = set.range(10..20).map(|n| *n).collect();
+    /// assert_eq!(page, (10..20).collect::<Vec<_>>());
+    /// ```
+    pub fn range<R>(&self, range: R) -> StateBTreeSetIter<K, M>
+    where
+        K: Serialize + Ord,
+        R: RangeBounds<K>, {
+        let mut iter = StateBTreeSetIter {
+            length:           self.len.try_into().unwrap_abort(),
+            exact_length:     false,
+            front:            self.start_cursor(range.start_bound()),
+            back:             self.end_cursor(range.end_bound()),
+            front_last:       None,
+            back_last:        None,
+            front_stop:       None,
+            back_stop:        None,
+            tree:             self,
+            _marker_lifetime: Default::default(),
+        };
+        // Each end stops at the bound of the other end, which is where it starts.
+        // An empty range, where the ends start past each other, is handled up
+        // front.
+        match (iter.front.front_position(self), iter.back.back_position(self)) {
+            (Some(front), Some(back)) if iter.front.peek(front) <= iter.back.peek(back) => {
+                iter.front_stop = Some(back);
+                iter.back_stop = Some(front);
+            }
+            _ => iter.finish(),
+        }
+        iter
+    }
+
+    /// Get an iterator over the elements of the `StateBTreeSet` within the
+    /// range, returning elements in decreasing order.
+    /// This is the same as reversing the iterator from [`range`](Self::range).
+    pub fn range_rev<R>(&self, range: R) -> Rev<StateBTreeSetIter<K, M>>
+    where
+        K: Serialize + Ord,
+        R: RangeBounds<K>, {
+        self.range(range).rev()
+    }
+
     /// Clears the set, removing all elements.
     pub fn clear(&mut self) {
         // Reset the information.
@@ -1024,6 +1200,80 @@ impl<const M: usize, K> StateBTreeSet<K, M> {
         new_larger_sibling
     }
 
+    /// Internal function for constructing a cursor iterating forward from the
+    /// start bound of a range.
+    fn start_cursor(&self, start: Bound<&K>) -> Cursor<M, K>
+    where
+        K: Deserial + Ord, {
+        let (key, inclusive) = match start {
+            Bound::Unbounded => return Cursor::new(self.root),
+            Bound::Included(key) => (key, true),
+            Bound::Excluded(key) => (key, false),
+        };
+        let mut cursor = Cursor::empty();
+        let mut next = self.root;
+        while let Some(id) = next {
+            let node: Node<M, KeyWrapper<K>> = self.get_node(id);
+            let search = node.search_wrapped(key);
+            let index = match search {
+                Ok(index) if !inclusive => index + 1,
+                Ok(index) | Err(index) => index,
+            };
+            let child = node.children.get(index).copied();
+            if index < node.keys.len() {
+                cursor.depth_first_stack.push((id, node, index));
+            }
+            match search {
+                // Every key in the child is smaller than the start.
+                Ok(_) if inclusive => break,
+                // Every key in the child is larger than the start.
+                Ok(_) => {
+                    cursor.next_node = child;
+                    break;
+                }
+                Err(_) => next = child,
+            }
+        }
+        cursor
+    }
+
+    /// Internal function for constructing a cursor iterating backward from
+    /// the end bound of a range.
+    fn end_cursor(&self, end: Bound<&K>) -> Cursor<M, K>
+    where
+        K: Deserial + Ord, {
+        let (key, inclusive) = match end {
+            Bound::Unbounded => return Cursor::new(self.root),
+            Bound::Included(key) => (key, true),
+            Bound::Excluded(key) => (key, false),
+        };
+        let mut cursor = Cursor::empty();
+        let mut next = self.root;
+        while let Some(id) = next {
+            let node: Node<M, KeyWrapper<K>> = self.get_node(id);
+            let search = node.search_wrapped(key);
+            let index = match search {
+                Ok(index) if inclusive => index + 1,
+                Ok(index) | Err(index) => index,
+            };
+            let child = node.children.get(index).copied();
+            if index > 0 {
+                cursor.depth_first_stack.push((id, node, index));
+            }
+            match search {
+                // Every key in the child is larger than the end.
+                Ok(_) if inclusive => break,
+                // Every key in the child is smaller than the end.
+                Ok(_) => {
+                    cursor.next_node = child;
+                    break;
+                }
+                Err(_) => next = child,
+            }
+        }
+        cursor
+    }
+
     /// Internal function for looking up a node in the tree.
     /// This assumes the node is present and traps if this is not the case.
     fn get_node<Key>(&self, node_id: NodeId) -> Node<M, Key>
@@ -1047,65 +1297,220 @@ impl<const M: usize, K> StateBTreeSet<K, M> {
 
 /// An iterator over the entries of a [`StateBTreeSet`].
 ///
-/// Ordered by `K`.
+/// Ordered by `K`. The iterator is double-ended, so it can also be used to
+/// iterate the entries in decreasing order, e.g., using
+/// [`rev`](Iterator::rev).
 ///
-/// This `struct` is created by the [`iter`][StateBTreeSet::iter] method on
-/// [`StateBTreeSet`]. See its documentation for more.
+/// This `struct` is created by the [`iter`][StateBTreeSet::iter] and
+/// [`range`][StateBTreeSet::range] methods on [`StateBTreeSet`]. See their
+/// documentation for more.
 pub struct StateBTreeSetIter<'a, 'b, K, const M: usize> {
-    /// The number of elements left to iterate.
-    length:            usize,
-    /// Reference to a node in the tree to load and iterate before the current
-    /// node.
-    next_node:         Option<NodeId>,
-    /// Tracking the nodes depth first, which are currently being iterated.
-    depth_first_stack: Vec<(Node<M, KeyWrapper<K>>, usize)>,
+    /// An upper bound on the number of elements left to iterate.
+    length:           usize,
+    /// Whether `length` is the exact number of elements left to iterate, which
+    /// is the case when iterating the entire set.
+    exact_length:     bool,
+    /// Cursor iterating from the smallest key.
+    front:            Cursor<M, K>,
+    /// Cursor iterating from the largest key.
+    back:             Cursor<M, K>,
+    /// The position of the key last returned by iterating from the front.
+    front_last:       Option<(NodeId, usize)>,
+    /// The position of the key last returned by iterating from the back.
+    back_last:        Option<(NodeId, usize)>,
+    /// The position of the last key to return iterating from the front, i.e.,
+    /// the end of the range, or `None` when iterating the entire set.
+    front_stop:       Option<(NodeId, usize)>,
+    /// The position of the last key to return iterating from the back, i.e.,
+    /// the start of the range, or `None` when iterating the entire set.
+    back_stop:        Option<(NodeId, usize)>,
     /// Reference to the set, needed for looking up the nodes.
-    tree:              &'a StateBTreeSet<K, M>,
+    tree:             &'a StateBTreeSet<K, M>,
     /// Marker for tracking the lifetime of the key.
-    _marker_lifetime:  PhantomData<&'b K>,
+    _marker_lifetime: PhantomData<&'b K>,
 }
 
-impl<'a, 'b, const M: usize, K> Iterator for StateBTreeSetIter<'a, 'b, K, M>
-where
-    'a: 'b,
-    K: Deserial,
-{
-    type Item = StateRef<'b, K>;
+/// One end of a [`StateBTreeSetIter`], tracking the nodes on the path from the
+/// root to the next key.
+struct Cursor<const M: usize, K> {
+    /// Reference to a node in the tree to load and iterate before the nodes
+    /// on the stack. This is loaded lazily to avoid looking up nodes which are
+    /// never iterated.
+    next_node:         Option<NodeId>,
+    /// Tracking the nodes depth first, which are currently being iterated,
+    /// along with the index of the next key in the node. Iterating from the
+    /// back, the index is one more than the index of the next key.
+    depth_first_stack: Vec<(NodeId, Node<M, KeyWrapper<K>>, usize)>,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
+impl<const M: usize, K> Cursor<M, K> {
+    /// A cursor which is done iterating.
+    fn empty() -> Self {
+        Self {
+            next_node:         None,
+            depth_first_stack: Vec::new(),
+        }
+    }
+
+    /// A cursor starting from the first or last key in the subtree with the
+    /// given root.
+    fn new(root: Option<NodeId>) -> Self {
+        Self {
+            next_node:         root,
+            depth_first_stack: Vec::new(),
+        }
+    }
+
+    /// Load the nodes down to the next key going forward and return the
+    /// position of the key.
+    fn front_position(&mut self, tree: &StateBTreeSet<K, M>) -> Option<(NodeId, usize)>
+    where
+        K: Deserial, {
         while let Some(id) = self.next_node.take() {
-            let node = self.tree.get_node(id);
+            let node: Node<M, KeyWrapper<K>> = tree.get_node(id);
             if !node.is_leaf() {
                 self.next_node = Some(node.children[0]);
             }
-            self.depth_first_stack.push((node, 0));
+            self.depth_first_stack.push((id, node, 0));
+        }
+        let (id, _, index) = self.depth_first_stack.last()?;
+        Some((*id, *index))
+    }
+
+    /// Load the nodes down to the next key going backward and return the
+    /// position of the key.
+    fn back_position(&mut self, tree: &StateBTreeSet<K, M>) -> Option<(NodeId, usize)>
+    where
+        K: Deserial, {
+        while let Some(id) = self.next_node.take() {
+            let node: Node<M, KeyWrapper<K>> = tree.get_node(id);
+            if !node.is_leaf() {
+                self.next_node = node.children.last().copied();
+            }
+            let keys_len = node.keys.len();
+            self.depth_first_stack.push((id, node, keys_len));
         }
+        let (id, _, index) = self.depth_first_stack.last()?;
+        Some((*id, *index - 1))
+    }
 
-        let (node, index) = self.depth_first_stack.last_mut()?;
+    /// Take the next key going forward. Assumes the nodes are loaded using
+    /// [`front_position`](Self::front_position).
+    fn take_front(&mut self) -> Option<K> {
+        let (_, node, index) = self.depth_first_stack.last_mut()?;
         let key = node.keys[*index].key.take().unwrap_abort();
         *index += 1;
-        let no_more_keys = index == &node.keys.len();
+        let no_more_keys = *index == node.keys.len();
         if !node.is_leaf() {
-            let child_id = node.children[*index];
-            self.next_node = Some(child_id);
+            self.next_node = Some(node.children[*index]);
         }
         if no_more_keys {
             // This was the last key in the node, so remove the node from the stack.
             let _ = self.depth_first_stack.pop();
         }
+        Some(key)
+    }
+
+    /// Take the next key going backward. Assumes the nodes are loaded using
+    /// [`back_position`](Self::back_position).
+    fn take_back(&mut self) -> Option<K> {
+        let (_, node, index) = self.depth_first_stack.last_mut()?;
+        *index -= 1;
+        let key = node.keys[*index].key.take().unwrap_abort();
+        if !node.is_leaf() {
+            self.next_node = Some(node.children[*index]);
+        }
+        if *index == 0 {
+            // This was the first key in the node, so remove the node from the stack.
+            let _ = self.depth_first_stack.pop();
+        }
+        Some(key)
+    }
+
+    /// Peek at the next key. Assumes the nodes are loaded and the position is
+    /// the one returned by [`front_position`](Self::front_position) or
+    /// [`back_position`](Self::back_position).
+    fn peek(&self, position: (NodeId, usize)) -> Option<&K> {
+        let (_, node, _) = self.depth_first_stack.last()?;
+        node.keys[position.1].key.as_ref()
+    }
+}
+
+impl<'a, 'b, const M: usize, K> StateBTreeSetIter<'a, 'b, K, M> {
+    /// Mark the iterator as done, e.g., when the two ends meet.
+    fn finish(&mut self) {
+        self.front = Cursor::empty();
+        self.back = Cursor::empty();
+        self.length = 0;
+        self.exact_length = true;
+    }
+}
+
+impl<'a, 'b, const M: usize, K> Iterator for StateBTreeSetIter<'a, 'b, K, M>
+where
+    'a: 'b,
+    K: Deserial,
+{
+    type Item = StateRef<'b, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.front.front_position(self.tree)?;
+        if self.back_last == Some(position) {
+            // The key was already returned from the back.
+            self.finish();
+            return None;
+        }
+        let key = self.front.take_front()?;
+        self.front_last = Some(position);
         self.length -= 1;
+        if self.front_stop == Some(position) {
+            // This was the end of the range.
+            self.finish();
+        }
         Some(StateRef::new(key))
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) { (self.length, Some(self.length)) }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.exact_length {
+            (self.length, Some(self.length))
+        } else {
+            (0, Some(self.length))
+        }
+    }
+}
+
+impl<'a, 'b, const M: usize, K> DoubleEndedIterator for StateBTreeSetIter<'a, 'b, K, M>
+where
+    'a: 'b,
+    K: Deserial,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let position = self.back.back_position(self.tree)?;
+        if self.front_last == Some(position) {
+            // The key was already returned from the front.
+            self.finish();
+            return None;
+        }
+        let key = self.back.take_back()?;
+        self.back_last = Some(position);
+        self.length -= 1;
+        if self.back_stop == Some(position) {
+            // This was the start of the range.
+            self.finish();
+        }
+        Some(StateRef::new(key))
+    }
 }
 
 /// An iterator over the entries of a [`StateBTreeMap`].
 ///
-/// Ordered by `K`.
+/// Ordered by `K`. The iterator is double-ended, so it can also be used to
+/// iterate the entries in decreasing order, e.g., using
+/// [`rev`](Iterator::rev).
 ///
-/// This `struct` is created by the [`iter`][StateBTreeMap::iter] method on
-/// [`StateBTreeMap`]. See its documentation for more.
+/// This `struct` is created by the [`iter`][StateBTreeMap::iter] and
+/// [`range`][StateBTreeMap::range] methods on [`StateBTreeMap`]. See their
+/// documentation for more.
 pub struct StateBTreeMapIter<'a, 'b, K, V, const M: usize> {
     /// Iterator over the keys in the map.
     key_iter: StateBTreeSetIter<'a, 'b, K, M>,
@@ -1131,9 +1536,223 @@ where
     fn size_hint(&self) -> (usize, Option<usize>) { self.key_iter.size_hint() }
 }
 
+impl<'a, 'b, const M: usize, K, V> DoubleEndedIterator for StateBTreeMapIter<'a, 'b, K, V, M>
+where
+    'a: 'b,
+    K: Serialize,
+    V: Serial + DeserialWithState<StateApi> + 'b,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let next_key = self.key_iter.next_back()?;
+        // Unwrap is safe, otherwise the map and the set have inconsistencies.
+        let value = self.map.get(&next_key).unwrap_abort();
+        Some((next_key, value))
+    }
+}
+
+/// A mutable iterator over the entries of a [`StateBTreeMap`].
+///
+/// Ordered by `K`. The iterator is double-ended, like [`StateBTreeMapIter`].
+///
+/// This `struct` is created by the [`iter_mut`][StateBTreeMap::iter_mut]
+/// method on [`StateBTreeMap`]. See its documentation for more.
+pub struct StateBTreeMapIterMut<'a, 'b, K, V, const M: usize> {
+    /// Iterator over the keys in the map.
+    key_iter: StateBTreeSetIter<'a, 'b, K, M>,
+    /// Reference to the map holding the values.
+    map:      &'a mut StateMap<K, V, StateApi>,
+}
+
+impl<'a, 'b, const M: usize, K, V> StateBTreeMapIterMut<'a, 'b, K, V, M>
+where
+    K: Serialize,
+    V: Serial + DeserialWithState<StateApi>,
+{
+    /// Look up the value for a key returned by the key iterator.
+    fn value_mut(&self, key: &K) -> StateRefMut<'b, V, StateApi> {
+        let key_bytes = self.map.key_with_map_prefix(key);
+        // Unwrap is safe, otherwise the map and the set have inconsistencies.
+        let entry = self.map.state_api.lookup_entry(&key_bytes).unwrap_abort();
+        StateRefMut::new(entry, self.map.state_api.clone())
+    }
+}
+
+impl<'a, 'b, const M: usize, K, V> Iterator for StateBTreeMapIterMut<'a, 'b, K, V, M>
+where
+    'a: 'b,
+    K: Serialize,
+    V: Serial + DeserialWithState<StateApi> + 'b,
+{
+    type Item = (StateRef<'b, K>, StateRefMut<'b, V, StateApi>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next_key = self.key_iter.next()?;
+        let value = self.value_mut(&next_key);
+        Some((next_key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.key_iter.size_hint() }
+}
+
+impl<'a, 'b, const M: usize, K, V> DoubleEndedIterator for StateBTreeMapIterMut<'a, 'b, K, V, M>
+where
+    'a: 'b,
+    K: Serialize,
+    V: Serial + DeserialWithState<StateApi> + 'b,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let next_key = self.key_iter.next_back()?;
+        let value = self.value_mut(&next_key);
+        Some((next_key, value))
+    }
+}
+
+/// A view into a single entry in a [`StateBTreeMap`], which may either be
+/// vacant or occupied.
+///
+/// This `enum` is constructed from the [`entry`][StateBTreeMap::entry] method
+/// on a [`StateBTreeMap`] type. An occupied entry is the same as for a
+/// [`StateMap`], since the key is already tracked in the ordering of the keys.
+pub enum StateBTreeMapEntry<'a, K, V: Serial, const M: usize> {
+    Vacant(StateBTreeMapVacantEntry<'a, K, V, M>),
+    Occupied(OccupiedEntry<'a, K, V, StateApi>),
+}
+
+/// A view into a vacant entry in a [`StateBTreeMap`]. It is part of the
+/// [`StateBTreeMapEntry`] enum.
+pub struct StateBTreeMapVacantEntry<'a, K, V, const M: usize> {
+    /// The vacant entry in the map from key to value.
+    entry:     VacantEntry<'a, K, V, StateApi>,
+    /// The set tracking the order of the keys, which the key is inserted into
+    /// along with the value.
+    key_order: &'a mut StateBTreeSet<K, M>,
+}
+
+impl<'a, const M: usize, K, V> StateBTreeMapVacantEntry<'a, K, V, M>
+where
+    K: Serialize + Ord,
+    V: Serial,
+{
+    /// Get a reference to the `StateBTreeMapVacantEntry`'s key.
+    #[inline(always)]
+    pub fn key(&self) -> &K { self.entry.key() }
+
+    /// Take ownership of the key.
+    #[inline(always)]
+    pub fn into_key(self) -> K { self.entry.into_key() }
+
+    /// Sets the value of the entry with the `StateBTreeMapVacantEntry`'s key.
+    pub fn insert(self, value: V) -> OccupiedEntry<'a, K, V, StateApi> {
+        // The key is moved into the occupied entry, so the key inserted into the
+        // ordering is deserialized from the serialized key instead of requiring
+        // `K: Clone`. Unwrapping is safe, since the bytes are a serialized key.
+        let key = crate::from_bytes(&self.entry.key_bytes[STATE_ITEM_PREFIX_SIZE..]).unwrap_abort();
+        if !self.key_order.insert(key) {
+            // Inconsistency between the map and ordered_set.
+            crate::trap();
+        }
+        self.entry.insert(value)
+    }
+}
+
+impl<'a, const M: usize, K, V> StateBTreeMapEntry<'a, K, V, M>
+where
+    K: Serialize + Ord,
+    V: Serial,
+{
+    /// Return whether the entry is vacant.
+    #[inline(always)]
+    pub fn is_vacant(&self) -> bool { matches!(self, StateBTreeMapEntry::Vacant(_)) }
+
+    /// Return whether the entry is occupied.
+    #[inline(always)]
+    pub fn is_occupied(&self) -> bool { matches!(self, StateBTreeMapEntry::Occupied(_)) }
+
+    /// If the entry is [`Occupied`](StateBTreeMapEntry::Occupied) return `Ok`.
+    /// Otherwise return the supplied error.
+    #[inline]
+    pub fn occupied_or<E>(self, e: E) -> Result<OccupiedEntry<'a, K, V, StateApi>, E> {
+        match self {
+            StateBTreeMapEntry::Vacant(_) => Err(e),
+            StateBTreeMapEntry::Occupied(oe) => Ok(oe),
+        }
+    }
+
+    /// If the entry is [`Vacant`](StateBTreeMapEntry::Vacant) return `Ok`.
+    /// Otherwise return the supplied error.
+    #[inline]
+    pub fn vacant_or<E>(self, e: E) -> Result<StateBTreeMapVacantEntry<'a, K, V, M>, E> {
+        match self {
+            StateBTreeMapEntry::Vacant(vac) => Ok(vac),
+            StateBTreeMapEntry::Occupied(_) => Err(e),
+        }
+    }
+
+    /// Ensure a value is in the entry by inserting the provided value if the
+    /// entry is vacant.
+    pub fn or_insert(self, value: V) -> OccupiedEntry<'a, K, V, StateApi> {
+        match self {
+            StateBTreeMapEntry::Vacant(vac) => vac.insert(value),
+            StateBTreeMapEntry::Occupied(oe) => oe,
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty.
+    pub fn or_insert_with<F>(self, default: F) -> OccupiedEntry<'a, K, V, StateApi>
+    where
+        F: FnOnce() -> V, {
+        match self {
+            StateBTreeMapEntry::Vacant(vac) => vac.insert(default()),
+            StateBTreeMapEntry::Occupied(oe) => oe,
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty.
+    #[allow(clippy::unwrap_or_default)]
+    pub fn or_default(self) -> OccupiedEntry<'a, K, V, StateApi>
+    where
+        V: Default, {
+        self.or_insert_with(Default::default)
+    }
+
+    /// If the entry is occupied apply the given function to its contents.
+    /// If the function returns an error the contents are not updated.
+    /// **If the supplied function returns an error then it should not modify
+    /// the given value. If it does so than the map will become
+    /// inconsistent.** If the entry is vacant no changes are made.
+    pub fn and_try_modify<F, E>(mut self, f: F) -> Result<StateBTreeMapEntry<'a, K, V, M>, E>
+    where
+        F: FnOnce(&mut V) -> Result<(), E>, {
+        if let StateBTreeMapEntry::Occupied(ref mut occ) = self {
+            occ.try_modify(f)?;
+        }
+        Ok(self)
+    }
+
+    /// If the entry is occupied apply the given function to its contents.
+    /// If the entry is vacant no changes are made.
+    pub fn and_modify<F>(mut self, f: F) -> StateBTreeMapEntry<'a, K, V, M>
+    where
+        F: FnOnce(&mut V), {
+        if let StateBTreeMapEntry::Occupied(ref mut occ) = self {
+            occ.modify(f);
+        }
+        self
+    }
+
+    /// Return a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            StateBTreeMapEntry::Vacant(vac) => vac.key(),
+            StateBTreeMapEntry::Occupied(occ) => occ.key(),
+        }
+    }
+}
+
 /// Identifier for a node in the tree. Used to construct the key, where this
 /// node is stored in the smart contract key-value store.
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
 #[repr(transparent)]
 struct NodeId {
     id: u64,
@@ -1234,6 +1853,14 @@ impl<K: Deserial> Deserial for KeyWrapper<K> {
     }
 }
 
+impl<const M: usize, K: Ord> Node<M, KeyWrapper<K>> {
+    /// Binary search for a key in a node where none of the keys have been
+    /// taken yet.
+    fn search_wrapped(&self, key: &K) -> Result<usize, usize> {
+        self.keys.binary_search_by(|wrapper| wrapper.key.as_ref().unwrap_abort().cmp(key))
+    }
+}
+
 impl<const M: usize, K> Serial for StateBTreeSet<K, M> {
     fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
         self.prefix.serial(out)?;
@@ -1799,6 +2426,178 @@ mod wasm_test_btree {
         claim_eq!(keys, iter_keys);
     }
 
+    /// Test reverse iteration of the set.
+    #[concordium_test]
+    fn test_btree_iter_rev() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut tree = state_builder.new_btree_set_degree::<2, _>();
+        let keys: Vec<u32> = (0..15).into_iter().collect();
+        for &k in &keys {
+            tree.insert(k);
+        }
+        let iter_keys: Vec<u32> = tree.iter().rev().map(|k| k.clone()).collect();
+        claim_eq!(keys.into_iter().rev().collect::<Vec<_>>(), iter_keys);
+    }
+
+    /// Test iterating the set alternately from both ends, ensuring every key
+    /// is returned exactly once.
+    #[concordium_test]
+    fn test_btree_iter_both_ends() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut tree = state_builder.new_btree_set_degree::<2, _>();
+        for k in 0..15u32 {
+            tree.insert(k);
+        }
+        let mut iter = tree.iter();
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        loop {
+            let Some(k) = iter.next() else {
+                break;
+            };
+            front.push(*k);
+            let Some(k) = iter.next_back() else {
+                break;
+            };
+            back.push(*k);
+        }
+        claim_eq!(front, [0, 1, 2, 3, 4, 5, 6, 7]);
+        claim_eq!(back, [14, 13, 12, 11, 10, 9, 8]);
+        claim!(iter.next().is_none());
+        claim!(iter.next_back().is_none());
+    }
+
+    /// Test range queries on the set with the different kinds of bounds,
+    /// including bounds which are not in the set and empty ranges.
+    #[concordium_test]
+    fn test_btree_range() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut tree = state_builder.new_btree_set_degree::<2, _>();
+        for k in (0..30u32).step_by(2) {
+            tree.insert(k);
+        }
+        fn collect<'b>(iter: impl Iterator<Item = StateRef<'b, u32>>) -> Vec<u32> {
+            iter.map(|k| *k).collect()
+        }
+        claim_eq!(collect(tree.range(4..10)), [4, 6, 8]);
+        claim_eq!(collect(tree.range(4..=10)), [4, 6, 8, 10]);
+        claim_eq!(collect(tree.range(3..11)), [4, 6, 8, 10]);
+        claim_eq!(collect(tree.range((Bound::Excluded(4), Bound::Included(10)))), [6, 8, 10]);
+        claim_eq!(collect(tree.range(..5)), [0, 2, 4]);
+        claim_eq!(collect(tree.range(25..)), [26, 28]);
+        claim_eq!(collect(tree.range(..)), (0..30).step_by(2).collect::<Vec<_>>());
+        claim_eq!(collect(tree.range(5..6)), Vec::<u32>::new());
+        claim_eq!(collect(tree.range(10..4)), Vec::<u32>::new());
+        claim_eq!(collect(tree.range(30..)), Vec::<u32>::new());
+        claim_eq!(collect(tree.range(4..4)), Vec::<u32>::new());
+        claim_eq!(collect(tree.range(4..=4)), [4]);
+        claim_eq!(collect(tree.range_rev(3..11)), [10, 8, 6, 4]);
+        // Both ends stop at the bounds of the range when interleaved.
+        let mut iter = tree.range(3..11);
+        claim_eq!(iter.next().as_deref(), Some(&4));
+        claim_eq!(iter.next_back().as_deref(), Some(&10));
+        claim_eq!(collect(iter), [6, 8]);
+    }
+
+    /// Test range queries on the map, ensuring the values follow the keys.
+    #[concordium_test]
+    fn test_btree_map_range() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_btree_map_degree::<2, _, _>();
+        for k in 0..20u32 {
+            let _ = map.insert(k, k * 10);
+        }
+        let entries: Vec<(u32, u32)> = map.range(5..8).map(|(k, v)| (*k, *v)).collect();
+        claim_eq!(entries, [(5, 50), (6, 60), (7, 70)]);
+        let entries: Vec<(u32, u32)> = map.range_rev(..3).map(|(k, v)| (*k, *v)).collect();
+        claim_eq!(entries, [(2, 20), (1, 10), (0, 0)]);
+        let last = map.iter().next_back().map(|(k, v)| (*k, *v));
+        claim_eq!(last, Some((19, 190)));
+    }
+
+    /// Test the entry API of the map, ensuring inserting through a vacant
+    /// entry also tracks the key in the ordering.
+    #[concordium_test]
+    fn test_btree_map_entry() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_btree_map_degree::<2, _, _>();
+        for k in [5u32, 1, 3] {
+            claim!(map.entry(k).is_vacant());
+            *map.entry(k).or_insert(0u64) += u64::from(k);
+        }
+        claim!(map.entry(3).is_occupied());
+        map.entry(3).and_modify(|v| *v *= 10);
+        let _ = map.entry(4).or_default();
+        claim_eq!(map.len(), 4);
+        let entries: Vec<(u32, u64)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        claim_eq!(entries, [(1, 1), (3, 30), (4, 0), (5, 5)]);
+        claim_eq!(map.first_key().as_deref(), Some(&1));
+        claim_eq!(map.entry(7).vacant_or(()).map(|vac| vac.into_key()), Ok(7));
+        claim!(!map.contains_key(&7));
+    }
+
+    /// Test modifying every value of the map using `iter_mut`.
+    #[concordium_test]
+    fn test_btree_map_iter_mut() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_btree_map_degree::<2, _, _>();
+        for k in 0..10u32 {
+            let _ = map.insert(k, u64::from(k) * 100);
+        }
+        for (_, mut stake) in map.iter_mut() {
+            *stake /= 2;
+        }
+        for (k, mut stake) in map.iter_mut().rev().take(2) {
+            *stake += u64::from(*k);
+        }
+        let entries: Vec<u64> = map.iter().map(|(_, v)| *v).collect();
+        claim_eq!(entries, [0, 50, 100, 150, 200, 250, 300, 350, 408, 459]);
+    }
+
+    /// Test `retain` removes the entries from both the map and the ordering of
+    /// the keys, and keeps the modifications of the retained values.
+    #[concordium_test]
+    fn test_btree_map_retain() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_btree_map_degree::<2, _, _>();
+        for k in 0..20u32 {
+            let _ = map.insert(k, k);
+        }
+        map.retain(|k, v| {
+            *v += 1;
+            k % 3 == 0
+        });
+        claim_eq!(map.len(), 7);
+        let entries: Vec<(u32, u32)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        claim_eq!(entries, [(0, 1), (3, 4), (6, 7), (9, 10), (12, 13), (15, 16), (18, 19)]);
+        claim!(map.get(&1).is_none());
+        claim_eq!(map.higher(&3).as_deref(), Some(&6));
+    }
+
+    /// Test `retain` deletes the nested state of the removed values.
+    #[concordium_test]
+    fn test_btree_map_retain_deletes_nested_state() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_btree_map();
+        for k in 0..4u32 {
+            let value = state_builder.new_box(k);
+            let _ = map.insert(k, value);
+        }
+        let count_entries = || {
+            let mut state_api = StateApi::open();
+            let mut iter = state_api.iterator(&[]).unwrap_abort();
+            let count = iter.by_ref().count();
+            // Delete the iterator to unlock the state.
+            state_api.delete_iterator(iter);
+            count
+        };
+        let entries_before = count_entries();
+        map.retain(|k, _| k % 2 == 0);
+        // The map entries and the boxes for the two removed keys are deleted. The
+        // keys are all in the root node of the tree, which is kept.
+        claim_eq!(count_entries(), entries_before - 4);
+    }
+
     /// Testcase for duplicate keys in the set. Due to an edge case where the
     /// key moved up as part of splitting a child node, is equal to the
     /// inserted key.
@@ -1906,6 +2705,38 @@ mod wasm_test_btree {
             TestResult::passed()
         }
 
+        /// Quickcheck inserting random items, then query the tree for a range
+        /// between two random bounds, comparing with the sorted items both
+        /// forward and in reverse.
+        #[concordium_quickcheck(num_tests = 100)]
+        fn quickcheck_btree_range(mut items: Vec<u32>, start: u32, end: u32) -> TestResult {
+            let mut state_builder = StateBuilder::open(StateApi::open());
+            let mut tree = state_builder.new_btree_set_degree::<2, _>();
+            for k in items.clone() {
+                tree.insert(k);
+            }
+
+            items.sort();
+            items.dedup();
+            let expected: Vec<u32> =
+                items.into_iter().filter(|k| (start..=end).contains(k)).collect();
+
+            let forward: Vec<u32> = tree.range(start..=end).map(|k| *k).collect();
+            if forward != expected {
+                return TestResult::error(format!(
+                    "range({start}..={end}) gave {forward:?} instead of {expected:?}"
+                ));
+            }
+            let mut backward: Vec<u32> = tree.range_rev(start..=end).map(|k| *k).collect();
+            backward.reverse();
+            if backward != expected {
+                return TestResult::error(format!(
+                    "range_rev({start}..={end}) gave {backward:?} reversed instead of {expected:?}"
+                ));
+            }
+            TestResult::passed()
+        }
+
         /// Quickcheck inserting random items, then we call query the tree for
         /// higher and lower of every item validating the outcome.
         #[concordium_quickcheck(num_tests = 100)]
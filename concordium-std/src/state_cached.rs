@@ -0,0 +1,341 @@
+use crate::{
+    cell::Cell, collections::BTreeMap, Deletable, DeserialWithState, HasStateApi, ParseResult,
+    Read, Serial, Serialize, StateMap, StateMapIter, UnwrapAbort, Vec, Write,
+};
+
+/// A [`StateMap`] which keeps the values it has looked up in memory, such that
+/// repeated lookups and modifications of the same keys only access the
+/// contract state once.
+///
+/// Values are deserialized on first access and kept in memory, and changes to
+/// the map are only written to the contract state when the map is stored,
+/// i.e., when the contract state is stored at the end of a mutable receive
+/// function, or before invoking another contract with
+/// [`invoke_contract`](crate::HasHost::invoke_contract). If the state is
+/// modified by the invoked contract, the contract state, including this map, is
+/// reloaded, which discards the cached values. The map can also be written
+/// explicitly using [`flush`](Self::flush).
+///
+/// The map is stored in the same way as a [`StateMap`], so a `StateMap` in the
+/// contract state can be replaced by a `CachedStateMap` with the same key and
+/// value types without migrating the state.
+///
+/// This is useful when the same entries are read and written many times in a
+/// single call, for example the balances in a batch of transfers. For maps
+/// where each entry is only accessed once per call, a [`StateMap`] is cheaper,
+/// since it avoids keeping track of the cache.
+///
+/// ## Usage
+///
+/// New maps can be constructed using the
+/// [`new_cached_map`](crate::StateBuilder::new_cached_map) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// let mut balances = state_builder.new_cached_map();
+/// let alice = AccountAddress([0u8; 32]);
+/// let _ = balances.insert(alice, 100u64);
+/// for _ in 0..10 {
+///     // Only the first lookup reads from the contract state.
+///     *balances.get_mut(&alice).unwrap() -= 1;
+/// }
+/// // Write the final balance to the contract state.
+/// balances.flush();
+/// ```
+///
+/// ### **Caution**
+///
+/// Changes to the map are not visible to other contracts until the map is
+/// stored. In particular, the contract state is not stored before a call to
+/// [`invoke_contract_read_only`](crate::HasHost::invoke_contract_read_only), so
+/// use [`flush`](Self::flush) before such calls if the invoked contract reads
+/// the map, for example through a view function.
+///
+/// Like `StateMap`s, `CachedStateMap`s must be explicitly deleted when they
+/// are no longer needed, otherwise they will remain in the contract's state,
+/// albeit unreachable.
+#[derive(Debug)]
+pub struct CachedStateMap<K, V, S> {
+    /// The map in the contract state.
+    pub(crate) map:   StateMap<K, V, S>,
+    /// The values which have been loaded or modified, indexed by the key of
+    /// the entry in the contract state.
+    pub(crate) cache: BTreeMap<Vec<u8>, CachedValue<V>>,
+}
+
+/// A value of a [`CachedStateMap`] kept in memory.
+#[derive(Debug)]
+pub(crate) struct CachedValue<V> {
+    /// The value, or `None` if there is no value with the key.
+    value:    Option<V>,
+    /// Whether the value differs from the one in the contract state. This is
+    /// a [`Cell`], such that it can be reset when the map is stored through a
+    /// shared reference.
+    modified: Cell<bool>,
+}
+
+impl<K, V, S> CachedStateMap<K, V, S> {
+    /// Create a new map with an empty cache.
+    pub(crate) fn new(map: StateMap<K, V, S>) -> Self {
+        CachedStateMap {
+            map,
+            cache: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K, V, S> CachedStateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serial + DeserialWithState<S>,
+{
+    /// Lookup the value with the given key. Return [None] if there is no value
+    /// with the given key.
+    ///
+    /// This takes a mutable reference, since the value is loaded into the
+    /// cache if it is not already there.
+    pub fn get(&mut self, key: &K) -> Option<&V> { self.cached(key).value.as_ref() }
+
+    /// Lookup a mutable reference to the value with the given key. Return
+    /// [None] if there is no value with the given key.
+    ///
+    /// The value is marked as modified, and is written to the contract state
+    /// when the map is stored.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let cached = self.cached(key);
+        *cached.modified.get_mut() |= cached.value.is_some();
+        cached.value.as_mut()
+    }
+
+    /// Return `true` if the map contains a value with the given key. Like
+    /// [`get`](Self::get), this loads the value into the cache.
+    pub fn contains_key(&mut self, key: &K) -> bool { self.cached(key).value.is_some() }
+
+    /// Inserts the value with the given key. If a value already exists at the
+    /// given key it is replaced and the old value is returned.
+    ///
+    /// *Caution*: If `Option<V>` is to be deleted and contains a data structure
+    /// prefixed with `State` (such as [StateBox](crate::StateBox) or
+    /// [StateMap]), then it is important to call [`Deletable::delete`] on the
+    /// value returned when you're finished with it. Otherwise, it will remain
+    /// in the contract state.
+    #[must_use]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let cached = self.cached(&key);
+        *cached.modified.get_mut() = true;
+        cached.value.replace(value)
+    }
+
+    /// Remove a key from the map, returning the value at the key if the key was
+    /// previously in the map.
+    ///
+    /// *Caution*: If `V` is a [StateBox](crate::StateBox), [StateMap], then it
+    /// is important to call [`Deletable::delete`] on the value returned when
+    /// you're finished with it. Otherwise, it will remain in the contract
+    /// state.
+    #[must_use]
+    pub fn remove_and_get(&mut self, key: &K) -> Option<V> {
+        let cached = self.cached(key);
+        let old_value = cached.value.take();
+        *cached.modified.get_mut() |= old_value.is_some();
+        old_value
+    }
+
+    /// Remove a key from the map.
+    /// This also deletes the value in the state.
+    pub fn remove(&mut self, key: &K)
+    where
+        V: Deletable, {
+        if let Some(v) = self.remove_and_get(key) {
+            v.delete()
+        }
+    }
+
+    /// Write the modified values to the contract state. The values are kept in
+    /// memory.
+    pub fn flush(&mut self) { self.flush_cache() }
+
+    /// Get an iterator over the key-value pairs of the map, after writing the
+    /// modified values to the contract state. The iterator returns values in
+    /// increasing order of keys, where keys are ordered lexicographically via
+    /// their serializations.
+    pub fn iter(&mut self) -> StateMapIter<'_, K, V, S> {
+        self.flush_cache();
+        self.map.iter()
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    /// This also includes values pointed at, if `V`, for example, is a
+    /// [StateBox](crate::StateBox). **If applicable use
+    /// [`clear_flat`](Self::clear_flat) instead.**
+    pub fn clear(&mut self)
+    where
+        V: Deletable, {
+        // Values which are only in memory might also point to state.
+        self.flush_cache();
+        self.cache.clear();
+        self.map.clear();
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    /// **This should be used over [`clear`](Self::clear) if it is
+    /// applicable.** It avoids recursive deletion of values since the
+    /// values are required to be _flat_.
+    pub fn clear_flat(&mut self)
+    where
+        V: crate::Deserial, {
+        self.cache.clear();
+        self.map.clear_flat();
+    }
+
+    /// Get the cached value with the given key, loading it from the contract
+    /// state if it is not cached.
+    fn cached(&mut self, key: &K) -> &mut CachedValue<V> {
+        let key_bytes = self.map.key_with_map_prefix(key);
+        let state_api = &self.map.state_api;
+        self.cache.entry(key_bytes).or_insert_with_key(|key_bytes| {
+            let value = state_api.lookup_entry(key_bytes).map(|mut entry| {
+                // Unwrapping is safe when using only the high-level API.
+                V::deserial_with_state(state_api, &mut entry).unwrap_abort()
+            });
+            CachedValue {
+                value,
+                modified: Cell::new(false),
+            }
+        })
+    }
+}
+
+impl<K, V: Serial, S: HasStateApi> CachedStateMap<K, V, S> {
+    /// Write the modified values to the contract state. This only takes a
+    /// shared reference, such that it can be done when serializing the map.
+    fn flush_cache(&self) {
+        let mut state_api = self.map.state_api.clone();
+        for (key_bytes, cached) in self.cache.iter().filter(|(_, cached)| cached.modified.get()) {
+            match &cached.value {
+                Some(value) => {
+                    // Creating the entry resets any existing value.
+                    // Unwrapping is safe when using only the high-level API.
+                    let mut entry = state_api.create_entry(key_bytes).unwrap_abort();
+                    value.serial(&mut entry).unwrap_abort();
+                }
+                None => {
+                    if let Some(entry) = state_api.lookup_entry(key_bytes) {
+                        state_api.delete_entry(entry).unwrap_abort();
+                    }
+                }
+            }
+            cached.modified.set(false);
+        }
+    }
+}
+
+impl<K, V: Serial, S: HasStateApi> Serial for CachedStateMap<K, V, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.flush_cache();
+        self.map.serial(out)
+    }
+}
+
+impl<K, V, S> DeserialWithState<S> for CachedStateMap<K, V, S>
+where
+    S: HasStateApi,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        Ok(CachedStateMap::new(StateMap::deserial_with_state(state, source)?))
+    }
+}
+
+impl<K, V, S> Deletable for CachedStateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serial + DeserialWithState<S> + Deletable,
+{
+    fn delete(mut self) { self.clear() }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_cached {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, StateApi, StateBuilder};
+
+    /// Test that changes are only visible in the contract state after flushing.
+    #[concordium_test]
+    fn test_cached_map_flush() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_cached_map();
+        claim!(map.insert(1u8, 10u32).is_none());
+        claim!(map.insert(2u8, 20u32).is_none());
+        *map.get_mut(&1).unwrap() += 1;
+        claim_eq!(map.get(&1), Some(&11));
+        claim!(map.map.get(&1).is_none(), "Changes should not be written before flushing.");
+
+        map.flush();
+        claim_eq!(map.map.get(&1).as_deref(), Some(&11));
+        claim_eq!(map.map.get(&2).as_deref(), Some(&20));
+
+        claim_eq!(map.remove_and_get(&2), Some(20));
+        claim!(!map.contains_key(&2));
+        claim!(map.map.get(&2).is_some());
+        map.flush();
+        claim!(map.map.get(&2).is_none());
+    }
+
+    /// Test that values already in the contract state are read through the
+    /// cache, and that storing the map writes the changes.
+    #[concordium_test]
+    fn test_cached_map_stored() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut inner = state_builder.new_map();
+        let _ = inner.insert(1u8, 1u32);
+        let _ = inner.insert(2u8, 2u32);
+        let bytes = crate::to_bytes(&inner);
+        let mut map: CachedStateMap<u8, u32, _> =
+            CachedStateMap::deserial_with_state(&StateApi::open(), &mut crate::Cursor::new(&bytes))
+                .expect("Deserializing should succeed");
+        claim_eq!(map.get(&1), Some(&1));
+        claim_eq!(map.get(&3), None);
+        *map.get_mut(&2).unwrap() = 5;
+        map.remove(&1);
+        claim_eq!(crate::to_bytes(&map), bytes, "The map is stored like a StateMap.");
+        claim!(inner.get(&1).is_none());
+        claim_eq!(inner.get(&2).as_deref(), Some(&5));
+    }
+
+    /// Test that only modified values are written back.
+    #[concordium_test]
+    fn test_cached_map_only_modified_written() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_cached_map();
+        let _ = map.insert(1u8, 1u32);
+        map.flush();
+        claim_eq!(map.get(&1), Some(&1));
+        // Change the value behind the back of the cache, such that we can
+        // observe whether the cached value is written again.
+        let _ = map.map.insert(1, 2);
+        map.flush();
+        claim_eq!(map.map.get(&1).as_deref(), Some(&2));
+    }
+
+    /// Test that iterating writes the changes first.
+    #[concordium_test]
+    fn test_cached_map_iter() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_cached_map();
+        for k in 0..5u8 {
+            let _ = map.insert(k, u32::from(k));
+        }
+        map.remove(&0);
+        let entries: Vec<(u8, u32)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        claim_eq!(entries, [(1, 1), (2, 2), (3, 3), (4, 4)]);
+        map.clear_flat();
+        claim!(map.get(&1).is_none());
+        claim!(map.map.is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{
+    Deletable, Deserial, DeserialWithState, Entry, Get, HasStateApi, ParseResult, Read, Serial,
+    Serialize, StateMap, StateMapIter, StateMapIterMut, StateRef, StateRefMut, StateSet,
+    StateSetIter, Write,
+};
+
+/// A [`StateMap`] which keeps track of the number of entries, providing
+/// [`len`](Self::len) in O(1).
+///
+/// The number of entries is stored along with the prefix of the map, i.e., in
+/// the value containing the map, such as the root state of the contract. This
+/// means that the containing value must be stored after modifying the map,
+/// which happens automatically when the map is part of the contract state.
+/// Apart from [`len`](Self::len), the map behaves exactly like a
+/// [`StateMap`], with the same performance.
+///
+/// ## Usage
+///
+/// New maps can be constructed using the
+/// [`new_counted_map`](crate::StateBuilder::new_counted_map) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// let mut voters = state_builder.new_counted_map();
+/// let _ = voters.insert(AccountAddress([0u8; 32]), 1u8);
+/// *voters.entry(AccountAddress([1u8; 32])).or_insert(0) += 1;
+/// assert_eq!(voters.len(), 2);
+/// ```
+///
+/// ### **Caution**
+///
+/// Like `StateMap`s, `CountedStateMap`s must be explicitly deleted when they
+/// are no longer needed, otherwise they will remain in the contract's state,
+/// albeit unreachable.
+#[derive(Debug)]
+pub struct CountedStateMap<K, V, S> {
+    /// The map holding the entries.
+    pub(crate) map: StateMap<K, V, S>,
+    /// The number of entries in the map.
+    pub(crate) len: u32,
+}
+
+/// A [`StateSet`] which keeps track of the number of elements, providing
+/// [`len`](Self::len) in O(1).
+///
+/// The number of elements is stored in the same way as for
+/// [`CountedStateMap`]. Apart from [`len`](Self::len), the set behaves exactly
+/// like a [`StateSet`].
+///
+/// New sets can be constructed using the
+/// [`new_counted_set`](crate::StateBuilder::new_counted_set) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+#[derive(Debug)]
+pub struct CountedStateSet<T, S> {
+    /// The set holding the elements.
+    pub(crate) set: StateSet<T, S>,
+    /// The number of elements in the set.
+    pub(crate) len: u32,
+}
+
+impl<K, V, S> CountedStateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serial + DeserialWithState<S>,
+{
+    /// Lookup the value with the given key. Return [None] if there is no value
+    /// with the given key.
+    #[inline(always)]
+    pub fn get(&self, key: &K) -> Option<StateRef<V>> {
+        // Since the length is tracked, we can return early for the empty map,
+        // saving a key lookup.
+        if self.len == 0 {
+            None
+        } else {
+            self.map.get(key)
+        }
+    }
+
+    /// Lookup a mutable reference to the value with the given key. Return
+    /// [None] if there is no value with the given key.
+    #[inline(always)]
+    pub fn get_mut(&mut self, key: &K) -> Option<StateRefMut<V, S>> {
+        if self.len == 0 {
+            None
+        } else {
+            self.map.get_mut(key)
+        }
+    }
+
+    /// Inserts the value with the given key. If a value already exists at the
+    /// given key it is replaced and the old value is returned.
+    ///
+    /// *Caution*: If `Option<V>` is to be deleted and contains a data structure
+    /// prefixed with `State` (such as [StateBox](crate::StateBox) or
+    /// [StateMap]), then it is important to call [`Deletable::delete`] on the
+    /// value returned when you're finished with it. Otherwise, it will remain
+    /// in the contract state.
+    #[must_use]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old_value = self.map.insert(key, value);
+        if old_value.is_none() {
+            self.len += 1;
+        }
+        old_value
+    }
+
+    /// Get an entry for the given key. Inserting into a vacant entry
+    /// increments the length of the map.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        match self.map.entry(key) {
+            Entry::Vacant(mut vac) => {
+                vac.counter = Some(&mut self.len);
+                Entry::Vacant(vac)
+            }
+            occupied => occupied,
+        }
+    }
+
+    /// Remove a key from the map, returning the value at the key if the key was
+    /// previously in the map.
+    ///
+    /// *Caution*: If `V` is a [StateBox](crate::StateBox), [StateMap], then it
+    /// is important to call [`Deletable::delete`] on the value returned when
+    /// you're finished with it. Otherwise, it will remain in the contract
+    /// state.
+    #[must_use]
+    pub fn remove_and_get(&mut self, key: &K) -> Option<V> {
+        let value = self.map.remove_and_get(key);
+        if value.is_some() {
+            self.len -= 1;
+        }
+        value
+    }
+
+    /// Remove a key from the map.
+    /// This also deletes the value in the state.
+    pub fn remove(&mut self, key: &K)
+    where
+        V: Deletable, {
+        if let Some(value) = self.remove_and_get(key) {
+            value.delete()
+        }
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    /// This also includes values pointed at, if `V`, for example, is a
+    /// [StateBox](crate::StateBox). **If applicable use
+    /// [`clear_flat`](Self::clear_flat) instead.**
+    pub fn clear(&mut self)
+    where
+        V: Deletable, {
+        self.map.clear();
+        self.len = 0;
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    /// **This should be used over [`clear`](Self::clear) if it is
+    /// applicable.** It avoids recursive deletion of values since the
+    /// values are required to be _flat_.
+    pub fn clear_flat(&mut self)
+    where
+        V: Deserial, {
+        self.map.clear_flat();
+        self.len = 0;
+    }
+}
+
+impl<K, V, S: HasStateApi> CountedStateMap<K, V, S> {
+    /// Return the number of entries in the map.
+    #[inline(always)]
+    pub fn len(&self) -> u32 { self.len }
+
+    /// Return `true` if the map contains no elements.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Get an iterator over the key-value pairs of the map. The iterator
+    /// returns values in increasing order of keys, where keys are ordered
+    /// lexicographically via their serializations.
+    #[inline(always)]
+    pub fn iter(&self) -> StateMapIter<'_, K, V, S> { self.map.iter() }
+
+    /// Like [iter](Self::iter), but allows modifying the values during
+    /// iteration.
+    #[inline(always)]
+    pub fn iter_mut(&mut self) -> StateMapIterMut<'_, K, V, S> { self.map.iter_mut() }
+}
+
+impl<T, S> CountedStateSet<T, S>
+where
+    T: Serialize,
+    S: HasStateApi,
+{
+    /// Adds a value to the set.
+    /// If the set did not have this value, `true` is returned. Otherwise,
+    /// `false`.
+    pub fn insert(&mut self, value: T) -> bool {
+        let new = self.set.insert(value);
+        if new {
+            self.len += 1;
+        }
+        new
+    }
+
+    /// Returns `true` if the set contains a value.
+    #[inline(always)]
+    pub fn contains(&self, value: &T) -> bool { self.len != 0 && self.set.contains(value) }
+
+    /// Removes a value from the set. Returns whether the value was present in
+    /// the set.
+    pub fn remove(&mut self, value: &T) -> bool {
+        let removed = self.set.remove(value);
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Clears the set, removing all values.
+    pub fn clear(&mut self) {
+        self.set.clear();
+        self.len = 0;
+    }
+}
+
+impl<T, S: HasStateApi> CountedStateSet<T, S> {
+    /// Return the number of elements in the set.
+    #[inline(always)]
+    pub fn len(&self) -> u32 { self.len }
+
+    /// Returns `true` if the set contains no elements.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Get an iterator over the elements in the set. The iterator returns
+    /// elements in increasing order, where elements are ordered
+    /// lexicographically via their serializations.
+    #[inline(always)]
+    pub fn iter(&self) -> StateSetIter<T, S> { self.set.iter() }
+}
+
+impl<K, V, S> Serial for CountedStateMap<K, V, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.map.serial(out)?;
+        self.len.serial(out)
+    }
+}
+
+impl<K, V, S> DeserialWithState<S> for CountedStateMap<K, V, S>
+where
+    S: HasStateApi,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        let map = StateMap::deserial_with_state(state, source)?;
+        let len = source.get()?;
+        Ok(Self {
+            map,
+            len,
+        })
+    }
+}
+
+impl<K, V, S> Deletable for CountedStateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serial + DeserialWithState<S> + Deletable,
+{
+    fn delete(self) { self.map.delete() }
+}
+
+impl<T, S> Serial for CountedStateSet<T, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.set.serial(out)?;
+        self.len.serial(out)
+    }
+}
+
+impl<T, S> DeserialWithState<S> for CountedStateSet<T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S>,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        let set = StateSet::deserial_with_state(state, source)?;
+        let len = source.get()?;
+        Ok(Self {
+            set,
+            len,
+        })
+    }
+}
+
+impl<T, S> Deletable for CountedStateSet<T, S>
+where
+    S: HasStateApi,
+{
+    fn delete(self) { self.set.delete() }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_counted {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, StateApi, StateBuilder};
+
+    /// Test the length of the map is tracked through inserting, overwriting,
+    /// using entries and removing.
+    #[concordium_test]
+    fn test_counted_map_len() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_counted_map();
+        claim!(map.is_empty());
+        for k in 0..10u8 {
+            claim!(map.insert(k, u32::from(k)).is_none());
+        }
+        claim!(map.insert(3, 30).is_some());
+        claim_eq!(map.len(), 10);
+        *map.entry(3).or_insert(0) += 1;
+        *map.entry(10).or_insert(0) += 1;
+        let _ = map.entry(11).or_default();
+        claim_eq!(map.len(), 12);
+        // Entries which are only looked up do not change the length.
+        claim!(map.entry(12).is_vacant());
+        claim_eq!(map.len(), 12);
+        claim_eq!(map.remove_and_get(&0), Some(0));
+        claim_eq!(map.remove_and_get(&0), None);
+        map.remove(&1);
+        map.remove(&1);
+        claim_eq!(map.len(), 10);
+        claim_eq!(map.iter().count(), 10);
+        map.clear_flat();
+        claim!(map.is_empty());
+        claim!(map.get(&3).is_none());
+    }
+
+    /// Test the length of the map survives storing and loading the map.
+    #[concordium_test]
+    fn test_counted_map_stored() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_counted_map();
+        let _ = map.insert(1u8, 2u8);
+        let _ = map.insert(2u8, 3u8);
+        let bytes = crate::to_bytes(&map);
+        let loaded: CountedStateMap<u8, u8, _> = CountedStateMap::deserial_with_state(
+            &StateApi::open(),
+            &mut crate::Cursor::new(&bytes),
+        )
+        .expect("Deserializing should succeed");
+        claim_eq!(loaded.len(), 2);
+        claim_eq!(loaded.get(&2).as_deref(), Some(&3));
+    }
+
+    /// Test the length of the set is tracked through inserting and removing.
+    #[concordium_test]
+    fn test_counted_set_len() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut set = state_builder.new_counted_set();
+        for k in [3u8, 1, 3, 2] {
+            let _ = set.insert(k);
+        }
+        claim_eq!(set.len(), 3);
+        claim!(set.remove(&3));
+        claim!(!set.remove(&3));
+        claim_eq!(set.len(), 2);
+        claim!(set.contains(&1));
+        set.clear();
+        claim!(set.is_empty());
+        claim!(!set.contains(&1));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    to_bytes, Deletable, Deserial, DeserialWithState, Get, HasStateApi, HasStateEntry, ParseResult,
+    Read, Serial, Serialize, StateMap, StateMapIter, StateRef, UnwrapAbort, Write,
+};
+
+/// A [`StateMap`] where the size of each value in the state is limited by a
+/// quota. Inserts and updates which would make a value exceed the quota are
+/// rejected with a [`QuotaExceededError`], without modifying the state.
+///
+/// This is useful for contracts where users can store arbitrary data, such as
+/// a map from accounts to the data they have registered, to cap the storage
+/// used by each user. The number of bytes used by a value is available via
+/// [`entry_size`](Self::entry_size), which can for instance be used to charge
+/// users for their storage.
+///
+/// The values must be _flat_, i.e., implement [`Serialize`], so that the
+/// serialized value is all the state used by an entry. Values containing
+/// nested state, such as a [`StateBox`](crate::StateBox) or a [`StateMap`],
+/// are not supported, since the nested state would not count towards the
+/// quota.
+///
+/// ## Usage
+///
+/// New maps can be constructed using the
+/// [`new_quota_map`](crate::StateBuilder::new_quota_map) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// // Allow each account to store at most 1 KiB.
+/// let mut notes = state_builder.new_quota_map(1024);
+/// let alice = AccountAddress([0u8; 32]);
+/// assert!(notes.insert(alice, String::from("Hello")).is_ok());
+/// assert!(notes.modify(&alice, |note| note.push_str(&"!".repeat(2048))).is_err());
+/// assert_eq!(notes.get(&alice).as_deref().map(String::as_str), Some("Hello"));
+/// ```
+///
+/// ### **Caution**
+///
+/// Like `StateMap`s, `QuotaStateMap`s must be explicitly deleted when they
+/// are no longer needed, otherwise they will remain in the contract's state,
+/// albeit unreachable.
+#[derive(Debug)]
+pub struct QuotaStateMap<K, V, S> {
+    /// The map holding the entries.
+    pub(crate) map:   StateMap<K, V, S>,
+    /// The maximum number of bytes of each serialized value.
+    pub(crate) limit: u32,
+}
+
+/// Error for inserting or updating a value of a [`QuotaStateMap`], where the
+/// serialized value is larger than the quota of the map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct QuotaExceededError {
+    /// The number of bytes of the serialized value.
+    pub size:  u32,
+    /// The quota of the map.
+    pub limit: u32,
+}
+
+impl<K, V, S> QuotaStateMap<K, V, S> {
+    /// The maximum number of bytes of each serialized value.
+    pub fn limit(&self) -> u32 { self.limit }
+
+    /// Set the maximum number of bytes of each serialized value.
+    /// Values which are already larger than the new limit are kept, but can
+    /// only be updated to values within the limit.
+    pub fn set_limit(&mut self, limit: u32) { self.limit = limit }
+}
+
+impl<K, V, S> QuotaStateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serialize,
+{
+    /// Lookup the value with the given key. Return [None] if there is no value
+    /// with the given key.
+    pub fn get(&self, key: &K) -> Option<StateRef<V>> { self.map.get(key) }
+
+    /// Get the number of bytes of the serialized value with the given key in
+    /// the state. Return [None] if there is no value with the given key.
+    pub fn entry_size(&self, key: &K) -> Option<u32> { self.map.entry_size(key) }
+
+    /// Get the number of bytes used by the map in the state, i.e., the sum of
+    /// the sizes of the keys and values of all entries.
+    pub fn size_in_state(&self) -> u64 { self.map.size_in_state() }
+
+    /// Get the number of bytes the value with the given key can grow by before
+    /// exceeding the quota. For keys without a value, this is the limit.
+    pub fn remaining(&self, key: &K) -> u32 {
+        self.limit.saturating_sub(self.entry_size(key).unwrap_or(0))
+    }
+
+    /// Inserts the value with the given key. If a value already exists at the
+    /// given key it is replaced and the old value is returned.
+    ///
+    /// If the serialized value is larger than the quota, the value is dropped
+    /// and an error is returned, leaving the map unchanged.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, QuotaExceededError> {
+        self.check_size(to_bytes(&value).len())?;
+        Ok(self.map.insert(key, value))
+    }
+
+    /// Update the value with the given key using the provided function, and
+    /// return the result of the function. Return `Ok(None)` if there is no
+    /// value with the given key.
+    ///
+    /// If the updated value is larger than the quota, an error is returned and
+    /// the value in the state is left unchanged.
+    pub fn modify<F, A>(&mut self, key: &K, f: F) -> Result<Option<A>, QuotaExceededError>
+    where
+        F: FnOnce(&mut V) -> A, {
+        let key_bytes = self.map.key_with_map_prefix(key);
+        let mut entry = match self.map.state_api.lookup_entry(&key_bytes) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        // Unwrapping is safe when using only the high-level API.
+        let mut value = V::deserial(&mut entry).unwrap_abort();
+        let res = f(&mut value);
+        let bytes = to_bytes(&value);
+        self.check_size(bytes.len())?;
+        entry.move_to_start();
+        entry.write_all(&bytes).unwrap_abort();
+        entry.truncate(bytes.len() as u32).unwrap_abort();
+        Ok(Some(res))
+    }
+
+    /// Remove a key from the map, returning the value at the key if the key was
+    /// previously in the map.
+    #[must_use]
+    pub fn remove_and_get(&mut self, key: &K) -> Option<V> { self.map.remove_and_get(key) }
+
+    /// Remove a key from the map.
+    /// This also deletes the value in the state.
+    pub fn remove(&mut self, key: &K) { self.map.remove(key) }
+
+    /// Clears the map, removing all key-value pairs.
+    pub fn clear(&mut self) { self.map.clear_flat() }
+
+    /// Return an error if a value with the given number of bytes exceeds the
+    /// quota.
+    fn check_size(&self, size: usize) -> Result<(), QuotaExceededError> {
+        let error = QuotaExceededError {
+            size:  u32::try_from(size).unwrap_or(u32::MAX),
+            limit: self.limit,
+        };
+        if error.size > self.limit {
+            Err(error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<K, V, S: HasStateApi> QuotaStateMap<K, V, S> {
+    /// Get an iterator over the key-value pairs of the map. The iterator
+    /// returns values in increasing order of keys, where keys are ordered
+    /// lexicographically via their serializations.
+    pub fn iter(&self) -> StateMapIter<'_, K, V, S> { self.map.iter() }
+}
+
+impl<K, V, S> Serial for QuotaStateMap<K, V, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.map.serial(out)?;
+        self.limit.serial(out)
+    }
+}
+
+impl<K, V, S> DeserialWithState<S> for QuotaStateMap<K, V, S>
+where
+    S: HasStateApi,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        let map = StateMap::deserial_with_state(state, source)?;
+        let limit = source.get()?;
+        Ok(QuotaStateMap {
+            map,
+            limit,
+        })
+    }
+}
+
+impl<K, V, S> Deletable for QuotaStateMap<K, V, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    V: Serialize,
+{
+    fn delete(mut self) { self.clear() }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_quota {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, StateApi, StateBuilder, Vec};
+
+    /// Test that inserts beyond the quota are rejected.
+    #[concordium_test]
+    fn test_quota_map_insert() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_quota_map(8);
+        claim_eq!(map.insert(1u8, [0u8; 8]), Ok(None));
+        claim_eq!(map.entry_size(&1), Some(8));
+        claim_eq!(map.remaining(&1), 0);
+        claim_eq!(map.remaining(&2), 8);
+
+        let mut map = state_builder.new_quota_map(4);
+        claim_eq!(map.insert(1u8, 7u32), Ok(None));
+        claim_eq!(
+            map.insert(2u8, 7u64),
+            Err(QuotaExceededError {
+                size:  8,
+                limit: 4,
+            })
+        );
+        claim!(map.get(&2).is_none());
+    }
+
+    /// Test that updates beyond the quota are rejected without modifying the
+    /// state.
+    #[concordium_test]
+    fn test_quota_map_modify() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_quota_map(12);
+        claim_eq!(map.insert(1u8, Vec::from([1u8, 2])), Ok(None));
+        claim_eq!(map.modify(&1, |v| v.extend_from_slice(&[3, 4])), Ok(Some(())));
+        claim_eq!(map.entry_size(&1), Some(8));
+        claim!(map.modify(&1, |v| v.extend_from_slice(&[0; 5])).is_err());
+        claim_eq!(map.get(&1).as_deref(), Some(&Vec::from([1u8, 2, 3, 4])));
+        claim_eq!(map.modify(&2, |v| v.push(0)), Ok(None));
+        // Values can shrink.
+        claim_eq!(map.modify(&1, |v| v.pop()), Ok(Some(Some(4))));
+        claim_eq!(map.entry_size(&1), Some(7));
+        claim_eq!(map.get(&1).as_deref(), Some(&Vec::from([1u8, 2, 3])));
+    }
+
+    /// Test the size of the map in the state.
+    #[concordium_test]
+    fn test_quota_map_size_in_state() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut map = state_builder.new_quota_map(100);
+        claim_eq!(map.size_in_state(), 0);
+        claim_eq!(map.insert(1u8, 1u32), Ok(None));
+        claim_eq!(map.insert(2u8, 2u32), Ok(None));
+        // Each key is the 8 byte prefix and the key, and each value is 4 bytes.
+        claim_eq!(map.size_in_state(), 2 * (9 + 4));
+        map.remove(&1);
+        claim_eq!(map.size_in_state(), 9 + 4);
+    }
+}
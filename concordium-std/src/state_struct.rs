@@ -0,0 +1,338 @@
+use crate::{
+    cell::UnsafeCell, Deletable, DeserialWithState, HasStateApi, ParseResult, Read, Serial,
+    StateApi, StateItemPrefix, UnwrapAbort, Write,
+};
+
+/// A field of a contract state deriving `StateStruct`.
+///
+/// The value of each field is stored under its own key in the contract state,
+/// as opposed to being serialized together with the rest of the struct, which
+/// only contains the location of each field. The value is loaded lazily, on
+/// first access, and is only written back to the state when the struct is
+/// stored and the value has been accessed mutably. This means that a receive
+/// function only pays for the fields it actually uses.
+///
+/// Each field gets its own location when it is created, so a struct deriving
+/// `StateStruct` can be used both as the root state of a contract and nested
+/// in other state, e.g., as the values of a [`StateMap`](crate::StateMap).
+///
+/// ## Usage
+///
+/// Derive `StateStruct` from the
+/// [`concordium-std-derive`](https://docs.rs/concordium-std-derive) crate for
+/// a struct where every field is a `StateField`. New fields are constructed
+/// using [`new_field`](crate::StateBuilder::new_field) on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```ignore
+/// # use concordium_std::*;
+/// use concordium_std_derive::StateStruct;
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     admin: AccountAddress,
+///     fee:   Amount,
+/// }
+///
+/// #[derive(StateStruct)]
+/// struct State<S = StateApi> {
+///     config:   StateField<Config, S>,
+///     balances: StateField<StateMap<AccountAddress, Amount, S>, S>,
+/// }
+///
+/// #[init(contract = "example")]
+/// fn init(ctx: &InitContext, state_builder: &mut StateBuilder) -> InitResult<State> {
+///     let balances = state_builder.new_map();
+///     Ok(State {
+///         config:   state_builder.new_field(Config {
+///             admin: ctx.init_origin(),
+///             fee:   Amount::zero(),
+///         }),
+///         balances: state_builder.new_field(balances),
+///     })
+/// }
+///
+/// #[receive(contract = "example", name = "setFee", parameter = "Amount", mutable)]
+/// fn set_fee(ctx: &ReceiveContext, host: &mut Host<State>) -> ReceiveResult<()> {
+///     let fee = ctx.parameter_cursor().get()?;
+///     // Only `config` is loaded and written back, `balances` is never touched.
+///     host.state_mut().config.fee = fee;
+///     Ok(())
+/// }
+/// ```
+///
+/// ### **Caution**
+///
+/// The struct is serialized as the locations of its fields in the order they
+/// are declared. Reordering, adding or removing fields therefore changes the
+/// layout of the struct, and requires migrating the state in a contract
+/// upgrade, as for other state types.
+#[derive(Debug)]
+pub struct StateField<T, S = StateApi> {
+    pub(crate) state_api: S,
+    /// The key under which the value is stored in the state.
+    pub(crate) prefix:    StateItemPrefix,
+    pub(crate) inner:     UnsafeCell<StateFieldInner<T>>,
+}
+
+pub(crate) enum StateFieldInner<T> {
+    /// The value is only stored in the state.
+    Unloaded,
+    /// The value is loaded in memory.
+    Loaded {
+        modified: bool,
+        value:    T,
+    },
+}
+
+/// A type which can be used as a field of a contract state deriving
+/// `StateStruct`.
+///
+/// The methods of this trait are used by the code generated by the derive
+/// macro, and are not intended to be called directly.
+pub trait StateStructField: Sized {
+    /// The state API the field uses for loading and storing its value.
+    type StateApi: HasStateApi;
+
+    /// Open the field whose location is read from `source`, without loading
+    /// its value.
+    fn open_field<R: Read>(state_api: Self::StateApi, source: &mut R) -> ParseResult<Self>;
+
+    /// Write the value of the field to the state, if it has been modified
+    /// since it was loaded, and write the location of the field to `out`.
+    fn store_field<W: Write>(&self, out: &mut W) -> Result<(), W::Err>;
+}
+
+impl<T, S> StateField<T, S> {
+    /// Create a new field stored under `prefix` with the value in memory. The
+    /// value is written to the state the next time the containing state
+    /// struct is stored.
+    pub(crate) fn new(value: T, state_api: S, prefix: StateItemPrefix) -> Self {
+        StateField {
+            state_api,
+            prefix,
+            inner: UnsafeCell::new(StateFieldInner::Loaded {
+                modified: true,
+                value,
+            }),
+        }
+    }
+}
+
+impl<T, S> StateField<T, S>
+where
+    T: DeserialWithState<S>,
+    S: HasStateApi,
+{
+    /// Get a reference to the value, loading it from the state if it has not
+    /// been loaded yet.
+    pub fn get(&self) -> &T {
+        let inner = unsafe { &mut *self.inner.get() };
+        self.ensure_loaded(inner).0
+    }
+
+    /// Get a mutable reference to the value, loading it from the state if it
+    /// has not been loaded yet. The field is marked as modified, and will be
+    /// written back when the contract state is stored.
+    pub fn get_mut(&mut self) -> &mut T {
+        let inner = unsafe { &mut *self.inner.get() };
+        let (value, modified) = self.ensure_loaded(inner);
+        *modified = true;
+        value
+    }
+
+    /// Replace the value with the provided one, returning the current value.
+    /// Note that if the type `T` contains references to state, e.g., is a
+    /// [`StateBox`](crate::StateBox), then it must be
+    /// [deleted](crate::Deletable::delete) to avoid space leaks.
+    #[must_use]
+    pub fn replace(&mut self, new_val: T) -> T { crate::mem::replace(self.get_mut(), new_val) }
+
+    /// Whether the value has been loaded from the state.
+    pub fn is_loaded(&self) -> bool {
+        matches!(unsafe { &*self.inner.get() }, StateFieldInner::Loaded { .. })
+    }
+
+    /// If the value is not loaded, load it from the state. Return a reference
+    /// to the value, and to the flag indicating whether it has been modified.
+    fn ensure_loaded<'a>(&self, inner: &'a mut StateFieldInner<T>) -> (&'a mut T, &'a mut bool) {
+        if let StateFieldInner::Unloaded = inner {
+            let mut entry = self.state_api.lookup_entry(&self.prefix).unwrap_abort();
+            let value = T::deserial_with_state(&self.state_api, &mut entry).unwrap_abort();
+            *inner = StateFieldInner::Loaded {
+                modified: false,
+                value,
+            };
+        }
+        match inner {
+            StateFieldInner::Loaded {
+                value,
+                modified,
+            } => (value, modified),
+            StateFieldInner::Unloaded => {
+                // We just set it to loaded.
+                unsafe { crate::hint::unreachable_unchecked() }
+            }
+        }
+    }
+}
+
+impl<T, S> crate::ops::Deref for StateField<T, S>
+where
+    T: DeserialWithState<S>,
+    S: HasStateApi,
+{
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target { self.get() }
+}
+
+impl<T, S> crate::ops::DerefMut for StateField<T, S>
+where
+    T: DeserialWithState<S>,
+    S: HasStateApi,
+{
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target { self.get_mut() }
+}
+
+impl<T, S> StateStructField for StateField<T, S>
+where
+    T: Serial + DeserialWithState<S>,
+    S: HasStateApi,
+{
+    type StateApi = S;
+
+    fn open_field<R: Read>(state_api: S, source: &mut R) -> ParseResult<Self> {
+        let prefix = source.read_array()?;
+        Ok(StateField {
+            state_api,
+            prefix,
+            inner: UnsafeCell::new(StateFieldInner::Unloaded),
+        })
+    }
+
+    fn store_field<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        let inner = unsafe { &mut *self.inner.get() };
+        if let StateFieldInner::Loaded {
+            modified,
+            value,
+        } = inner
+        {
+            if *modified {
+                // Creating the entry resets any existing value.
+                let mut entry = self.state_api.clone().create_entry(&self.prefix).unwrap_abort();
+                value.serial(&mut entry).unwrap_abort();
+                *modified = false;
+            }
+        }
+        out.write_all(&self.prefix)
+    }
+}
+
+impl<T, S> Deletable for StateField<T, S>
+where
+    T: DeserialWithState<S> + Deletable,
+    S: HasStateApi,
+{
+    fn delete(mut self) {
+        let value = match self.inner.into_inner() {
+            StateFieldInner::Loaded {
+                value,
+                ..
+            } => value,
+            // The value is loaded first, since it might be necessary to delete nested
+            // values, as for a `StateBox`.
+            StateFieldInner::Unloaded => {
+                let mut entry = self.state_api.lookup_entry(&self.prefix).unwrap_abort();
+                T::deserial_with_state(&self.state_api, &mut entry).unwrap_abort()
+            }
+        };
+        // The entry does not exist if the value has never been stored.
+        if let Some(entry) = self.state_api.lookup_entry(&self.prefix) {
+            self.state_api.delete_entry(entry).unwrap_abort();
+        }
+        value.delete()
+    }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_state_struct {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, Cursor, StateApi, StateBuilder, Vec};
+
+    /// Store a field and open it again from its serialized location.
+    fn store_and_open<T: Serial + DeserialWithState<StateApi>>(
+        field: &StateField<T>,
+    ) -> StateField<T> {
+        let mut location = Vec::new();
+        field.store_field(&mut location).unwrap_abort();
+        StateField::open_field(StateApi::open(), &mut Cursor::new(location)).unwrap_abort()
+    }
+
+    /// Test that a new field is written on store, and can be loaded again.
+    #[concordium_test]
+    fn test_state_field_store_and_load() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let field = state_builder.new_field(42u64);
+        claim!(StateApi::open().lookup_entry(&field.prefix).is_none());
+
+        let loaded = store_and_open(&field);
+        claim_eq!(loaded.prefix, field.prefix);
+        claim!(!loaded.is_loaded());
+        claim_eq!(*loaded, 42);
+        claim!(loaded.is_loaded());
+    }
+
+    /// Test that a field is only written back when it has been accessed
+    /// mutably.
+    #[concordium_test]
+    fn test_state_field_store_only_modified() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let field = state_builder.new_field(1u32);
+        let read = store_and_open(&field);
+
+        // Overwrite the stored value behind the back of the field, such that
+        // we can observe whether it is written again.
+        let mut state_api = StateApi::open();
+        let mut entry = state_api.create_entry(&field.prefix).unwrap_abort();
+        2u32.serial(&mut entry).unwrap_abort();
+        claim_eq!(*read, 2);
+        let mut entry = state_api.create_entry(&field.prefix).unwrap_abort();
+        3u32.serial(&mut entry).unwrap_abort();
+        let mut written = store_and_open(&read);
+        claim_eq!(*written, 3, "Fields which are only read should not be written.");
+
+        *written += 10;
+        let stored = store_and_open(&written);
+        claim_eq!(*stored, 13);
+    }
+
+    /// Test that separate fields, e.g., of different instances of a state
+    /// struct, are stored independently.
+    #[concordium_test]
+    fn test_state_field_instances_are_independent() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let first = state_builder.new_field(1u8);
+        let second = state_builder.new_field(2u8);
+        claim!(first.prefix != second.prefix);
+        let first = store_and_open(&first);
+        let second = store_and_open(&second);
+        claim_eq!(*first, 1);
+        claim_eq!(*second, 2);
+    }
+
+    /// Test that deleting a field deletes its value from the state.
+    #[concordium_test]
+    fn test_state_field_delete() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let field = state_builder.new_field(7u16);
+        let stored = store_and_open(&field);
+        let prefix = stored.prefix;
+        stored.delete();
+        claim!(StateApi::open().lookup_entry(&prefix).is_none());
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{
+    Deletable, Deserial, DeserialWithState, Get, HasStateApi, ParseResult, Read, Serial, StateMap,
+    StateRef, StateRefMut, UnwrapAbort, Write,
+};
+
+/// A growable sequence, where each element is stored separately in the
+/// low-level key-value store.
+///
+/// | Operation                                       | Performance   |
+/// |-------------------------------------------------|---------------|
+/// | [`get`](Self::get) / [`get_mut`](Self::get_mut) | O(1)          |
+/// | [`push`](Self::push) / [`pop`](Self::pop)       | O(1)          |
+/// | [`swap_remove`](Self::swap_remove)              | O(1)          |
+/// | [`truncate`](Self::truncate)                    | O(m)          |
+///
+/// Where `m` is the number of removed elements. The cost of accessing an
+/// element depends on the serialized size of the element, but not on the
+/// length of the sequence.
+///
+/// The length is stored along with the prefix of the sequence, i.e., in the
+/// value containing the sequence, such as the root state of the contract.
+///
+/// ## Usage
+///
+/// New sequences can be constructed using the
+/// [`new_vec`](crate::StateBuilder::new_vec) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// let mut log = state_builder.new_vec();
+/// log.push(Timestamp::from_timestamp_millis(1000));
+/// log.push(Timestamp::from_timestamp_millis(2000));
+/// assert_eq!(log.len(), 2);
+/// assert_eq!(log.get(1).as_deref(), Some(&Timestamp::from_timestamp_millis(2000)));
+/// ```
+///
+/// ### **Caution**
+///
+/// `StateVec`s must be explicitly deleted when they are no longer needed,
+/// otherwise they will remain in the contract's state, albeit unreachable.
+#[derive(Debug)]
+pub struct StateVec<T, S> {
+    /// The elements indexed by their position.
+    pub(crate) elements: StateMap<u32, T, S>,
+    /// The number of elements.
+    pub(crate) len:      u32,
+}
+
+/// A double-ended queue implemented as a ring buffer, where each element is
+/// stored separately in the low-level key-value store.
+///
+/// All operations adding, removing or accessing a single element at either end
+/// or at an index are O(1), in the same way as for [`StateVec`].
+///
+/// ## Usage
+///
+/// New queues can be constructed using the
+/// [`new_deque`](crate::StateBuilder::new_deque) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// let mut queue = state_builder.new_deque();
+/// queue.push_back(2u64);
+/// queue.push_front(1u64);
+/// assert_eq!(queue.pop_front(), Some(1));
+/// assert_eq!(queue.back().as_deref(), Some(&2));
+/// ```
+///
+/// ### **Caution**
+///
+/// `StateDeque`s must be explicitly deleted when they are no longer needed,
+/// otherwise they will remain in the contract's state, albeit unreachable.
+#[derive(Debug)]
+pub struct StateDeque<T, S> {
+    /// The elements indexed by their position in the ring buffer.
+    pub(crate) elements: StateMap<u32, T, S>,
+    /// The position of the first element in the ring buffer.
+    pub(crate) head:     u32,
+    /// The number of elements.
+    pub(crate) len:      u32,
+}
+
+/// An iterator over the elements of a [`StateVec`] or [`StateDeque`].
+///
+/// Ordered by index. The iterator is double-ended.
+///
+/// This `struct` is created by the [`iter`][StateVec::iter] method on
+/// [`StateVec`] and [`StateDeque`]. See their documentation for more.
+pub struct StateVecIter<'a, T, S> {
+    /// The elements indexed by their position.
+    elements: &'a StateMap<u32, T, S>,
+    /// The position of the first element.
+    head:     u32,
+    /// The index of the next element from the front.
+    front:    u32,
+    /// One more than the index of the next element from the back.
+    back:     u32,
+}
+
+impl<T, S> StateVec<T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S>,
+{
+    /// Append an element to the back of the sequence.
+    pub fn push(&mut self, value: T) {
+        // Trap if the length would overflow.
+        let len = self.len.checked_add(1).unwrap_abort();
+        let _ = self.elements.insert(self.len, value);
+        self.len = len;
+    }
+
+    /// Remove the last element and return it, or `None` if the sequence is
+    /// empty.
+    ///
+    /// *Caution*: If `T` contains a data structure prefixed with `State` (such
+    /// as [StateBox](crate::StateBox) or [StateMap]), then it is important to
+    /// call [`Deletable::delete`] on the value returned when you're finished
+    /// with it. Otherwise, it will remain in the contract state.
+    #[must_use]
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        // Unwrapping is safe, since the length is tracked.
+        Some(self.elements.remove_and_get(&self.len).unwrap_abort())
+    }
+
+    /// Get a reference to the element at the index, or `None` if the index is
+    /// out of bounds.
+    pub fn get(&self, index: u32) -> Option<StateRef<T>> {
+        if index < self.len {
+            self.elements.get(&index)
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the element at the index, or `None` if the
+    /// index is out of bounds.
+    pub fn get_mut(&mut self, index: u32) -> Option<StateRefMut<T, S>> {
+        if index < self.len {
+            self.elements.get_mut(&index)
+        } else {
+            None
+        }
+    }
+
+    /// Remove the element at the index and return it, or `None` if the index is
+    /// out of bounds. The removed element is replaced by the last element,
+    /// so this does not preserve the ordering, but is O(1).
+    ///
+    /// *Caution*: If `T` contains a data structure prefixed with `State`, then
+    /// it is important to call [`Deletable::delete`] on the value returned.
+    #[must_use]
+    pub fn swap_remove(&mut self, index: u32) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        let last = self.pop().unwrap_abort();
+        if index == self.len {
+            return Some(last);
+        }
+        // Unwrapping is safe, since the index is in bounds.
+        Some(self.elements.insert(index, last).unwrap_abort())
+    }
+
+    /// Shorten the sequence, keeping the first `len` elements. The removed
+    /// elements are deleted from the state, including values pointed at, if
+    /// `T`, for example, is a [StateBox](crate::StateBox). Has no effect if
+    /// `len` is larger than the current length.
+    pub fn truncate(&mut self, len: u32)
+    where
+        T: Deletable, {
+        while self.len > len {
+            if let Some(value) = self.pop() {
+                value.delete();
+            }
+        }
+    }
+
+    /// Clear the sequence, removing all elements.
+    /// This also includes values pointed at, if `T`, for example, is a
+    /// [StateBox](crate::StateBox). **If applicable use
+    /// [`clear_flat`](Self::clear_flat) instead.**
+    pub fn clear(&mut self)
+    where
+        T: Deletable, {
+        self.elements.clear();
+        self.len = 0;
+    }
+
+    /// Clear the sequence, removing all elements.
+    /// **This should be used over [`clear`](Self::clear) if it is
+    /// applicable.** It avoids recursive deletion of values since the
+    /// values are required to be _flat_.
+    pub fn clear_flat(&mut self)
+    where
+        T: Deserial, {
+        self.elements.clear_flat();
+        self.len = 0;
+    }
+}
+
+impl<T, S: HasStateApi> StateVec<T, S> {
+    /// Return the number of elements in the sequence.
+    #[inline(always)]
+    pub fn len(&self) -> u32 { self.len }
+
+    /// Returns `true` if the sequence contains no elements.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Get an iterator over the elements, ordered by index.
+    pub fn iter(&self) -> StateVecIter<T, S> {
+        StateVecIter {
+            elements: &self.elements,
+            head:     0,
+            front:    0,
+            back:     self.len,
+        }
+    }
+}
+
+impl<T, S> StateDeque<T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S>,
+{
+    /// Append an element to the back of the queue.
+    pub fn push_back(&mut self, value: T) {
+        // Trap if the length would overflow.
+        let len = self.len.checked_add(1).unwrap_abort();
+        let _ = self.elements.insert(self.head.wrapping_add(self.len), value);
+        self.len = len;
+    }
+
+    /// Prepend an element to the front of the queue.
+    pub fn push_front(&mut self, value: T) {
+        // Trap if the length would overflow.
+        let len = self.len.checked_add(1).unwrap_abort();
+        self.head = self.head.wrapping_sub(1);
+        let _ = self.elements.insert(self.head, value);
+        self.len = len;
+    }
+
+    /// Remove the last element and return it, or `None` if the queue is
+    /// empty.
+    ///
+    /// *Caution*: If `T` contains a data structure prefixed with `State`, then
+    /// it is important to call [`Deletable::delete`] on the value returned.
+    #[must_use]
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        // Unwrapping is safe, since the length is tracked.
+        Some(self.elements.remove_and_get(&self.head.wrapping_add(self.len)).unwrap_abort())
+    }
+
+    /// Remove the first element and return it, or `None` if the queue is
+    /// empty.
+    ///
+    /// *Caution*: If `T` contains a data structure prefixed with `State`, then
+    /// it is important to call [`Deletable::delete`] on the value returned.
+    #[must_use]
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        // Unwrapping is safe, since the length is tracked.
+        let value = self.elements.remove_and_get(&self.head).unwrap_abort();
+        self.head = self.head.wrapping_add(1);
+        Some(value)
+    }
+
+    /// Get a reference to the first element, or `None` if the queue is empty.
+    #[inline(always)]
+    pub fn front(&self) -> Option<StateRef<T>> { self.get(0) }
+
+    /// Get a reference to the last element, or `None` if the queue is empty.
+    pub fn back(&self) -> Option<StateRef<T>> { self.get(self.len.checked_sub(1)?) }
+
+    /// Get a reference to the element at the index counted from the front, or
+    /// `None` if the index is out of bounds.
+    pub fn get(&self, index: u32) -> Option<StateRef<T>> {
+        if index < self.len {
+            self.elements.get(&self.head.wrapping_add(index))
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the element at the index counted from the
+    /// front, or `None` if the index is out of bounds.
+    pub fn get_mut(&mut self, index: u32) -> Option<StateRefMut<T, S>> {
+        if index < self.len {
+            self.elements.get_mut(&self.head.wrapping_add(index))
+        } else {
+            None
+        }
+    }
+
+    /// Clear the queue, removing all elements.
+    /// This also includes values pointed at, if `T`, for example, is a
+    /// [StateBox](crate::StateBox). **If applicable use
+    /// [`clear_flat`](Self::clear_flat) instead.**
+    pub fn clear(&mut self)
+    where
+        T: Deletable, {
+        self.elements.clear();
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Clear the queue, removing all elements.
+    /// **This should be used over [`clear`](Self::clear) if it is
+    /// applicable.** It avoids recursive deletion of values since the
+    /// values are required to be _flat_.
+    pub fn clear_flat(&mut self)
+    where
+        T: Deserial, {
+        self.elements.clear_flat();
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+impl<T, S: HasStateApi> StateDeque<T, S> {
+    /// Return the number of elements in the queue.
+    #[inline(always)]
+    pub fn len(&self) -> u32 { self.len }
+
+    /// Returns `true` if the queue contains no elements.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Get an iterator over the elements, ordered from the front to the back.
+    pub fn iter(&self) -> StateVecIter<T, S> {
+        StateVecIter {
+            elements: &self.elements,
+            head:     self.head,
+            front:    0,
+            back:     self.len,
+        }
+    }
+}
+
+impl<'a, T, S> Iterator for StateVecIter<'a, T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S> + 'a,
+{
+    type Item = StateRef<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        // Unwrapping is safe, since the index is in bounds.
+        let value = self.elements.get(&self.head.wrapping_add(self.front)).unwrap_abort();
+        self.front += 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.back - self.front) as usize;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, S> DoubleEndedIterator for StateVecIter<'a, T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S> + 'a,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        // Unwrapping is safe, since the index is in bounds.
+        Some(self.elements.get(&self.head.wrapping_add(self.back)).unwrap_abort())
+    }
+}
+
+impl<'a, T, S> ExactSizeIterator for StateVecIter<'a, T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S> + 'a,
+{
+}
+
+impl<T, S> Serial for StateVec<T, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.elements.serial(out)?;
+        self.len.serial(out)
+    }
+}
+
+impl<T, S> DeserialWithState<S> for StateVec<T, S>
+where
+    S: HasStateApi,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        let elements = StateMap::deserial_with_state(state, source)?;
+        let len = source.get()?;
+        Ok(Self {
+            elements,
+            len,
+        })
+    }
+}
+
+impl<T, S> Deletable for StateVec<T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S> + Deletable,
+{
+    fn delete(self) { self.elements.delete() }
+}
+
+impl<T, S> Serial for StateDeque<T, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.elements.serial(out)?;
+        self.head.serial(out)?;
+        self.len.serial(out)
+    }
+}
+
+impl<T, S> DeserialWithState<S> for StateDeque<T, S>
+where
+    S: HasStateApi,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        let elements = StateMap::deserial_with_state(state, source)?;
+        let head = source.get()?;
+        let len = source.get()?;
+        Ok(Self {
+            elements,
+            head,
+            len,
+        })
+    }
+}
+
+impl<T, S> Deletable for StateDeque<T, S>
+where
+    S: HasStateApi,
+    T: Serial + DeserialWithState<S> + Deletable,
+{
+    fn delete(self) { self.elements.delete() }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_vec {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, vec::Vec, StateApi, StateBuilder};
+
+    /// Test pushing, popping and accessing elements of a `StateVec`.
+    #[concordium_test]
+    fn test_vec_push_pop() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut vec = state_builder.new_vec();
+        claim!(vec.is_empty());
+        for n in 0..10u64 {
+            vec.push(n);
+        }
+        claim_eq!(vec.len(), 10);
+        claim_eq!(vec.get(3).as_deref(), Some(&3));
+        claim!(vec.get(10).is_none());
+        *vec.get_mut(3).unwrap() = 30;
+        claim_eq!(vec.get(3).as_deref(), Some(&30));
+        claim_eq!(vec.pop(), Some(9));
+        claim_eq!(vec.len(), 9);
+        let elements: Vec<u64> = vec.iter().map(|n| *n).collect();
+        claim_eq!(elements, [0, 1, 2, 30, 4, 5, 6, 7, 8]);
+        let reversed: Vec<u64> = vec.iter().rev().take(2).map(|n| *n).collect();
+        claim_eq!(reversed, [8, 7]);
+    }
+
+    /// Test `swap_remove` and `truncate` on a `StateVec`.
+    #[concordium_test]
+    fn test_vec_swap_remove_truncate() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut vec = state_builder.new_vec();
+        for n in 0..6u64 {
+            vec.push(n);
+        }
+        claim_eq!(vec.swap_remove(1), Some(1));
+        claim_eq!(vec.swap_remove(4), Some(4));
+        claim_eq!(vec.swap_remove(4), None);
+        claim_eq!(vec.iter().map(|n| *n).collect::<Vec<_>>(), [0, 5, 2, 3]);
+        vec.truncate(2);
+        claim_eq!(vec.iter().map(|n| *n).collect::<Vec<_>>(), [0, 5]);
+        vec.truncate(5);
+        claim_eq!(vec.len(), 2);
+        vec.clear_flat();
+        claim!(vec.is_empty());
+        claim!(vec.pop().is_none());
+    }
+
+    /// Test a `StateDeque` wrapping around the start of the ring buffer.
+    #[concordium_test]
+    fn test_deque() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut deque = state_builder.new_deque();
+        deque.push_back(2u64);
+        deque.push_back(3u64);
+        deque.push_front(1u64);
+        deque.push_front(0u64);
+        claim_eq!(deque.len(), 4);
+        claim_eq!(deque.front().as_deref(), Some(&0));
+        claim_eq!(deque.back().as_deref(), Some(&3));
+        claim_eq!(deque.get(1).as_deref(), Some(&1));
+        claim_eq!(deque.iter().map(|n| *n).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        claim_eq!(deque.pop_front(), Some(0));
+        claim_eq!(deque.pop_back(), Some(3));
+        claim_eq!(deque.iter().rev().map(|n| *n).collect::<Vec<_>>(), [2, 1]);
+        claim_eq!(deque.pop_front(), Some(1));
+        claim_eq!(deque.pop_front(), Some(2));
+        claim!(deque.pop_front().is_none());
+        claim!(deque.back().is_none());
+    }
+}
@@ -0,0 +1,493 @@
+use crate::{
+    Amount, CallContractError, ContractAddress, Deletable, DeserialWithState, EntrypointName,
+    ExternCallResponse, ExternLowLevelHost, HasHost, HasStateApi, ModuleReference, Parameter,
+    ParseError, ParseResult, Read, Serial, Serialize, StateApi, StateBuilder, StateMap, StateRef,
+    StateRefMut, UpgradeError, Vec, Write,
+};
+
+/// A contract state with a version, which can be migrated from the layouts of
+/// the previous versions.
+///
+/// The version of the layout in the contract state is stored separately from
+/// the root state, and is read using
+/// [`state_version`](crate::StateBuilder::state_version). Contracts which have
+/// never stored a version are at version `0`, meaning that the layout of the
+/// state before introducing `VersionedState` should be version `0`. The init
+/// function of a contract using this trait should store the current version
+/// using [`write_state_version`](crate::StateBuilder::write_state_version).
+///
+/// The state is migrated using
+/// [`LowLevelHost::migrate_state`](crate::ExternLowLevelHost::migrate_state),
+/// which calls [`migrate`](Self::migrate) for each version from the stored
+/// version up to [`VERSION`](Self::VERSION).
+///
+/// ```ignore
+/// # use concordium_std::*;
+/// #[derive(Serialize)]
+/// struct StateV0 {
+///     admin: AccountAddress,
+///     name:  String,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct State {
+///     admin:       AccountAddress,
+///     name:        String,
+///     description: String,
+/// }
+///
+/// impl VersionedState for State {
+///     const VERSION: u32 = 1;
+///
+///     fn migrate(
+///         from_version: u32,
+///         state_api: &mut StateApi,
+///         _state_builder: &mut StateBuilder,
+///     ) -> Result<(), MigrationError> {
+///         match from_version {
+///             0 => {
+///                 let old: StateV0 = state_api.read_root()?;
+///                 state_api.write_root(&State {
+///                     admin:       old.admin,
+///                     name:        old.name,
+///                     description: String::new(),
+///                 });
+///                 Ok(())
+///             }
+///             _ => Err(MigrationError::UnsupportedVersion),
+///         }
+///     }
+/// }
+///
+/// /// Called by the upgrade entrypoint of the previous module, using
+/// /// `LowLevelHost::upgrade_and_migrate`.
+/// #[receive(contract = "example", name = "migrate", low_level)]
+/// fn migrate(ctx: &ReceiveContext, host: &mut LowLevelHost) -> ReceiveResult<()> {
+///     ensure!(ctx.sender().matches_contract(&ctx.self_address()));
+///     host.migrate_state::<State>()?;
+///     Ok(())
+/// }
+/// ```
+pub trait VersionedState: Sized + DeserialWithState<StateApi> {
+    /// The version of the current layout of the state.
+    const VERSION: u32;
+
+    /// Migrate the state in the contract state from the layout of
+    /// `from_version` to the layout of `from_version + 1`. The root state can
+    /// be read and written using [`read_root`](HasStateApi::read_root) and
+    /// [`write_root`](HasStateApi::write_root).
+    fn migrate(
+        from_version: u32,
+        state_api: &mut StateApi,
+        state_builder: &mut StateBuilder,
+    ) -> Result<(), MigrationError>;
+}
+
+/// Error for migrating a [`VersionedState`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The stored version of the state is not supported, e.g., it is newer
+    /// than the current version.
+    UnsupportedVersion,
+    /// The state could not be parsed in the layout of the version it is
+    /// migrated from.
+    MalformedState,
+    /// The state could not be parsed in the current layout after migrating.
+    InvalidState,
+}
+
+impl From<ParseError> for MigrationError {
+    #[inline(always)]
+    fn from(_: ParseError) -> Self { MigrationError::MalformedState }
+}
+
+impl ExternLowLevelHost {
+    /// Migrate the contract state from the stored version to the current
+    /// version `T::VERSION`, by calling [`VersionedState::migrate`] for each
+    /// version in between. After migrating, the new version is stored and the
+    /// root state is parsed in the current layout, which is returned.
+    ///
+    /// This should be called in the entrypoint of the new module which is
+    /// invoked as part of the upgrade, see
+    /// [`upgrade_and_migrate`](Self::upgrade_and_migrate).
+    ///
+    /// It fails if:
+    /// - the stored version is newer than `T::VERSION`,
+    /// - one of the migration steps fails,
+    /// - the migrated root state cannot be parsed as `T`.
+    pub fn migrate_state<T: VersionedState>(&mut self) -> Result<T, MigrationError> {
+        let stored_version = self.state_builder.state_version();
+        if stored_version > T::VERSION {
+            return Err(MigrationError::UnsupportedVersion);
+        }
+        for from_version in stored_version..T::VERSION {
+            T::migrate(from_version, &mut self.state_api, &mut self.state_builder)?;
+        }
+        let state = self.state_api.read_root().map_err(|_| MigrationError::InvalidState)?;
+        self.state_builder.write_state_version(T::VERSION);
+        Ok(state)
+    }
+
+    /// Upgrade the contract instance to the given module and invoke the
+    /// migration entrypoint of the new module with the given parameter. The
+    /// upgrade is reverted if the migration entrypoint fails.
+    ///
+    /// This is intended for the upgrade entrypoint of a contract, where the
+    /// migration entrypoint of the new module calls
+    /// [`migrate_state`](Self::migrate_state). The upgrade entrypoint must be
+    /// `low_level`, since a high-level entrypoint would overwrite the migrated
+    /// state with the old state at the end of execution.
+    pub fn upgrade_and_migrate<E>(
+        &mut self,
+        self_address: &ContractAddress,
+        module: ModuleReference,
+        migration: EntrypointName,
+        parameter: Parameter,
+    ) -> Result<(), E>
+    where
+        E: From<UpgradeError> + From<CallContractError<ExternCallResponse>>, {
+        self.upgrade(module)?;
+        self.invoke_contract_raw(self_address, parameter, migration, Amount::zero())?;
+        Ok(())
+    }
+}
+
+/// A [`StateMap`] whose values are being migrated from the type `Old` to the
+/// type `New`, for when the type of the values of a map changes in a new
+/// version of the state.
+///
+/// Migrating all the values of a large map as part of the upgrade might be too
+/// expensive, so instead the values are kept in the old map until they are
+/// migrated, and moved to a new map when they are modified or explicitly
+/// migrated using [`migrate_batch`](Self::migrate_batch). Values which have
+/// not been migrated yet are converted when they are looked up.
+///
+/// When the migration is complete, the map can be turned into a [`StateMap`]
+/// using [`into_map`](Self::into_map), for instance in the migration to the
+/// next version of the state.
+///
+/// ## Usage
+///
+/// Migrating maps are constructed from the old map using the
+/// [`new_migrating_map`](crate::StateBuilder::new_migrating_map) method on the
+/// [`StateBuilder`](crate::StateBuilder).
+///
+/// ```no_run
+/// # use concordium_std::*;
+/// # let mut state_builder = StateBuilder::open(StateApi::open());
+/// let mut old_map = state_builder.new_map();
+/// let _ = old_map.insert(1u8, 1u32);
+/// let _ = old_map.insert(2u8, 2u32);
+/// let mut map = state_builder.new_migrating_map::<u8, u32, u64>(old_map);
+/// assert_eq!(map.get(&1).as_deref(), Some(&1u64));
+/// while !map.migrate_batch(1) {}
+/// let map = map.into_map().ok().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MigratingStateMap<K, Old, New, S> {
+    /// The values which have not been migrated yet.
+    pub(crate) old: StateMap<K, Old, S>,
+    /// The migrated values.
+    pub(crate) new: StateMap<K, New, S>,
+}
+
+impl<K, Old, New, S> MigratingStateMap<K, Old, New, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    Old: Serial + DeserialWithState<S>,
+    New: Serial + DeserialWithState<S> + From<Old>,
+{
+    /// Lookup the value with the given key, converting it if it has not been
+    /// migrated yet. Return [None] if there is no value with the given key.
+    pub fn get(&self, key: &K) -> Option<StateRef<New>> {
+        self.new.get(key).or_else(|| self.old.get(key).map(|old| StateRef::new(old.value.into())))
+    }
+
+    /// Lookup a mutable reference to the value with the given key, migrating
+    /// it if it has not been migrated yet. Return [None] if there is no value
+    /// with the given key.
+    pub fn get_mut(&mut self, key: &K) -> Option<StateRefMut<New, S>> {
+        self.migrate_key(key);
+        self.new.get_mut(key)
+    }
+
+    /// Inserts the value with the given key. If a value already exists at the
+    /// given key it is replaced and the old value is returned, converted if it
+    /// had not been migrated yet.
+    ///
+    /// *Caution*: If `Option<New>` is to be deleted and contains a data
+    /// structure prefixed with `State` (such as [StateBox](crate::StateBox)
+    /// or [StateMap]), then it is important to call [`Deletable::delete`] on
+    /// the value returned when you're finished with it. Otherwise, it will
+    /// remain in the contract state.
+    #[must_use]
+    pub fn insert(&mut self, key: K, value: New) -> Option<New> {
+        self.migrate_key(&key);
+        self.new.insert(key, value)
+    }
+
+    /// Remove a key from the map, returning the value at the key if the key was
+    /// previously in the map, converted if it had not been migrated yet.
+    ///
+    /// *Caution*: If `New` is a [StateBox](crate::StateBox), [StateMap], then
+    /// it is important to call [`Deletable::delete`] on the value returned
+    /// when you're finished with it. Otherwise, it will remain in the contract
+    /// state.
+    #[must_use]
+    pub fn remove_and_get(&mut self, key: &K) -> Option<New> {
+        self.migrate_key(key);
+        self.new.remove_and_get(key)
+    }
+
+    /// Remove a key from the map.
+    /// This also deletes the value in the state.
+    pub fn remove(&mut self, key: &K)
+    where
+        New: Deletable, {
+        self.migrate_key(key);
+        self.new.remove(key)
+    }
+
+    /// Migrate at most `max_values` values which have not been migrated yet.
+    /// Return whether the migration is complete.
+    pub fn migrate_batch(&mut self, max_values: u32) -> bool {
+        // The keys are collected first, since the map cannot be modified while
+        // iterating.
+        let keys: Vec<K> =
+            self.old.iter().take(max_values as usize).map(|(key, _)| key.value).collect();
+        for key in keys.iter() {
+            self.migrate_key(key);
+        }
+        self.is_complete()
+    }
+
+    /// Return `true` if all values have been migrated.
+    pub fn is_complete(&self) -> bool { self.old.is_empty() }
+
+    /// Get the map with the migrated values, if the migration is complete.
+    /// Otherwise the migrating map is returned as the error.
+    pub fn into_map(self) -> Result<StateMap<K, New, S>, Self> {
+        if self.is_complete() {
+            Ok(self.new)
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Move the value with the given key to the new map, if it has not been
+    /// migrated yet.
+    fn migrate_key(&mut self, key: &K) {
+        if let Some(old) = self.old.remove_and_get(key) {
+            let _ = self.new.insert_borrowed(key, old.into());
+        }
+    }
+}
+
+impl<K, Old, New, S> Serial for MigratingStateMap<K, Old, New, S> {
+    fn serial<W: Write>(&self, out: &mut W) -> Result<(), W::Err> {
+        self.old.serial(out)?;
+        self.new.serial(out)
+    }
+}
+
+impl<K, Old, New, S> DeserialWithState<S> for MigratingStateMap<K, Old, New, S>
+where
+    S: HasStateApi,
+{
+    fn deserial_with_state<R: Read>(state: &S, source: &mut R) -> ParseResult<Self> {
+        let old = StateMap::deserial_with_state(state, source)?;
+        let new = StateMap::deserial_with_state(state, source)?;
+        Ok(MigratingStateMap {
+            old,
+            new,
+        })
+    }
+}
+
+impl<K, Old, New, S> Deletable for MigratingStateMap<K, Old, New, S>
+where
+    S: HasStateApi,
+    K: Serialize,
+    Old: Serial + DeserialWithState<S> + Deletable,
+    New: Serial + DeserialWithState<S> + Deletable,
+{
+    fn delete(self) {
+        self.old.delete();
+        self.new.delete();
+    }
+}
+
+/// This test module relies on the runtime providing host functions and can only
+/// be run using `cargo concordium test`.
+#[cfg(feature = "internal-wasm-test")]
+mod wasm_test_versioned {
+    use super::*;
+    use crate::{claim, claim_eq, concordium_test, fail, Deserial, StateApi, StateBuilder};
+
+    /// A state at version 2, where version 0 stored a `u8` and version 1 a
+    /// `u16`.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Counter(u64);
+
+    impl Deserial for Counter {
+        fn deserial<R: Read>(source: &mut R) -> ParseResult<Self> {
+            u64::deserial(source).map(Counter)
+        }
+    }
+
+    impl VersionedState for Counter {
+        const VERSION: u32 = 2;
+
+        fn migrate(
+            from_version: u32,
+            state_api: &mut StateApi,
+            _state_builder: &mut StateBuilder,
+        ) -> Result<(), MigrationError> {
+            match from_version {
+                0 => {
+                    let old: u8 = state_api.read_root()?;
+                    state_api.write_root(&(u16::from(old) * 10));
+                    Ok(())
+                }
+                1 => {
+                    let old: u16 = state_api.read_root()?;
+                    state_api.write_root(&(u64::from(old) + 1));
+                    Ok(())
+                }
+                _ => Err(MigrationError::UnsupportedVersion),
+            }
+        }
+    }
+
+    /// A state at version 1, whose migration leaves a root state that cannot
+    /// be parsed.
+    struct Broken;
+
+    impl Deserial for Broken {
+        fn deserial<R: Read>(source: &mut R) -> ParseResult<Self> {
+            u64::deserial(source).map(|_| Broken)
+        }
+    }
+
+    impl VersionedState for Broken {
+        const VERSION: u32 = 1;
+
+        fn migrate(
+            _from_version: u32,
+            state_api: &mut StateApi,
+            _state_builder: &mut StateBuilder,
+        ) -> Result<(), MigrationError> {
+            state_api.write_root(&0u8);
+            Ok(())
+        }
+    }
+
+    /// Test that the state version defaults to `0` and can be written.
+    #[concordium_test]
+    fn test_state_version() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        claim_eq!(state_builder.state_version(), 0);
+        state_builder.write_state_version(3);
+        claim_eq!(state_builder.state_version(), 3);
+        // The version does not interfere with allocating new collections.
+        let mut map = state_builder.new_map();
+        claim!(map.insert(1u8, 1u8).is_none());
+        claim_eq!(state_builder.state_version(), 3);
+    }
+
+    /// Test looking up, modifying and removing values during a migration.
+    #[concordium_test]
+    fn test_migrating_map_lazy() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut old_map = state_builder.new_map();
+        for k in 0..4u8 {
+            let _ = old_map.insert(k, u32::from(k));
+        }
+        let mut map = state_builder.new_migrating_map::<u8, u32, u64>(old_map);
+        claim_eq!(map.get(&1).as_deref(), Some(&1));
+        claim!(map.get(&4).is_none());
+        *map.get_mut(&1).unwrap() += 10;
+        claim_eq!(map.get(&1).as_deref(), Some(&11));
+        claim_eq!(map.insert(2, 20), Some(2));
+        claim_eq!(map.remove_and_get(&3), Some(3));
+        claim!(map.get(&3).is_none());
+        claim!(!map.is_complete());
+        claim_eq!(map.old.iter().count(), 1, "Only the untouched value should remain.");
+    }
+
+    /// Test migrating the values in batches.
+    #[concordium_test]
+    fn test_migrating_map_batches() {
+        let mut state_builder = StateBuilder::open(StateApi::open());
+        let mut old_map = state_builder.new_map();
+        for k in 0..5u8 {
+            let _ = old_map.insert(k, u32::from(k));
+        }
+        let mut map = state_builder.new_migrating_map::<u8, u32, u64>(old_map);
+        claim!(!map.migrate_batch(2));
+        claim!(!map.migrate_batch(2));
+        claim!(map.migrate_batch(2));
+        let map = match map.into_map() {
+            Ok(map) => map,
+            Err(_) => fail!("The migration should be complete."),
+        };
+        for k in 0..5u8 {
+            claim_eq!(map.get(&k).as_deref(), Some(&u64::from(k)));
+        }
+    }
+
+    /// Test that the migration steps are applied in order from the stored
+    /// version, and that the current version is stored afterwards.
+    #[concordium_test]
+    fn test_migrate_state_chained() {
+        let mut host = ExternLowLevelHost::default();
+        host.state_api.write_root(&3u8);
+        claim_eq!(host.migrate_state::<Counter>(), Ok(Counter(31)));
+        claim_eq!(host.state_builder.state_version(), 2);
+        claim_eq!(host.state_api.read_root::<u64>(), Ok(31));
+        // Migrating again does nothing, since the state is at the current version.
+        claim_eq!(host.migrate_state::<Counter>(), Ok(Counter(31)));
+    }
+
+    /// Test that only the steps from the stored version are applied.
+    #[concordium_test]
+    fn test_migrate_state_from_stored_version() {
+        let mut host = ExternLowLevelHost::default();
+        host.state_builder.write_state_version(1);
+        host.state_api.write_root(&30u16);
+        claim_eq!(host.migrate_state::<Counter>(), Ok(Counter(31)));
+        claim_eq!(host.state_builder.state_version(), 2);
+    }
+
+    /// Test that a state stored by a newer version is not migrated.
+    #[concordium_test]
+    fn test_migrate_state_newer_version() {
+        let mut host = ExternLowLevelHost::default();
+        host.state_builder.write_state_version(3);
+        host.state_api.write_root(&31u64);
+        claim_eq!(host.migrate_state::<Counter>(), Err(MigrationError::UnsupportedVersion));
+        claim_eq!(host.state_builder.state_version(), 3);
+    }
+
+    /// Test that a migration step failing to parse the old state fails with
+    /// `MalformedState`.
+    #[concordium_test]
+    fn test_migrate_state_malformed() {
+        let mut host = ExternLowLevelHost::default();
+        host.state_builder.write_state_version(1);
+        host.state_api.write_root(&0u8);
+        claim_eq!(host.migrate_state::<Counter>(), Err(MigrationError::MalformedState));
+        claim_eq!(host.state_builder.state_version(), 1);
+    }
+
+    /// Test that a migrated state which cannot be parsed in the current layout
+    /// fails with `InvalidState`, without storing the new version.
+    #[concordium_test]
+    fn test_migrate_state_invalid() {
+        let mut host = ExternLowLevelHost::default();
+        host.state_api.write_root(&0u64);
+        claim!(matches!(host.migrate_state::<Broken>(), Err(MigrationError::InvalidState)));
+        claim_eq!(host.state_builder.state_version(), 0);
+    }
+}
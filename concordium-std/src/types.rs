@@ -467,6 +467,9 @@ pub struct VacantEntry<'a, K, V, S> {
     pub(crate) key:              K,
     pub(crate) key_bytes:        Vec<u8>,
     pub(crate) state_api:        S,
+    /// Length to increment when inserting, used by
+    /// [`CountedStateMap`](crate::CountedStateMap).
+    pub(crate) counter:          Option<&'a mut u32>,
     pub(crate) _lifetime_marker: PhantomData<&'a mut (K, V)>,
 }
 
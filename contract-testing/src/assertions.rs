@@ -0,0 +1,407 @@
+//! Fluent assertions on the outcomes of contract updates and invocations.
+//!
+//! The entrypoint is the [`assert_update`] function.
+use crate::types::*;
+use concordium_rust_sdk::base::{
+    contracts_common::{
+        AccountAddress, Amount, ContractAddress, Deserial, EntrypointName, ModuleReference,
+    },
+    smart_contracts::{ContractTraceElement, InstanceUpdatedEvent},
+};
+use std::fmt::Debug;
+
+/// The outcome of a contract update or invocation that assertions are made
+/// on.
+///
+/// It is constructed implicitly by [`assert_update`] from either a
+/// `Result<ContractInvokeSuccess, ContractInvokeError>` or one of its
+/// variants.
+pub struct UpdateOutcome<'a>(Result<&'a ContractInvokeSuccess, &'a ContractInvokeError>);
+
+impl<'a> From<&'a Result<ContractInvokeSuccess, ContractInvokeError>> for UpdateOutcome<'a> {
+    fn from(result: &'a Result<ContractInvokeSuccess, ContractInvokeError>) -> Self {
+        Self(result.as_ref())
+    }
+}
+
+impl<'a> From<&'a ContractInvokeSuccess> for UpdateOutcome<'a> {
+    fn from(success: &'a ContractInvokeSuccess) -> Self { Self(Ok(success)) }
+}
+
+impl<'a> From<&'a ContractInvokeError> for UpdateOutcome<'a> {
+    fn from(error: &'a ContractInvokeError) -> Self { Self(Err(error)) }
+}
+
+/// Assertions on the outcome of a contract update or invocation.
+///
+/// Created with [`assert_update`]. All the assertion methods return the
+/// assertion so that they can be chained.
+pub struct UpdateAssertion<'a> {
+    /// The outcome the assertions are made on.
+    outcome: Result<&'a ContractInvokeSuccess, &'a ContractInvokeError>,
+}
+
+/// Start making assertions on the outcome of a contract update or invocation.
+///
+/// Takes the result of [`Chain::contract_update`] or
+/// [`Chain::contract_invoke`] and returns an [`UpdateAssertion`] on which
+/// assertions can be chained. Each assertion panics with a description of the
+/// expected and the actual outcome if it does not hold.
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// # let chain = Chain::new();
+/// # let payload: UpdateContractPayload = todo!();
+/// # const ACC: AccountAddress = AccountAddress([0; 32]);
+/// let res = chain.contract_invoke(ACC, Address::Account(ACC), Energy::from(10000), payload);
+/// assert_update(&res)
+///     .succeeded()
+///     .transferred(ACC, Amount::from_micro_ccd(17))
+///     .emitted_event(42u32);
+/// ```
+pub fn assert_update<'a>(outcome: impl Into<UpdateOutcome<'a>>) -> UpdateAssertion<'a> {
+    UpdateAssertion {
+        outcome: outcome.into().0,
+    }
+}
+
+impl<'a> UpdateAssertion<'a> {
+    /// Assert that the update succeeded.
+    #[track_caller]
+    pub fn succeeded(self) -> Self {
+        self.success();
+        self
+    }
+
+    /// Assert that the update failed for any reason.
+    #[track_caller]
+    pub fn failed(self) -> Self {
+        self.error();
+        self
+    }
+
+    /// Assert that the contract rejected the update.
+    #[track_caller]
+    pub fn rejected(self) -> Self {
+        self.reject_code();
+        self
+    }
+
+    /// Assert that the contract rejected the update with the provided reject
+    /// code.
+    #[track_caller]
+    pub fn rejected_with_code(self, expected: i32) -> Self {
+        let actual = self.reject_code();
+        if actual != expected {
+            panic!(
+                "Expected the update to be rejected with code {expected}, but it was rejected \
+                 with code {actual}."
+            );
+        }
+        self
+    }
+
+    /// Assert that the contract rejected the update with the provided error.
+    ///
+    /// The error is parsed from the return value of the rejection, which is
+    /// where `concordium-std` places the errors returned by entrypoints.
+    #[track_caller]
+    pub fn rejected_with<E: Deserial + PartialEq + Debug>(self, expected: E) -> Self {
+        let code = self.reject_code();
+        let error = self.error();
+        match error.parse_return_value::<E>() {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => panic!(
+                "Expected the update to be rejected with\n{expected:#?}\nbut it was rejected with \
+                 code {code} and\n{actual:#?}"
+            ),
+            Err(_) => panic!(
+                "Expected the update to be rejected with\n{expected:#?}\nbut it was rejected with \
+                 code {code} and a return value that could not be parsed: {:?}",
+                error.return_value()
+            ),
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and returned the provided value.
+    #[track_caller]
+    pub fn returned<T: Deserial + PartialEq + Debug>(self, expected: T) -> Self {
+        let success = self.success();
+        match success.parse_return_value::<T>() {
+            Ok(actual) if actual == expected => {}
+            Ok(actual) => {
+                panic!("Expected the return value\n{expected:#?}\nbut it was\n{actual:#?}")
+            }
+            Err(_) => panic!(
+                "Expected the return value\n{expected:#?}\nbut the return value could not be \
+                 parsed: {:?}",
+                success.return_value
+            ),
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that the state of the invoked
+    /// contract changed.
+    #[track_caller]
+    pub fn state_changed(self) -> Self {
+        if !self.success().state_changed {
+            panic!("Expected the state of the invoked contract to change, but it did not.");
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that any contract emitted the
+    /// provided event.
+    ///
+    /// Only events that have not been rolled back are considered.
+    #[track_caller]
+    pub fn emitted_event<E: Deserial + PartialEq + Debug>(self, expected: E) -> Self {
+        let events = self.parsed_events::<E>();
+        if !events.iter().any(|(_, event)| event.as_ref() == Some(&expected)) {
+            panic!(
+                "Expected the event\n{expected:#?}\nto be emitted, but the emitted events \
+                 were:\n{}",
+                format_events(&events)
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that the contract at the provided
+    /// address emitted the provided event.
+    ///
+    /// Only events that have not been rolled back are considered.
+    #[track_caller]
+    pub fn emitted_event_from<E: Deserial + PartialEq + Debug>(
+        self,
+        address: ContractAddress,
+        expected: E,
+    ) -> Self {
+        let events = self.parsed_events::<E>();
+        if !events.iter().any(|(addr, event)| *addr == address && event.as_ref() == Some(&expected))
+        {
+            panic!(
+                "Expected the event\n{expected:#?}\nto be emitted by {address}, but the emitted \
+                 events were:\n{}",
+                format_events(&events)
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that exactly the provided events
+    /// were emitted, in order, by any contracts.
+    ///
+    /// Only events that have not been rolled back are considered.
+    #[track_caller]
+    pub fn emitted_events<E: Deserial + PartialEq + Debug>(self, expected: &[E]) -> Self {
+        let events = self.parsed_events::<E>();
+        let matches = events.len() == expected.len()
+            && events
+                .iter()
+                .zip(expected)
+                .all(|((_, actual), expected)| actual.as_ref() == Some(expected));
+        if !matches {
+            panic!(
+                "Expected the events\n{expected:#?}\nto be emitted, but the emitted events \
+                 were:\n{}",
+                format_events(&events)
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that a contract transferred the
+    /// `amount` to the account `to`.
+    ///
+    /// Only transfers that have not been rolled back are considered.
+    #[track_caller]
+    pub fn transferred(self, to: AccountAddress, amount: Amount) -> Self {
+        let success = self.success();
+        if !success
+            .account_transfers()
+            .any(|(_, actual_amount, actual_to)| actual_to == to && actual_amount == amount)
+        {
+            let transfers: Vec<_> = success.account_transfers().collect();
+            panic!(
+                "Expected a transfer of {amount} to {to}, but the transfers (from, amount, to) \
+                 were:\n{transfers:#?}"
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that the entrypoint of the
+    /// contract was called successfully, either by an account or by another
+    /// contract.
+    ///
+    /// Only calls that have not been rolled back are considered.
+    #[track_caller]
+    pub fn called(self, contract: ContractAddress, entrypoint: EntrypointName) -> Self {
+        let updates: Vec<&InstanceUpdatedEvent> = self.success().updates().collect();
+        if !updates.iter().any(|update| {
+            update.address == contract
+                && update.receive_name.as_receive_name().entrypoint_name() == entrypoint
+        }) {
+            panic!(
+                "Expected entrypoint '{entrypoint}' of {contract} to be called, but the \
+                 successful calls were:\n{}",
+                format_calls(&updates)
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that the entrypoint of the
+    /// contract was not called successfully.
+    #[track_caller]
+    pub fn not_called(self, contract: ContractAddress, entrypoint: EntrypointName) -> Self {
+        let updates: Vec<&InstanceUpdatedEvent> = self.success().updates().collect();
+        if updates.iter().any(|update| {
+            update.address == contract
+                && update.receive_name.as_receive_name().entrypoint_name() == entrypoint
+        }) {
+            panic!(
+                "Expected entrypoint '{entrypoint}' of {contract} not to be called, but the \
+                 successful calls were:\n{}",
+                format_calls(&updates)
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded and that the contract was upgraded to
+    /// the provided module.
+    #[track_caller]
+    pub fn upgraded(self, contract: ContractAddress, to: ModuleReference) -> Self {
+        let success = self.success();
+        let upgrades: Vec<_> = success
+            .effective_trace_elements()
+            .filter_map(|element| match element {
+                ContractTraceElement::Upgraded {
+                    address,
+                    from,
+                    to,
+                } => Some((*address, *from, *to)),
+                _ => None,
+            })
+            .collect();
+        if !upgrades.iter().any(|(address, _, actual_to)| *address == contract && *actual_to == to)
+        {
+            panic!(
+                "Expected {contract} to be upgraded to module {to}, but the upgrades (address, \
+                 from, to) were:\n{upgrades:#?}"
+            );
+        }
+        self
+    }
+
+    /// Assert that the update succeeded, but that some of the calls it made
+    /// failed and were rolled back.
+    #[track_caller]
+    pub fn rolled_back(self) -> Self {
+        if !self.success().rollbacks_occurred() {
+            panic!("Expected some calls to be rolled back, but no rollbacks occurred.");
+        }
+        self
+    }
+
+    /// Assert that the update succeeded, and that none of the calls it made
+    /// were rolled back.
+    #[track_caller]
+    pub fn not_rolled_back(self) -> Self {
+        let success = self.success();
+        if success.rollbacks_occurred() {
+            let failures: Vec<_> = success
+                .trace_elements
+                .iter()
+                .filter_map(|element| match element {
+                    DebugTraceElement::WithFailures {
+                        contract_address,
+                        entrypoint,
+                        error,
+                        ..
+                    } => Some(format!("  {contract_address} '{entrypoint}': {error:?}")),
+                    _ => None,
+                })
+                .collect();
+            panic!("Expected no rollbacks, but these calls failed:\n{}", failures.join("\n"));
+        }
+        self
+    }
+
+    /// Get the successful outcome, or panic with the error.
+    #[track_caller]
+    fn success(&self) -> &'a ContractInvokeSuccess {
+        match self.outcome {
+            Ok(success) => success,
+            Err(error) => panic!("Expected the update to succeed, but it failed with: {error}"),
+        }
+    }
+
+    /// Get the failed outcome, or panic.
+    #[track_caller]
+    fn error(&self) -> &'a ContractInvokeError {
+        match self.outcome {
+            Ok(success) => panic!(
+                "Expected the update to fail, but it succeeded with the return value {:?}",
+                success.return_value
+            ),
+            Err(error) => error,
+        }
+    }
+
+    /// Get the reject code, or panic if the contract did not reject.
+    #[track_caller]
+    fn reject_code(&self) -> i32 {
+        let error = self.error();
+        match error.reject_code() {
+            Some(code) => code,
+            None => panic!(
+                "Expected the contract to reject the update, but it failed with: {}",
+                error.kind
+            ),
+        }
+    }
+
+    /// Get all the effective events, paired with the emitting contract, and
+    /// parsed as `E`. Events that cannot be parsed are `None`.
+    #[track_caller]
+    fn parsed_events<E: Deserial>(&self) -> Vec<(ContractAddress, Option<E>)> {
+        self.success()
+            .events()
+            .flat_map(|(address, events)| {
+                events.iter().map(move |event| (address, event.parse::<E>().ok()))
+            })
+            .collect()
+    }
+}
+
+/// Format parsed events for a failure message, one per line.
+fn format_events<E: Debug>(events: &[(ContractAddress, Option<E>)]) -> String {
+    if events.is_empty() {
+        return "  (none)".into();
+    }
+    events
+        .iter()
+        .map(|(address, event)| match event {
+            Some(event) => format!("  {address}: {event:?}"),
+            None => format!("  {address}: (event could not be parsed)"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Format successful calls for a failure message, one per line.
+fn format_calls(updates: &[&InstanceUpdatedEvent]) -> String {
+    if updates.is_empty() {
+        return "  (none)".into();
+    }
+    updates
+        .iter()
+        .map(|update| format!("  {} '{}'", update.address, update.receive_name))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,310 @@
+//! Benchmarks of contract entrypoints, measuring both the wall-clock time and
+//! the energy of repeated executions against a fixed state.
+use crate::{impls::check_header_cost, types::*};
+use concordium_rust_sdk::base::{
+    base::Energy,
+    contracts_common::{AccountAddress, Address},
+    transactions::UpdateContractPayload,
+};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// A benchmark of contract updates or invocations.
+///
+/// Each sample executes the transaction on the chain passed to
+/// [`Benchmark::update`] or [`Benchmark::invoke`], so every sample starts
+/// from the same state. For updates, the chain is copied before each sample,
+/// and the time for copying is not measured. A number of warm-up executions,
+/// which are not measured, are made first.
+///
+/// The [`BenchmarkResult`] summarizes the wall-clock time and the energy of
+/// the samples. To see how the cost scales with some input, such as the size
+/// of the state, create a chain for each input and collect the results in a
+/// [`ScalingCurve`].
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// # let chain = Chain::new();
+/// # const ACC: AccountAddress = AccountAddress([0; 32]);
+/// # let payload: UpdateContractPayload = todo!();
+/// let result = Benchmark::new()
+///     .samples(50)
+///     .update(
+///         &chain,
+///         Signer::with_one_key(),
+///         ACC,
+///         Address::Account(ACC),
+///         Energy::from(100_000),
+///         payload,
+///     )
+///     .unwrap();
+/// println!("{result}");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Benchmark {
+    samples: usize,
+    warm_up: usize,
+}
+
+/// The result of a [`Benchmark`].
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    /// The wall-clock time of each sample.
+    pub times:            Vec<Duration>,
+    /// The energy used by each sample, as in
+    /// [`ContractInvokeSuccess::energy_used`].
+    pub energy_used:      Vec<Energy>,
+    /// The energy used for executing the contracts in each sample, i.e. the
+    /// energy used minus the header, storage and module load energy. This is
+    /// the same for updates and invocations of the same entrypoint, since
+    /// invocations are not charged for checking the transaction header.
+    pub execution_energy: Vec<Energy>,
+}
+
+/// A summary of a distribution of measurements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// The smallest measurement.
+    pub min:     f64,
+    /// The largest measurement.
+    pub max:     f64,
+    /// The mean of the measurements.
+    pub mean:    f64,
+    /// The median of the measurements.
+    pub median:  f64,
+    /// The standard deviation of the measurements.
+    pub std_dev: f64,
+}
+
+/// The results of benchmarks for a range of inputs, e.g., sizes of the
+/// contract state.
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// # fn setup(size: u64) -> (Chain, UpdateContractPayload) { todo!() }
+/// # const ACC: AccountAddress = AccountAddress([0; 32]);
+/// let curve = ScalingCurve::measure([10, 100, 1000], |size| {
+///     let (chain, payload) = setup(size);
+///     Benchmark::new().update(
+///         &chain,
+///         Signer::with_one_key(),
+///         ACC,
+///         Address::Account(ACC),
+///         Energy::from(100_000),
+///         payload,
+///     )
+/// })
+/// .unwrap();
+/// std::fs::write("transfer-cost.csv", curve.to_csv()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScalingCurve {
+    /// The inputs and the result of the benchmark for each of them, in the
+    /// order they were measured.
+    pub points: Vec<(u64, BenchmarkResult)>,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self {
+            samples: 100,
+            warm_up: 3,
+        }
+    }
+}
+
+impl Benchmark {
+    /// Create a benchmark with 100 samples and 3 warm-up executions.
+    pub fn new() -> Self { Self::default() }
+
+    /// Set the number of measured samples.
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Set the number of executions before the samples, which are not
+    /// measured.
+    pub fn warm_up(mut self, warm_up: usize) -> Self {
+        self.warm_up = warm_up;
+        self
+    }
+
+    /// Benchmark a contract update. The parameters are the same as for
+    /// [`Chain::contract_update`], and the `chain` is not modified.
+    ///
+    /// Returns the error of the first execution that fails.
+    pub fn update(
+        &self,
+        chain: &Chain,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
+    ) -> Result<BenchmarkResult, ContractInvokeError> {
+        let header_energy = check_header_cost(signer, payload.size());
+        self.run(header_energy, || {
+            let mut chain = chain.dry_run_copy();
+            let start = Instant::now();
+            let result =
+                chain.contract_update(signer, invoker, sender, energy_reserved, payload.clone());
+            (start.elapsed(), result)
+        })
+    }
+
+    /// Benchmark a contract invocation. The parameters are the same as for
+    /// [`Chain::contract_invoke`].
+    ///
+    /// Returns the error of the first execution that fails.
+    pub fn invoke(
+        &self,
+        chain: &Chain,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
+    ) -> Result<BenchmarkResult, ContractInvokeError> {
+        self.run(Energy::from(0), || {
+            let start = Instant::now();
+            let result = chain.contract_invoke(invoker, sender, energy_reserved, payload.clone());
+            (start.elapsed(), result)
+        })
+    }
+
+    /// Run the benchmark, where `header_energy` is the energy charged for
+    /// checking the transaction header in each execution.
+    fn run(
+        &self,
+        header_energy: Energy,
+        mut execute: impl FnMut() -> (Duration, Result<ContractInvokeSuccess, ContractInvokeError>),
+    ) -> Result<BenchmarkResult, ContractInvokeError> {
+        for _ in 0..self.warm_up {
+            execute().1?;
+        }
+        let mut result = BenchmarkResult {
+            times:            Vec::with_capacity(self.samples),
+            energy_used:      Vec::with_capacity(self.samples),
+            execution_energy: Vec::with_capacity(self.samples),
+        };
+        for _ in 0..self.samples {
+            let (time, success) = execute();
+            let success = success?;
+            result.times.push(time);
+            result.energy_used.push(success.energy_used);
+            result.execution_energy.push(
+                success.energy_used
+                    - header_energy
+                    - success.storage_energy
+                    - success.module_load_energy,
+            );
+        }
+        Ok(result)
+    }
+}
+
+impl BenchmarkResult {
+    /// A summary of the times in seconds. Returns `None` if there are no
+    /// samples.
+    pub fn time_summary(&self) -> Option<Summary> {
+        Summary::new(self.times.iter().map(Duration::as_secs_f64))
+    }
+
+    /// A summary of the energy used. Returns `None` if there are no samples.
+    pub fn energy_summary(&self) -> Option<Summary> {
+        Summary::new(self.energy_used.iter().map(|energy| energy.energy as f64))
+    }
+
+    /// A summary of the execution energy. Returns `None` if there are no
+    /// samples.
+    pub fn execution_energy_summary(&self) -> Option<Summary> {
+        Summary::new(self.execution_energy.iter().map(|energy| energy.energy as f64))
+    }
+}
+
+impl Summary {
+    /// Summarize the measurements. Returns `None` if there are none.
+    pub fn new(measurements: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut sorted: Vec<f64> = measurements.into_iter().collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(f64::total_cmp);
+        let len = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / len as f64;
+        let median = if len % 2 == 0 {
+            (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0
+        } else {
+            sorted[len / 2]
+        };
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / len as f64;
+        Some(Self {
+            min: sorted[0],
+            max: sorted[len - 1],
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+        })
+    }
+}
+
+impl ScalingCurve {
+    /// Run a benchmark for each of the `inputs`. Returns the first error.
+    pub fn measure<E>(
+        inputs: impl IntoIterator<Item = u64>,
+        mut benchmark: impl FnMut(u64) -> Result<BenchmarkResult, E>,
+    ) -> Result<Self, E> {
+        let points = inputs
+            .into_iter()
+            .map(|input| Ok((input, benchmark(input)?)))
+            .collect::<Result<_, E>>()?;
+        Ok(Self {
+            points,
+        })
+    }
+
+    /// Render the curve as CSV with a header and a row for each input with the
+    /// median and mean time in seconds and the median and mean execution
+    /// energy.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("input,median_time,mean_time,median_energy,mean_energy\n");
+        for (input, result) in &self.points {
+            let (Some(time), Some(energy)) =
+                (result.time_summary(), result.execution_energy_summary())
+            else {
+                continue;
+            };
+            csv.push_str(&format!(
+                "{input},{},{},{},{}\n",
+                time.median, time.mean, energy.median, energy.mean
+            ));
+        }
+        csv
+    }
+}
+
+impl fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some(time), Some(energy)) = (self.time_summary(), self.execution_energy_summary())
+        else {
+            return write!(f, "no samples");
+        };
+        let secs = Duration::from_secs_f64;
+        writeln!(
+            f,
+            "time: median {:.1?}, mean {:.1?}, min {:.1?}, max {:.1?}, std. dev. {:.1?}",
+            secs(time.median),
+            secs(time.mean),
+            secs(time.min),
+            secs(time.max),
+            secs(time.std_dev)
+        )?;
+        write!(
+            f,
+            "execution energy: median {}, mean {}, min {}, max {}",
+            energy.median, energy.mean, energy.min, energy.max
+        )
+    }
+}
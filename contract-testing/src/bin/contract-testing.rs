@@ -0,0 +1,29 @@
+//! Runs the scenario files given as arguments with
+//! [`concordium_smart_contract_testing::run_scenario_file`], and exits with a
+//! non-zero status if any of them fail.
+use concordium_smart_contract_testing::run_scenario_file;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: contract-testing <scenario.json>...");
+        return ExitCode::FAILURE;
+    }
+    let mut failed = 0;
+    for path in &paths {
+        match run_scenario_file(path) {
+            Ok(_) => println!("{path} ... ok"),
+            Err(err) => {
+                println!("{path} ... FAILED\n{err}");
+                failed += 1;
+            }
+        }
+    }
+    println!("{} passed, {failed} failed", paths.len() - failed);
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
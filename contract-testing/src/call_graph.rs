@@ -0,0 +1,521 @@
+//! Call graphs of contract interactions, reconstructed from the
+//! [`DebugTraceElement`]s of a contract invocation.
+use crate::types::*;
+use concordium_rust_sdk::base::{
+    base::Energy,
+    contracts_common::{
+        AccountAddress, Amount, ContractAddress, ModuleReference, OwnedEntrypointName,
+    },
+    smart_contracts::ContractTraceElement,
+};
+use std::fmt::{self, Write};
+
+/// The tree of contract calls, transfers and upgrades made during a single
+/// contract invocation.
+///
+/// Construct it with [`ContractInvokeSuccess::call_graph`],
+/// [`ContractInvokeError::call_graph`], or [`CallGraph::from_trace_elements`].
+/// It can be rendered
+///  - as indented text with its [`Display`](fmt::Display) implementation,
+///  - in the Graphviz DOT format with [`CallGraph::to_dot`], and
+///  - as a Mermaid flowchart with [`CallGraph::to_mermaid`].
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// # let res: ContractInvokeSuccess = todo!();
+/// println!("{}", res.call_graph());
+/// // <0,0> forward (713 NRG)
+/// //   <1,0> deposit (302 NRG)
+/// //   transfer 0.000017 CCD to 2wkBET2rRgE8pahuaczxKbmv7ciehqsne57F9gtzf1PVdr2VP3
+/// std::fs::write("calls.dot", res.call_graph().to_dot()).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallGraph {
+    /// The top-level items. For an invocation of a V1 contract this is the
+    /// single call of the invoked entrypoint.
+    pub roots: Vec<CallGraphItem>,
+}
+
+/// An item in a [`CallGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallGraphItem {
+    /// A call of a contract entrypoint.
+    Call(CallNode),
+    /// A transfer from a contract to an account.
+    Transfer {
+        /// The contract that sent the amount.
+        from:        ContractAddress,
+        /// The amount transferred.
+        amount:      Amount,
+        /// The receiving account.
+        to:          AccountAddress,
+        /// Whether the transfer was rolled back.
+        rolled_back: bool,
+    },
+    /// An upgrade of a contract to a new module.
+    Upgrade {
+        /// The contract that was upgraded.
+        address:     ContractAddress,
+        /// The module before the upgrade.
+        from:        ModuleReference,
+        /// The module after the upgrade.
+        to:          ModuleReference,
+        /// Whether the upgrade was rolled back.
+        rolled_back: bool,
+    },
+    /// A transfer, call or upgrade that failed without executing any code,
+    /// for example a transfer with insufficient funds or a call to a missing
+    /// contract.
+    Failed {
+        /// Whether the part of the execution with the failure was itself
+        /// rolled back.
+        rolled_back: bool,
+    },
+}
+
+/// A call of a contract entrypoint in a [`CallGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallNode {
+    /// The contract that was called.
+    pub address:     ContractAddress,
+    /// The entrypoint that was called.
+    pub entrypoint:  OwnedEntrypointName,
+    /// The energy used by the call, including the energy used by nested
+    /// calls.
+    pub energy_used: Energy,
+    /// How the call ended.
+    pub outcome:     CallOutcome,
+    /// Whether the effects of the call were rolled back, either because the
+    /// call itself failed or because one of its callers failed.
+    pub rolled_back: bool,
+    /// The calls, transfers and upgrades made by the call, in the order they
+    /// occurred.
+    pub children:    Vec<CallGraphItem>,
+}
+
+/// How a [`CallNode`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    /// The call succeeded.
+    Success,
+    /// The contract rejected with the reject code.
+    Rejected {
+        /// The reject code.
+        reason: i32,
+    },
+    /// The contract trapped.
+    Trapped,
+    /// The execution did not finish, for example because it ran out of
+    /// energy.
+    Unfinished,
+}
+
+impl ContractInvokeSuccess {
+    /// Construct the [`CallGraph`] of the invocation.
+    pub fn call_graph(&self) -> CallGraph { CallGraph::from_trace_elements(&self.trace_elements) }
+}
+
+impl ContractInvokeError {
+    /// Construct the [`CallGraph`] of the invocation.
+    pub fn call_graph(&self) -> CallGraph { CallGraph::from_trace_elements(&self.trace_elements) }
+}
+
+impl CallGraph {
+    /// Reconstruct the call graph from the trace elements of a single
+    /// invocation.
+    pub fn from_trace_elements(trace_elements: &[DebugTraceElement]) -> Self {
+        let mut builder = CallGraphBuilder {
+            roots:  Vec::new(),
+            stack:  Vec::new(),
+            energy: Energy::from(0),
+        };
+        builder.process(trace_elements);
+        builder.finish()
+    }
+
+    /// Render the call graph in the Graphviz DOT format.
+    ///
+    /// Calls are boxes labelled with the address, entrypoint and energy used.
+    /// Rolled back items are drawn dashed and red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph calls {\n    node [shape=box];\n");
+        let mut next_id = 0;
+        for root in &self.roots {
+            dot_item(&mut out, root, None, &mut next_id);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the call graph as a Mermaid flowchart.
+    ///
+    /// Rolled back items are given the class `rolledBack`, which is drawn
+    /// dashed and red.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        let mut next_id = 0;
+        for root in &self.roots {
+            mermaid_item(&mut out, root, None, &mut next_id);
+        }
+        out.push_str("    classDef rolledBack stroke:#f00,stroke-dasharray:5 5\n");
+        out
+    }
+}
+
+/// Renders the call graph as indented text, one item per line and nested
+/// items indented by two spaces.
+impl fmt::Display for CallGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for root in &self.roots {
+            text_item(f, root, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl CallGraphItem {
+    /// Whether the item was rolled back.
+    pub fn rolled_back(&self) -> bool {
+        match self {
+            CallGraphItem::Call(node) => node.rolled_back,
+            CallGraphItem::Transfer {
+                rolled_back,
+                ..
+            }
+            | CallGraphItem::Upgrade {
+                rolled_back,
+                ..
+            }
+            | CallGraphItem::Failed {
+                rolled_back,
+            } => *rolled_back,
+        }
+    }
+
+    /// Mark the item and everything nested in it as rolled back.
+    fn mark_rolled_back(&mut self) {
+        match self {
+            CallGraphItem::Call(node) => node.mark_rolled_back(),
+            CallGraphItem::Transfer {
+                rolled_back,
+                ..
+            }
+            | CallGraphItem::Upgrade {
+                rolled_back,
+                ..
+            }
+            | CallGraphItem::Failed {
+                rolled_back,
+            } => *rolled_back = true,
+        }
+    }
+
+    /// A one-line description of the item.
+    fn label(&self) -> String {
+        match self {
+            CallGraphItem::Call(node) => {
+                let outcome = match node.outcome {
+                    CallOutcome::Success => String::new(),
+                    CallOutcome::Rejected {
+                        reason,
+                    } => format!(", rejected with {reason}"),
+                    CallOutcome::Trapped => ", trapped".into(),
+                    CallOutcome::Unfinished => ", unfinished".into(),
+                };
+                format!("{} {} ({} NRG{outcome})", node.address, node.entrypoint, node.energy_used)
+            }
+            CallGraphItem::Transfer {
+                amount,
+                to,
+                ..
+            } => format!("transfer {amount} to {to}"),
+            CallGraphItem::Upgrade {
+                address,
+                to,
+                ..
+            } => format!("upgrade {address} to module {to}"),
+            CallGraphItem::Failed {
+                ..
+            } => "failed interaction".into(),
+        }
+    }
+
+    /// The nested items.
+    fn children(&self) -> &[CallGraphItem] {
+        match self {
+            CallGraphItem::Call(node) => &node.children,
+            _ => &[],
+        }
+    }
+}
+
+impl CallNode {
+    /// Mark the call and everything nested in it as rolled back.
+    fn mark_rolled_back(&mut self) {
+        self.rolled_back = true;
+        for child in &mut self.children {
+            child.mark_rolled_back();
+        }
+    }
+}
+
+/// A call that has started, but not yet finished, while reconstructing a
+/// [`CallGraph`].
+struct Frame {
+    /// The call. Its `energy_used` is the energy used by the transaction when
+    /// the call started until the call is finished.
+    node:                  CallNode,
+    /// Whether the call is currently interrupted.
+    interrupted:           bool,
+    /// The number of children when the call was last interrupted. Used to
+    /// detect interrupts that failed without leaving a trace.
+    children_at_interrupt: usize,
+}
+
+/// Reconstructs a [`CallGraph`] from the flat sequence of trace elements.
+///
+/// The trace elements of a call are laid out as its `Interrupted` elements,
+/// each followed by the elements of the interaction (a nested call, transfer
+/// or upgrade) and a `Resumed` element, and finally an `Updated` element when
+/// the call finishes. Failed calls are instead recorded as a single
+/// `WithFailures` element containing the elements that were rolled back.
+struct CallGraphBuilder {
+    roots:  Vec<CallGraphItem>,
+    stack:  Vec<Frame>,
+    /// The energy used by the transaction at the last processed element.
+    energy: Energy,
+}
+
+impl CallGraphBuilder {
+    fn process(&mut self, trace_elements: &[DebugTraceElement]) {
+        for element in trace_elements {
+            self.element(element);
+        }
+    }
+
+    fn element(&mut self, element: &DebugTraceElement) {
+        match element {
+            DebugTraceElement::Debug {
+                ..
+            } => {}
+            DebugTraceElement::WithFailures {
+                contract_address,
+                entrypoint,
+                error,
+                trace_elements,
+                energy_used,
+                ..
+            } => {
+                self.start(*contract_address, entrypoint.clone());
+                let depth = self.stack.len();
+                self.process(trace_elements);
+                // Close any calls that were left open in the failed part.
+                while self.stack.len() > depth {
+                    self.end(CallOutcome::Unfinished, self.energy);
+                }
+                let outcome = match error {
+                    InvokeExecutionError::Reject {
+                        reason,
+                        ..
+                    } => CallOutcome::Rejected {
+                        reason: *reason,
+                    },
+                    InvokeExecutionError::Trap {
+                        ..
+                    } => CallOutcome::Trapped,
+                };
+                self.end(outcome, *energy_used);
+            }
+            DebugTraceElement::Regular {
+                entrypoint,
+                trace_element,
+                energy_used,
+                ..
+            } => {
+                match trace_element {
+                    ContractTraceElement::Interrupted {
+                        address,
+                        ..
+                    } => {
+                        if !self.top_is(*address, false) {
+                            self.start(*address, entrypoint.clone());
+                        }
+                        if let Some(top) = self.stack.last_mut() {
+                            top.interrupted = true;
+                            top.children_at_interrupt = top.node.children.len();
+                        }
+                    }
+                    ContractTraceElement::Resumed {
+                        address,
+                        success,
+                    } => {
+                        if self.top_is(*address, true) {
+                            if let Some(top) = self.stack.last_mut() {
+                                top.interrupted = false;
+                                if !success && top.node.children.len() == top.children_at_interrupt
+                                {
+                                    top.node.children.push(CallGraphItem::Failed {
+                                        rolled_back: false,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    ContractTraceElement::Transferred {
+                        from,
+                        amount,
+                        to,
+                    } => self.attach(CallGraphItem::Transfer {
+                        from:        *from,
+                        amount:      *amount,
+                        to:          *to,
+                        rolled_back: false,
+                    }),
+                    ContractTraceElement::Upgraded {
+                        address,
+                        from,
+                        to,
+                    } => self.attach(CallGraphItem::Upgrade {
+                        address:     *address,
+                        from:        *from,
+                        to:          *to,
+                        rolled_back: false,
+                    }),
+                    ContractTraceElement::Updated {
+                        data,
+                    } => {
+                        // A call without interrupts has no elements before it is updated.
+                        if !self.top_is(data.address, false) {
+                            self.start(data.address, entrypoint.clone());
+                        }
+                        self.end(CallOutcome::Success, *energy_used);
+                    }
+                }
+                self.energy = *energy_used;
+            }
+        }
+    }
+
+    /// Whether the innermost open call is of the contract and is interrupted
+    /// or not.
+    fn top_is(&self, address: ContractAddress, interrupted: bool) -> bool {
+        self.stack
+            .last()
+            .is_some_and(|top| top.node.address == address && top.interrupted == interrupted)
+    }
+
+    /// Open a new call, starting at the current energy.
+    fn start(&mut self, address: ContractAddress, entrypoint: OwnedEntrypointName) {
+        self.stack.push(Frame {
+            node:                  CallNode {
+                address,
+                entrypoint,
+                energy_used: self.energy,
+                outcome: CallOutcome::Unfinished,
+                rolled_back: false,
+                children: Vec::new(),
+            },
+            interrupted:           false,
+            children_at_interrupt: 0,
+        });
+    }
+
+    /// Close the innermost open call and attach it to its caller.
+    fn end(&mut self, outcome: CallOutcome, energy_at_end: Energy) {
+        let Some(Frame {
+            mut node,
+            ..
+        }) = self.stack.pop()
+        else {
+            return;
+        };
+        node.energy_used =
+            Energy::from(energy_at_end.energy.saturating_sub(node.energy_used.energy));
+        node.outcome = outcome;
+        let mut item = CallGraphItem::Call(node);
+        if outcome != CallOutcome::Success {
+            item.mark_rolled_back();
+        }
+        self.energy = energy_at_end;
+        self.attach(item);
+    }
+
+    /// Attach an item to the innermost open call, or as a root if there is
+    /// none.
+    fn attach(&mut self, item: CallGraphItem) {
+        match self.stack.last_mut() {
+            Some(top) => top.node.children.push(item),
+            None => self.roots.push(item),
+        }
+    }
+
+    fn finish(mut self) -> CallGraph {
+        while !self.stack.is_empty() {
+            self.end(CallOutcome::Unfinished, self.energy);
+        }
+        CallGraph {
+            roots: self.roots,
+        }
+    }
+}
+
+fn text_item(f: &mut fmt::Formatter<'_>, item: &CallGraphItem, depth: usize) -> fmt::Result {
+    let rolled_back = if item.rolled_back() {
+        " [rolled back]"
+    } else {
+        ""
+    };
+    writeln!(f, "{:indent$}{}{rolled_back}", "", item.label(), indent = 2 * depth)?;
+    for child in item.children() {
+        text_item(f, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn dot_item(out: &mut String, item: &CallGraphItem, parent: Option<usize>, next_id: &mut usize) {
+    let id = *next_id;
+    *next_id += 1;
+    let label = item.label().replace('\\', "\\\\").replace('"', "\\\"");
+    let shape = match item {
+        CallGraphItem::Call(_) => "",
+        _ => ", shape=ellipse",
+    };
+    let style = if item.rolled_back() {
+        ", style=dashed, color=red"
+    } else {
+        ""
+    };
+    let _ = writeln!(out, "    n{id} [label=\"{label}\"{shape}{style}];");
+    if let Some(parent) = parent {
+        let _ = writeln!(out, "    n{parent} -> n{id};");
+    }
+    for child in item.children() {
+        dot_item(out, child, Some(id), next_id);
+    }
+}
+
+fn mermaid_item(
+    out: &mut String,
+    item: &CallGraphItem,
+    parent: Option<usize>,
+    next_id: &mut usize,
+) {
+    let id = *next_id;
+    *next_id += 1;
+    // Mermaid interprets `<`, `>` and `"` in labels, so they are replaced by
+    // entity codes.
+    let label = item.label().replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+    let _ = match item {
+        CallGraphItem::Call(_) => writeln!(out, "    n{id}[\"{label}\"]"),
+        _ => writeln!(out, "    n{id}([\"{label}\"])"),
+    };
+    if let Some(parent) = parent {
+        let _ = writeln!(out, "    n{parent} --> n{id}");
+    }
+    if item.rolled_back() {
+        let _ = writeln!(out, "    class n{id} rolledBack");
+    }
+    for child in item.children() {
+        mermaid_item(out, child, Some(id), next_id);
+    }
+}
@@ -0,0 +1,867 @@
+//! A conformance test suite for token contracts implementing the
+//! [CIS-2 standard](https://proposals.concordium.software/CIS/cis-2.html).
+//!
+//! The parameters, return values and events are encoded and decoded here
+//! according to the standard, so the suite works with any token contract,
+//! regardless of the token id and amount types it uses.
+use crate::types::*;
+use concordium_rust_sdk::base::{
+    base::Energy,
+    contracts_common::{
+        to_bytes, AccountAddress, Address, Amount, ContractAddress, Cursor, Deserial,
+        OwnedContractName, OwnedEntrypointName, OwnedParameter, OwnedReceiveName, ParseResult,
+    },
+    transactions::UpdateContractPayload,
+};
+use std::fmt;
+
+/// The reject code for an invalid token id.
+const INVALID_TOKEN_ID: i32 = -42000001;
+/// The reject code for insufficient funds.
+const INSUFFICIENT_FUNDS: i32 = -42000002;
+/// The reject code for an unauthorized sender.
+const UNAUTHORIZED: i32 = -42000003;
+
+/// The tag of the transfer event.
+const TRANSFER_EVENT_TAG: u8 = u8::MAX;
+/// The tag of the mint event.
+const MINT_EVENT_TAG: u8 = u8::MAX - 1;
+/// The tag of the update operator event.
+const UPDATE_OPERATOR_EVENT_TAG: u8 = u8::MAX - 3;
+
+/// A hook that mints tokens of the token id under test to the provided owner.
+type MintHook<'a> = Box<
+    dyn FnMut(&mut Chain, AccountAddress) -> Result<ContractInvokeSuccess, ContractInvokeError>
+        + 'a,
+>;
+
+/// A conformance test suite for a CIS-2 token contract.
+///
+/// Each rule of the standard is checked on a fresh copy of the chain passed to
+/// [`Cis2Conformance::run`], in which three accounts are created: an owner, an
+/// operator, and another account. The mint hook is then called to mint tokens
+/// with the token id under test to the owner, and the rule is checked with
+/// transactions from these accounts. The result of each rule is listed in the
+/// [`Cis2Report`].
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// # let chain = Chain::new();
+/// # let contract = ContractAddress::new(0, 0);
+/// # fn mint_parameter(owner: AccountAddress) -> OwnedParameter { todo!() }
+/// // The token id `01` of a `cis2-nft` contract, which mints with the `mint` entrypoint.
+/// let report = Cis2Conformance::new(contract, vec![1], |chain, owner| {
+///     chain.contract_update(
+///         Signer::with_one_key(),
+///         owner,
+///         Address::Account(owner),
+///         Energy::from(10_000),
+///         UpdateContractPayload {
+///             amount:       Amount::zero(),
+///             address:      contract,
+///             receive_name: OwnedReceiveName::new_unchecked("cis2_nft.mint".into()),
+///             message:      mint_parameter(owner),
+///         },
+///     )
+/// })
+/// .run(&chain)
+/// .unwrap();
+/// assert!(report.passed(), "{report}");
+/// ```
+pub struct Cis2Conformance<'a> {
+    contract:         ContractAddress,
+    token_id:         Vec<u8>,
+    unknown_token_id: Vec<u8>,
+    mint:             MintHook<'a>,
+    receiver:         Option<(ContractAddress, OwnedEntrypointName)>,
+    energy:           Energy,
+}
+
+/// A rule of the CIS-2 standard checked by [`Cis2Conformance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cis2Rule {
+    /// `supports` reports support for CIS-0 and CIS-2.
+    Supports,
+    /// Minting logs mint events for the minted amount.
+    MintEvent,
+    /// `balanceOf` returns a result for each query, in order, and zero for
+    /// addresses without tokens.
+    BalanceOfBatch,
+    /// `balanceOf` rejects with `InvalidTokenId` for unknown tokens.
+    BalanceOfInvalidToken,
+    /// `operatorOf` returns a result for each query, in order.
+    OperatorOfBatch,
+    /// `tokenMetadata` returns a result for each query, in order.
+    TokenMetadataBatch,
+    /// `tokenMetadata` rejects with `InvalidTokenId` for unknown tokens.
+    TokenMetadataInvalidToken,
+    /// The owner can transfer tokens, which logs a transfer event.
+    TransferByOwner,
+    /// Transfers of zero tokens succeed, change no balances and log a transfer
+    /// event.
+    TransferZeroAmount,
+    /// Transferring more tokens than owned rejects with `InsufficientFunds`.
+    TransferInsufficientFunds,
+    /// Transferring tokens of another address without being an operator
+    /// rejects with `Unauthorized`.
+    TransferUnauthorized,
+    /// Transferring unknown tokens rejects with `InvalidTokenId`.
+    TransferInvalidToken,
+    /// Adding and removing an operator is reflected by `operatorOf` and logs
+    /// update operator events.
+    UpdateOperator,
+    /// An operator can transfer the tokens of the owner, which logs a transfer
+    /// event.
+    TransferByOperator,
+    /// Transferring to a contract calls the receive hook of the contract, and
+    /// logs a transfer event.
+    TransferToContract,
+}
+
+/// The outcome of checking a [`Cis2Rule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cis2RuleOutcome {
+    /// The contract follows the rule.
+    Passed,
+    /// The contract does not follow the rule, for the reason given.
+    Failed(String),
+    /// The rule was not checked, since the suite was not configured for it.
+    Skipped,
+}
+
+/// The result of [`Cis2Conformance::run`].
+#[derive(Debug, Clone)]
+pub struct Cis2Report {
+    /// The outcome of each rule, in the order they were checked.
+    pub results: Vec<(Cis2Rule, Cis2RuleOutcome)>,
+}
+
+impl Cis2Report {
+    /// Whether no rules failed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|(_, outcome)| !matches!(outcome, Cis2RuleOutcome::Failed(_)))
+    }
+
+    /// The outcome of a rule.
+    pub fn outcome(&self, rule: Cis2Rule) -> Option<&Cis2RuleOutcome> {
+        self.results.iter().find(|(r, _)| *r == rule).map(|(_, outcome)| outcome)
+    }
+}
+
+impl fmt::Display for Cis2Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (rule, outcome) in &self.results {
+            match outcome {
+                Cis2RuleOutcome::Passed => writeln!(f, "{rule:?} ... passed")?,
+                Cis2RuleOutcome::Failed(reason) => writeln!(f, "{rule:?} ... FAILED: {reason}")?,
+                Cis2RuleOutcome::Skipped => writeln!(f, "{rule:?} ... skipped")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Cis2Conformance<'a> {
+    /// Create a suite for the token contract at `contract`, checking the token
+    /// with the `token_id`. It is given as the bytes of the token id without
+    /// the length prefix, e.g., `vec![1]` for a `TokenIdU8(1)` or `vec![]`
+    /// for the `TokenIdUnit` of `cis2-wccd`.
+    ///
+    /// The `mint` hook must mint tokens with the `token_id` to the provided
+    /// account, which exists, and return the result of the update. Contracts
+    /// that have no minting entrypoint can instead transfer tokens to the
+    /// account in the hook.
+    pub fn new(
+        contract: ContractAddress,
+        token_id: Vec<u8>,
+        mint: impl FnMut(&mut Chain, AccountAddress) -> Result<ContractInvokeSuccess, ContractInvokeError>
+            + 'a,
+    ) -> Self {
+        Self {
+            contract,
+            token_id,
+            unknown_token_id: vec![0xff; 32],
+            mint: Box::new(mint),
+            receiver: None,
+            energy: Energy::from(100_000),
+        }
+    }
+
+    /// Set a token id that the contract does not know, given as in
+    /// [`Cis2Conformance::new`]. Defaults to 32 bytes of `0xff`.
+    pub fn unknown_token_id(mut self, token_id: Vec<u8>) -> Self {
+        self.unknown_token_id = token_id;
+        self
+    }
+
+    /// Set a contract and its entrypoint for receiving CIS-2 tokens, e.g., the
+    /// `depositCis2Tokens` entrypoint of a wallet contract. Without it,
+    /// [`Cis2Rule::TransferToContract`] is skipped.
+    pub fn receiver(mut self, contract: ContractAddress, entrypoint: OwnedEntrypointName) -> Self {
+        self.receiver = Some((contract, entrypoint));
+        self
+    }
+
+    /// Set the energy reserved for each transaction. Defaults to 100000.
+    pub fn energy(mut self, energy: Energy) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    /// Check all rules on copies of the `chain`, which is not modified.
+    pub fn run(mut self, chain: &Chain) -> Result<Cis2Report, ContractDoesNotExist> {
+        let contract_name = chain
+            .contracts
+            .get(&self.contract)
+            .ok_or(ContractDoesNotExist {
+                address: self.contract,
+            })?
+            .contract_name
+            .clone();
+
+        let rules: [(Cis2Rule, fn(&mut Context) -> Result<(), String>); 15] = [
+            (Cis2Rule::Supports, check_supports),
+            (Cis2Rule::MintEvent, check_mint_event),
+            (Cis2Rule::BalanceOfBatch, check_balance_of_batch),
+            (Cis2Rule::BalanceOfInvalidToken, check_balance_of_invalid_token),
+            (Cis2Rule::OperatorOfBatch, check_operator_of_batch),
+            (Cis2Rule::TokenMetadataBatch, check_token_metadata_batch),
+            (Cis2Rule::TokenMetadataInvalidToken, check_token_metadata_invalid_token),
+            (Cis2Rule::TransferByOwner, check_transfer_by_owner),
+            (Cis2Rule::TransferZeroAmount, check_transfer_zero_amount),
+            (Cis2Rule::TransferInsufficientFunds, check_transfer_insufficient_funds),
+            (Cis2Rule::TransferUnauthorized, check_transfer_unauthorized),
+            (Cis2Rule::TransferInvalidToken, check_transfer_invalid_token),
+            (Cis2Rule::UpdateOperator, check_update_operator),
+            (Cis2Rule::TransferByOperator, check_transfer_by_operator),
+            (Cis2Rule::TransferToContract, check_transfer_to_contract),
+        ];
+        let mut results = Vec::new();
+        for (rule, check) in rules {
+            if rule == Cis2Rule::TransferToContract && self.receiver.is_none() {
+                results.push((rule, Cis2RuleOutcome::Skipped));
+                continue;
+            }
+            let outcome =
+                match self.setup(chain, &contract_name).and_then(|mut context| check(&mut context))
+                {
+                    Ok(()) => Cis2RuleOutcome::Passed,
+                    Err(reason) => Cis2RuleOutcome::Failed(reason),
+                };
+            results.push((rule, outcome));
+        }
+        Ok(Cis2Report {
+            results,
+        })
+    }
+
+    /// Create the accounts and mint tokens to the owner on a copy of the
+    /// chain.
+    fn setup(
+        &mut self,
+        chain: &Chain,
+        contract_name: &OwnedContractName,
+    ) -> Result<Context, String> {
+        let mut chain = chain.dry_run_copy();
+        let [owner, operator, other] = ["cis2-owner", "cis2-operator", "cis2-other"].map(|seed| {
+            let account = TestAccount::from_seed(seed);
+            chain.create_account(account.account(Amount::from_ccd(1_000_000)));
+            account.address()
+        });
+        let mint =
+            (self.mint)(&mut chain, owner).map_err(|err| format!("Minting failed: {err}"))?;
+        let mut context = Context {
+            chain,
+            contract: self.contract,
+            contract_name: contract_name.as_contract_name().contract_name().to_string(),
+            token_id: self.token_id.clone(),
+            unknown_token_id: self.unknown_token_id.clone(),
+            receiver: self.receiver.clone(),
+            energy: self.energy,
+            owner,
+            operator,
+            other,
+            amount: 0,
+            mint,
+        };
+        context.amount =
+            context.balance_of(&[(context.token_id.as_slice(), Address::Account(owner))])?[0];
+        if context.amount == 0 {
+            return Err("The mint hook did not give the owner any tokens.".into());
+        }
+        Ok(context)
+    }
+}
+
+/// The state of checking a single rule.
+struct Context {
+    chain:            Chain,
+    contract:         ContractAddress,
+    /// The name of the contract without the `init_` prefix.
+    contract_name:    String,
+    token_id:         Vec<u8>,
+    unknown_token_id: Vec<u8>,
+    receiver:         Option<(ContractAddress, OwnedEntrypointName)>,
+    energy:           Energy,
+    owner:            AccountAddress,
+    operator:         AccountAddress,
+    other:            AccountAddress,
+    /// The amount of tokens minted to the owner.
+    amount:           u128,
+    /// The result of minting.
+    mint:             ContractInvokeSuccess,
+}
+
+/// An event defined by CIS-2.
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    Transfer {
+        token_id: Vec<u8>,
+        amount:   u128,
+        from:     Address,
+        to:       Address,
+    },
+    Mint {
+        token_id: Vec<u8>,
+        amount:   u128,
+        owner:    Address,
+    },
+    UpdateOperator {
+        add:      bool,
+        owner:    Address,
+        operator: Address,
+    },
+    /// Another event, such as a burn event, or an event not defined by CIS-2.
+    Other,
+}
+
+/// A receiver of a transfer.
+enum Receiver {
+    Account(AccountAddress),
+    Contract(ContractAddress, OwnedEntrypointName),
+}
+
+impl Context {
+    fn payload(
+        &self,
+        entrypoint: &str,
+        parameter: Vec<u8>,
+    ) -> Result<UpdateContractPayload, String> {
+        Ok(UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      self.contract,
+            receive_name: OwnedReceiveName::new_unchecked(format!(
+                "{}.{entrypoint}",
+                self.contract_name
+            )),
+            message:      OwnedParameter::try_from(parameter)
+                .map_err(|_| "The parameter is too large.".to_string())?,
+        })
+    }
+
+    fn update(
+        &mut self,
+        sender: AccountAddress,
+        entrypoint: &str,
+        parameter: Vec<u8>,
+    ) -> Result<Result<ContractInvokeSuccess, ContractInvokeError>, String> {
+        let payload = self.payload(entrypoint, parameter)?;
+        Ok(self.chain.contract_update(
+            Signer::with_one_key(),
+            sender,
+            Address::Account(sender),
+            self.energy,
+            payload,
+        ))
+    }
+
+    /// Invoke a query entrypoint and return its return value.
+    fn query(&self, entrypoint: &str, parameter: Vec<u8>) -> Result<Vec<u8>, String> {
+        self.chain
+            .contract_invoke(
+                self.owner,
+                Address::Account(self.owner),
+                self.energy,
+                self.payload(entrypoint, parameter)?,
+            )
+            .map(|success| success.return_value)
+            .map_err(|err| format!("`{entrypoint}` failed: {err}"))
+    }
+
+    /// Invoke a query entrypoint and return its reject code.
+    fn query_reject_code(&self, entrypoint: &str, parameter: Vec<u8>) -> Result<i32, String> {
+        match self.chain.contract_invoke(
+            self.owner,
+            Address::Account(self.owner),
+            self.energy,
+            self.payload(entrypoint, parameter)?,
+        ) {
+            Ok(_) => Err(format!("`{entrypoint}` succeeded, but it should reject.")),
+            Err(err) => err
+                .reject_code()
+                .ok_or_else(|| format!("`{entrypoint}` failed without rejecting: {err}")),
+        }
+    }
+
+    fn balance_of(&self, queries: &[(&[u8], Address)]) -> Result<Vec<u128>, String> {
+        let mut parameter = list_length(queries.len());
+        for (token_id, address) in queries {
+            write_token_id(&mut parameter, token_id);
+            parameter.extend(to_bytes(address));
+        }
+        let return_value = self.query("balanceOf", parameter)?;
+        let amounts = parse_list(&return_value, read_token_amount)
+            .map_err(|_| "The response of `balanceOf` is malformed.".to_string())?;
+        check_length("balanceOf", queries.len(), amounts.len())?;
+        Ok(amounts)
+    }
+
+    fn operator_of(
+        &self,
+        queries: &[(AccountAddress, AccountAddress)],
+    ) -> Result<Vec<bool>, String> {
+        let mut parameter = list_length(queries.len());
+        for (owner, address) in queries {
+            parameter.extend(to_bytes(&Address::Account(*owner)));
+            parameter.extend(to_bytes(&Address::Account(*address)));
+        }
+        let return_value = self.query("operatorOf", parameter)?;
+        let results = parse_list(&return_value, bool::deserial)
+            .map_err(|_| "The response of `operatorOf` is malformed.".to_string())?;
+        check_length("operatorOf", queries.len(), results.len())?;
+        Ok(results)
+    }
+
+    fn update_operator(&mut self, add: bool) -> Result<ContractInvokeSuccess, String> {
+        let mut parameter = list_length(1);
+        parameter.push(u8::from(add));
+        parameter.extend(to_bytes(&Address::Account(self.operator)));
+        self.update(self.owner, "updateOperator", parameter)?
+            .map_err(|err| format!("`updateOperator` failed: {err}"))
+    }
+
+    fn transfer(
+        &mut self,
+        sender: AccountAddress,
+        token_id: &[u8],
+        amount: u128,
+        from: AccountAddress,
+        to: Receiver,
+    ) -> Result<Result<ContractInvokeSuccess, ContractInvokeError>, String> {
+        let mut parameter = list_length(1);
+        write_token_id(&mut parameter, token_id);
+        write_token_amount(&mut parameter, amount);
+        parameter.extend(to_bytes(&Address::Account(from)));
+        match to {
+            Receiver::Account(address) => {
+                parameter.push(0);
+                parameter.extend(to_bytes(&address));
+            }
+            Receiver::Contract(address, entrypoint) => {
+                parameter.push(1);
+                parameter.extend(to_bytes(&address));
+                parameter.extend(to_bytes(&entrypoint));
+            }
+        }
+        // Empty additional data.
+        parameter.extend(0u16.to_le_bytes());
+        self.update(sender, "transfer", parameter)
+    }
+
+    /// Transfer all tokens from the owner to the other account.
+    fn transfer_all(&mut self, sender: AccountAddress) -> Result<ContractInvokeSuccess, String> {
+        let token_id = self.token_id.clone();
+        self.transfer(sender, &token_id, self.amount, self.owner, Receiver::Account(self.other))?
+            .map_err(|err| format!("`transfer` failed: {err}"))
+    }
+
+    /// Check the balances of the owner and the other account.
+    fn check_balances(&self, owner: u128, other: u128) -> Result<(), String> {
+        let balances = self.balance_of(&[
+            (self.token_id.as_slice(), Address::Account(self.owner)),
+            (self.token_id.as_slice(), Address::Account(self.other)),
+        ])?;
+        if balances != [owner, other] {
+            return Err(format!(
+                "Expected the balances of the owner and the other account to be {owner} and \
+                 {other}, but they were {} and {}.",
+                balances[0], balances[1]
+            ));
+        }
+        Ok(())
+    }
+
+    /// The CIS-2 events logged by the contract.
+    fn events(&self, success: &ContractInvokeSuccess) -> Result<Vec<Event>, String> {
+        success
+            .events()
+            .filter(|(address, _)| *address == self.contract)
+            .flat_map(|(_, events)| events.iter())
+            .map(|event| {
+                parse_event(event.as_ref()).map_err(|_| "An event is malformed.".to_string())
+            })
+            .collect()
+    }
+
+    fn expect_event(&self, success: &ContractInvokeSuccess, expected: Event) -> Result<(), String> {
+        if self.events(success)?.contains(&expected) {
+            Ok(())
+        } else {
+            Err(format!("The event {expected:?} was not logged."))
+        }
+    }
+}
+
+fn check_supports(context: &mut Context) -> Result<(), String> {
+    let mut parameter = list_length(2);
+    for identifier in ["CIS-0", "CIS-2"] {
+        parameter.push(identifier.len() as u8);
+        parameter.extend(identifier.as_bytes());
+    }
+    let return_value = context.query("supports", parameter)?;
+    let results = parse_list(&return_value, |cursor| {
+        let tag = u8::deserial(cursor)?;
+        if tag == 2 {
+            // The addresses of the contracts implementing the standard.
+            let len = u8::deserial(cursor)?;
+            for _ in 0..len {
+                ContractAddress::deserial(cursor)?;
+            }
+        }
+        Ok(tag)
+    })
+    .map_err(|_| "The response of `supports` is malformed.".to_string())?;
+    check_length("supports", 2, results.len())?;
+    if results.contains(&0) {
+        return Err("`supports` does not report support for both CIS-0 and CIS-2.".into());
+    }
+    Ok(())
+}
+
+fn check_mint_event(context: &mut Context) -> Result<(), String> {
+    let minted: u128 = context
+        .events(&context.mint)?
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Mint {
+                token_id,
+                amount,
+                owner,
+            } if token_id == context.token_id && owner == Address::Account(context.owner) => {
+                Some(amount)
+            }
+            _ => None,
+        })
+        .sum();
+    if minted != context.amount {
+        return Err(format!(
+            "The mint events are for {minted} tokens, but the owner got {} tokens.",
+            context.amount
+        ));
+    }
+    Ok(())
+}
+
+fn check_balance_of_batch(context: &mut Context) -> Result<(), String> {
+    let owner = Address::Account(context.owner);
+    let balances = context.balance_of(&[
+        (context.token_id.as_slice(), owner),
+        (context.token_id.as_slice(), Address::Account(context.other)),
+        (context.token_id.as_slice(), owner),
+    ])?;
+    if balances != [context.amount, 0, context.amount] {
+        return Err(format!(
+            "Expected the balances [{0}, 0, {0}], but they were {balances:?}.",
+            context.amount
+        ));
+    }
+    if !context.balance_of(&[])?.is_empty() {
+        return Err("`balanceOf` returned results for no queries.".into());
+    }
+    Ok(())
+}
+
+fn check_balance_of_invalid_token(context: &mut Context) -> Result<(), String> {
+    let mut parameter = list_length(1);
+    write_token_id(&mut parameter, &context.unknown_token_id);
+    parameter.extend(to_bytes(&Address::Account(context.owner)));
+    expect_code(context.query_reject_code("balanceOf", parameter)?, INVALID_TOKEN_ID)
+}
+
+fn check_operator_of_batch(context: &mut Context) -> Result<(), String> {
+    context.update_operator(true)?;
+    let results = context.operator_of(&[
+        (context.owner, context.operator),
+        (context.owner, context.other),
+        (context.other, context.operator),
+    ])?;
+    if results != [true, false, false] {
+        return Err(format!("Expected [true, false, false], but got {results:?}."));
+    }
+    Ok(())
+}
+
+fn check_token_metadata_batch(context: &mut Context) -> Result<(), String> {
+    let mut parameter = list_length(2);
+    write_token_id(&mut parameter, &context.token_id);
+    write_token_id(&mut parameter, &context.token_id);
+    let return_value = context.query("tokenMetadata", parameter)?;
+    let urls = parse_list(&return_value, |cursor| {
+        let len = u16::deserial(cursor)?;
+        let url = (0..len).map(|_| u8::deserial(cursor)).collect::<ParseResult<Vec<u8>>>()?;
+        // The optional hash of the metadata.
+        if u8::deserial(cursor)? == 1 {
+            <[u8; 32]>::deserial(cursor)?;
+        }
+        Ok(url)
+    })
+    .map_err(|_| "The response of `tokenMetadata` is malformed.".to_string())?;
+    check_length("tokenMetadata", 2, urls.len())?;
+    if urls[0] != urls[1] {
+        return Err("`tokenMetadata` returned different URLs for the same token.".into());
+    }
+    Ok(())
+}
+
+fn check_token_metadata_invalid_token(context: &mut Context) -> Result<(), String> {
+    let mut parameter = list_length(1);
+    write_token_id(&mut parameter, &context.unknown_token_id);
+    expect_code(context.query_reject_code("tokenMetadata", parameter)?, INVALID_TOKEN_ID)
+}
+
+fn check_transfer_by_owner(context: &mut Context) -> Result<(), String> {
+    let success = context.transfer_all(context.owner)?;
+    context.check_balances(0, context.amount)?;
+    context.expect_event(&success, Event::Transfer {
+        token_id: context.token_id.clone(),
+        amount:   context.amount,
+        from:     Address::Account(context.owner),
+        to:       Address::Account(context.other),
+    })
+}
+
+fn check_transfer_zero_amount(context: &mut Context) -> Result<(), String> {
+    let token_id = context.token_id.clone();
+    let success = context
+        .transfer(context.owner, &token_id, 0, context.owner, Receiver::Account(context.other))?
+        .map_err(|err| format!("`transfer` failed: {err}"))?;
+    context.check_balances(context.amount, 0)?;
+    context.expect_event(&success, Event::Transfer {
+        token_id,
+        amount: 0,
+        from: Address::Account(context.owner),
+        to: Address::Account(context.other),
+    })
+}
+
+fn check_transfer_insufficient_funds(context: &mut Context) -> Result<(), String> {
+    let token_id = context.token_id.clone();
+    let result = context.transfer(
+        context.owner,
+        &token_id,
+        context.amount + 1,
+        context.owner,
+        Receiver::Account(context.other),
+    )?;
+    expect_update_code(result, INSUFFICIENT_FUNDS)
+}
+
+fn check_transfer_unauthorized(context: &mut Context) -> Result<(), String> {
+    let token_id = context.token_id.clone();
+    let result = context.transfer(
+        context.other,
+        &token_id,
+        context.amount,
+        context.owner,
+        Receiver::Account(context.other),
+    )?;
+    expect_update_code(result, UNAUTHORIZED)
+}
+
+fn check_transfer_invalid_token(context: &mut Context) -> Result<(), String> {
+    let token_id = context.unknown_token_id.clone();
+    let result = context.transfer(
+        context.owner,
+        &token_id,
+        1,
+        context.owner,
+        Receiver::Account(context.other),
+    )?;
+    expect_update_code(result, INVALID_TOKEN_ID)
+}
+
+fn check_update_operator(context: &mut Context) -> Result<(), String> {
+    for add in [true, false] {
+        let success = context.update_operator(add)?;
+        context.expect_event(&success, Event::UpdateOperator {
+            add,
+            owner: Address::Account(context.owner),
+            operator: Address::Account(context.operator),
+        })?;
+        if context.operator_of(&[(context.owner, context.operator)])? != [add] {
+            return Err(format!("`operatorOf` does not reflect the update (add: {add})."));
+        }
+    }
+    Ok(())
+}
+
+fn check_transfer_by_operator(context: &mut Context) -> Result<(), String> {
+    context.update_operator(true)?;
+    let success = context.transfer_all(context.operator)?;
+    context.check_balances(0, context.amount)?;
+    context.expect_event(&success, Event::Transfer {
+        token_id: context.token_id.clone(),
+        amount:   context.amount,
+        from:     Address::Account(context.owner),
+        to:       Address::Account(context.other),
+    })
+}
+
+fn check_transfer_to_contract(context: &mut Context) -> Result<(), String> {
+    let Some((receiver, entrypoint)) = context.receiver.clone() else {
+        return Ok(());
+    };
+    let token_id = context.token_id.clone();
+    let success = context
+        .transfer(
+            context.owner,
+            &token_id,
+            context.amount,
+            context.owner,
+            Receiver::Contract(receiver, entrypoint.clone()),
+        )?
+        .map_err(|err| format!("`transfer` failed: {err}"))?;
+    let hook_called = success.updates().any(|update| {
+        update.address == receiver
+            && update.receive_name.as_receive_name().entrypoint_name()
+                == entrypoint.as_entrypoint_name()
+    });
+    if !hook_called {
+        return Err(format!("The entrypoint `{entrypoint}` of the receiver was not called."));
+    }
+    let balance = context.balance_of(&[(token_id.as_slice(), Address::Contract(receiver))])?[0];
+    if balance != context.amount {
+        return Err(format!(
+            "Expected the receiver to have {} tokens, but it has {balance}.",
+            context.amount
+        ));
+    }
+    context.expect_event(&success, Event::Transfer {
+        token_id,
+        amount: context.amount,
+        from: Address::Account(context.owner),
+        to: Address::Contract(receiver),
+    })
+}
+
+fn expect_code(actual: i32, expected: i32) -> Result<(), String> {
+    if actual != expected {
+        return Err(format!("Expected the reject code {expected}, but it was {actual}."));
+    }
+    Ok(())
+}
+
+fn expect_update_code(
+    result: Result<ContractInvokeSuccess, ContractInvokeError>,
+    expected: i32,
+) -> Result<(), String> {
+    match result {
+        Ok(_) => Err("The update succeeded, but it should reject.".into()),
+        Err(err) => expect_code(
+            err.reject_code()
+                .ok_or_else(|| format!("The update failed without rejecting: {err}"))?,
+            expected,
+        ),
+    }
+}
+
+fn check_length(entrypoint: &str, expected: usize, actual: usize) -> Result<(), String> {
+    if expected != actual {
+        return Err(format!("`{entrypoint}` returned {actual} results for {expected} queries."));
+    }
+    Ok(())
+}
+
+/// The length prefix of a list in a CIS-2 parameter.
+fn list_length(len: usize) -> Vec<u8> { (len as u16).to_le_bytes().to_vec() }
+
+/// Parse a list with a two byte length prefix, which must make up all the
+/// bytes.
+fn parse_list<T>(
+    bytes: &[u8],
+    mut item: impl FnMut(&mut Cursor<&[u8]>) -> ParseResult<T>,
+) -> ParseResult<Vec<T>> {
+    let mut cursor = Cursor::new(bytes);
+    let len = u16::deserial(&mut cursor)?;
+    let items = (0..len).map(|_| item(&mut cursor)).collect::<ParseResult<Vec<T>>>()?;
+    if cursor.offset != bytes.len() {
+        return Err(Default::default());
+    }
+    Ok(items)
+}
+
+/// Write a token amount, which is LEB128 encoded.
+fn write_token_amount(out: &mut Vec<u8>, mut amount: u128) {
+    loop {
+        let byte = (amount & 0x7f) as u8;
+        amount >>= 7;
+        if amount == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Read a LEB128 encoded token amount. Amounts that do not fit in a `u128` are
+/// rejected.
+fn read_token_amount(cursor: &mut Cursor<&[u8]>) -> ParseResult<u128> {
+    let mut amount: u128 = 0;
+    for shift in (0..128).step_by(7) {
+        let byte = u8::deserial(cursor)?;
+        let bits = u128::from(byte & 0x7f);
+        if shift > 121 && bits >> (128 - shift) != 0 {
+            return Err(Default::default());
+        }
+        amount |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(amount);
+        }
+    }
+    Err(Default::default())
+}
+
+/// Write a token id, which has a one byte length prefix.
+fn write_token_id(out: &mut Vec<u8>, token_id: &[u8]) {
+    out.push(token_id.len() as u8);
+    out.extend(token_id);
+}
+
+fn read_token_id(cursor: &mut Cursor<&[u8]>) -> ParseResult<Vec<u8>> {
+    let len = u8::deserial(cursor)?;
+    (0..len).map(|_| u8::deserial(cursor)).collect()
+}
+
+fn parse_event(bytes: &[u8]) -> ParseResult<Event> {
+    let mut cursor = Cursor::new(bytes);
+    let cursor = &mut cursor;
+    Ok(match u8::deserial(cursor)? {
+        TRANSFER_EVENT_TAG => Event::Transfer {
+            token_id: read_token_id(cursor)?,
+            amount:   read_token_amount(cursor)?,
+            from:     Address::deserial(cursor)?,
+            to:       Address::deserial(cursor)?,
+        },
+        MINT_EVENT_TAG => Event::Mint {
+            token_id: read_token_id(cursor)?,
+            amount:   read_token_amount(cursor)?,
+            owner:    Address::deserial(cursor)?,
+        },
+        UPDATE_OPERATOR_EVENT_TAG => Event::UpdateOperator {
+            add:      u8::deserial(cursor)? == 1,
+            owner:    Address::deserial(cursor)?,
+            operator: Address::deserial(cursor)?,
+        },
+        _ => Event::Other,
+    })
+}
@@ -76,7 +76,24 @@ pub(crate) const UPDATE_CONTRACT_INSTANCE_BASE_COST: Energy = Energy {
     energy: 300,
 };
 
+/// The upper bound on the energy used when estimating the energy needed for a
+/// transaction. This is the maximum energy of a block on mainnet.
+pub(crate) const MAX_ESTIMATION_ENERGY: Energy = Energy {
+    energy: 3_000_000,
+};
+
 /// The name of the environment variable that holds the path to the contract
 /// module file. To load the module, use the
 /// [`module_load_output`](crate::module_load_output) function.
 pub const CONTRACT_MODULE_OUTPUT_PATH_ENV_VAR: &str = "CARGO_CONCORDIUM_TEST_MODULE_OUTPUT_PATH";
+
+/// The name of the environment variable that holds the path to a directory in
+/// which compiled modules are persisted across test runs. If it is not set,
+/// compiled modules are only cached in memory. See
+/// [`ModuleCache`](crate::ModuleCache) for details.
+pub const MODULE_CACHE_DIR_ENV_VAR: &str = "CONCORDIUM_TEST_MODULE_CACHE_DIR";
+
+/// The reject code used by contracts when a reentrant call is blocked by a
+/// `ReentrancyGuard` from `concordium-std`. See
+/// [`ReentrancyReport::blocked`](crate::ReentrancyReport::blocked).
+pub const REENTRANCY_GUARD_REJECT_CODE: i32 = i32::MIN + 31;
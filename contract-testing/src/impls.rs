@@ -1,21 +1,26 @@
 use crate::{
     constants,
-    invocation::{ChangeSet, EntrypointInvocationHandler, TestConfigurationError},
+    invocation::{ChangeSet, EntrypointInvocationHandler, ReentrancyProbe, TestConfigurationError},
     types::*,
-    CONTRACT_MODULE_OUTPUT_PATH_ENV_VAR,
+    CONTRACT_MODULE_OUTPUT_PATH_ENV_VAR, MODULE_CACHE_DIR_ENV_VAR,
 };
+#[cfg(feature = "native")]
+use crate::{invocation::invoke_init_native, NativeEntrypoint, NativeModule, NativeModuleError};
 use anyhow::anyhow;
 use concordium_rust_sdk::{
     self as sdk, base,
     base::{
         base::{AccountThreshold, Energy, InsufficientEnergy},
+        common::types::{CredentialIndex, KeyIndex},
         constants::MAX_WASM_MODULE_SIZE,
         contracts_common::{
-            self, AccountAddress, AccountBalance, Address, Amount, ChainMetadata, ContractAddress,
-            Deserial, Duration, ExchangeRate, ExchangeRates, ModuleReference, OwnedPolicy,
-            ParseResult, SlotTime, Timestamp,
+            self, AccountAddress, AccountBalance, Address, Amount, AttributeTag, AttributeValue,
+            ChainMetadata, ContractAddress, Deserial, Duration, ExchangeRate, ExchangeRates,
+            ModuleReference, OwnedPolicy, ParseResult, SignatureThreshold, SlotTime, Timestamp,
         },
+        ed25519,
         hashes::BlockHash,
+        id::types::{AccountKeys, CredentialData, KeyPair},
         smart_contracts::{ContractEvent, ModuleSource, WasmModule, WasmVersion},
         transactions::{
             self, cost, AccountAccessStructure, InitContractPayload, UpdateContractPayload,
@@ -36,12 +41,13 @@ use sdk::{
     smart_contracts::engine::wasm::CostConfigurationV1,
     types::smart_contracts::InvokeContractResult,
 };
+use sha2::{Digest, Sha256};
 use std::{
     collections::{BTreeMap, BTreeSet},
     env,
     future::Future,
-    path::Path,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock},
 };
 use tokio::{runtime, time::timeout};
 
@@ -424,16 +430,20 @@ impl Chain {
         euro_per_energy: ExchangeRate,
     ) -> Result<Self, ExchangeRateError> {
         Ok(Self {
-            parameters:               ChainParameters::new_with_time_and_rates(
+            parameters: ChainParameters::new_with_time_and_rates(
                 block_time,
                 micro_ccd_per_euro,
                 euro_per_energy,
             )?,
-            accounts:                 BTreeMap::new(),
-            modules:                  BTreeMap::new(),
-            contracts:                BTreeMap::new(),
-            next_contract_index:      0,
+            accounts: BTreeMap::new(),
+            modules: BTreeMap::new(),
+            contracts: BTreeMap::new(),
+            next_contract_index: 0,
             external_node_connection: None,
+            reentrancy_probe: None,
+            #[cfg(feature = "native")]
+            native_modules: BTreeMap::new(),
+            checkpoints: None,
         })
     }
 
@@ -517,6 +527,22 @@ impl Chain {
         sender: AccountAddress,
         wasm_module: WasmModule,
         enable_debug: bool,
+    ) -> Result<ModuleDeploySuccess, ModuleDeployError> {
+        let description = format!("module_deploy_v1 by {sender}");
+        let result =
+            self.module_deploy_v1_debug_unrecorded(signer, sender, wasm_module, enable_debug);
+        self.record_checkpoint(description);
+        result
+    }
+
+    /// Like [`module_deploy_v1_debug`](Self::module_deploy_v1_debug), but
+    /// without recording a checkpoint.
+    fn module_deploy_v1_debug_unrecorded(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        wasm_module: WasmModule,
+        enable_debug: bool,
     ) -> Result<ModuleDeploySuccess, ModuleDeployError> {
         // For maintainers:
         //
@@ -586,27 +612,19 @@ impl Chain {
         // Charge the account.
         sender_account.balance.total -= transaction_fee;
 
-        // Construct the artifact.
-        let artifact = match wasm::utils::instantiate_with_metering::<v1::ProcessedImports>(
-            ValidationConfig::V1,
-            CostConfigurationV1,
-            &v1::ConcordiumAllowedImports {
-                support_upgrade: true,
-                enable_debug,
-            },
-            wasm_module.source.as_ref(),
-        ) {
-            Ok(artifact) => artifact,
-            Err(err) => {
-                return Err(ModuleDeployError {
-                    kind: ModuleInvalidError(err).into(),
-                    energy_used,
-                    transaction_fee,
-                })
-            }
-        };
-
+        // Construct the artifact, or reuse it if the module has been compiled before.
         let module_reference: ModuleReference = wasm_module.get_module_ref();
+        let cached_module =
+            match module_cache().get_or_compile(module_reference, &wasm_module, enable_debug) {
+                Ok(cached_module) => cached_module,
+                Err(err) => {
+                    return Err(ModuleDeployError {
+                        kind: err.into(),
+                        energy_used,
+                        transaction_fee,
+                    })
+                }
+            };
 
         // Ensure module hasn't been deployed before.
         if self.modules.contains_key(&module_reference) {
@@ -619,8 +637,8 @@ impl Chain {
         self.modules.insert(module_reference, ContractModule {
             // we follow protocol 6 semantics, and don't count the custom section size towards
             // module size.
-            size:     wasm_module.source.size().saturating_sub(artifact.custom_sections_size),
-            artifact: Arc::new(artifact.artifact),
+            size:     wasm_module.source.size().saturating_sub(cached_module.custom_sections_size),
+            artifact: cached_module.artifact,
         });
         Ok(ModuleDeploySuccess {
             module_reference,
@@ -629,6 +647,47 @@ impl Chain {
         })
     }
 
+    /// Execute entrypoints of a deployed module natively instead of in the
+    /// Wasm interpreter. This makes it possible to debug the contract code
+    /// with a native debugger. See [`NativeModule`] for how to register the
+    /// entrypoints and for the limitations of native execution.
+    ///
+    /// The entrypoints of the module that are not in `native_module` are still
+    /// executed in Wasm. Registering a module again replaces the previously
+    /// registered entrypoints.
+    ///
+    /// Returns an error if the module has not been deployed, or if one of the
+    /// entrypoints does not exist in the module.
+    #[cfg(feature = "native")]
+    pub fn module_register_native(
+        &mut self,
+        module_reference: ModuleReference,
+        native_module: NativeModule,
+    ) -> Result<(), NativeModuleError> {
+        let module = self.contract_module(module_reference)?;
+        if let Some(name) = native_module
+            .entrypoints
+            .keys()
+            .find(|name| !module.artifact.export.contains_key(name.as_str()))
+        {
+            return Err(NativeModuleError::NotExported {
+                name: name.clone(),
+            });
+        }
+        self.native_modules.insert(module_reference, native_module);
+        Ok(())
+    }
+
+    /// Look up a natively executed entrypoint by its name in the Wasm module.
+    #[cfg(feature = "native")]
+    pub(crate) fn native_entrypoint(
+        &self,
+        module_reference: ModuleReference,
+        name: &str,
+    ) -> Option<NativeEntrypoint> {
+        self.native_modules.get(&module_reference)?.entrypoint(name)
+    }
+
     /// Initialize a contract.
     ///
     /// **Parameters:**
@@ -650,6 +709,21 @@ impl Chain {
         sender: AccountAddress,
         energy_reserved: Energy,
         payload: InitContractPayload,
+    ) -> Result<ContractInitSuccess, ContractInitError> {
+        let description = format!("contract_init of {} by {sender}", payload.init_name);
+        let result = self.contract_init_unrecorded(signer, sender, energy_reserved, payload);
+        self.record_checkpoint(description);
+        result
+    }
+
+    /// Like [`contract_init`](Self::contract_init), but without recording a
+    /// checkpoint.
+    fn contract_init_unrecorded(
+        &mut self,
+        signer: Signer,
+        sender: AccountAddress,
+        energy_reserved: Energy,
+        payload: InitContractPayload,
     ) -> Result<ContractInitSuccess, ContractInitError> {
         let mut remaining_energy = energy_reserved;
         if !self.account_exists(sender) {
@@ -714,12 +788,7 @@ impl Chain {
         }
 
         // Compute the base cost for checking the transaction header.
-        let check_header_cost = {
-            // 1 byte for the tag.
-            let transaction_size =
-                transactions::construct::TRANSACTION_HEADER_SIZE + 1 + payload.size() as u64;
-            transactions::cost::base_cost(transaction_size, signer.num_keys)
-        };
+        let check_header_cost = check_header_cost(signer, payload.size());
 
         // Charge the header cost.
         exit_ooe!(remaining_energy.tick_energy(check_header_cost), DebugTracker::empty_trace());
@@ -782,18 +851,31 @@ impl Chain {
 
         let energy_given_to_interpreter =
             InterpreterEnergy::new(to_interpreter_energy(*remaining_energy));
-        let res = v1::invoke_init::<_, _, DebugTracker>(
-            module.artifact,
-            init_ctx,
-            v1::InitInvocation {
-                amount: payload.amount,
-                init_name,
-                parameter: payload.param.as_ref(),
-                energy: energy_given_to_interpreter,
-            },
-            false, // We only support protocol P5 and up, so no limiting.
-            loader,
-        );
+        let res = 'execution: {
+            // Execute the init function natively if it has been registered for that.
+            #[cfg(feature = "native")]
+            if let Some(native_entrypoint) = self.native_entrypoint(payload.mod_ref, init_name) {
+                break 'execution Ok(invoke_init_native(
+                    native_entrypoint,
+                    init_ctx,
+                    payload.amount,
+                    payload.param.as_ref(),
+                    energy_given_to_interpreter,
+                ));
+            }
+            v1::invoke_init::<_, _, DebugTracker>(
+                module.artifact,
+                init_ctx,
+                v1::InitInvocation {
+                    amount: payload.amount,
+                    init_name,
+                    parameter: payload.param.as_ref(),
+                    energy: energy_given_to_interpreter,
+                },
+                false, // We only support protocol P5 and up, so no limiting.
+                loader,
+            )
+        };
         // Handle the result
         match res {
             Ok(v1::InitResult::Success {
@@ -851,6 +933,8 @@ impl Chain {
                     contract_address,
                     events: contract_events_from_logs(logs),
                     energy_used,
+                    storage_energy: energy_for_state_storage,
+                    module_load_energy: lookup_cost,
                     transaction_fee,
                     debug_trace: trace,
                 })
@@ -1068,6 +1152,26 @@ impl Chain {
         sender: Address,
         energy_reserved: Energy,
         payload: UpdateContractPayload,
+    ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+        let description = format!(
+            "contract_update of {} on {} by {invoker}",
+            payload.receive_name, payload.address
+        );
+        let result =
+            self.contract_update_unrecorded(signer, invoker, sender, energy_reserved, payload);
+        self.record_checkpoint(description);
+        result
+    }
+
+    /// Like [`contract_update`](Self::contract_update), but without recording a
+    /// checkpoint.
+    fn contract_update_unrecorded(
+        &mut self,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
     ) -> Result<ContractInvokeSuccess, ContractInvokeError> {
         // Ensure the sender exists.
         if !self.address_exists(sender) {
@@ -1097,12 +1201,7 @@ impl Chain {
         };
 
         // Compute the base cost for checking the transaction header.
-        let check_header_cost = {
-            // 1 byte for the tag.
-            let transaction_size =
-                transactions::construct::TRANSACTION_HEADER_SIZE + 1 + payload.size() as u64;
-            transactions::cost::base_cost(transaction_size, signer.num_keys)
-        };
+        let check_header_cost = check_header_cost(signer, payload.size());
 
         // Charge the header cost.
         let mut remaining_energy =
@@ -1303,6 +1402,265 @@ impl Chain {
         }
     }
 
+    /// Estimate the minimal energy to reserve for
+    /// [`Chain::contract_update`](Self::contract_update) to succeed.
+    ///
+    /// The update is dry run on a copy of the chain, so no changes are made
+    /// and nothing is charged. The result includes a breakdown of where the
+    /// energy is used. If the update fails even with the most energy that the
+    /// `invoker` can afford, the failure is returned instead, wrapped in
+    /// [`EnergyEstimateError::TransactionFailed`].
+    ///
+    /// The parameters are the same as for
+    /// [`Chain::contract_update`](Self::contract_update), except for the
+    /// reserved energy.
+    pub fn estimate_update_energy(
+        &self,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        payload: UpdateContractPayload,
+    ) -> Result<EnergyEstimate, EnergyEstimateError<ContractInvokeError>> {
+        let dry_run = |energy_reserved| {
+            self.dry_run_copy().contract_update(
+                signer,
+                invoker,
+                sender,
+                energy_reserved,
+                payload.clone(),
+            )
+        };
+        let max_energy = self.max_affordable_energy(invoker, payload.amount);
+        let success = dry_run(max_energy)?;
+        let energy = minimal_sufficient_energy(success.energy_used, max_energy, |energy| {
+            dry_run(energy).is_ok()
+        });
+        let success = dry_run(energy)?;
+        let header_energy = check_header_cost(signer, payload.size());
+        let execution_energy = execution_energy(
+            success.energy_used,
+            header_energy,
+            success.storage_energy,
+            success.module_load_energy,
+        )
+        .ok_or(EnergyEstimateError::InconsistentEnergy {
+            energy_used: success.energy_used,
+        })?;
+        Ok(EnergyEstimate {
+            energy,
+            header_energy,
+            execution_energy,
+            storage_energy: success.storage_energy,
+            module_load_energy: success.module_load_energy,
+        })
+    }
+
+    /// Estimate the minimal energy to reserve for
+    /// [`Chain::contract_init`](Self::contract_init) to succeed.
+    ///
+    /// The initialization is dry run on a copy of the chain, so no changes are
+    /// made and nothing is charged. The result includes a breakdown of where
+    /// the energy is used. If the initialization fails even with the most
+    /// energy that the `sender` can afford, the failure is returned instead,
+    /// wrapped in [`EnergyEstimateError::TransactionFailed`].
+    ///
+    /// The parameters are the same as for
+    /// [`Chain::contract_init`](Self::contract_init), except for the reserved
+    /// energy.
+    pub fn estimate_init_energy(
+        &self,
+        signer: Signer,
+        sender: AccountAddress,
+        payload: InitContractPayload,
+    ) -> Result<EnergyEstimate, EnergyEstimateError<ContractInitError>> {
+        let dry_run = |energy_reserved| {
+            self.dry_run_copy().contract_init(signer, sender, energy_reserved, payload.clone())
+        };
+        let max_energy = self.max_affordable_energy(sender, payload.amount);
+        let success = dry_run(max_energy)?;
+        let energy = minimal_sufficient_energy(success.energy_used, max_energy, |energy| {
+            dry_run(energy).is_ok()
+        });
+        let success = dry_run(energy)?;
+        let header_energy = check_header_cost(signer, payload.size());
+        let execution_energy = execution_energy(
+            success.energy_used,
+            header_energy,
+            success.storage_energy,
+            success.module_load_energy,
+        )
+        .ok_or(EnergyEstimateError::InconsistentEnergy {
+            energy_used: success.energy_used,
+        })?;
+        Ok(EnergyEstimate {
+            energy,
+            header_energy,
+            execution_energy,
+            storage_energy: success.storage_energy,
+            module_load_energy: success.module_load_energy,
+        })
+    }
+
+    /// Explore whether the contract updated by a transaction is vulnerable to
+    /// reentrancy.
+    ///
+    /// The transaction is first run as is, to record the calls that the
+    /// contract at `payload.address` makes to other contracts. It is then run
+    /// once for every combination of a recorded call and one of the
+    /// `reentrant_calls`. In each run, the reentrant call is made right before
+    /// the recorded call is executed, as if the contract being called made
+    /// it. All runs are done on copies of the chain, so no changes are made
+    /// and nothing is charged.
+    ///
+    /// A finding is reported for a run that succeeds if
+    ///  - the `invariant` does not hold on the resulting chain, or
+    ///  - the reentrant call modified the state of the contract, and the
+    ///    contract wrote its state again after the outgoing call resumed. See
+    ///    [`ReentrancyFindingKind::StaleStateAfterResume`].
+    ///
+    /// Runs that fail are not reported, since all their changes are rolled
+    /// back. If the transaction fails without a reentrant call, the error is
+    /// returned.
+    ///
+    /// Runs in which the contract rejects the reentrant call with the
+    /// [`REENTRANCY_GUARD_REJECT_CODE`](crate::REENTRANCY_GUARD_REJECT_CODE),
+    /// as done by a `ReentrancyGuard` from `concordium-std`, are counted in
+    /// [`ReentrancyReport::blocked`]. Use [`ReentrancyReport::is_guarded`] to
+    /// check that the guard blocks every reentrant call.
+    ///
+    /// The remaining parameters are the same as for
+    /// [`Chain::contract_update`](Self::contract_update).
+    #[allow(clippy::too_many_arguments)]
+    pub fn explore_reentrancy(
+        &self,
+        signer: Signer,
+        invoker: AccountAddress,
+        sender: Address,
+        energy_reserved: Energy,
+        payload: UpdateContractPayload,
+        reentrant_calls: &[ReentrantCall],
+        invariant: impl Fn(&Chain) -> Result<(), String>,
+    ) -> Result<ReentrancyReport, ContractInvokeError> {
+        let run = |attack| {
+            let mut chain = self.dry_run_copy();
+            chain.reentrancy_probe = Some(Mutex::new(ReentrancyProbe {
+                target: payload.address,
+                attack,
+                attacking: false,
+                outgoing_calls: Vec::new(),
+                injection: None,
+                final_modification_index: None,
+            }));
+            let result =
+                chain.contract_update(signer, invoker, sender, energy_reserved, payload.clone());
+            let probe = chain
+                .reentrancy_probe
+                .take()
+                .expect("Probe was set")
+                .into_inner()
+                .expect("Probe lock poisoned");
+            (chain, result, probe)
+        };
+
+        let (_, result, baseline) = run(None);
+        result?;
+        let outgoing_calls = baseline.outgoing_calls;
+
+        let mut attempts = 0;
+        let mut blocked = 0;
+        let mut findings = Vec::new();
+        for (call_index, outgoing_call) in outgoing_calls.iter().enumerate() {
+            for reentrant_call in reentrant_calls {
+                let (chain, result, probe) = run(Some((call_index, reentrant_call.clone())));
+                // The transaction might not reach the call with the reentrant call injected.
+                let Some(injection) = probe.injection else {
+                    continue;
+                };
+                attempts += 1;
+                if injection.blocked_by_guard {
+                    blocked += 1;
+                }
+                if result.is_err() {
+                    continue;
+                }
+
+                let mut kinds = Vec::new();
+                if let Err(message) = invariant(&chain) {
+                    kinds.push(ReentrancyFindingKind::InvariantBroken {
+                        message,
+                    });
+                }
+                let modified_by_reentrant_call = injection.succeeded
+                    && injection.modification_index_after != injection.modification_index_before;
+                if modified_by_reentrant_call
+                    && probe.final_modification_index != Some(injection.modification_index_after)
+                {
+                    kinds.push(ReentrancyFindingKind::StaleStateAfterResume);
+                }
+                findings.extend(kinds.into_iter().map(|kind| ReentrancyFinding {
+                    call_index,
+                    outgoing_call: outgoing_call.clone(),
+                    reentrant_call: reentrant_call.clone(),
+                    kind,
+                }));
+            }
+        }
+
+        Ok(ReentrancyReport {
+            outgoing_calls,
+            attempts,
+            blocked,
+            findings,
+        })
+    }
+
+    /// Create a copy of the chain for dry runs. The copy has no connection to
+    /// an external node.
+    pub(crate) fn dry_run_copy(&self) -> Chain {
+        Chain {
+            parameters: self.parameters.clone(),
+            accounts: self.accounts.clone(),
+            modules: self.modules.clone(),
+            contracts: self.contracts.clone(),
+            next_contract_index: self.next_contract_index,
+            external_node_connection: None,
+            reentrancy_probe: None,
+            #[cfg(feature = "native")]
+            native_modules: self.native_modules.clone(),
+            checkpoints: None,
+        }
+    }
+
+    /// The most energy, up to [`constants::MAX_ESTIMATION_ENERGY`], that the
+    /// account can reserve for a transaction that also sends the `amount`.
+    ///
+    /// If the account does not exist, the maximum is returned, so that the
+    /// transaction fails for that reason instead.
+    fn max_affordable_energy(&self, address: AccountAddress, amount: Amount) -> Energy {
+        let Some(available) = self.account_balance_available(address) else {
+            return constants::MAX_ESTIMATION_ENERGY;
+        };
+        let available = available.checked_sub(amount).unwrap_or_else(Amount::zero);
+        let affordable =
+            |energy: u64| self.calculate_energy_cost(Energy::from(energy)) <= available;
+        if affordable(constants::MAX_ESTIMATION_ENERGY.energy) {
+            return constants::MAX_ESTIMATION_ENERGY;
+        }
+        // Binary search for the most energy that is affordable, where `low` is always
+        // affordable and `high` never is.
+        let mut low = 0;
+        let mut high = constants::MAX_ESTIMATION_ENERGY.energy;
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if affordable(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Energy::from(low)
+    }
+
     /// Invoke an external contract entrypoint.
     ///
     /// Similar to [`Chain::contract_invoke`](Self::contract_invoke) except that
@@ -1523,6 +1881,152 @@ impl Chain {
         self.contracts.get(&address).map(|ci| ci.self_balance)
     }
 
+    /// Stake an additional `amount` on the account.
+    ///
+    /// The staked amount cannot exceed the total balance of the account, but
+    /// it may overlap with the locked amount, as on the chain. The staked
+    /// amount is not available for transfers, see
+    /// [`AccountBalance::available`].
+    ///
+    /// Returns the new balance of the account.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// const ACC: AccountAddress = AccountAddress([0; 32]);
+    /// chain.create_account(Account::new(ACC, Amount::from_ccd(100)));
+    ///
+    /// chain.account_add_stake(ACC, Amount::from_ccd(30)).unwrap();
+    /// assert_eq!(chain.account_balance_available(ACC), Some(Amount::from_ccd(70)));
+    /// ```
+    pub fn account_add_stake(
+        &mut self,
+        address: AccountAddress,
+        amount: Amount,
+    ) -> Result<AccountBalance, AccountBalanceUpdateError> {
+        let account = self.account_mut(address)?;
+        let new_stake = account
+            .balance
+            .staked
+            .checked_add(amount)
+            .ok_or(AccountBalanceUpdateError::BalanceOverflow)?;
+        if new_stake > account.balance.total {
+            return Err(AccountBalanceUpdateError::InsufficientBalance);
+        }
+        account.balance.staked = new_stake;
+        Ok(account.balance)
+    }
+
+    /// Remove `amount` from the stake of the account after a `cooldown`
+    /// period.
+    ///
+    /// The amount remains staked, and thus unavailable, until the block time
+    /// reaches the current block time plus the `cooldown`. The release happens
+    /// when the block time is advanced with [`Chain::tick_block_time`].
+    /// A zero `cooldown` releases the stake immediately.
+    ///
+    /// Returns an error if the amount exceeds the part of the stake that is
+    /// not already in cooldown, see [`Account::active_stake`].
+    /// Otherwise, the new balance of the account is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// const ACC: AccountAddress = AccountAddress([0; 32]);
+    /// chain.create_account(Account::new(ACC, Amount::from_ccd(100)));
+    /// chain.account_add_stake(ACC, Amount::from_ccd(30)).unwrap();
+    ///
+    /// chain.account_remove_stake(ACC, Amount::from_ccd(30), Duration::from_days(1)).unwrap();
+    /// // The stake is still unavailable during the cooldown.
+    /// assert_eq!(chain.account_balance_available(ACC), Some(Amount::from_ccd(70)));
+    ///
+    /// chain.tick_block_time(Duration::from_days(1)).unwrap();
+    /// assert_eq!(chain.account_balance_available(ACC), Some(Amount::from_ccd(100)));
+    /// ```
+    pub fn account_remove_stake(
+        &mut self,
+        address: AccountAddress,
+        amount: Amount,
+        cooldown: Duration,
+    ) -> Result<AccountBalance, AccountBalanceUpdateError> {
+        let block_time = self.parameters.block_time;
+        let release_time = block_time
+            .checked_add(cooldown)
+            .ok_or(AccountBalanceUpdateError::ReleaseTimeOverflow)?;
+        let account = self.account_mut(address)?;
+        if amount > account.active_stake() {
+            return Err(AccountBalanceUpdateError::InsufficientStake);
+        }
+        let in_cooldown = account.stake_cooldowns.entry(release_time).or_insert(Amount::zero());
+        *in_cooldown =
+            in_cooldown.checked_add(amount).ok_or(AccountBalanceUpdateError::BalanceOverflow)?;
+        account.release_until(block_time);
+        Ok(account.balance)
+    }
+
+    /// Add amounts to the account that are locked until the times given in the
+    /// `schedule`.
+    ///
+    /// This corresponds to the account receiving a transfer with a release
+    /// schedule. The amounts are added to both the total and the locked
+    /// balance, and each amount is unlocked once the block time reaches its
+    /// release time, see [`Chain::tick_block_time`]. Amounts with a release
+    /// time that has already passed are unlocked immediately.
+    ///
+    /// Returns the new balance of the account.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// const ACC: AccountAddress = AccountAddress([0; 32]);
+    /// chain.create_account(Account::new(ACC, Amount::zero()));
+    ///
+    /// chain
+    ///     .account_add_release_schedule(ACC, [
+    ///         (Timestamp::from_timestamp_millis(10), Amount::from_ccd(5)),
+    ///         (Timestamp::from_timestamp_millis(20), Amount::from_ccd(5)),
+    ///     ])
+    ///     .unwrap();
+    /// assert_eq!(chain.account_balance_available(ACC), Some(Amount::zero()));
+    ///
+    /// chain.tick_block_time(Duration::from_millis(10)).unwrap();
+    /// assert_eq!(chain.account_balance_available(ACC), Some(Amount::from_ccd(5)));
+    /// ```
+    pub fn account_add_release_schedule(
+        &mut self,
+        address: AccountAddress,
+        schedule: impl IntoIterator<Item = (Timestamp, Amount)>,
+    ) -> Result<AccountBalance, AccountBalanceUpdateError> {
+        let block_time = self.parameters.block_time;
+        let account = self.account_mut(address)?;
+        let mut balance = account.balance;
+        let mut release_schedule = account.release_schedule.clone();
+        for (release_time, amount) in schedule {
+            balance.total = balance
+                .total
+                .checked_add(amount)
+                .ok_or(AccountBalanceUpdateError::BalanceOverflow)?;
+            balance.locked = balance
+                .locked
+                .checked_add(amount)
+                .ok_or(AccountBalanceUpdateError::BalanceOverflow)?;
+            let scheduled = release_schedule.entry(release_time).or_insert(Amount::zero());
+            *scheduled =
+                scheduled.checked_add(amount).ok_or(AccountBalanceUpdateError::BalanceOverflow)?;
+        }
+        // Only update the account once the whole schedule is known to be valid.
+        account.balance = balance;
+        account.release_schedule = release_schedule;
+        account.release_until(block_time);
+        Ok(account.balance)
+    }
+
     /// Helper method for looking up part of the state of a smart contract,
     /// which is a key-value store.
     pub fn contract_state_lookup(&self, address: ContractAddress, key: &[u8]) -> Option<Vec<u8>> {
@@ -1687,6 +2191,10 @@ impl Chain {
 
     /// Tick the block time on the [`Chain`] by a [`Duration`].
     ///
+    /// Stake cooldowns and scheduled releases of locked amounts that are due
+    /// at the new block time are released, see [`Chain::account_remove_stake`]
+    /// and [`Chain::account_add_release_schedule`].
+    ///
     /// Returns an error if ticking causes the block time to overflow.
     ///
     /// # Example
@@ -1704,11 +2212,89 @@ impl Chain {
     /// assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(123));
     /// ```
     pub fn tick_block_time(&mut self, duration: Duration) -> Result<(), BlockTimeOverflow> {
-        self.parameters.block_time =
+        let block_time =
             self.parameters.block_time.checked_add(duration).ok_or(BlockTimeOverflow)?;
+        self.parameters.block_time = block_time;
+        for account in self.accounts.values_mut() {
+            account.release_until(block_time);
+        }
+        Ok(())
+    }
+
+    /// Start recording a [`Checkpoint`] after each transaction, i.e. each call
+    /// of [`Chain::module_deploy_v1`], [`Chain::contract_init`], and
+    /// [`Chain::contract_update`], whether it succeeds or not. The current
+    /// state is recorded as checkpoint `0`. Does nothing if checkpoints are
+    /// already enabled.
+    ///
+    /// Checkpoints are cheap, since the contract states are persistent and
+    /// shared between checkpoints, but the accounts are copied for each
+    /// checkpoint.
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// chain.enable_checkpoints();
+    /// // ... run a long scenario ...
+    /// # chain.tick_block_time(Duration::from_millis(10)).unwrap();
+    /// for checkpoint in chain.checkpoints() {
+    ///     println!("{}: {}", checkpoint.index, checkpoint.description);
+    /// }
+    /// // Go back to the state before the scenario.
+    /// chain.rewind_to(0).unwrap();
+    /// assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(0));
+    /// ```
+    pub fn enable_checkpoints(&mut self) {
+        if self.checkpoints.is_none() {
+            self.checkpoints = Some(Vec::new());
+            self.record_checkpoint("checkpoints enabled".into());
+        }
+    }
+
+    /// The checkpoints recorded since [`Chain::enable_checkpoints`], in order.
+    /// Empty if checkpoints are not enabled.
+    pub fn checkpoints(&self) -> &[Checkpoint] { self.checkpoints.as_deref().unwrap_or_default() }
+
+    /// Restore the chain to the state at the checkpoint with the `index`,
+    /// including the block time, accounts, modules, and contracts. The
+    /// checkpoints after it are discarded, and new checkpoints are recorded
+    /// from it, so the chain can be rewound repeatedly to try variations.
+    pub fn rewind_to(&mut self, index: usize) -> Result<(), CheckpointDoesNotExist> {
+        let checkpoints = self.checkpoints.as_mut().ok_or(CheckpointDoesNotExist {
+            index,
+        })?;
+        let checkpoint = checkpoints.get(index).ok_or(CheckpointDoesNotExist {
+            index,
+        })?;
+        self.parameters = checkpoint.parameters.clone();
+        self.accounts = checkpoint.accounts.clone();
+        self.modules = checkpoint.modules.clone();
+        self.contracts = checkpoint.contracts.clone();
+        self.next_contract_index = checkpoint.next_contract_index;
+        checkpoints.truncate(index + 1);
         Ok(())
     }
 
+    /// Record a checkpoint if checkpoints are enabled.
+    fn record_checkpoint(&mut self, description: String) {
+        let Some(checkpoints) = &self.checkpoints else {
+            return;
+        };
+        let checkpoint = Checkpoint {
+            index: checkpoints.len(),
+            block_time: self.parameters.block_time,
+            description,
+            parameters: self.parameters.clone(),
+            accounts: self.accounts.clone(),
+            modules: self.modules.clone(),
+            contracts: self.contracts.clone(),
+            next_contract_index: self.next_contract_index,
+        };
+        if let Some(checkpoints) = &mut self.checkpoints {
+            checkpoints.push(checkpoint);
+        }
+    }
+
     /// Set the block time by querying the external node.
     ///
     /// The default query block is always used.
@@ -1919,6 +2505,8 @@ impl Account {
             policy,
             address,
             keys,
+            stake_cooldowns: BTreeMap::new(),
+            release_schedule: BTreeMap::new(),
         }
     }
 
@@ -1929,12 +2517,7 @@ impl Account {
         balance: AccountBalance,
         keys: AccountAccessStructure,
     ) -> Self {
-        Self {
-            balance,
-            policy: Self::empty_policy(),
-            address,
-            keys,
-        }
+        Self::new_with_policy_and_keys(address, balance, Self::empty_policy(), keys)
     }
 
     /// Create new [`Account`](Self) with the provided account policy.
@@ -1986,11 +2569,73 @@ impl Account {
         )
     }
 
+    /// The part of the staked amount that is not in cooldown.
+    pub fn active_stake(&self) -> Amount {
+        let in_cooldown = self.stake_cooldowns.values().fold(Amount::zero(), |acc, a| acc + *a);
+        self.balance.staked.checked_sub(in_cooldown).unwrap_or_else(Amount::zero)
+    }
+
+    /// Release all the cooldowns and scheduled amounts that are due at or
+    /// before the provided `time`.
+    ///
+    /// Amounts in cooldown are subtracted from the staked balance, and the
+    /// scheduled releases are subtracted from the locked balance.
+    pub(crate) fn release_until(&mut self, time: Timestamp) {
+        while let Some(entry) = self.stake_cooldowns.first_entry() {
+            if *entry.key() > time {
+                break;
+            }
+            let amount = entry.remove();
+            self.balance.staked =
+                self.balance.staked.checked_sub(amount).unwrap_or_else(Amount::zero);
+        }
+        while let Some(entry) = self.release_schedule.first_entry() {
+            if *entry.key() > time {
+                break;
+            }
+            let amount = entry.remove();
+            self.balance.locked =
+                self.balance.locked.checked_sub(amount).unwrap_or_else(Amount::zero);
+        }
+    }
+
+    /// Get an [`AccountBuilder`] for constructing a new [`Account`] with a
+    /// builder pattern.
+    ///
+    /// See the [`AccountBuilder`] for more details.
+    pub fn builder(address: AccountAddress) -> AccountBuilder { AccountBuilder::new(address) }
+
+    /// Get the alias of the account address with the provided `counter`.
+    ///
+    /// All aliases of an account share the first 29 bytes of the address, and
+    /// they are all resolved to this account by the [`Chain`].
+    ///
+    /// Returns `None` if the counter is larger than `2^24 - 1`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let mut chain = Chain::new();
+    /// let account = Account::new(AccountAddress([0; 32]), Amount::from_ccd(10));
+    /// let alias = account.alias(1).unwrap();
+    /// chain.create_account(account);
+    ///
+    /// assert_eq!(chain.account_balance_available(alias), Some(Amount::from_ccd(10)));
+    /// ```
+    pub fn alias(&self, counter: u32) -> Option<AccountAddress> { self.address.get_alias(counter) }
+
+    /// Get an iterator over the aliases of the account address, starting from
+    /// the alias with counter `0`.
+    pub fn aliases(&self) -> impl Iterator<Item = AccountAddress> + '_ {
+        (0..).map_while(move |counter| self.alias(counter))
+    }
+
     /// Helper for creating an empty policy.
     ///
     /// It has identity provider `0`, no items, and is valid from unix epoch
     /// until unix epoch + u64::MAX milliseconds.
-    fn empty_policy() -> OwnedPolicy {
+    pub(crate) fn empty_policy() -> OwnedPolicy {
         OwnedPolicy {
             identity_provider: 0,
             created_at:        Timestamp::from_timestamp_millis(0),
@@ -2000,6 +2645,535 @@ impl Account {
     }
 }
 
+impl AccountBuilder {
+    /// Create a new [`AccountBuilder`] for constructing an [`Account`] with
+    /// the provided address.
+    ///
+    /// Can also be created via the [`Account::builder`] method.
+    ///
+    /// Unless configured otherwise, the account has
+    ///  - a zero balance,
+    ///  - the policy described in [`Account::new`],
+    ///  - an [`AccountAccessStructure`] with a threshold of 1, and no keys.
+    ///
+    /// To complete the building process, use [`AccountBuilder::build`], see
+    /// the example below.
+    ///
+    /// # Example
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let account = AccountBuilder::new(AccountAddress([0; 32]))
+    ///     // Use zero or more builder methods, for example:
+    ///     .total_balance(Amount::from_ccd(1000))
+    ///     .identity_provider(1)
+    ///     .attribute(attributes::COUNTRY_OF_RESIDENCE, *b"DK")
+    ///     // Then build:
+    ///     .build();
+    /// ```
+    pub fn new(address: AccountAddress) -> Self {
+        Self {
+            address,
+            balance: AccountBalance {
+                total:  Amount::zero(),
+                staked: Amount::zero(),
+                locked: Amount::zero(),
+            },
+            policy: Account::empty_policy(),
+            keys: AccountAccessStructure {
+                threshold: AccountThreshold::try_from(1u8).expect("1 is a valid threshold."),
+                keys:      BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Configure the balance of the account.
+    ///
+    /// By default the balance is zero.
+    pub fn balance(mut self, balance: AccountBalance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    /// Configure the total balance of the account, with nothing staked or
+    /// locked.
+    ///
+    /// By default the balance is zero.
+    pub fn total_balance(mut self, total_balance: Amount) -> Self {
+        self.balance = AccountBalance {
+            total:  total_balance,
+            staked: Amount::zero(),
+            locked: Amount::zero(),
+        };
+        self
+    }
+
+    /// Configure the public keys of the account.
+    ///
+    /// By default the account has no keys, so it is impossible to verify any
+    /// signatures with the access structure.
+    pub fn keys(mut self, keys: AccountAccessStructure) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Replace the whole account policy.
+    ///
+    /// This overrides any policy configuration made by previous builder
+    /// methods, and it can be refined by subsequent ones.
+    pub fn policy(mut self, policy: OwnedPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Configure the identity provider that issued the identity of the
+    /// account.
+    ///
+    /// By default the identity provider is `0`.
+    pub fn identity_provider(mut self, identity_provider: u32) -> Self {
+        self.policy.identity_provider = identity_provider;
+        self
+    }
+
+    /// Configure the time at which the credential of the account was created.
+    ///
+    /// By default this is unix epoch.
+    pub fn created_at(mut self, created_at: Timestamp) -> Self {
+        self.policy.created_at = created_at;
+        self
+    }
+
+    /// Configure the time at which the credential of the account expires.
+    ///
+    /// By default this is unix epoch + `u64::MAX` milliseconds.
+    pub fn valid_to(mut self, valid_to: Timestamp) -> Self {
+        self.policy.valid_to = valid_to;
+        self
+    }
+
+    /// Add an attribute that the account reveals in its policy, replacing any
+    /// existing value for the same tag.
+    ///
+    /// The tags for the known attributes are found in the [`attributes`]
+    /// module. The attributes are kept sorted by their tag, which matches the
+    /// policies seen on the chain.
+    ///
+    /// # Example
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let account = Account::builder(AccountAddress([0; 32]))
+    ///     .attribute(attributes::COUNTRY_OF_RESIDENCE, *b"DK")
+    ///     .attribute(attributes::NATIONALITY, *b"DK")
+    ///     .build();
+    /// assert_eq!(account.policy.items.len(), 2);
+    /// ```
+    pub fn attribute(mut self, tag: AttributeTag, value: impl Into<AttributeValue>) -> Self {
+        self.policy.items.retain(|(existing, _)| existing.0 != tag.0);
+        self.policy.items.push((tag, value.into()));
+        self.policy.items.sort_by_key(|(tag, _)| tag.0);
+        self
+    }
+
+    /// Build the [`Account`] with the configured options.
+    pub fn build(self) -> Account {
+        Account::new_with_policy_and_keys(self.address, self.balance, self.policy, self.keys)
+    }
+}
+
+impl TestAccount {
+    /// Create a test account with a single credential with a single key, and
+    /// thresholds of one.
+    ///
+    /// The address and the keys are derived from the `seed`.
+    pub fn from_seed(seed: impl AsRef<[u8]>) -> Self {
+        Self::from_seed_with_credentials(seed, AccountThreshold::ONE, &[(
+            CredentialIndex::from(0),
+            SignatureThreshold::ONE,
+            &[KeyIndex(0)],
+        )])
+    }
+
+    /// Create a test account with the provided credentials.
+    ///
+    /// Each credential is given as a triple of its index, its signature
+    /// threshold, and the indices of its keys. The `threshold` is the number of
+    /// credentials that must sign for a signature to be valid. The address and
+    /// the keys are derived from the `seed`.
+    ///
+    /// ```
+    /// # use concordium_smart_contract_testing::*;
+    /// let account = TestAccount::from_seed_with_credentials("alice", AccountThreshold::TWO, &[
+    ///     (CredentialIndex::from(0), SignatureThreshold::ONE, &[KeyIndex(0)]),
+    ///     (CredentialIndex::from(1), SignatureThreshold::TWO, &[KeyIndex(0), KeyIndex(1)]),
+    /// ]);
+    /// assert_eq!(account.sign_message(b"message").num_signatures(), 3);
+    /// ```
+    pub fn from_seed_with_credentials(
+        seed: impl AsRef<[u8]>,
+        threshold: AccountThreshold,
+        credentials: &[(CredentialIndex, SignatureThreshold, &[KeyIndex])],
+    ) -> Self {
+        let seed: [u8; 32] = Sha256::new()
+            .chain_update(b"concordium-smart-contract-testing account seed")
+            .chain_update(seed.as_ref())
+            .finalize()
+            .into();
+        let address = AccountAddress(
+            Sha256::new().chain_update(b"address").chain_update(seed).finalize().into(),
+        );
+        let credentials = credentials
+            .iter()
+            .map(|(cred_index, threshold, key_indices)| {
+                (*cred_index, (*threshold, key_indices.to_vec()))
+            })
+            .collect();
+        Self {
+            address,
+            seed,
+            threshold,
+            credentials,
+        }
+    }
+
+    /// The address of the account.
+    pub fn address(&self) -> AccountAddress { self.address }
+
+    /// The key pairs of the account.
+    pub fn keys(&self) -> AccountKeys {
+        let keys = self
+            .credentials
+            .iter()
+            .map(|(cred_index, (threshold, key_indices))| {
+                let keys = key_indices
+                    .iter()
+                    .map(|key_index| (*key_index, self.key_pair(*cred_index, *key_index)))
+                    .collect();
+                (*cred_index, CredentialData {
+                    keys,
+                    threshold: *threshold,
+                })
+            })
+            .collect();
+        AccountKeys {
+            keys,
+            threshold: self.threshold,
+        }
+    }
+
+    /// The public keys and thresholds of the account.
+    pub fn access_structure(&self) -> AccountAccessStructure { (&self.keys()).into() }
+
+    /// Create an [`Account`] with the address and public keys of the test
+    /// account and the provided total balance, ready to be added to the chain
+    /// with [`Chain::create_account`].
+    pub fn account(&self, balance: Amount) -> Account {
+        self.builder().total_balance(balance).build()
+    }
+
+    /// Create an [`AccountBuilder`] with the address and public keys of the
+    /// test account.
+    pub fn builder(&self) -> AccountBuilder {
+        Account::builder(self.address).keys(self.access_structure())
+    }
+
+    /// Sign the message with all the keys of the account.
+    ///
+    /// The signatures are checked against the message by the
+    /// `check_account_signature` host function. Use
+    /// `contracts_common::AccountSignatures::from` to get the representation
+    /// used in `concordium-std`.
+    pub fn sign_message(&self, message: &[u8]) -> AccountSignatures {
+        AccountSignatures::from(self.keys().sign_data(message))
+    }
+
+    /// Sign the message with only the provided keys of the account, e.g., to
+    /// test signatures that do not meet the thresholds.
+    ///
+    /// Keys that the account does not have are ignored.
+    pub fn sign_message_with_keys(
+        &self,
+        message: &[u8],
+        keys: &[(CredentialIndex, KeyIndex)],
+    ) -> AccountSignatures {
+        let mut all = self.sign_message(message);
+        for (cred_index, sigs) in all.sigs.iter_mut() {
+            sigs.retain(|key_index, _| keys.contains(&(*cred_index, *key_index)));
+        }
+        all.sigs.retain(|_, sigs| !sigs.is_empty());
+        all
+    }
+
+    /// Derive the key pair at the provided indices from the seed.
+    fn key_pair(&self, cred_index: CredentialIndex, key_index: KeyIndex) -> KeyPair {
+        let secret_bytes: [u8; 32] = Sha256::new()
+            .chain_update(b"key")
+            .chain_update(self.seed)
+            .chain_update([cred_index.index, key_index.0])
+            .finalize()
+            .into();
+        let secret = ed25519::SecretKey::from_bytes(&secret_bytes)
+            .expect("Any 32 bytes are a valid secret key.");
+        let public = ed25519::PublicKey::from(&secret);
+        KeyPair {
+            secret,
+            public,
+        }
+    }
+}
+
+/// Get the process-wide [`ModuleCache`].
+///
+/// On first use, the cache is configured to persist compiled modules in the
+/// directory given by the environment variable
+/// [`MODULE_CACHE_DIR_ENV_VAR`](crate::MODULE_CACHE_DIR_ENV_VAR), if it is
+/// set.
+pub fn module_cache() -> &'static ModuleCache {
+    static MODULE_CACHE: OnceLock<ModuleCache> = OnceLock::new();
+    MODULE_CACHE.get_or_init(|| ModuleCache {
+        entries:         Mutex::new(BTreeMap::new()),
+        persistence_dir: RwLock::new(env::var_os(MODULE_CACHE_DIR_ENV_VAR).map(PathBuf::from)),
+    })
+}
+
+impl ModuleCache {
+    /// Configure the directory in which compiled modules are persisted, so
+    /// that they can be reused across test runs. A good choice is a directory
+    /// inside `target/`, e.g., `target/concordium-module-cache`, as it is
+    /// removed by `cargo clean`.
+    ///
+    /// If `None` is provided, modules are only cached in memory.
+    ///
+    /// The artifacts are stored in files named after the module reference, the
+    /// version of this library and a fingerprint of the execution engine, so
+    /// artifacts compiled by another version of the library or of the engine
+    /// are never reused. The fingerprint is computed by compiling a small
+    /// reference module once per process.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use concordium_smart_contract_testing::*;
+    /// module_cache().set_persistence_dir(Some("target/concordium-module-cache".into()));
+    /// ```
+    pub fn set_persistence_dir(&self, dir: Option<PathBuf>) {
+        *self.persistence_dir.write().unwrap_or_else(PoisonError::into_inner) = dir;
+    }
+
+    /// The directory in which compiled modules are persisted, if any.
+    pub fn persistence_dir(&self) -> Option<PathBuf> {
+        self.persistence_dir.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// The number of compiled modules that are cached in memory.
+    pub fn len(&self) -> usize {
+        // Release the lock of the whole cache before locking the entries, since the
+        // lock of an entry is held while its module is compiled.
+        let entries: Vec<_> = self.entries().values().cloned().collect();
+        entries
+            .iter()
+            .filter(|entry| entry.lock().unwrap_or_else(PoisonError::into_inner).is_some())
+            .count()
+    }
+
+    /// Whether no compiled modules are cached in memory.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Check whether the module has been compiled and cached in memory,
+    /// either with or without support for debug output.
+    pub fn contains(&self, module_reference: ModuleReference, enable_debug: bool) -> bool {
+        // Release the lock of the whole cache before locking the entry, as in `len`.
+        let entry = self.entries().get(&(module_reference, enable_debug)).cloned();
+        entry.is_some_and(|entry| entry.lock().unwrap_or_else(PoisonError::into_inner).is_some())
+    }
+
+    /// Remove all the modules that are cached in memory. Persisted modules are
+    /// not removed.
+    pub fn clear(&self) { self.entries().clear(); }
+
+    /// Get the compiled module from the cache, or validate and compile it if
+    /// it is not present.
+    ///
+    /// Modules are looked up in memory first, then in the persistence
+    /// directory. Failing to read or write the persistence directory is not
+    /// an error, the module is compiled instead.
+    pub(crate) fn get_or_compile(
+        &self,
+        module_reference: ModuleReference,
+        wasm_module: &WasmModule,
+        enable_debug: bool,
+    ) -> Result<CachedModule, ModuleInvalidError> {
+        // Only hold the lock of the whole cache while looking up the entry, so that
+        // other modules can be compiled in parallel.
+        let entry = self.entries().entry((module_reference, enable_debug)).or_default().clone();
+        let mut entry = entry.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached_module) = entry.as_ref() {
+            return Ok(cached_module.clone());
+        }
+        let persisted =
+            self.persistence_dir().zip(engine_fingerprint()).map(|(dir, fingerprint)| {
+                let path = dir.join(format!(
+                    "{module_reference}{}-{}-{}.artifact",
+                    if enable_debug {
+                        "-debug"
+                    } else {
+                        ""
+                    },
+                    env!("CARGO_PKG_VERSION"),
+                    fingerprint.iter().map(|byte| format!("{byte:02x}")).collect::<String>()
+                ));
+                (path, fingerprint)
+            });
+        let cached_module = match persisted
+            .as_ref()
+            .and_then(|(path, fingerprint)| read_persisted_module(path, fingerprint).ok())
+        {
+            Some(cached_module) => cached_module,
+            None => {
+                let instantiated = wasm::utils::instantiate_with_metering::<v1::ProcessedImports>(
+                    ValidationConfig::V1,
+                    CostConfigurationV1,
+                    &v1::ConcordiumAllowedImports {
+                        support_upgrade: true,
+                        enable_debug,
+                    },
+                    wasm_module.source.as_ref(),
+                )
+                .map_err(ModuleInvalidError)?;
+                let cached_module = CachedModule {
+                    artifact:             Arc::new(instantiated.artifact),
+                    custom_sections_size: instantiated.custom_sections_size,
+                };
+                if let Some((path, fingerprint)) = &persisted {
+                    // Persisting is only an optimization, so errors are ignored.
+                    let _ = write_persisted_module(path, fingerprint, &cached_module);
+                }
+                cached_module
+            }
+        };
+        *entry = Some(cached_module.clone());
+        Ok(cached_module)
+    }
+
+    /// Lock the map of entries. A panic in another thread while holding the
+    /// lock cannot leave the map in an inconsistent state, so poisoning is
+    /// ignored.
+    fn entries(
+        &self,
+    ) -> MutexGuard<'_, BTreeMap<ModuleCacheKey, Arc<Mutex<Option<CachedModule>>>>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Compute the base cost for checking the header of a transaction with a
+/// payload of the given size, signed by the `signer`.
+pub(crate) fn check_header_cost(signer: Signer, payload_size: usize) -> Energy {
+    // 1 byte for the tag.
+    let transaction_size =
+        transactions::construct::TRANSACTION_HEADER_SIZE + 1 + payload_size as u64;
+    transactions::cost::base_cost(transaction_size, signer.num_keys)
+}
+
+/// The version of the format of persisted modules. It must be bumped whenever
+/// the format written by [`write_persisted_module`] changes.
+const PERSISTED_MODULE_FORMAT_VERSION: u32 = 1;
+
+/// The size of an [`engine_fingerprint`] in bytes.
+const ENGINE_FINGERPRINT_SIZE: usize = 8;
+
+/// A minimal V1 contract module with the init function `init_fingerprint`,
+/// which returns its argument plus one. It is compiled to compute the
+/// [`engine_fingerprint`].
+const ENGINE_FINGERPRINT_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // Magic and version.
+    0x01, 0x06, 0x01, 0x60, 0x01, 0x7e, 0x01, 0x7f, // Type section: (i64) -> i32.
+    0x03, 0x02, 0x01, 0x00, // Function section.
+    0x07, 0x14, 0x01, 0x10, b'i', b'n', b'i', b't', b'_', b'f', b'i', b'n', b'g', b'e', b'r', b'p',
+    b'r', b'i', b'n', b't', 0x00, 0x00, // Export section.
+    0x0a, 0x0a, 0x01, 0x08, 0x00, 0x20, 0x00, 0xa7, 0x41, 0x01, 0x6a, 0x0b, // Code section.
+];
+
+/// A fingerprint of the execution engine, i.e., a hash of the artifact
+/// produced by compiling [`ENGINE_FINGERPRINT_MODULE`]. It changes when the
+/// artifact format, the metering transformation or the compilation of common
+/// instructions changes, such that artifacts persisted by such a version of
+/// the engine are not loaded.
+///
+/// It is computed once per process. `None` is returned if the reference module
+/// fails to compile, in which case modules are not persisted.
+fn engine_fingerprint() -> Option<[u8; ENGINE_FINGERPRINT_SIZE]> {
+    static ENGINE_FINGERPRINT: OnceLock<Option<[u8; ENGINE_FINGERPRINT_SIZE]>> = OnceLock::new();
+    *ENGINE_FINGERPRINT.get_or_init(|| {
+        let instantiated = wasm::utils::instantiate_with_metering::<v1::ProcessedImports>(
+            ValidationConfig::V1,
+            CostConfigurationV1,
+            &v1::ConcordiumAllowedImports {
+                support_upgrade: true,
+                enable_debug:    false,
+            },
+            ENGINE_FINGERPRINT_MODULE,
+        )
+        .ok()?;
+        let mut bytes = Vec::new();
+        instantiated.artifact.output(&mut bytes).ok()?;
+        let hash = Sha256::digest(bytes);
+        hash[..ENGINE_FINGERPRINT_SIZE].try_into().ok()
+    })
+}
+
+/// Read a module persisted by [`write_persisted_module`]. An error is returned
+/// if the module was persisted with another format or by another version of
+/// the execution engine, i.e., if the `fingerprint` does not match.
+///
+/// The file consists of
+///  - the [`PERSISTED_MODULE_FORMAT_VERSION`] as 4 little-endian bytes,
+///  - the [`engine_fingerprint`],
+///  - the size of the custom sections as 8 little-endian bytes, and
+///  - the serialized artifact.
+fn read_persisted_module(
+    path: &Path,
+    fingerprint: &[u8; ENGINE_FINGERPRINT_SIZE],
+) -> anyhow::Result<CachedModule> {
+    let bytes = std::fs::read(path)?;
+    if bytes.len() < 4 + ENGINE_FINGERPRINT_SIZE + 8 {
+        return Err(anyhow!("The persisted module is too short."));
+    }
+    let (version_bytes, rest) = bytes.split_at(4);
+    if u32::from_le_bytes(version_bytes.try_into()?) != PERSISTED_MODULE_FORMAT_VERSION {
+        return Err(anyhow!("The persisted module has an unsupported format."));
+    }
+    let (fingerprint_bytes, rest) = rest.split_at(ENGINE_FINGERPRINT_SIZE);
+    if fingerprint_bytes != fingerprint {
+        return Err(anyhow!("The persisted module was compiled by another engine."));
+    }
+    let (size_bytes, artifact_bytes) = rest.split_at(8);
+    let artifact = wasm::utils::parse_artifact(artifact_bytes)?;
+    Ok(CachedModule {
+        artifact:             Arc::new(artifact.into()),
+        custom_sections_size: u64::from_le_bytes(size_bytes.try_into()?),
+    })
+}
+
+/// Persist a compiled module to the provided path.
+///
+/// The module is first written to a temporary file which is then renamed, so
+/// that concurrent test processes never observe a partially written file.
+fn write_persisted_module(
+    path: &Path,
+    fingerprint: &[u8; ENGINE_FINGERPRINT_SIZE],
+    cached_module: &CachedModule,
+) -> anyhow::Result<()> {
+    let mut bytes = PERSISTED_MODULE_FORMAT_VERSION.to_le_bytes().to_vec();
+    bytes.extend_from_slice(fingerprint);
+    bytes.extend_from_slice(&cached_module.custom_sections_size.to_le_bytes());
+    cached_module.artifact.output(&mut bytes)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension(format!("tmp-{}", std::process::id()));
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
 /// Load a raw wasm module, i.e. one **without** the prefix of 4 version
 /// bytes and 4 module length bytes.
 /// The module still has to be a valid V1 smart contract module.
@@ -2172,6 +3346,50 @@ pub(crate) fn lookup_module_cost(module: &ContractModule) -> Energy {
     Energy::from(module.size / 500)
 }
 
+/// Find the least energy in `lower..=upper` for which `succeeds` returns
+/// `true`, given that it succeeds with `upper`.
+///
+/// The energy used by a successful transaction is almost always sufficient
+/// when reserved, so `lower` is tried first. It can be insufficient if the
+/// outcome depends on the reserved energy, e.g., when a contract queries the
+/// balance of the invoker, in which case a binary search is used.
+fn minimal_sufficient_energy(
+    lower: Energy,
+    upper: Energy,
+    succeeds: impl Fn(Energy) -> bool,
+) -> Energy {
+    if lower >= upper || succeeds(lower) {
+        return lower.min(upper);
+    }
+    // `low` always fails and `high` always succeeds.
+    let mut low = lower.energy;
+    let mut high = upper.energy;
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if succeeds(Energy::from(mid)) {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Energy::from(high)
+}
+
+/// The energy used for executing the contracts in a transaction, i.e., the
+/// energy used minus the energy for checking the header, storing state and
+/// loading modules. Returns `None` if the parts exceed the energy used.
+fn execution_energy(
+    energy_used: Energy,
+    header_energy: Energy,
+    storage_energy: Energy,
+    module_load_energy: Energy,
+) -> Option<Energy> {
+    energy_used
+        .checked_sub(header_energy)?
+        .checked_sub(storage_energy)?
+        .checked_sub(module_load_energy)
+}
+
 /// Calculate the microCCD(mCCD) cost of energy(NRG) using the two exchange
 /// rates provided.
 ///
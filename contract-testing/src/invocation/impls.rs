@@ -6,7 +6,8 @@ use crate::{
         to_interpreter_energy,
     },
     types::{Account, BalanceError, Contract, ContractModule, TransferError},
-    AccountSignatures, DebugTraceElement, ExecutionError, InvokeExecutionError,
+    AccountSignatures, DebugTraceElement, ExecutionError, InvokeExecutionError, OutgoingCall,
+    ReentrantCall,
 };
 use concordium_rust_sdk::{
     base::{
@@ -44,18 +45,6 @@ macro_rules! exit_ooe {
     };
 }
 
-impl InvocationData {
-    /// An internal helper function to construct a [`DebugTraceElement`] from a
-    /// set of debug events.
-    pub(crate) fn debug_trace(&self, debug_trace: DebugTracker) -> DebugTraceElement {
-        DebugTraceElement::Debug {
-            entrypoint: self.entrypoint.clone(),
-            address: self.address,
-            debug_trace,
-        }
-    }
-}
-
 /// Ok response from the `invoke_entrypoint_initial` method. This is the
 /// execution up to either success, failure, or the first interrupt.
 ///
@@ -64,9 +53,14 @@ impl InvocationData {
 /// or contract) the response is the `Err` variant.
 ///
 /// If we managed to get to running the contract entrypoint then the response is
-/// the `Ok` variant, with more detailed information inside.
-type InitialInvokeResponse =
-    Result<(v1::ReceiveResult<CompiledFunction, DebugTracker>, InvocationData), InvokeResponse>;
+/// the `Ok` variant, with more detailed information inside. This includes the
+/// trace elements of the calls made by an entrypoint executed natively, which
+/// are handled during its execution. For entrypoints executed in Wasm, the
+/// trace elements are empty.
+type InitialInvokeResponse = Result<
+    (v1::ReceiveResult<CompiledFunction, DebugTracker>, InvocationData, Vec<DebugTraceElement>),
+    InvokeResponse,
+>;
 
 impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     /// Used for handling the *initial* part of invoking an entrypoint.
@@ -214,11 +208,36 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
 
         let mod_idx_before_invoke = self.next_contract_modification_index;
 
-        // Construct the instance state
+        // Construct the mutable state
         let mut loader = v1::trie::Loader::new(&[][..]); // An empty loader is fine currently, as we do not use caching in this lib.
         let mut mutable_state = self.contract_state(payload.address);
-        let mut mutable_state = mutable_state.make_fresh_generation(&mut loader);
-        let inner = mutable_state.get_inner(&mut loader);
+        let mutable_state = mutable_state.make_fresh_generation(&mut loader);
+
+        // Set up some data needed for recursively processing the receive until the end,
+        // i.e. beyond interrupts.
+        let mut invocation_data = InvocationData {
+            sender,
+            address: payload.address,
+            contract_name,
+            entrypoint: entrypoint_name,
+            parameter: payload.message,
+            amount: payload.amount,
+            state: mutable_state,
+            trace_elements_checkpoint,
+            next_mod_idx_checkpoint: mod_idx_before_invoke,
+            mod_idx_before_invoke,
+        };
+
+        // Execute the entrypoint natively if it has been registered for that.
+        #[cfg(feature = "native")]
+        if let Some(native_entrypoint) = self.native_entrypoint(payload.address, &receive_name) {
+            let (initial_result, native_trace_elements) =
+                self.invoke_receive_native(native_entrypoint, receive_ctx, &mut invocation_data)?;
+            return Ok(Ok((initial_result, invocation_data, native_trace_elements)));
+        }
+
+        // Construct the instance state
+        let inner = invocation_data.state.get_inner(&mut loader);
         let instance_state = v1::InstanceState::new(loader, inner);
 
         // Get the initial result from invoking receive
@@ -227,30 +246,17 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                 module.artifact,
                 receive_ctx,
                 v1::ReceiveInvocation {
-                    amount: payload.amount,
+                    amount: invocation_data.amount,
                     // This will either be the one provided on the fallback receive name.
                     receive_name: receive_name.as_receive_name(),
-                    parameter: payload.message.as_ref(),
+                    parameter: invocation_data.parameter.as_ref(),
                     energy,
                 },
                 instance_state,
                 v1::ReceiveParams::new_p7(),
             )
         })?;
-        // Set up some data needed for recursively processing the receive until the end,
-        // i.e. beyond interrupts.
-        Ok(Ok((initial_result, InvocationData {
-            sender,
-            address: payload.address,
-            contract_name,
-            entrypoint: entrypoint_name,
-            parameter: payload.message,
-            amount: payload.amount,
-            state: mutable_state,
-            trace_elements_checkpoint,
-            next_mod_idx_checkpoint: mod_idx_before_invoke,
-            mod_idx_before_invoke,
-        })))
+        Ok(Ok((initial_result, invocation_data, Vec::new())))
     }
 
     /// Used for handling contract entrypoint invocations internally.
@@ -266,13 +272,27 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
         invoker: AccountAddress,
         sender: Address,
         payload: UpdateContractPayload,
+    ) -> Result<(InvokeResponse, Vec<DebugTraceElement>), TestConfigurationError> {
+        self.invoke_entrypoint_with_trace(invoker, sender, payload, Vec::new(), 0)
+    }
+
+    /// Like [`invoke_entrypoint`](Self::invoke_entrypoint), but the trace
+    /// elements of the invocation are added to `trace_elements`. If the
+    /// invocation fails, the elements from `trace_elements_checkpoint` onwards
+    /// are included in the failure, as for calls made by contracts in Wasm.
+    pub(super) fn invoke_entrypoint_with_trace(
+        &mut self,
+        invoker: AccountAddress,
+        sender: Address,
+        payload: UpdateContractPayload,
+        mut trace_elements: Vec<DebugTraceElement>,
+        trace_elements_checkpoint: usize,
     ) -> Result<(InvokeResponse, Vec<DebugTraceElement>), TestConfigurationError> {
         let mut stack = Vec::new();
-        let mut trace_elements = Vec::new();
         stack.push(Next::Initial {
             sender,
             payload,
-            trace_elements_checkpoint: 0,
+            trace_elements_checkpoint,
         });
         // Initialized to a dummy value. This will always be set or the function will
         // terminate with an Err.
@@ -303,56 +323,18 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                         }
                         None => {
                             // we are resuming from a contract call
-                            let (success, call_response) = match invoke_response
-                                .take()
-                                .expect("Response should be available")
-                            {
-                                v1::InvokeResponse::Success {
-                                    data: return_value,
-                                    ..
-                                } => {
-                                    let invoke_response = v1::InvokeResponse::Success {
-                                        // The balance returned by `invoke_entrypoint`
-                                        // is the balance of the contract called. But we
-                                        // are interested in the new balance of the caller.
-                                        new_balance: self.contract_balance_unchecked(data.address),
-                                        data:        return_value,
-                                    };
-                                    (true, invoke_response)
-                                }
-                                failure => (false, failure),
-                            };
-
-                            // Remove the last state changes if the invocation failed.
-                            let state_changed = if !success {
-                                self.rollback();
-                                false // We rolled back, so no changes were
-                                      // made to this contract.
-                            } else {
-                                let mod_idx_after_invoke = self.modification_index(data.address);
-                                let state_changed =
-                                    mod_idx_after_invoke != data.mod_idx_before_invoke;
-                                if state_changed {
-                                    // Update the state field with the newest value from the
-                                    // changeset.
-                                    data.state = self.contract_state(data.address);
-                                }
-                                state_changed
-                            };
-
-                            // Add resume event
-                            let resume_event = ContractTraceElement::Resumed {
-                                address: data.address,
-                                success,
-                            };
-
-                            self.push_regular_trace_element(
+                            let (call_response, state_changed) = self.resume_after_call(
+                                data.address,
+                                &data.entrypoint,
+                                invoke_response.take().expect("Response should be available"),
+                                data.mod_idx_before_invoke,
                                 &mut trace_elements,
-                                resume_event,
-                                data.entrypoint.clone(),
-                                DebugTracker::empty_trace(), /* Resume events do not have any
-                                                              * debug trace. */
                             );
+                            if state_changed {
+                                // Update the state field with the newest value from the
+                                // changeset.
+                                data.state = self.contract_state(data.address);
+                            }
                             let receive_result = self.run_interpreter(|energy| {
                                 v1::resume_receive(
                                     config,
@@ -380,7 +362,10 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                         payload,
                         trace_elements_checkpoint,
                     )? {
-                        Ok(x) => x,
+                        Ok((receive_result, invocation_data, native_trace_elements)) => {
+                            trace_elements.extend(native_trace_elements);
+                            (receive_result, invocation_data)
+                        }
                         Err(ier) => {
                             // invocation has failed. No more to do for this call.
                             // Since no traces were produced we don't have to roll them back.
@@ -447,13 +432,6 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                 } => {
                     // Update the remaining_energy field.
                     self.update_energy(remaining_energy);
-                    // Create the interrupt event, which will be included for transfers, calls,
-                    // and upgrades, but not for the remaining
-                    // interrupts.
-                    let interrupt_event = ContractTraceElement::Interrupted {
-                        address: invocation_data.address,
-                        events:  contract_events_from_logs(logs),
-                    };
                     // Remember what state we are in before invoking.
                     // This is used to report, upon resume, whether the contracts's
                     // state has changed.
@@ -462,510 +440,40 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                     } else {
                         self.modification_index(invocation_data.address)
                     };
-                    match interrupt {
-                        v1::Interrupt::Transfer {
-                            to,
-                            amount,
-                        } => {
-                            // Add the interrupt event
-                            self.push_regular_trace_element(
-                                &mut trace_elements,
-                                interrupt_event,
-                                invocation_data.entrypoint.clone(),
-                                trace,
-                            );
-
-                            let response = match self.transfer_from_contract_to_account(
-                                amount,
-                                invocation_data.address,
-                                to,
-                            ) {
-                                Ok(new_balance) => v1::InvokeResponse::Success {
-                                    new_balance,
-                                    data: None,
-                                },
-                                Err(err) => {
-                                    let kind = match err {
-                                        TransferError::ToMissing => {
-                                            v1::InvokeFailure::NonExistentAccount
-                                        }
-
-                                        TransferError::BalanceError {
-                                            error: BalanceError::Insufficient,
-                                        } => v1::InvokeFailure::InsufficientAmount,
-
-                                        TransferError::BalanceError {
-                                            error: BalanceError::Overflow,
-                                        } => {
-                                            // Balance overflows are unrecoverable and short
-                                            // circuit.
-                                            return Err(TestConfigurationError::BalanceOverflow);
-                                        }
-                                    };
-                                    v1::InvokeResponse::Failure {
-                                        kind,
-                                    }
-                                }
-                            };
-
-                            let success = matches!(response, v1::InvokeResponse::Success { .. });
-                            if success {
-                                // Add transfer event
-                                let transfer_event = ContractTraceElement::Transferred {
-                                    from: invocation_data.address,
-                                    amount,
-                                    to,
-                                };
-                                self.push_regular_trace_element(
-                                    &mut trace_elements,
-                                    transfer_event,
-                                    invocation_data.entrypoint.clone(),
-                                    DebugTracker::empty_trace(), // No trace for transfer events.
-                                );
-                            }
-                            // Add resume event
-                            let resume_event = ContractTraceElement::Resumed {
-                                address: invocation_data.address,
-                                success,
-                            };
-                            self.push_regular_trace_element(
-                                &mut trace_elements,
-                                resume_event,
-                                invocation_data.entrypoint.clone(),
-                                DebugTracker::empty_trace(), /* Resume events don't have a new
-                                                              * trace on their own. */
-                            );
-
-                            exit_ooe!(
-                                self.remaining_energy
-                                    .tick_energy(base::transactions::cost::SIMPLE_TRANSFER),
-                                DebugTracker::empty_trace()
-                            );
-
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                // the state never changes on transfers
-                                config,
-                                response: Some(response),
-                            });
-                        }
-                        v1::Interrupt::Call {
-                            address,
-                            parameter,
-                            name,
-                            amount,
-                        } => {
-                            // Add the interrupt event
-                            self.push_regular_trace_element(
-                                &mut trace_elements,
-                                interrupt_event,
-                                invocation_data.entrypoint.clone(),
-                                trace,
-                            );
-
-                            match self
-                                .chain
-                                .contracts
-                                .get(&address)
-                                .map(|c| c.contract_name.as_contract_name())
-                            {
-                                // The contract to call does not exist.
-                                None => {
-                                    let response = v1::InvokeResponse::Failure {
-                                        kind: v1::InvokeFailure::NonExistentContract,
-                                    };
-                                    // Add resume event
-                                    let resume_event = ContractTraceElement::Resumed {
-                                        address: invocation_data.address,
-                                        success: false,
-                                    };
-                                    self.push_regular_trace_element(
-                                        &mut trace_elements,
-                                        resume_event,
-                                        invocation_data.entrypoint.clone(),
-                                        DebugTracker::empty_trace(), /* Nothing happened since
-                                                                      * the contract does not
-                                                                      * exist. */
-                                    );
-                                    stack.push(Next::Resume {
-                                        data: invocation_data,
-                                        config,
-                                        response: Some(response),
-                                    });
-                                }
-                                Some(contract_name) => {
-                                    // Make a checkpoint before calling another contract so that we
-                                    // may roll back.
-                                    self.checkpoint();
-
-                                    let receive_name = OwnedReceiveName::construct_unchecked(
-                                        contract_name,
-                                        name.as_entrypoint_name(),
-                                    );
-                                    let message = OwnedParameter::new_unchecked(parameter);
-
-                                    let sender = Address::Contract(invocation_data.address);
-                                    // Remember to continue the current execution after handling the
-                                    // call.
-                                    stack.push(Next::Resume {
-                                        data: invocation_data,
-                                        config,
-                                        response: None,
-                                    });
-
-                                    // Add the call to the stack to execute.
-                                    stack.push(Next::Initial {
-                                        sender,
-                                        payload: UpdateContractPayload {
-                                            amount,
-                                            address,
-                                            receive_name,
-                                            message,
-                                        },
-                                        trace_elements_checkpoint: trace_elements.len(),
-                                    });
-                                }
-                            };
-                        }
-                        v1::Interrupt::Upgrade {
-                            module_ref,
-                        } => {
-                            // Add the interrupt event.
-                            self.push_regular_trace_element(
-                                &mut trace_elements,
-                                interrupt_event,
-                                invocation_data.entrypoint.clone(),
-                                trace,
-                            );
-
-                            // Charge a base cost.
-                            exit_ooe!(
-                                self.remaining_energy
-                                    .tick_energy(constants::INITIALIZE_CONTRACT_INSTANCE_BASE_COST),
-                                DebugTracker::empty_trace()
-                            );
-
-                            let response =
-                                match self.chain.modules.get(&module_ref) {
-                                    None => v1::InvokeResponse::Failure {
-                                        kind: v1::InvokeFailure::UpgradeInvalidModuleRef,
-                                    },
-                                    Some(module) => {
-                                        // Charge for the module lookup.
-                                        let lookup_costs = lookup_module_cost(module);
-                                        self.module_load_energy.energy += lookup_costs.energy;
-                                        exit_ooe!(
-                                            self.remaining_energy.tick_energy(lookup_costs),
-                                            DebugTracker::empty_trace()
-                                        );
-
-                                        if module.artifact.export.contains_key(
-                                            invocation_data
-                                                .contract_name
-                                                .as_contract_name()
-                                                .get_chain_name(),
-                                        ) {
-                                            // Update module reference in the changeset.
-                                            let old_module_ref = self.save_module_upgrade(
-                                                invocation_data.address,
-                                                module_ref,
-                                            );
-
-                                            // Charge for the initialization cost.
-                                            exit_ooe!(self.remaining_energy.tick_energy(
-                                                constants::INITIALIZE_CONTRACT_INSTANCE_CREATE_COST,
-                                            ), DebugTracker::empty_trace());
-
-                                            let upgrade_event = ContractTraceElement::Upgraded {
-                                                address: invocation_data.address,
-                                                from:    old_module_ref,
-                                                to:      module_ref,
-                                            };
-
-                                            self.push_regular_trace_element(
-                                                &mut trace_elements,
-                                                upgrade_event,
-                                                invocation_data.entrypoint.clone(),
-                                                DebugTracker::empty_trace(), /* Nothing happened
-                                                                              * in
-                                                                              * between. */
-                                            );
-
-                                            v1::InvokeResponse::Success {
-                                                new_balance: self.contract_balance_unchecked(
-                                                    invocation_data.address,
-                                                ),
-                                                data:        None,
-                                            }
-                                        } else {
-                                            v1::InvokeResponse::Failure {
-                                                kind: v1::InvokeFailure::UpgradeInvalidContractName,
-                                            }
-                                        }
-                                    }
-                                };
-
-                            let success = matches!(response, v1::InvokeResponse::Success { .. });
-                            let resumed_event = ContractTraceElement::Resumed {
-                                address: invocation_data.address,
-                                success,
-                            };
-                            self.push_regular_trace_element(
-                                &mut trace_elements,
-                                resumed_event,
-                                invocation_data.entrypoint.clone(),
-                                DebugTracker::empty_trace(),
-                            );
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                config,
-                                response: Some(response),
-                            });
-                        }
-                        v1::Interrupt::QueryAccountBalance {
-                            address,
-                        } => {
-                            let response = match self.account_balance(address) {
-                                Some(balance) => v1::InvokeResponse::Success {
-                                    new_balance: self
-                                        .contract_balance_unchecked(invocation_data.address),
-                                    data:        Some(to_bytes(&balance)),
-                                },
-                                None => v1::InvokeResponse::Failure {
-                                    kind: v1::InvokeFailure::NonExistentAccount,
-                                },
-                            };
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_ACCOUNT_BALANCE_COST,
-                                ),
-                                trace
-                            );
-                            trace_elements.push(invocation_data.debug_trace(trace));
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                config,
-                                response: Some(response),
-                            });
-                        }
-                        v1::Interrupt::QueryContractBalance {
-                            address,
-                        } => {
-                            let response = match self.contract_balance(address) {
-                                None => v1::InvokeResponse::Failure {
-                                    kind: v1::InvokeFailure::NonExistentContract,
-                                },
-                                Some(bal) => v1::InvokeResponse::Success {
-                                    // Balance of contract querying. Won't change. Notice the
-                                    // `invocation_data.address`.
-                                    new_balance: self
-                                        .contract_balance_unchecked(invocation_data.address),
-                                    data:        Some(to_bytes(&bal)),
-                                },
-                            };
-
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_CONTRACT_BALANCE_COST,
-                                ),
-                                trace
-                            );
-                            trace_elements.push(invocation_data.debug_trace(trace));
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                config,
-                                response: Some(response),
-                            });
-                        }
-                        v1::Interrupt::QueryExchangeRates => {
-                            let exchange_rates = ExchangeRates {
-                                euro_per_energy:    self.chain.parameters.euro_per_energy,
-                                micro_ccd_per_euro: self.chain.parameters.micro_ccd_per_euro,
-                            };
-
-                            let response = v1::InvokeResponse::Success {
-                                new_balance: self
-                                    .contract_balance_unchecked(invocation_data.address),
-                                data:        Some(to_bytes(&exchange_rates)),
-                            };
-
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_EXCHANGE_RATE_COST,
-                                ),
-                                trace
-                            );
-
-                            trace_elements.push(invocation_data.debug_trace(trace));
+                    match self.handle_interrupt(
+                        invoker,
+                        invocation_data.address,
+                        &invocation_data.entrypoint,
+                        interrupt,
+                        logs,
+                        trace,
+                        &mut trace_elements,
+                    )? {
+                        InterruptOutcome::Resume(response) => {
                             stack.push(Next::Resume {
                                 data: invocation_data,
                                 config,
                                 response: Some(response),
                             });
                         }
-                        v1::Interrupt::CheckAccountSignature {
-                            address,
+                        InterruptOutcome::Call {
+                            sender,
                             payload,
+                            trace_elements_checkpoint,
                         } => {
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_ACCOUNT_KEYS_BASE_COST,
-                                ),
-                                trace
-                            );
-                            // Due to borrow checker limitations we don't use self.account_keys here
-                            // since it leads to clashes with the mutable borrow of
-                            // self.remaining_energy below.
-                            let response = match self
-                                .chain
-                                .accounts
-                                .get(&address.into())
-                                .map(|a| &a.keys)
-                            {
-                                Some(keys) => {
-                                    // attempt to deserialize the payload after
-                                    // looking up the account.
-                                    // This is the order in the scheduler as
-                                    // well, and the order matters
-                                    // since the response to the contract is
-                                    // different depending on failure kind.
-                                    match deserial_signature_and_data_from_contract(&payload) {
-                                        Ok((sigs, data)) => {
-                                            let num_sigs = sigs.num_signatures();
-                                            exit_ooe!(
-                                                self.remaining_energy.tick_energy(
-                                                    // This cannot overflow on any data that can be
-                                                    // supplied.
-                                                    // Data_len will always be at most u32, and the
-                                                    // number of
-                                                    // signatures is at most 256*256.
-                                                    verify_ed25519_energy_cost(
-                                                        num_sigs,
-                                                        data.len() as u32,
-                                                    ),
-                                                ),
-                                                trace
-                                            );
-                                            if verify_data_signature(keys, data, &sigs.into()) {
-                                                v1::InvokeResponse::Success {
-                                                    new_balance: self.contract_balance_unchecked(
-                                                        invocation_data.address,
-                                                    ),
-                                                    data:        None,
-                                                }
-                                            } else {
-                                                v1::InvokeResponse::Failure {
-                                                    kind: v1::InvokeFailure::SignatureCheckFailed,
-                                                }
-                                            }
-                                        }
-                                        Err(_) => v1::InvokeResponse::Failure {
-                                            kind: v1::InvokeFailure::SignatureDataMalformed,
-                                        },
-                                    }
-                                }
-                                None => v1::InvokeResponse::Failure {
-                                    kind: v1::InvokeFailure::NonExistentAccount,
-                                },
-                            };
-                            trace_elements.push(invocation_data.debug_trace(trace));
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                config,
-                                response: Some(response),
-                            });
-                        }
-                        v1::Interrupt::QueryAccountKeys {
-                            address,
-                        } => {
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_ACCOUNT_KEYS_BASE_COST,
-                                ),
-                                trace
-                            );
-                            let response = match self.account_keys(address) {
-                                Some(keys) => {
-                                    let response_data = common::to_bytes(&keys);
-                                    let num_keys = keys.num_keys();
-                                    exit_ooe!(self.remaining_energy.tick_energy(
-                                        constants::contract_instance_query_account_keys_return_cost(
-                                            num_keys,
-                                        ),
-                                    ), trace);
-                                    v1::InvokeResponse::Success {
-                                        // Balance of contract querying. Does not change for this
-                                        // request.
-                                        new_balance: self
-                                            .contract_balance_unchecked(invocation_data.address),
-                                        data:        Some(response_data),
-                                    }
-                                }
-                                None => v1::InvokeResponse::Failure {
-                                    kind: v1::InvokeFailure::NonExistentAccount,
-                                },
-                            };
-                            trace_elements.push(invocation_data.debug_trace(trace));
+                            // Remember to continue the current execution after handling the
+                            // call.
                             stack.push(Next::Resume {
                                 data: invocation_data,
                                 config,
-                                response: Some(response),
+                                response: None,
                             });
-                        }
-                        v1::Interrupt::QueryContractModuleReference {
-                            address,
-                        } => {
-                            let response = match self.contract_module_reference(address) {
-                                None => v1::InvokeResponse::Failure {
-                                    kind: v1::InvokeFailure::NonExistentContract,
-                                },
-                                Some(mod_ref) => v1::InvokeResponse::Success {
-                                    // The balance of the calling contract is unchanged.
-                                    new_balance: self
-                                        .contract_balance_unchecked(invocation_data.address),
-                                    data:        Some(to_bytes(&mod_ref)),
-                                },
-                            };
 
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_CONTRACT_MODULE_REFERENCE_COST,
-                                ),
-                                trace
-                            );
-                            trace_elements.push(invocation_data.debug_trace(trace));
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                config,
-                                response: Some(response),
-                            });
-                        }
-                        v1::Interrupt::QueryContractName {
-                            address,
-                        } => {
-                            let response = match self.contract_name(address) {
-                                None => v1::InvokeResponse::Failure {
-                                    kind: v1::InvokeFailure::NonExistentContract,
-                                },
-                                Some(cname) => v1::InvokeResponse::Success {
-                                    // The balance of the calling contract is unchanged.
-                                    new_balance: self
-                                        .contract_balance_unchecked(invocation_data.address),
-                                    data:        Some(cname.get_chain_name().into()),
-                                },
-                            };
-
-                            exit_ooe!(
-                                self.remaining_energy.tick_energy(
-                                    constants::CONTRACT_INSTANCE_QUERY_CONTRACT_NAME_COST,
-                                ),
-                                trace
-                            );
-                            trace_elements.push(invocation_data.debug_trace(trace));
-                            stack.push(Next::Resume {
-                                data: invocation_data,
-                                config,
-                                response: Some(response),
+                            // Add the call to the stack to execute.
+                            stack.push(Next::Initial {
+                                sender,
+                                payload,
+                                trace_elements_checkpoint,
                             });
                         }
                     }
@@ -1047,9 +555,663 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
                 }
             }
         }
+        self.record_final_modification_index();
         Ok((invoke_response.expect("Response should have been set."), trace_elements))
     }
 
+    /// Handle an interrupt of the contract at `address` while executing
+    /// `entrypoint`, i.e., an operation such as a transfer, a call or a query
+    /// invoked by the contract. This is shared by entrypoints executed in Wasm
+    /// and natively.
+    ///
+    /// The `logs` are the events logged since the previous interrupt and the
+    /// `trace` is the debug trace of the contract until the interrupt. Any
+    /// changes to the state of the contract must be saved before this is
+    /// called.
+    ///
+    /// Returns the response to resume the contract with, or the call to
+    /// execute if the contract calls another contract. In the latter case, the
+    /// contract must be resumed with
+    /// [`resume_after_call`](Self::resume_after_call) after executing the
+    /// call.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn handle_interrupt(
+        &mut self,
+        invoker: AccountAddress,
+        address: ContractAddress,
+        entrypoint: &OwnedEntrypointName,
+        interrupt: v1::Interrupt,
+        logs: v0::Logs,
+        trace: DebugTracker,
+        trace_elements: &mut Vec<DebugTraceElement>,
+    ) -> Result<InterruptOutcome, TestConfigurationError> {
+        // Create the interrupt event, which will be included for transfers, calls,
+        // and upgrades, but not for the remaining
+        // interrupts.
+        let interrupt_event = ContractTraceElement::Interrupted {
+            address,
+            events: contract_events_from_logs(logs),
+        };
+        // The trace element of the queries, which only includes the debug trace.
+        let debug_trace = |debug_trace| DebugTraceElement::Debug {
+            entrypoint: entrypoint.clone(),
+            address,
+            debug_trace,
+        };
+        let response = match interrupt {
+            v1::Interrupt::Transfer {
+                to,
+                amount,
+            } => {
+                // Add the interrupt event
+                self.push_regular_trace_element(
+                    trace_elements,
+                    interrupt_event,
+                    entrypoint.clone(),
+                    trace,
+                );
+
+                let response = match self.transfer_from_contract_to_account(amount, address, to) {
+                    Ok(new_balance) => v1::InvokeResponse::Success {
+                        new_balance,
+                        data: None,
+                    },
+                    Err(err) => {
+                        let kind = match err {
+                            TransferError::ToMissing => v1::InvokeFailure::NonExistentAccount,
+
+                            TransferError::BalanceError {
+                                error: BalanceError::Insufficient,
+                            } => v1::InvokeFailure::InsufficientAmount,
+
+                            TransferError::BalanceError {
+                                error: BalanceError::Overflow,
+                            } => {
+                                // Balance overflows are unrecoverable and short
+                                // circuit.
+                                return Err(TestConfigurationError::BalanceOverflow);
+                            }
+                        };
+                        v1::InvokeResponse::Failure {
+                            kind,
+                        }
+                    }
+                };
+
+                let success = matches!(response, v1::InvokeResponse::Success { .. });
+                if success {
+                    // Add transfer event
+                    let transfer_event = ContractTraceElement::Transferred {
+                        from: address,
+                        amount,
+                        to,
+                    };
+                    self.push_regular_trace_element(
+                        trace_elements,
+                        transfer_event,
+                        entrypoint.clone(),
+                        DebugTracker::empty_trace(), // No trace for transfer events.
+                    );
+                }
+                // Add resume event
+                let resume_event = ContractTraceElement::Resumed {
+                    address,
+                    success,
+                };
+                self.push_regular_trace_element(
+                    trace_elements,
+                    resume_event,
+                    entrypoint.clone(),
+                    DebugTracker::empty_trace(), /* Resume events don't have a new
+                                                  * trace on their own. */
+                );
+
+                exit_ooe!(
+                    self.remaining_energy.tick_energy(base::transactions::cost::SIMPLE_TRANSFER),
+                    DebugTracker::empty_trace()
+                );
+                response
+            }
+            v1::Interrupt::Call {
+                address: callee,
+                parameter,
+                name,
+                amount,
+            } => {
+                // Add the interrupt event
+                self.push_regular_trace_element(
+                    trace_elements,
+                    interrupt_event,
+                    entrypoint.clone(),
+                    trace,
+                );
+
+                match self.chain.contracts.get(&callee).map(|c| c.contract_name.as_contract_name())
+                {
+                    // The contract to call does not exist.
+                    None => {
+                        // Add resume event
+                        let resume_event = ContractTraceElement::Resumed {
+                            address,
+                            success: false,
+                        };
+                        self.push_regular_trace_element(
+                            trace_elements,
+                            resume_event,
+                            entrypoint.clone(),
+                            DebugTracker::empty_trace(), /* Nothing happened since the
+                                                          * contract does not exist. */
+                        );
+                        v1::InvokeResponse::Failure {
+                            kind: v1::InvokeFailure::NonExistentContract,
+                        }
+                    }
+                    Some(contract_name) => {
+                        // Make a checkpoint before calling another contract so that we
+                        // may roll back.
+                        self.checkpoint();
+
+                        let receive_name = OwnedReceiveName::construct_unchecked(
+                            contract_name,
+                            name.as_entrypoint_name(),
+                        );
+                        let message = OwnedParameter::new_unchecked(parameter);
+
+                        // Any traces of a reentrant call injected below belong to the
+                        // call, and are rolled back with it.
+                        let trace_elements_checkpoint = trace_elements.len();
+                        if let Some(reentrant_call) =
+                            self.record_outgoing_call(address, entrypoint, callee, &name)
+                        {
+                            let reentrant_trace = self.inject_reentrant_call(
+                                invoker,
+                                address,
+                                entrypoint,
+                                callee,
+                                reentrant_call,
+                            )?;
+                            trace_elements.extend(reentrant_trace);
+                        }
+
+                        return Ok(InterruptOutcome::Call {
+                            sender: Address::Contract(address),
+                            payload: UpdateContractPayload {
+                                amount,
+                                address: callee,
+                                receive_name,
+                                message,
+                            },
+                            trace_elements_checkpoint,
+                        });
+                    }
+                }
+            }
+            v1::Interrupt::Upgrade {
+                module_ref,
+            } => {
+                // Add the interrupt event.
+                self.push_regular_trace_element(
+                    trace_elements,
+                    interrupt_event,
+                    entrypoint.clone(),
+                    trace,
+                );
+
+                // Charge a base cost.
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::INITIALIZE_CONTRACT_INSTANCE_BASE_COST),
+                    DebugTracker::empty_trace()
+                );
+
+                let response = match self.chain.modules.get(&module_ref) {
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::UpgradeInvalidModuleRef,
+                    },
+                    Some(module) => {
+                        // Charge for the module lookup.
+                        let lookup_costs = lookup_module_cost(module);
+                        self.module_load_energy.energy += lookup_costs.energy;
+                        exit_ooe!(
+                            self.remaining_energy.tick_energy(lookup_costs),
+                            DebugTracker::empty_trace()
+                        );
+
+                        let contract_name = self
+                            .contract_name(address)
+                            .expect("Precondition violation: contract must exist.");
+                        if module.artifact.export.contains_key(contract_name.get_chain_name()) {
+                            // Update module reference in the changeset.
+                            let old_module_ref = self.save_module_upgrade(address, module_ref);
+
+                            // Charge for the initialization cost.
+                            exit_ooe!(
+                                self.remaining_energy.tick_energy(
+                                    constants::INITIALIZE_CONTRACT_INSTANCE_CREATE_COST,
+                                ),
+                                DebugTracker::empty_trace()
+                            );
+
+                            let upgrade_event = ContractTraceElement::Upgraded {
+                                address,
+                                from: old_module_ref,
+                                to: module_ref,
+                            };
+
+                            self.push_regular_trace_element(
+                                trace_elements,
+                                upgrade_event,
+                                entrypoint.clone(),
+                                DebugTracker::empty_trace(), // Nothing happened in between.
+                            );
+
+                            v1::InvokeResponse::Success {
+                                new_balance: self.contract_balance_unchecked(address),
+                                data:        None,
+                            }
+                        } else {
+                            v1::InvokeResponse::Failure {
+                                kind: v1::InvokeFailure::UpgradeInvalidContractName,
+                            }
+                        }
+                    }
+                };
+
+                let success = matches!(response, v1::InvokeResponse::Success { .. });
+                let resumed_event = ContractTraceElement::Resumed {
+                    address,
+                    success,
+                };
+                self.push_regular_trace_element(
+                    trace_elements,
+                    resumed_event,
+                    entrypoint.clone(),
+                    DebugTracker::empty_trace(),
+                );
+                response
+            }
+            v1::Interrupt::QueryAccountBalance {
+                address: account,
+            } => {
+                let response = match self.account_balance(account) {
+                    Some(balance) => v1::InvokeResponse::Success {
+                        new_balance: self.contract_balance_unchecked(address),
+                        data:        Some(to_bytes(&balance)),
+                    },
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::NonExistentAccount,
+                    },
+                };
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::CONTRACT_INSTANCE_QUERY_ACCOUNT_BALANCE_COST,),
+                    trace
+                );
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+            v1::Interrupt::QueryContractBalance {
+                address: contract,
+            } => {
+                let response = match self.contract_balance(contract) {
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::NonExistentContract,
+                    },
+                    Some(bal) => v1::InvokeResponse::Success {
+                        // Balance of contract querying. Won't change. Notice the
+                        // `address`.
+                        new_balance: self.contract_balance_unchecked(address),
+                        data:        Some(to_bytes(&bal)),
+                    },
+                };
+
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::CONTRACT_INSTANCE_QUERY_CONTRACT_BALANCE_COST,),
+                    trace
+                );
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+            v1::Interrupt::QueryExchangeRates => {
+                let exchange_rates = ExchangeRates {
+                    euro_per_energy:    self.chain.parameters.euro_per_energy,
+                    micro_ccd_per_euro: self.chain.parameters.micro_ccd_per_euro,
+                };
+
+                let response = v1::InvokeResponse::Success {
+                    new_balance: self.contract_balance_unchecked(address),
+                    data:        Some(to_bytes(&exchange_rates)),
+                };
+
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::CONTRACT_INSTANCE_QUERY_EXCHANGE_RATE_COST,),
+                    trace
+                );
+
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+            v1::Interrupt::CheckAccountSignature {
+                address: account,
+                payload,
+            } => {
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::CONTRACT_INSTANCE_QUERY_ACCOUNT_KEYS_BASE_COST,),
+                    trace
+                );
+                // Due to borrow checker limitations we don't use self.account_keys here
+                // since it leads to clashes with the mutable borrow of
+                // self.remaining_energy below.
+                let response = match self.chain.accounts.get(&account.into()).map(|a| &a.keys) {
+                    Some(keys) => {
+                        // attempt to deserialize the payload after
+                        // looking up the account.
+                        // This is the order in the scheduler as
+                        // well, and the order matters
+                        // since the response to the contract is
+                        // different depending on failure kind.
+                        match deserial_signature_and_data_from_contract(&payload) {
+                            Ok((sigs, data)) => {
+                                let num_sigs = sigs.num_signatures();
+                                exit_ooe!(
+                                    self.remaining_energy.tick_energy(
+                                        // This cannot overflow on any data that can be
+                                        // supplied.
+                                        // Data_len will always be at most u32, and the
+                                        // number of
+                                        // signatures is at most 256*256.
+                                        verify_ed25519_energy_cost(num_sigs, data.len() as u32),
+                                    ),
+                                    trace
+                                );
+                                if verify_data_signature(keys, data, &sigs.into()) {
+                                    v1::InvokeResponse::Success {
+                                        new_balance: self.contract_balance_unchecked(address),
+                                        data:        None,
+                                    }
+                                } else {
+                                    v1::InvokeResponse::Failure {
+                                        kind: v1::InvokeFailure::SignatureCheckFailed,
+                                    }
+                                }
+                            }
+                            Err(_) => v1::InvokeResponse::Failure {
+                                kind: v1::InvokeFailure::SignatureDataMalformed,
+                            },
+                        }
+                    }
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::NonExistentAccount,
+                    },
+                };
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+            v1::Interrupt::QueryAccountKeys {
+                address: account,
+            } => {
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::CONTRACT_INSTANCE_QUERY_ACCOUNT_KEYS_BASE_COST,),
+                    trace
+                );
+                let response = match self.account_keys(account) {
+                    Some(keys) => {
+                        let response_data = common::to_bytes(&keys);
+                        let num_keys = keys.num_keys();
+                        exit_ooe!(
+                            self.remaining_energy.tick_energy(
+                                constants::contract_instance_query_account_keys_return_cost(
+                                    num_keys,
+                                ),
+                            ),
+                            trace
+                        );
+                        v1::InvokeResponse::Success {
+                            // Balance of contract querying. Does not change for this
+                            // request.
+                            new_balance: self.contract_balance_unchecked(address),
+                            data:        Some(response_data),
+                        }
+                    }
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::NonExistentAccount,
+                    },
+                };
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+            v1::Interrupt::QueryContractModuleReference {
+                address: contract,
+            } => {
+                let response = match self.contract_module_reference(contract) {
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::NonExistentContract,
+                    },
+                    Some(mod_ref) => v1::InvokeResponse::Success {
+                        // The balance of the calling contract is unchanged.
+                        new_balance: self.contract_balance_unchecked(address),
+                        data:        Some(to_bytes(&mod_ref)),
+                    },
+                };
+
+                exit_ooe!(
+                    self.remaining_energy.tick_energy(
+                        constants::CONTRACT_INSTANCE_QUERY_CONTRACT_MODULE_REFERENCE_COST,
+                    ),
+                    trace
+                );
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+            v1::Interrupt::QueryContractName {
+                address: contract,
+            } => {
+                let response = match self.contract_name(contract) {
+                    None => v1::InvokeResponse::Failure {
+                        kind: v1::InvokeFailure::NonExistentContract,
+                    },
+                    Some(cname) => v1::InvokeResponse::Success {
+                        // The balance of the calling contract is unchanged.
+                        new_balance: self.contract_balance_unchecked(address),
+                        data:        Some(cname.get_chain_name().into()),
+                    },
+                };
+
+                exit_ooe!(
+                    self.remaining_energy
+                        .tick_energy(constants::CONTRACT_INSTANCE_QUERY_CONTRACT_NAME_COST,),
+                    trace
+                );
+                trace_elements.push(debug_trace(trace));
+                response
+            }
+        };
+        Ok(InterruptOutcome::Resume(response))
+    }
+
+    /// Handle the `response` of a call made by the contract at `address`
+    /// while executing `entrypoint`, before the contract is resumed. This is
+    /// shared by entrypoints executed in Wasm and natively.
+    ///
+    /// The changes made by the call are rolled back if it failed. Returns the
+    /// response to resume the contract with, and whether the state of the
+    /// contract was modified by the call, in which case the contract must
+    /// continue with the state from [`contract_state`](Self::contract_state).
+    pub(super) fn resume_after_call(
+        &mut self,
+        address: ContractAddress,
+        entrypoint: &OwnedEntrypointName,
+        response: InvokeResponse,
+        mod_idx_before_invoke: u32,
+        trace_elements: &mut Vec<DebugTraceElement>,
+    ) -> (InvokeResponse, bool) {
+        let (success, call_response) = match response {
+            v1::InvokeResponse::Success {
+                data: return_value,
+                ..
+            } => {
+                let invoke_response = v1::InvokeResponse::Success {
+                    // The balance returned by `invoke_entrypoint`
+                    // is the balance of the contract called. But we
+                    // are interested in the new balance of the caller.
+                    new_balance: self.contract_balance_unchecked(address),
+                    data:        return_value,
+                };
+                (true, invoke_response)
+            }
+            failure => (false, failure),
+        };
+
+        // Remove the last state changes if the invocation failed.
+        let state_changed = if !success {
+            self.rollback();
+            false // We rolled back, so no changes were
+                  // made to this contract.
+        } else {
+            self.modification_index(address) != mod_idx_before_invoke
+        };
+
+        // Add resume event
+        let resume_event = ContractTraceElement::Resumed {
+            address,
+            success,
+        };
+
+        self.push_regular_trace_element(
+            trace_elements,
+            resume_event,
+            entrypoint.clone(),
+            DebugTracker::empty_trace(), /* Resume events do not have any
+                                          * debug trace. */
+        );
+        (call_response, state_changed)
+    }
+
+    /// Record an outgoing call made by the contract targeted by reentrancy
+    /// exploration, if any. Returns the reentrant call to inject before the
+    /// outgoing call is executed.
+    ///
+    /// Calls made while a reentrant call is executing are not recorded.
+    fn record_outgoing_call(
+        &self,
+        caller: ContractAddress,
+        caller_entrypoint: &OwnedEntrypointName,
+        callee: ContractAddress,
+        callee_entrypoint: &OwnedEntrypointName,
+    ) -> Option<ReentrantCall> {
+        let mut probe = self.chain.reentrancy_probe.as_ref()?.lock().expect("Probe lock poisoned");
+        if probe.attacking || probe.target != caller {
+            return None;
+        }
+        let call_index = probe.outgoing_calls.len();
+        probe.outgoing_calls.push(OutgoingCall {
+            caller_entrypoint: caller_entrypoint.clone(),
+            callee,
+            callee_entrypoint: callee_entrypoint.clone(),
+        });
+        match &probe.attack {
+            Some((attack_index, call)) if *attack_index == call_index => Some(call.clone()),
+            _ => None,
+        }
+    }
+
+    /// Invoke the reentrant call on behalf of the `callee` before it is
+    /// called by the `target`. The changes made by the reentrant call are
+    /// rolled back if it fails, and the outcome is recorded in the reentrancy
+    /// probe.
+    ///
+    /// Returns the trace elements of the reentrant call.
+    fn inject_reentrant_call(
+        &mut self,
+        invoker: AccountAddress,
+        target: ContractAddress,
+        target_entrypoint: &OwnedEntrypointName,
+        callee: ContractAddress,
+        call: ReentrantCall,
+    ) -> Result<Vec<DebugTraceElement>, TestConfigurationError> {
+        let payload = match call {
+            ReentrantCall::Reenter {
+                entrypoint,
+                parameter,
+                amount,
+            } => UpdateContractPayload {
+                amount,
+                address: target,
+                receive_name: OwnedReceiveName::construct_unchecked(
+                    self.contract_name(target).expect("Target contract exists"),
+                    entrypoint.as_entrypoint_name(),
+                ),
+                message: parameter,
+            },
+            ReentrantCall::Attacker {
+                address,
+                entrypoint,
+                amount,
+            } => {
+                let Some(contract_name) = self.contract_name(address) else {
+                    return Ok(Vec::new());
+                };
+                UpdateContractPayload {
+                    amount,
+                    address,
+                    receive_name: OwnedReceiveName::construct_unchecked(
+                        contract_name,
+                        entrypoint.as_entrypoint_name(),
+                    ),
+                    message: OwnedParameter::new_unchecked(to_bytes(&(
+                        target,
+                        target_entrypoint.as_entrypoint_name(),
+                    ))),
+                }
+            }
+        };
+
+        let modification_index_before = self.modification_index(target);
+        self.set_attacking(true);
+        // Make a checkpoint so that the reentrant call may be rolled back.
+        self.checkpoint();
+        let result = self.invoke_entrypoint(invoker, Address::Contract(callee), payload);
+        self.set_attacking(false);
+        let (response, trace_elements) = result?;
+
+        let succeeded = matches!(response, v1::InvokeResponse::Success { .. });
+        if !succeeded {
+            self.rollback();
+        }
+        let injection = ReentrancyInjection {
+            succeeded,
+            modification_index_before,
+            modification_index_after: self.modification_index(target),
+            blocked_by_guard: rejected_by_reentrancy_guard(&trace_elements, target),
+        };
+        if let Some(probe) = &self.chain.reentrancy_probe {
+            probe.lock().expect("Probe lock poisoned").injection = Some(injection);
+        }
+        Ok(trace_elements)
+    }
+
+    /// Mark whether a reentrant call is currently executing.
+    fn set_attacking(&self, attacking: bool) {
+        if let Some(probe) = &self.chain.reentrancy_probe {
+            probe.lock().expect("Probe lock poisoned").attacking = attacking;
+        }
+    }
+
+    /// Record the modification index of the contract targeted by reentrancy
+    /// exploration at the end of the transaction.
+    fn record_final_modification_index(&self) {
+        if let Some(probe) = &self.chain.reentrancy_probe {
+            let mut probe = probe.lock().expect("Probe lock poisoned");
+            if !probe.attacking {
+                probe.final_modification_index = Some(self.modification_index(probe.target));
+            }
+        }
+    }
+
     /// Make a transfer from a contract to an account in the changeset.
     ///
     /// Returns the new balance of `from`.
@@ -1232,7 +1394,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ///
     /// **Preconditions:**
     ///  - Contract must exist.
-    fn contract_balance_unchecked(&self, address: ContractAddress) -> Amount {
+    pub(super) fn contract_balance_unchecked(&self, address: ContractAddress) -> Amount {
         self.contract_balance(address).expect("Precondition violation: contract must exist")
     }
 
@@ -1297,7 +1459,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ///
     /// **Preconditions:**
     ///  - Contract instance must exist.
-    fn contract_state(&self, address: ContractAddress) -> trie::MutableState {
+    pub(super) fn contract_state(&self, address: ContractAddress) -> trie::MutableState {
         match self.changeset.current().contracts.get(&address).and_then(|c| c.state.clone()) {
             // Contract state has been modified.
             Some(modified_state) => modified_state,
@@ -1354,7 +1516,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ///
     /// **Preconditions:**
     ///  - Contract must exist.
-    fn save_state_changes(
+    pub(super) fn save_state_changes(
         &mut self,
         address: ContractAddress,
         state: &mut trie::MutableState,
@@ -1438,7 +1600,7 @@ impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
     ///
     /// It looks it up in the changeset, and if it isn't there, it will return
     /// `0`.
-    fn modification_index(&self, address: ContractAddress) -> u32 {
+    pub(super) fn modification_index(&self, address: ContractAddress) -> u32 {
         self.changeset.current().contracts.get(&address).map_or(0, |c| c.modification_index)
     }
 
@@ -1639,7 +1801,7 @@ impl ChangeSet {
     }
 
     /// Get an immutable reference the current (latest) checkpoint.
-    fn current(&self) -> &Changes {
+    pub(super) fn current(&self) -> &Changes {
         self.stack.last().expect("Internal error: change set stack should never be empty.")
     }
 
@@ -1876,6 +2038,30 @@ impl From<(InsufficientEnergy, DebugTracker)> for TestConfigurationError {
     }
 }
 
+/// Whether the `target` rejected a call with the
+/// [`REENTRANCY_GUARD_REJECT_CODE`](constants::REENTRANCY_GUARD_REJECT_CODE)
+/// anywhere in the trace elements, including failed calls whose failure was
+/// handled by the caller.
+fn rejected_by_reentrancy_guard(
+    trace_elements: &[DebugTraceElement],
+    target: ContractAddress,
+) -> bool {
+    trace_elements.iter().any(|element| match element {
+        DebugTraceElement::WithFailures {
+            contract_address,
+            error,
+            trace_elements,
+            ..
+        } => {
+            let blocked = matches!(error, InvokeExecutionError::Reject { reason, .. }
+                if *reason == constants::REENTRANCY_GUARD_REJECT_CODE);
+            (blocked && *contract_address == target)
+                || rejected_by_reentrancy_guard(trace_elements, target)
+        }
+        _ => false,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     mod amount_delta {
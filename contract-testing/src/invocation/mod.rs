@@ -13,5 +13,12 @@
 //!  - Balances of contracts and accounts.
 
 mod impls;
+#[cfg(feature = "native")]
+mod native;
 mod types;
-pub(crate) use types::{ChangeSet, EntrypointInvocationHandler, TestConfigurationError};
+#[cfg(feature = "native")]
+pub(crate) use native::invoke_init_native;
+pub(crate) use types::{
+    ChangeSet, EntrypointInvocationHandler, ReentrancyInjection, ReentrancyProbe,
+    TestConfigurationError,
+};
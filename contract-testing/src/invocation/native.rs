@@ -0,0 +1,790 @@
+//! Native execution of contract entrypoints registered with
+//! [`Chain::module_register_native`](crate::Chain::module_register_native).
+//!
+//! A native entrypoint runs to completion in one go. Calls and queries made by
+//! the contract are therefore handled by the host functions while it runs.
+//! They are handled as the corresponding interrupts in the Wasm interpreter,
+//! using [`EntrypointInvocationHandler::handle_interrupt`], so the effects of
+//! the calls, such as trace elements and changes to the contract states, are
+//! the same as if the contract had been executed in Wasm. Only the encoding of
+//! the responses for the contract, which the Wasm engine does for contracts in
+//! Wasm, is done here.
+use super::types::*;
+use crate::{impls::to_interpreter_energy, DebugTraceElement, NativeEntrypoint};
+use concordium_rust_sdk::{
+    base::contracts_common::{
+        from_bytes, AccountAddress, Address, Amount, ChainMetadata, ContractAddress,
+        ModuleReference, OwnedEntrypointName, OwnedParameter, OwnedReceiveName, Timestamp,
+    },
+    smart_contracts::engine::{
+        v0,
+        v1::{self, trie, DebugTracker, InvokeResponse},
+        wasm::artifact::CompiledFunction,
+        InterpreterEnergy,
+    },
+};
+use concordium_std::native::{NativeHost, Trapped};
+use std::collections::VecDeque;
+
+/// Bit set in the return value index of a successful call if the state of the
+/// calling contract was modified by the call.
+const STATE_CHANGED_FLAG: u64 = 0x80_0000;
+
+/// The energy given to the operations on the state. Native execution is not
+/// metered.
+const STATE_ENERGY: u64 = u64::MAX;
+
+impl<'a, 'b> EntrypointInvocationHandler<'a, 'b> {
+    /// Look up the natively executed entrypoint for the receive name of a
+    /// contract, taking upgrades into account.
+    ///
+    /// **Preconditions:**
+    ///  - Contract must exist.
+    pub(super) fn native_entrypoint(
+        &self,
+        address: ContractAddress,
+        receive_name: &OwnedReceiveName,
+    ) -> Option<NativeEntrypoint> {
+        let module_reference =
+            match self.changeset.current().contracts.get(&address).and_then(|c| c.module) {
+                Some(module_reference) => module_reference,
+                None => self.chain.contracts.get(&address)?.module_reference,
+            };
+        self.chain
+            .native_entrypoint(module_reference, receive_name.as_receive_name().get_chain_name())
+    }
+
+    /// Execute a receive function natively. This is the native counterpart of
+    /// [`v1::invoke_receive`], except that the result is never an interrupt.
+    ///
+    /// The state of the contract is taken from and written back to
+    /// `invocation_data`. Returns the result and the trace elements of the
+    /// calls made by the contract.
+    pub(super) fn invoke_receive_native(
+        &mut self,
+        entrypoint: NativeEntrypoint,
+        receive_ctx: v1::ReceiveContext<Vec<u8>>,
+        invocation_data: &mut InvocationData,
+    ) -> Result<
+        (v1::ReceiveResult<CompiledFunction, DebugTracker>, Vec<DebugTraceElement>),
+        TestConfigurationError,
+    > {
+        let state =
+            std::mem::replace(&mut invocation_data.state, trie::MutableState::initial_state());
+        let mut host = NativeExecution::new(
+            Some(self),
+            NativeContext::Receive(receive_ctx),
+            invocation_data.parameter.as_ref(),
+            state,
+        );
+        let amount = invocation_data.amount;
+        let code = entrypoint.run(&mut host, amount);
+        let NativeExecution {
+            return_value,
+            logs,
+            state,
+            trace_elements,
+            error,
+            ..
+        } = host;
+        if let Some(error) = error {
+            return Err(error);
+        }
+        invocation_data.state = state.state;
+        let remaining_energy =
+            InterpreterEnergy::new(to_interpreter_energy(*self.remaining_energy));
+        let trace = DebugTracker::empty_trace();
+        let result = match code {
+            Err(Trapped) => v1::ReceiveResult::Trap {
+                error: anyhow::anyhow!("The contract trapped."),
+                remaining_energy,
+                trace,
+            },
+            Ok(0) => v1::ReceiveResult::Success {
+                logs: v0::Logs {
+                    logs: logs.into_iter().collect(),
+                },
+                state_changed: state.changed,
+                return_value,
+                remaining_energy,
+                trace,
+            },
+            Ok(reason) if reason < 0 => v1::ReceiveResult::Reject {
+                reason,
+                return_value,
+                remaining_energy,
+                trace,
+            },
+            Ok(code) => v1::ReceiveResult::Trap {
+                error: anyhow::anyhow!("Invalid return value received: {code}"),
+                remaining_energy,
+                trace,
+            },
+        };
+        Ok((result, trace_elements))
+    }
+}
+
+/// Execute an init function natively. This is the native counterpart of
+/// [`v1::invoke_init`]. Since native execution is not metered, all of the
+/// `energy` remains.
+pub(crate) fn invoke_init_native(
+    entrypoint: NativeEntrypoint,
+    init_ctx: v0::InitContext<Vec<u8>>,
+    amount: Amount,
+    parameter: &[u8],
+    energy: InterpreterEnergy,
+) -> v1::InitResult<DebugTracker> {
+    let mut host = NativeExecution::new(
+        None,
+        NativeContext::Init(init_ctx),
+        parameter,
+        trie::MutableState::initial_state(),
+    );
+    let code = entrypoint.run(&mut host, amount);
+    let trace = DebugTracker::empty_trace();
+    match code {
+        Err(Trapped) => v1::InitResult::Trap {
+            error: anyhow::anyhow!("The contract trapped."),
+            remaining_energy: energy,
+            trace,
+        },
+        Ok(0) => v1::InitResult::Success {
+            logs: v0::Logs {
+                logs: host.logs.into_iter().collect(),
+            },
+            return_value: host.return_value,
+            remaining_energy: energy,
+            state: host.state.state,
+            trace,
+        },
+        Ok(reason) if reason < 0 => v1::InitResult::Reject {
+            reason,
+            return_value: host.return_value,
+            remaining_energy: energy,
+            trace,
+        },
+        Ok(code) => v1::InitResult::Trap {
+            error: anyhow::anyhow!("Invalid return value received: {code}"),
+            remaining_energy: energy,
+            trace,
+        },
+    }
+}
+
+/// The context of a natively executed entrypoint.
+enum NativeContext {
+    Init(v0::InitContext<Vec<u8>>),
+    Receive(v1::ReceiveContext<Vec<u8>>),
+}
+
+/// The host of a natively executed entrypoint, which handles the host
+/// functions called by the contract.
+struct NativeExecution<'h, 'a, 'b> {
+    /// The handler of the invocation, which is used for the operations invoked
+    /// by the contract. Not available for init functions.
+    handler:        Option<&'h mut EntrypointInvocationHandler<'a, 'b>>,
+    context:        NativeContext,
+    /// The parameter at index 0, followed by the return values of the
+    /// operations invoked by the contract.
+    parameters:     Vec<Vec<u8>>,
+    return_value:   Vec<u8>,
+    /// The events logged since the last operation that produced trace
+    /// elements.
+    logs:           Vec<Vec<u8>>,
+    state:          NativeState,
+    /// The trace elements of the operations invoked by the contract.
+    trace_elements: Vec<DebugTraceElement>,
+    /// An error that stops the whole invocation, such as running out of
+    /// energy. Since the contract cannot be stopped, the operations it invokes
+    /// afterwards fail, and the error is returned once it finishes.
+    error:          Option<TestConfigurationError>,
+}
+
+impl<'h, 'a, 'b> NativeExecution<'h, 'a, 'b> {
+    fn new(
+        handler: Option<&'h mut EntrypointInvocationHandler<'a, 'b>>,
+        context: NativeContext,
+        parameter: &[u8],
+        state: trie::MutableState,
+    ) -> Self {
+        Self {
+            handler,
+            context,
+            parameters: vec![parameter.to_vec()],
+            return_value: Vec::new(),
+            logs: Vec::new(),
+            state: NativeState::new(state),
+            trace_elements: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// The receive context. Panics for init functions, which do not have
+    /// access to the host functions that use it.
+    fn receive_context(&self) -> &v1::ReceiveContext<Vec<u8>> {
+        match &self.context {
+            NativeContext::Receive(receive_ctx) => receive_ctx,
+            NativeContext::Init(_) => panic!("Not available in init functions."),
+        }
+    }
+
+    /// The chain metadata and the serialized policies of the sender.
+    fn common_context(&self) -> (&ChainMetadata, &[u8]) {
+        match &self.context {
+            NativeContext::Init(init_ctx) => (&init_ctx.metadata, &init_ctx.sender_policies),
+            NativeContext::Receive(receive_ctx) => {
+                (&receive_ctx.common.metadata, &receive_ctx.common.sender_policies)
+            }
+        }
+    }
+
+    /// Encode the response to an invoked operation for the contract, as
+    /// described in [`concordium_std::prims::invoke`]. The return value, if
+    /// any, is added to the parameters.
+    fn encode_response(&mut self, response: InvokeResponse, state_changed: bool) -> u64 {
+        let mut return_value_index = |data: Vec<u8>| {
+            self.parameters.push(data);
+            self.parameters.len() as u64 - 1
+        };
+        match response {
+            InvokeResponse::Success {
+                data,
+                ..
+            } => {
+                let index = data.map_or(0, &mut return_value_index);
+                let flag = if state_changed {
+                    STATE_CHANGED_FLAG
+                } else {
+                    0
+                };
+                (index | flag) << 40
+            }
+            InvokeResponse::Failure {
+                kind,
+            } => {
+                let code: u64 = match kind {
+                    v1::InvokeFailure::ContractReject {
+                        code,
+                        data,
+                    } => return (return_value_index(data) << 40) | u64::from(code as u32),
+                    v1::InvokeFailure::InsufficientAmount => 0x01,
+                    v1::InvokeFailure::NonExistentAccount => 0x02,
+                    v1::InvokeFailure::NonExistentContract => 0x03,
+                    v1::InvokeFailure::NonExistentEntrypoint => 0x04,
+                    v1::InvokeFailure::SendingV0Failed => 0x05,
+                    v1::InvokeFailure::RuntimeError => 0x06,
+                    v1::InvokeFailure::UpgradeInvalidModuleRef => 0x07,
+                    v1::InvokeFailure::UpgradeInvalidContractName => 0x08,
+                    v1::InvokeFailure::UpgradeInvalidVersion => 0x09,
+                    v1::InvokeFailure::SignatureDataMalformed => 0x0a,
+                    v1::InvokeFailure::SignatureCheckFailed => 0x0b,
+                };
+                code << 32
+            }
+        }
+    }
+
+    /// Handle an interrupt invoked by the contract, and return the response
+    /// together with whether the state of the contract was modified by it.
+    ///
+    /// The interrupt is handled by
+    /// [`EntrypointInvocationHandler::handle_interrupt`], as in Wasm. Calls to
+    /// other contracts are executed right away, since the contract cannot be
+    /// suspended.
+    fn handle_invoke(
+        &mut self,
+        interrupt: v1::Interrupt,
+    ) -> Result<(InvokeResponse, bool), TestConfigurationError> {
+        let Self {
+            handler,
+            context,
+            logs,
+            state,
+            trace_elements,
+            ..
+        } = self;
+        let (Some(handler), NativeContext::Receive(receive_ctx)) = (handler, context) else {
+            // Operations cannot be invoked by init functions.
+            return Ok((
+                InvokeResponse::Failure {
+                    kind: v1::InvokeFailure::RuntimeError,
+                },
+                false,
+            ));
+        };
+        let invoker = receive_ctx.common.invoker;
+        let address = receive_ctx.common.self_address;
+        let entrypoint = &receive_ctx.entrypoint;
+        // Save the state, so that it is seen by reentrant calls.
+        let mod_idx_before_invoke = if state.changed {
+            state.changed = false;
+            handler.save_state_changes(address, &mut state.state)
+        } else {
+            handler.modification_index(address)
+        };
+        // As in Wasm, the events logged so far are only included in the trace of
+        // transfers, calls and upgrades.
+        let logs = match interrupt {
+            v1::Interrupt::Transfer {
+                ..
+            }
+            | v1::Interrupt::Call {
+                ..
+            }
+            | v1::Interrupt::Upgrade {
+                ..
+            } => logs.drain(..).collect(),
+            _ => Vec::new(),
+        };
+        let outcome = handler.handle_interrupt(
+            invoker,
+            address,
+            entrypoint,
+            interrupt,
+            v0::Logs {
+                logs,
+            },
+            DebugTracker::empty_trace(),
+            trace_elements,
+        )?;
+        match outcome {
+            InterruptOutcome::Resume(response) => Ok((response, false)),
+            InterruptOutcome::Call {
+                sender,
+                payload,
+                trace_elements_checkpoint,
+            } => {
+                // The trace elements from the checkpoint, such as those of an injected
+                // reentrant call, are rolled back with the call if it fails.
+                let (response, call_trace) = handler.invoke_entrypoint_with_trace(
+                    invoker,
+                    sender,
+                    payload,
+                    std::mem::take(trace_elements),
+                    trace_elements_checkpoint,
+                )?;
+                *trace_elements = call_trace;
+                let (response, state_changed) = handler.resume_after_call(
+                    address,
+                    entrypoint,
+                    response,
+                    mod_idx_before_invoke,
+                    trace_elements,
+                );
+                if state_changed {
+                    state.replace(handler.contract_state(address));
+                }
+                Ok((response, state_changed))
+            }
+        }
+    }
+
+    /// Handle an interrupt invoked by the contract and encode the response.
+    fn interrupt(&mut self, interrupt: v1::Interrupt) -> u64 {
+        if let Some(error) = self.error.take() {
+            return self.fail(error);
+        }
+        match self.handle_invoke(interrupt) {
+            Ok((response, state_changed)) => self.encode_response(response, state_changed),
+            Err(error) => self.fail(error),
+        }
+    }
+
+    /// Fail an invoked operation after an error that stops the invocation.
+    fn fail(&mut self, error: TestConfigurationError) -> u64 {
+        self.error = Some(error);
+        self.encode_response(
+            InvokeResponse::Failure {
+                kind: v1::InvokeFailure::RuntimeError,
+            },
+            false,
+        )
+    }
+}
+
+impl<'h, 'a, 'b> NativeHost for NativeExecution<'h, 'a, 'b> {
+    fn invoke(&mut self, tag: u32, data: &[u8]) -> u64 {
+        match parse_interrupt(tag, data) {
+            Some(interrupt) => self.interrupt(interrupt),
+            // Malformed operations fail without stopping the invocation, since they are
+            // under the control of the contract.
+            None => self.encode_response(
+                InvokeResponse::Failure {
+                    kind: v1::InvokeFailure::RuntimeError,
+                },
+                false,
+            ),
+        }
+    }
+
+    fn write_output(&mut self, data: &[u8], offset: u32) -> u32 {
+        let end = offset as usize + data.len();
+        if self.return_value.len() < end {
+            self.return_value.resize(end, 0);
+        }
+        self.return_value[offset as usize..end].copy_from_slice(data);
+        data.len() as u32
+    }
+
+    fn upgrade(&mut self, module_ref: ModuleReference) -> u64 {
+        self.interrupt(v1::Interrupt::Upgrade {
+            module_ref,
+        })
+    }
+
+    fn get_parameter_size(&mut self, i: u32) -> i32 {
+        self.parameters.get(i as usize).map_or(-1, |parameter| parameter.len() as i32)
+    }
+
+    fn get_parameter_section(&mut self, i: u32, dest: &mut [u8], offset: u32) -> i32 {
+        match self.parameters.get(i as usize) {
+            Some(parameter) => copy_section(parameter, dest, offset) as i32,
+            None => -1,
+        }
+    }
+
+    fn get_policy_section(&mut self, dest: &mut [u8], offset: u32) -> u32 {
+        copy_section(self.common_context().1, dest, offset)
+    }
+
+    fn log_event(&mut self, event: &[u8]) -> i32 {
+        self.logs.push(event.to_vec());
+        1
+    }
+
+    fn state_lookup_entry(&mut self, key: &[u8]) -> u64 {
+        match self.state.get(key) {
+            Some(_) => self.state.new_entry(key),
+            None => u64::MAX,
+        }
+    }
+
+    fn state_create_entry(&mut self, key: &[u8]) -> u64 {
+        if self.state.get(key).is_none() {
+            self.state.set(key, &[]);
+        }
+        self.state.new_entry(key)
+    }
+
+    fn state_delete_entry(&mut self, key: &[u8]) -> u32 {
+        if self.state.delete(key) {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn state_delete_prefix(&mut self, prefix: &[u8]) -> u32 {
+        let keys = self.state.keys(prefix);
+        for key in &keys {
+            self.state.delete(key);
+        }
+        if keys.is_empty() {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn state_iterate_prefix(&mut self, prefix: &[u8]) -> u64 {
+        let keys = self.state.keys(prefix);
+        if keys.is_empty() {
+            return u64::MAX;
+        }
+        self.state.iterators.push(Some(NativeIterator {
+            keys:    keys.into(),
+            current: None,
+        }));
+        self.state.iterators.len() as u64 - 1
+    }
+
+    fn state_iterator_next(&mut self, iterator: u64) -> u64 {
+        let Some(Some(iterator)) = self.state.iterators.get_mut(iterator as usize) else {
+            return u64::MAX - 1;
+        };
+        match iterator.keys.pop_front() {
+            Some(key) => {
+                iterator.current = Some(key.clone());
+                self.state.new_entry(&key)
+            }
+            None => u64::MAX,
+        }
+    }
+
+    fn state_iterator_delete(&mut self, iterator: u64) -> u32 {
+        match self.state.iterators.get_mut(iterator as usize) {
+            Some(iterator @ Some(_)) => {
+                *iterator = None;
+                1
+            }
+            _ => u32::MAX,
+        }
+    }
+
+    fn state_iterator_key_size(&mut self, iterator: u64) -> u32 {
+        match self.state.iterator_key(iterator) {
+            Some(key) => key.len() as u32,
+            None => u32::MAX,
+        }
+    }
+
+    fn state_iterator_key_read(&mut self, iterator: u64, dest: &mut [u8], offset: u32) -> u32 {
+        match self.state.iterator_key(iterator) {
+            Some(key) => copy_section(key, dest, offset),
+            None => u32::MAX,
+        }
+    }
+
+    fn state_entry_read(&mut self, entry: u64, dest: &mut [u8], offset: u32) -> u32 {
+        match self.state.entry_value(entry) {
+            Some((_, value)) => copy_section(&value, dest, offset),
+            None => u32::MAX,
+        }
+    }
+
+    fn state_entry_write(&mut self, entry: u64, src: &[u8], offset: u32) -> u32 {
+        let Some((key, mut value)) = self.state.entry_value(entry) else {
+            return u32::MAX;
+        };
+        let end = offset as usize + src.len();
+        if value.len() < end {
+            value.resize(end, 0);
+        }
+        value[offset as usize..end].copy_from_slice(src);
+        self.state.set(&key, &value);
+        src.len() as u32
+    }
+
+    fn state_entry_size(&mut self, entry: u64) -> u32 {
+        match self.state.entry_value(entry) {
+            Some((_, value)) => value.len() as u32,
+            None => u32::MAX,
+        }
+    }
+
+    fn state_entry_resize(&mut self, entry: u64, new_size: u32) -> u32 {
+        let Some((key, mut value)) = self.state.entry_value(entry) else {
+            return u32::MAX;
+        };
+        value.resize(new_size as usize, 0);
+        self.state.set(&key, &value);
+        1
+    }
+
+    fn get_init_origin(&mut self) -> AccountAddress {
+        match &self.context {
+            NativeContext::Init(init_ctx) => init_ctx.init_origin,
+            NativeContext::Receive(_) => panic!("Not available in receive functions."),
+        }
+    }
+
+    fn get_receive_invoker(&mut self) -> AccountAddress { self.receive_context().common.invoker }
+
+    fn get_receive_self_address(&mut self) -> ContractAddress {
+        self.receive_context().common.self_address
+    }
+
+    fn get_receive_self_balance(&mut self) -> Amount {
+        // The balance changes when the contract transfers or calls, so it is looked up
+        // instead of taken from the context.
+        let address = self.receive_context().common.self_address;
+        self.handler
+            .as_ref()
+            .expect("Receive functions have a handler.")
+            .contract_balance_unchecked(address)
+    }
+
+    fn get_receive_sender(&mut self) -> Address { self.receive_context().common.sender }
+
+    fn get_receive_owner(&mut self) -> AccountAddress { self.receive_context().common.owner }
+
+    fn get_receive_entrypoint(&mut self) -> OwnedEntrypointName {
+        self.receive_context().entrypoint.clone()
+    }
+
+    fn get_slot_time(&mut self) -> Timestamp { self.common_context().0.slot_time }
+}
+
+/// Parse an operation invoked by the contract through
+/// [`concordium_std::prims::invoke`] into the corresponding interrupt. Returns
+/// `None` if the operation is unknown or its data is malformed.
+fn parse_interrupt(tag: u32, data: &[u8]) -> Option<v1::Interrupt> {
+    let interrupt = match tag {
+        0 => {
+            let (to, amount) = from_bytes(data).ok()?;
+            v1::Interrupt::Transfer {
+                to,
+                amount,
+            }
+        }
+        1 => {
+            let (address, parameter, name, amount): (
+                ContractAddress,
+                OwnedParameter,
+                OwnedEntrypointName,
+                Amount,
+            ) = from_bytes(data).ok()?;
+            v1::Interrupt::Call {
+                address,
+                parameter: parameter.as_ref().to_vec(),
+                name,
+                amount,
+            }
+        }
+        2 => v1::Interrupt::QueryAccountBalance {
+            address: from_bytes(data).ok()?,
+        },
+        3 => v1::Interrupt::QueryContractBalance {
+            address: from_bytes(data).ok()?,
+        },
+        4 => v1::Interrupt::QueryExchangeRates,
+        5 => {
+            let address = data.get(..32)?;
+            v1::Interrupt::CheckAccountSignature {
+                address: from_bytes(address).ok()?,
+                payload: data[32..].to_vec(),
+            }
+        }
+        6 => v1::Interrupt::QueryAccountKeys {
+            address: from_bytes(data).ok()?,
+        },
+        7 => v1::Interrupt::QueryContractModuleReference {
+            address: from_bytes(data).ok()?,
+        },
+        8 => v1::Interrupt::QueryContractName {
+            address: from_bytes(data).ok()?,
+        },
+        _ => return None,
+    };
+    Some(interrupt)
+}
+
+/// Copy the section of `source` starting at `offset` into `dest`, as much as
+/// fits. Returns the number of bytes copied.
+fn copy_section(source: &[u8], dest: &mut [u8], offset: u32) -> u32 {
+    let section = source.get(offset as usize..).unwrap_or_default();
+    let length = section.len().min(dest.len());
+    dest[..length].copy_from_slice(&section[..length]);
+    length as u32
+}
+
+/// An iterator handed out to a natively executed contract.
+struct NativeIterator {
+    /// The keys that have not been visited yet.
+    keys:    VecDeque<Vec<u8>>,
+    /// The key of the entry last returned.
+    current: Option<Vec<u8>>,
+}
+
+/// The state of a natively executed contract.
+///
+/// The entries and iterators handed out to the contract are tracked by key,
+/// and every access to the state goes through a fresh [`v1::InstanceState`].
+/// This allows replacing the state when it has been modified by a reentrant
+/// call, which invalidates the entries and iterators, as it does in Wasm.
+struct NativeState {
+    state:     trie::MutableState,
+    /// The keys of the entries handed out, indexed by the entry.
+    entries:   Vec<Vec<u8>>,
+    iterators: Vec<Option<NativeIterator>>,
+    /// Whether the state was modified since it was last saved.
+    changed:   bool,
+}
+
+impl NativeState {
+    fn new(state: trie::MutableState) -> Self {
+        Self {
+            state,
+            entries: Vec::new(),
+            iterators: Vec::new(),
+            changed: false,
+        }
+    }
+
+    /// Replace the state after it has been modified by a call.
+    fn replace(&mut self, state: trie::MutableState) {
+        self.state = state;
+        self.entries.clear();
+        self.iterators.clear();
+        self.changed = false;
+    }
+
+    fn new_entry(&mut self, key: &[u8]) -> u64 {
+        self.entries.push(key.to_vec());
+        self.entries.len() as u64 - 1
+    }
+
+    /// The key and the current value of an entry, if the entry is valid and
+    /// has not been deleted.
+    fn entry_value(&mut self, entry: u64) -> Option<(Vec<u8>, Vec<u8>)> {
+        let key = self.entries.get(entry as usize)?.clone();
+        let value = self.get(&key)?;
+        Some((key, value))
+    }
+
+    fn iterator_key(&self, iterator: u64) -> Option<&[u8]> {
+        self.iterators.get(iterator as usize)?.as_ref()?.current.as_deref()
+    }
+
+    fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let mut loader = trie::Loader::new(&[][..]);
+        let inner = self.state.get_inner(&mut loader);
+        let mut instance_state = v1::InstanceState::new(loader, inner);
+        let entry = u64::from(instance_state.lookup_entry(key));
+        if entry == u64::MAX {
+            return None;
+        }
+        let entry = v1::InstanceStateEntry::from(entry);
+        let mut value = vec![0; u32::from(instance_state.entry_size(entry)) as usize];
+        instance_state.entry_read(entry, &mut value, 0);
+        Some(value)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        let mut energy = InterpreterEnergy::new(STATE_ENERGY);
+        let mut loader = trie::Loader::new(&[][..]);
+        let inner = self.state.get_inner(&mut loader);
+        let mut instance_state = v1::InstanceState::new(loader, inner);
+        let entry = instance_state.create_entry(key).expect("The key is valid.");
+        let entry = v1::InstanceStateEntry::from(u64::from(entry));
+        instance_state
+            .entry_resize(&mut energy, entry, value.len() as u32)
+            .expect("Sufficient energy.");
+        instance_state.entry_write(&mut energy, entry, value, 0).expect("Sufficient energy.");
+        self.changed = true;
+    }
+
+    fn delete(&mut self, key: &[u8]) -> bool {
+        let mut loader = trie::Loader::new(&[][..]);
+        let inner = self.state.get_inner(&mut loader);
+        let mut instance_state = v1::InstanceState::new(loader, inner);
+        let deleted = instance_state.delete_entry(key).expect("The key is valid.") == 2;
+        self.changed |= deleted;
+        deleted
+    }
+
+    /// The keys of the entries with the given prefix.
+    fn keys(&mut self, prefix: &[u8]) -> Vec<Vec<u8>> {
+        let mut energy = InterpreterEnergy::new(STATE_ENERGY);
+        let mut loader = trie::Loader::new(&[][..]);
+        let inner = self.state.get_inner(&mut loader);
+        let mut instance_state = v1::InstanceState::new(loader, inner);
+        let iterator = u64::from(instance_state.iterator(prefix));
+        let mut keys = Vec::new();
+        if iterator == u64::MAX {
+            return keys;
+        }
+        let iterator = v1::InstanceStateIterator::from(iterator);
+        while u64::from(
+            instance_state.iterator_next(&mut energy, iterator).expect("Sufficient energy."),
+        ) != u64::MAX
+        {
+            let size = u32::from(instance_state.iterator_key_size(&mut energy, iterator));
+            let mut key = vec![0; size as usize];
+            instance_state.iterator_key_read(&mut energy, iterator, &mut key, 0);
+            keys.push(key);
+        }
+        keys
+    }
+}
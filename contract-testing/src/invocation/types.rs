@@ -1,4 +1,4 @@
-use crate::Chain;
+use crate::{Chain, OutgoingCall, ReentrantCall};
 use concordium_rust_sdk::{
     base::{
         base::{AccountAddressEq, Energy},
@@ -73,6 +73,22 @@ pub(super) enum Next {
     },
 }
 
+/// The outcome of handling an interrupt in
+/// `EntrypointInvocationHandler::handle_interrupt`.
+pub(super) enum InterruptOutcome {
+    /// The contract should be resumed immediately with the response.
+    Resume(InvokeResponse),
+    /// The contract called another contract. The call must be executed before
+    /// the contract is resumed.
+    Call {
+        sender:                    Address,
+        payload:                   UpdateContractPayload,
+        /// The length of the trace before the call, used for rolling back the
+        /// trace in case the call fails. See [`Next::Initial`].
+        trace_elements_checkpoint: usize,
+    },
+}
+
 /// The set of [`Changes`] represented as a stack.
 // For maintainers. It would be better if `Changes` had a form of copy-on-write.
 // At the moment we make a full clone of the changes when we need to checkpoint.
@@ -163,6 +179,40 @@ pub(super) struct InvocationData {
     pub(super) mod_idx_before_invoke:     u32,
 }
 
+/// Records the outgoing calls of a contract and injects a reentrant call at
+/// one of them. Used by [`Chain::explore_reentrancy`].
+#[derive(Debug)]
+pub(crate) struct ReentrancyProbe {
+    /// The contract whose outgoing calls are recorded.
+    pub(crate) target:                   ContractAddress,
+    /// The index of the outgoing call at which to inject the reentrant call,
+    /// and the call to inject.
+    pub(crate) attack:                   Option<(usize, ReentrantCall)>,
+    /// Whether the reentrant call is currently executing. Outgoing calls made
+    /// while attacking are not recorded.
+    pub(crate) attacking:                bool,
+    /// The outgoing calls made by the target so far.
+    pub(crate) outgoing_calls:           Vec<OutgoingCall>,
+    /// The outcome of the injected call, if it was made.
+    pub(crate) injection:                Option<ReentrancyInjection>,
+    /// The modification index of the target when the transaction finished.
+    pub(crate) final_modification_index: Option<u32>,
+}
+
+/// The outcome of a reentrant call injected by a [`ReentrancyProbe`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReentrancyInjection {
+    /// Whether the reentrant call succeeded.
+    pub(crate) succeeded:                 bool,
+    /// The modification index of the target before the reentrant call.
+    pub(crate) modification_index_before: u32,
+    /// The modification index of the target after the reentrant call.
+    pub(crate) modification_index_after:  u32,
+    /// Whether the target rejected a call made by the reentrant call with the
+    /// [`REENTRANCY_GUARD_REJECT_CODE`](crate::REENTRANCY_GUARD_REJECT_CODE).
+    pub(crate) blocked_by_guard:          bool,
+}
+
 /// A positive or negative delta in for an [`Amount`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(super) enum AmountDelta {
@@ -84,13 +84,35 @@
 //!     - initialization.transaction_fee
 //!     - update.transaction_fee));
 //! ```
+mod assertions;
+mod benchmark;
+mod call_graph;
+mod cis2_conformance;
 mod constants;
 mod impls;
 mod invocation;
+mod module_info;
+#[cfg(feature = "native")]
+mod native;
+mod scenario;
 mod types;
-pub use constants::CONTRACT_MODULE_OUTPUT_PATH_ENV_VAR;
-pub use impls::{is_debug_enabled, module_load_output, module_load_v1, module_load_v1_raw};
+mod upgrade_check;
+pub use assertions::*;
+pub use benchmark::*;
+pub use call_graph::*;
+pub use cis2_conformance::*;
+pub use constants::{
+    CONTRACT_MODULE_OUTPUT_PATH_ENV_VAR, MODULE_CACHE_DIR_ENV_VAR, REENTRANCY_GUARD_REJECT_CODE,
+};
+pub use impls::{
+    is_debug_enabled, module_cache, module_load_output, module_load_v1, module_load_v1_raw,
+};
+pub use module_info::*;
+#[cfg(feature = "native")]
+pub use native::*;
+pub use scenario::*;
 pub use types::*;
+pub use upgrade_check::*;
 
 // Re-export types.
 pub use concordium_rust_sdk::{
@@ -98,15 +120,18 @@ pub use concordium_rust_sdk::{
         base::Energy,
         common::types::{CredentialIndex, KeyIndex},
         contracts_common::{
-            from_bytes, to_bytes, AccountAddress, AccountBalance, AccountThreshold, Address,
-            Amount, ContractAddress, ContractName, Duration, EntrypointName, ExchangeRate,
-            ModuleReference, OwnedContractName, OwnedEntrypointName, OwnedParameter,
-            OwnedReceiveName, Parameter, ReceiveName, SignatureThreshold, SlotTime, Timestamp,
+            attributes, from_bytes, to_bytes, AccountAddress, AccountBalance, AccountThreshold,
+            Address, Amount, AttributeTag, AttributeValue, ContractAddress, ContractName, Duration,
+            EntrypointName, ExchangeRate, ModuleReference, OwnedContractName, OwnedEntrypointName,
+            OwnedParameter, OwnedPolicy, OwnedReceiveName, Parameter, ReceiveName,
+            SignatureThreshold, SlotTime, Timestamp,
         },
         ed25519,
         hashes::BlockHash,
         id::types::{AccountKeys, CredentialPublicKeys, VerifyKey},
-        smart_contracts::{ContractEvent, ContractTraceElement, InstanceUpdatedEvent, WasmVersion},
+        smart_contracts::{
+            ContractEvent, ContractTraceElement, InstanceUpdatedEvent, WasmModule, WasmVersion,
+        },
         transactions::{AccountAccessStructure, InitContractPayload, UpdateContractPayload},
     },
     smart_contracts::engine::v1::InvokeFailure,
@@ -0,0 +1,239 @@
+//! Inspection of smart contract modules, e.g., to check that a release build
+//! exposes exactly the intended entrypoints.
+use crate::types::ContractModule;
+use concordium_rust_sdk::{
+    base::{
+        contracts_common::{schema::VersionedModuleSchema, OwnedContractName, OwnedEntrypointName},
+        smart_contracts::{WasmModule, WasmVersion},
+    },
+    smart_contracts::engine::{
+        utils::get_embedded_schema_v1,
+        wasm::{
+            parse::{parse_custom, parse_sec_with_default, parse_skeleton, EMPTY_CTX},
+            types::{ExportDescription, ExportSection, ImportSection, Name},
+        },
+    },
+};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// The name of the custom section with the build information added by
+/// `cargo-concordium build --verifiable`.
+pub const BUILD_INFO_SECTION: &str = "concordium-build-info";
+
+/// Information about a smart contract module, created with
+/// [`ModuleInfo::from_wasm_module`].
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// let module = module_load_v1("counter.wasm.v1").unwrap();
+/// let info = ModuleInfo::from_wasm_module(&module).unwrap();
+/// let counter = &info.contracts[&OwnedContractName::new_unchecked("init_counter".into())];
+/// assert_eq!(
+///     counter.entrypoints,
+///     [OwnedEntrypointName::new_unchecked("increment".into())].into_iter().collect()
+/// );
+/// assert!(!counter.has_fallback);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ModuleInfo {
+    /// The version of the module.
+    pub version:         WasmVersion,
+    /// The contracts in the module, indexed by the names of their init
+    /// functions, e.g., `init_counter`.
+    pub contracts:       BTreeMap<OwnedContractName, ContractInfo>,
+    /// The functions imported from the host, in the order they are imported.
+    pub imports:         Vec<ImportInfo>,
+    /// The sections of the module, in the order they appear.
+    pub sections:        Vec<SectionInfo>,
+    /// The contents of the custom sections, indexed by their names.
+    pub custom_sections: BTreeMap<String, Vec<u8>>,
+    /// The embedded schema, if any. The variant is the version of the schema.
+    pub schema:          Option<VersionedModuleSchema>,
+}
+
+/// The entrypoints of a contract in a module.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractInfo {
+    /// The receive functions, excluding the fallback entrypoint.
+    pub entrypoints:  BTreeSet<OwnedEntrypointName>,
+    /// Whether the contract has a fallback entrypoint.
+    pub has_fallback: bool,
+}
+
+/// A function imported by a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportInfo {
+    /// The name of the module the function is imported from, which is
+    /// `concordium` for host functions.
+    pub module: String,
+    /// The name of the function.
+    pub name:   String,
+}
+
+/// A section of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionInfo {
+    /// The id of the section. Custom sections have id 0.
+    pub id:   u8,
+    /// The name of the section, e.g., `code`, or the name of a custom section.
+    pub name: String,
+    /// The size of the contents of the section in bytes.
+    pub size: u64,
+}
+
+/// The module could not be inspected, since it is not a valid Wasm module.
+#[derive(Debug, Error)]
+#[error("The module is malformed: {0}")]
+pub struct MalformedModule(#[from] pub(crate) anyhow::Error);
+
+impl ModuleInfo {
+    /// Inspect a module, e.g., one loaded with
+    /// [`module_load_v1`](crate::module_load_v1).
+    pub fn from_wasm_module(module: &WasmModule) -> Result<Self, MalformedModule> {
+        let source: &[u8] = module.source.as_ref();
+        let skeleton = parse_skeleton(source)?;
+        let imports: ImportSection = parse_sec_with_default(EMPTY_CTX, &skeleton.import)?;
+        let exports: ExportSection = parse_sec_with_default(EMPTY_CTX, &skeleton.export)?;
+        let mut custom_sections = BTreeMap::new();
+        let mut custom_names = Vec::new();
+        for section in skeleton.custom.iter() {
+            let section = parse_custom(section)?;
+            let name = name_to_string(&section.name);
+            custom_sections.insert(name.clone(), section.contents.to_vec());
+            custom_names.push(name);
+        }
+
+        // Custom sections are listed by the skeleton in the order they appear.
+        let mut custom_names = custom_names.into_iter();
+        let sections = section_headers(source)
+            .into_iter()
+            .map(|(id, size)| SectionInfo {
+                id,
+                name: match id {
+                    0 => custom_names.next().unwrap_or_default(),
+                    _ => SECTION_NAMES.get(usize::from(id)).copied().unwrap_or("unknown").into(),
+                },
+                size,
+            })
+            .collect();
+
+        let function_exports = exports
+            .exports
+            .iter()
+            .filter(|export| matches!(export.description, ExportDescription::Func { .. }))
+            .map(|export| name_to_string(&export.name))
+            .collect::<Vec<_>>();
+        Ok(Self {
+            version: module.version,
+            contracts: contracts_from_exports(function_exports.iter().map(String::as_str)),
+            // Concordium modules can only import functions.
+            imports: imports
+                .imports
+                .iter()
+                .map(|import| ImportInfo {
+                    module: name_to_string(&import.mod_name),
+                    name:   name_to_string(&import.item_name),
+                })
+                .collect(),
+            sections,
+            custom_sections,
+            schema: get_embedded_schema_v1(source).ok(),
+        })
+    }
+
+    /// The build information added by `cargo-concordium build --verifiable`, in
+    /// its serialized form.
+    pub fn build_info(&self) -> Option<&[u8]> {
+        self.custom_sections.get(BUILD_INFO_SECTION).map(Vec::as_slice)
+    }
+}
+
+impl ContractModule {
+    /// The contracts in the deployed module, indexed by the names of their
+    /// init functions.
+    ///
+    /// Only the entrypoints are available for a deployed module. Use
+    /// [`ModuleInfo::from_wasm_module`] on the module before deployment to
+    /// also get the imports, sections and schema.
+    pub fn contracts(&self) -> BTreeMap<OwnedContractName, ContractInfo> {
+        contracts_from_exports(self.artifact.export.keys().map(|name| {
+            let name: &str = name.as_ref();
+            name
+        }))
+    }
+}
+
+/// The names of the sections, indexed by their ids.
+const SECTION_NAMES: [&str; 13] = [
+    "custom",
+    "type",
+    "import",
+    "function",
+    "table",
+    "memory",
+    "global",
+    "export",
+    "start",
+    "element",
+    "code",
+    "data",
+    "datacount",
+];
+
+/// Group the names of exported functions into contracts. Receive functions
+/// without a corresponding init function cannot be called and are left out.
+fn contracts_from_exports<'a>(
+    names: impl Iterator<Item = &'a str> + Clone,
+) -> BTreeMap<OwnedContractName, ContractInfo> {
+    let mut contracts: BTreeMap<OwnedContractName, ContractInfo> = names
+        .clone()
+        .filter(|name| name.starts_with("init_") && !name.contains('.'))
+        .map(|name| (OwnedContractName::new_unchecked(name.into()), ContractInfo::default()))
+        .collect();
+    for name in names {
+        let Some((contract, entrypoint)) = name.split_once('.') else {
+            continue;
+        };
+        let init_name = OwnedContractName::new_unchecked(format!("init_{contract}"));
+        let Some(info) = contracts.get_mut(&init_name) else {
+            continue;
+        };
+        if entrypoint.is_empty() {
+            info.has_fallback = true;
+        } else {
+            info.entrypoints.insert(OwnedEntrypointName::new_unchecked(entrypoint.into()));
+        }
+    }
+    contracts
+}
+
+/// Convert a name in a Wasm module to a string.
+fn name_to_string(name: &Name) -> String {
+    let name: &str = name.as_ref();
+    name.into()
+}
+
+/// The ids and sizes of the sections of a module, in the order they appear.
+/// The module must already have been parsed with [`parse_skeleton`], which
+/// checks that it is well-formed, but does not expose the section sizes.
+fn section_headers(source: &[u8]) -> Vec<(u8, u64)> {
+    let mut headers = Vec::new();
+    // Skip the magic number and the version.
+    let mut rest = source.get(8..).unwrap_or_default();
+    while let Some((&id, after_id)) = rest.split_first() {
+        // The size is an unsigned LEB128 encoded integer.
+        let mut size = 0u64;
+        let mut len = 0;
+        for (i, byte) in after_id.iter().enumerate() {
+            size |= u64::from(byte & 0x7f) << (7 * i);
+            len = i + 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        headers.push((id, size));
+        rest = after_id.get(len + size as usize..).unwrap_or_default();
+    }
+    headers
+}
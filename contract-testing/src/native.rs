@@ -0,0 +1,142 @@
+//! Types for executing contracts natively instead of in the Wasm interpreter.
+//! The execution itself is implemented in the `invocation` module.
+use crate::ModuleDoesNotExist;
+use concordium_rust_sdk::base::contracts_common::{Amount, ModuleReference};
+use concordium_std::native::{with_native_host, NativeHost, Trapped, Unwind};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// An entrypoint of a contract compiled natively.
+///
+/// The function returns `0` on success and a negative reject reason
+/// otherwise.
+#[derive(Debug, Clone, Copy)]
+pub enum NativeEntrypoint {
+    /// An entrypoint with the `"C"` ABI. These are the functions generated by
+    /// `#[init]` and `#[receive]` in `concordium-std`, named
+    /// `export_<function name>`.
+    C(extern "C" fn(Amount) -> i32),
+    /// An entrypoint with the `"C-unwind"` ABI, which a trap can unwind out
+    /// of.
+    CUnwind(extern "C-unwind" fn(Amount) -> i32),
+}
+
+impl NativeEntrypoint {
+    /// Execute the entrypoint with the host functions handled by `host`, see
+    /// [`with_native_host`].
+    pub(crate) fn run(self, host: &mut dyn NativeHost, amount: Amount) -> Result<i32, Trapped> {
+        match self {
+            NativeEntrypoint::C(entrypoint) => {
+                with_native_host(host, Unwind::Forbidden, move || entrypoint(amount))
+            }
+            NativeEntrypoint::CUnwind(entrypoint) => {
+                with_native_host(host, Unwind::Allowed, move || entrypoint(amount))
+            }
+        }
+    }
+}
+
+/// The entrypoints of a module that are executed natively.
+///
+/// A contract crate that depends on `concordium-std` with the `native` feature
+/// can be linked into a test binary. The entrypoints generated by `#[init]`
+/// and `#[receive]` are then ordinary functions of the test binary, which can
+/// be registered for a deployed module with
+/// [`Chain::module_register_native`](crate::Chain::module_register_native).
+/// Afterwards, the chain executes the registered entrypoints natively, which
+/// makes it possible to set breakpoints in contract code and step through it
+/// with a debugger such as `gdb` or `lldb`.
+///
+/// The Wasm module must still be deployed, and it should be built from the
+/// same source, since it determines the entrypoints that exist, and the
+/// entrypoints that are not registered are executed in Wasm.
+///
+/// Native execution differs from Wasm execution in the following ways:
+///  - The execution of the contract code itself is not metered. The fixed costs
+///    of the update and the costs of the operations done by the chain, such as
+///    transfers and queries, are still charged.
+///  - Each entrypoint is executed on a separate thread. A trap, such as a panic
+///    in the contract, fails the execution with a runtime error as in Wasm. The
+///    thread then exits if the entrypoint was registered with
+///    [`init_unwind`](Self::init_unwind) or
+///    [`receive_unwind`](Self::receive_unwind). Otherwise, it is blocked
+///    forever, since a panic cannot unwind out of an entrypoint with the `"C"`
+///    ABI. See [`with_native_host`].
+///  - Iterators over the state do not lock the part of the state they iterate
+///    over.
+#[derive(Debug, Clone, Default)]
+pub struct NativeModule {
+    /// The entrypoints, indexed by their name in the Wasm module, e.g.,
+    /// `init_counter` and `counter.increment`.
+    pub(crate) entrypoints: BTreeMap<String, NativeEntrypoint>,
+}
+
+impl NativeModule {
+    /// Create a module without any native entrypoints.
+    pub fn new() -> Self { Self::default() }
+
+    /// Add an init function. The `name` is the name of the function in the
+    /// Wasm module, e.g., `init_counter`.
+    pub fn init(
+        mut self,
+        name: impl Into<String>,
+        entrypoint: extern "C" fn(Amount) -> i32,
+    ) -> Self {
+        self.entrypoints.insert(name.into(), NativeEntrypoint::C(entrypoint));
+        self
+    }
+
+    /// Add an init function with the `"C-unwind"` ABI, see
+    /// [`init`](Self::init).
+    pub fn init_unwind(
+        mut self,
+        name: impl Into<String>,
+        entrypoint: extern "C-unwind" fn(Amount) -> i32,
+    ) -> Self {
+        self.entrypoints.insert(name.into(), NativeEntrypoint::CUnwind(entrypoint));
+        self
+    }
+
+    /// Add a receive function. The `name` is the name of the function in the
+    /// Wasm module, e.g., `counter.increment`, or `counter.` for a fallback
+    /// entrypoint.
+    pub fn receive(
+        mut self,
+        name: impl Into<String>,
+        entrypoint: extern "C" fn(Amount) -> i32,
+    ) -> Self {
+        self.entrypoints.insert(name.into(), NativeEntrypoint::C(entrypoint));
+        self
+    }
+
+    /// Add a receive function with the `"C-unwind"` ABI, see
+    /// [`receive`](Self::receive).
+    pub fn receive_unwind(
+        mut self,
+        name: impl Into<String>,
+        entrypoint: extern "C-unwind" fn(Amount) -> i32,
+    ) -> Self {
+        self.entrypoints.insert(name.into(), NativeEntrypoint::CUnwind(entrypoint));
+        self
+    }
+
+    /// Look up an entrypoint by its name in the Wasm module.
+    pub(crate) fn entrypoint(&self, name: &str) -> Option<NativeEntrypoint> {
+        self.entrypoints.get(name).copied()
+    }
+}
+
+/// An error that occurred in
+/// [`Chain::module_register_native`](crate::Chain::module_register_native).
+#[derive(Debug, Error)]
+pub enum NativeModuleError {
+    /// The module has not been deployed.
+    #[error("{0}")]
+    ModuleDoesNotExist(#[from] ModuleDoesNotExist),
+    /// An entrypoint does not exist in the deployed module.
+    #[error("The entrypoint '{name}' does not exist in the module.")]
+    NotExported {
+        /// The name of the entrypoint.
+        name: String,
+    },
+}
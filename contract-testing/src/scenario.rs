@@ -0,0 +1,779 @@
+//! Declarative test scenarios, which describe the accounts, modules and
+//! transactions of a test, along with the expected outcomes, in JSON.
+use crate::{
+    impls::{module_load_v1, module_load_v1_raw},
+    types::*,
+};
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{
+            schema::{Type, VersionedModuleSchema},
+            AccountAddress, Address, Amount, ContractAddress, Duration, ModuleReference,
+            OwnedContractName, OwnedParameter, OwnedReceiveName,
+        },
+        smart_contracts::ContractEvent,
+        transactions::{InitContractPayload, UpdateContractPayload},
+    },
+    smart_contracts::engine::utils::get_embedded_schema_v1,
+};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// A test scenario, which lists the accounts to create, the modules to deploy,
+/// and a sequence of steps with their expected outcomes.
+///
+/// Scenarios are written in JSON, so that tests can be contributed without
+/// writing Rust. They are run against a new [`Chain`] with [`Scenario::run`],
+/// [`run_scenario_file`], or the `contract-testing` binary, which takes the
+/// paths of scenario files as arguments.
+///
+/// Parameters, return values, events, and errors are given as JSON, which is
+/// converted with the schemas embedded in the modules. Amounts are given in
+/// CCD as strings, e.g., `"10.5"`, and durations as strings such as `"1d 2h"`.
+/// A string of the form `"$name"` in a parameter, return value, event, or
+/// error is replaced by the address of the account or contract with that name.
+///
+/// ```json
+/// {
+///   "accounts": [{ "name": "alice", "balance": "1000" }],
+///   "modules": [{ "name": "counter", "path": "counter.wasm.v1" }],
+///   "steps": [
+///     { "init": { "name": "c", "sender": "alice", "module": "counter", "contract": "counter" } },
+///     { "update": {
+///         "sender": "alice", "contract": "c", "entrypoint": "increment", "parameter": 5,
+///         "expect": { "events": [{ "Incremented": [5] }] }
+///     } },
+///     { "tick": "1h" },
+///     { "update": {
+///         "sender": "alice", "contract": "c", "entrypoint": "increment", "parameter": -1,
+///         "expect": { "error": { "NegativeIncrement": [] } }
+///     } },
+///     { "check_balances": { "c": "0" } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// The accounts to create.
+    #[serde(default)]
+    pub accounts: Vec<ScenarioAccount>,
+    /// The modules to deploy.
+    #[serde(default)]
+    pub modules:  Vec<ScenarioModule>,
+    /// The steps, which are run in order.
+    pub steps:    Vec<ScenarioStep>,
+}
+
+/// An account in a [`Scenario`].
+///
+/// The address and keys are derived from the name with
+/// [`TestAccount::from_seed`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAccount {
+    /// The name used to refer to the account.
+    pub name:    String,
+    /// The initial total balance.
+    #[serde(deserialize_with = "deserialize_ccd")]
+    pub balance: Amount,
+}
+
+/// A module in a [`Scenario`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioModule {
+    /// The name used to refer to the module.
+    pub name:   String,
+    /// The path to the module, relative to the scenario file. Files ending in
+    /// `.wasm` are loaded with [`module_load_v1_raw`], and other files, such as
+    /// the `.wasm.v1` files built by `cargo-concordium`, with
+    /// [`module_load_v1`].
+    pub path:   PathBuf,
+    /// The account deploying the module. Defaults to the first account.
+    #[serde(default)]
+    pub sender: Option<String>,
+}
+
+/// A step in a [`Scenario`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScenarioStep {
+    /// Initialize a contract.
+    Init(InitStep),
+    /// Update a contract.
+    Update(UpdateStep),
+    /// Advance the block time with [`Chain::tick_block_time`].
+    Tick(#[serde(deserialize_with = "deserialize_duration")] Duration),
+    /// Check the total balances of accounts and contracts, given by name.
+    /// Account balances are reduced by the fees for the transactions the
+    /// account sends, including module deployments, so they are only
+    /// predictable for accounts which send no transactions.
+    CheckBalances(#[serde(deserialize_with = "deserialize_balances")] BTreeMap<String, Amount>),
+}
+
+/// A contract initialization in a [`Scenario`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InitStep {
+    /// The name used to refer to the new contract instance.
+    pub name:      String,
+    /// The account sending the transaction.
+    pub sender:    String,
+    /// The name of the module.
+    pub module:    String,
+    /// The name of the contract, without the `init_` prefix.
+    pub contract:  String,
+    /// The parameter, which is encoded with the schema of the module.
+    #[serde(default)]
+    pub parameter: Option<Value>,
+    /// The amount to send. Defaults to zero.
+    #[serde(default, deserialize_with = "deserialize_ccd")]
+    pub amount:    Amount,
+    /// The energy to reserve.
+    #[serde(default = "default_energy")]
+    pub energy:    u64,
+    /// The expected outcome. Defaults to success.
+    #[serde(default)]
+    pub expect:    Expectation,
+}
+
+/// A contract update in a [`Scenario`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateStep {
+    /// The account sending the transaction.
+    pub sender:     String,
+    /// The name of the contract instance.
+    pub contract:   String,
+    /// The name of the entrypoint.
+    pub entrypoint: String,
+    /// The parameter, which is encoded with the schema of the module.
+    #[serde(default)]
+    pub parameter:  Option<Value>,
+    /// The amount to send. Defaults to zero.
+    #[serde(default, deserialize_with = "deserialize_ccd")]
+    pub amount:     Amount,
+    /// The energy to reserve.
+    #[serde(default = "default_energy")]
+    pub energy:     u64,
+    /// The expected outcome. Defaults to success.
+    #[serde(default)]
+    pub expect:     Expectation,
+}
+
+/// The expected outcome of an [`InitStep`] or [`UpdateStep`].
+///
+/// The transaction is expected to be rejected if `reject` is set, or if a
+/// `reject_code` or an `error` is given. Otherwise it is expected to succeed.
+/// Only the given fields are checked.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    /// Whether the contract rejects.
+    #[serde(default)]
+    pub reject:       bool,
+    /// The code the contract rejects with.
+    #[serde(default)]
+    pub reject_code:  Option<i32>,
+    /// The error the contract rejects with, which is decoded with the error
+    /// schema.
+    #[serde(default)]
+    pub error:        Option<Value>,
+    /// The return value of an update, which is decoded with the return value
+    /// schema.
+    #[serde(default)]
+    pub return_value: Option<Value>,
+    /// The events logged by the initialized or updated contract, in order,
+    /// which are decoded with the event schema. Events logged by other
+    /// contracts are not included.
+    #[serde(default)]
+    pub events:       Option<Vec<Value>>,
+}
+
+impl Expectation {
+    fn expects_reject(&self) -> bool {
+        self.reject || self.reject_code.is_some() || self.error.is_some()
+    }
+}
+
+/// An error that occurred while running a [`Scenario`].
+#[derive(Debug, Error)]
+pub enum ScenarioError {
+    /// The scenario file could not be read.
+    #[error("Could not read the scenario file {path:?}: {error}")]
+    Read {
+        /// The path of the file.
+        path:  PathBuf,
+        /// The underlying error.
+        error: std::io::Error,
+    },
+    /// The scenario is not valid JSON or does not have the expected format.
+    #[error("Could not parse the scenario: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// A module could not be loaded.
+    #[error("Could not load the module '{name}': {error}")]
+    ModuleLoad {
+        /// The name of the module.
+        name:  String,
+        /// The underlying error.
+        error: ModuleLoadError,
+    },
+    /// The sender of a module is not a known account.
+    #[error("The module '{name}' is invalid: {message}")]
+    InvalidModule {
+        /// The name of the module.
+        name:    String,
+        /// A description of the problem.
+        message: String,
+    },
+    /// A module could not be deployed.
+    #[error("Could not deploy the module '{name}': {error}")]
+    ModuleDeploy {
+        /// The name of the module.
+        name:  String,
+        /// The underlying error.
+        error: ModuleDeployError,
+    },
+    /// A step could not be run, e.g., because it refers to an unknown account
+    /// or its parameter does not match the schema.
+    #[error("Step {step} is invalid: {message}")]
+    InvalidStep {
+        /// The position of the step, starting from 1.
+        step:    usize,
+        /// A description of the problem.
+        message: String,
+    },
+    /// The outcome of a step was not as expected.
+    #[error("Step {step} did not have the expected outcome:\n{message}")]
+    ExpectationFailed {
+        /// The position of the step, starting from 1.
+        step:    usize,
+        /// A description of the differences.
+        message: String,
+    },
+}
+
+/// Read a [`Scenario`] from a JSON file and run it with the directory of the
+/// file as the base directory for module paths.
+pub fn run_scenario_file(path: impl AsRef<Path>) -> Result<Chain, ScenarioError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|error| ScenarioError::Read {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let scenario = Scenario::from_json(&contents)?;
+    scenario.run(path.parent().unwrap_or_else(|| Path::new(".")))
+}
+
+impl Scenario {
+    /// Parse a scenario from JSON.
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> { Ok(serde_json::from_str(json)?) }
+
+    /// Run the scenario on a new [`Chain`], and return the chain after the last
+    /// step, so that it can be inspected further.
+    ///
+    /// Module paths are relative to `base_dir`. The scenario stops at the
+    /// first step that is invalid or does not have the expected outcome.
+    pub fn run(&self, base_dir: impl AsRef<Path>) -> Result<Chain, ScenarioError> {
+        let mut runner = Runner {
+            chain:     Chain::new(),
+            accounts:  BTreeMap::new(),
+            modules:   BTreeMap::new(),
+            contracts: BTreeMap::new(),
+        };
+        for account in &self.accounts {
+            let test_account = TestAccount::from_seed(&account.name);
+            runner.chain.create_account(test_account.account(account.balance));
+            runner.accounts.insert(account.name.clone(), test_account.address());
+        }
+        for module in &self.modules {
+            runner.deploy(base_dir.as_ref(), module, self.accounts.first())?;
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            runner.run_step(step).map_err(|failure| match failure {
+                StepFailure::Invalid(message) => ScenarioError::InvalidStep {
+                    step: index + 1,
+                    message,
+                },
+                StepFailure::Expectation(message) => ScenarioError::ExpectationFailed {
+                    step: index + 1,
+                    message,
+                },
+            })?;
+        }
+        Ok(runner.chain)
+    }
+}
+
+/// The reason a step did not succeed.
+enum StepFailure {
+    Invalid(String),
+    Expectation(String),
+}
+
+/// A module deployed by a [`Runner`].
+struct DeployedModule {
+    reference: ModuleReference,
+    schema:    Option<VersionedModuleSchema>,
+}
+
+/// A contract initialized by a [`Runner`].
+struct InitializedContract {
+    address:  ContractAddress,
+    /// The name of the module the contract was initialized from. Its schema is
+    /// used for the contract, also after upgrades.
+    module:   String,
+    /// The name of the contract, without the `init_` prefix.
+    contract: String,
+}
+
+/// The state of a running [`Scenario`].
+struct Runner {
+    chain:     Chain,
+    accounts:  BTreeMap<String, AccountAddress>,
+    modules:   BTreeMap<String, DeployedModule>,
+    contracts: BTreeMap<String, InitializedContract>,
+}
+
+impl Runner {
+    fn deploy(
+        &mut self,
+        base_dir: &Path,
+        module: &ScenarioModule,
+        first_account: Option<&ScenarioAccount>,
+    ) -> Result<(), ScenarioError> {
+        let path = base_dir.join(&module.path);
+        let wasm_module = if path.extension().map_or(false, |ext| ext == "wasm") {
+            module_load_v1_raw(&path)
+        } else {
+            module_load_v1(&path)
+        }
+        .map_err(|error| ScenarioError::ModuleLoad {
+            name: module.name.clone(),
+            error,
+        })?;
+        // Modules without an embedded schema can still be used with steps that
+        // have no parameters or expected values.
+        let schema = get_embedded_schema_v1(wasm_module.source.as_ref()).ok();
+        let sender_name = module.sender.as_ref().or(first_account.map(|account| &account.name));
+        let sender = match sender_name {
+            Some(name) => {
+                self.accounts.get(name).copied().ok_or_else(|| ScenarioError::InvalidModule {
+                    name:    module.name.clone(),
+                    message: format!("There is no account named '{name}'."),
+                })?
+            }
+            None => {
+                return Err(ScenarioError::InvalidModule {
+                    name:    module.name.clone(),
+                    message: "The module has no sender, and there are no accounts.".into(),
+                })
+            }
+        };
+        let res = self
+            .chain
+            .module_deploy_v1(Signer::with_one_key(), sender, wasm_module)
+            .map_err(|error| ScenarioError::ModuleDeploy {
+                name: module.name.clone(),
+                error,
+            })?;
+        self.modules.insert(module.name.clone(), DeployedModule {
+            reference: res.module_reference,
+            schema,
+        });
+        Ok(())
+    }
+
+    fn run_step(&mut self, step: &ScenarioStep) -> Result<(), StepFailure> {
+        match step {
+            ScenarioStep::Init(step) => self.run_init(step),
+            ScenarioStep::Update(step) => self.run_update(step),
+            ScenarioStep::Tick(duration) => self
+                .chain
+                .tick_block_time(*duration)
+                .map_err(|err| StepFailure::Invalid(err.to_string())),
+            ScenarioStep::CheckBalances(balances) => {
+                let mut differences = Vec::new();
+                for (name, expected) in balances {
+                    let actual = match self.resolve(name)? {
+                        Address::Account(address) => {
+                            self.chain.account_balance(address).map(|balance| balance.total)
+                        }
+                        Address::Contract(address) => self.chain.contract_balance(address),
+                    };
+                    if actual != Some(*expected) {
+                        differences.push(format!(
+                            "Expected the balance of '{name}' to be {expected}, but it was {}.",
+                            actual.map_or_else(|| "missing".into(), |amount| amount.to_string())
+                        ));
+                    }
+                }
+                check(differences)
+            }
+        }
+    }
+
+    fn run_init(&mut self, step: &InitStep) -> Result<(), StepFailure> {
+        if step.expect.return_value.is_some() {
+            return Err(StepFailure::Invalid("Initializations do not have a return value.".into()));
+        }
+        if self.contracts.contains_key(&step.name) || self.accounts.contains_key(&step.name) {
+            return Err(StepFailure::Invalid(format!("The name '{}' is already used.", step.name)));
+        }
+        let sender = self.account(&step.sender)?;
+        let module_reference = self.module(&step.module)?.reference;
+        let param = match &step.parameter {
+            Some(value) => self.encode(
+                &step.module,
+                |schema| schema.get_init_param_schema(&step.contract),
+                value,
+                "parameter",
+            )?,
+            None => Vec::new(),
+        };
+        let res = self.chain.contract_init(
+            Signer::with_one_key(),
+            sender,
+            Energy::from(step.energy),
+            InitContractPayload {
+                mod_ref:   module_reference,
+                init_name: OwnedContractName::new(format!("init_{}", step.contract))
+                    .map_err(|err| StepFailure::Invalid(err.to_string()))?,
+                param:     parameter(param)?,
+                amount:    step.amount,
+            },
+        );
+        let mut differences = Vec::new();
+        match (res, step.expect.expects_reject()) {
+            (Ok(success), false) => {
+                self.contracts.insert(step.name.clone(), InitializedContract {
+                    address:  success.contract_address,
+                    module:   step.module.clone(),
+                    contract: step.contract.clone(),
+                });
+                if let Some(expected) = &step.expect.events {
+                    self.check_events(
+                        &step.module,
+                        &step.contract,
+                        expected,
+                        &success.events,
+                        &mut differences,
+                    )?;
+                }
+            }
+            (Ok(success), true) => {
+                differences.push(format!(
+                    "Expected the initialization to be rejected, but it succeeded with address {}.",
+                    success.contract_address
+                ));
+            }
+            (Err(error), expects_reject) => match error.kind {
+                ContractInitErrorKind::ExecutionError {
+                    error:
+                        InitExecutionError::Reject {
+                            reason,
+                            return_value,
+                        },
+                    ..
+                } if expects_reject => {
+                    self.check_reject(
+                        &step.module,
+                        |schema| schema.get_init_error_schema(&step.contract),
+                        &step.expect,
+                        reason,
+                        &return_value,
+                        &mut differences,
+                    )?;
+                }
+                kind => differences.push(format!("The initialization failed: {kind}")),
+            },
+        }
+        check(differences)
+    }
+
+    fn run_update(&mut self, step: &UpdateStep) -> Result<(), StepFailure> {
+        let sender = self.account(&step.sender)?;
+        let contract = self.contracts.get(&step.contract).ok_or_else(|| {
+            StepFailure::Invalid(format!("There is no contract named '{}'.", step.contract))
+        })?;
+        let (address, module, contract_name) =
+            (contract.address, contract.module.clone(), contract.contract.clone());
+        let param = match &step.parameter {
+            Some(value) => self.encode(
+                &module,
+                |schema| schema.get_receive_param_schema(&contract_name, &step.entrypoint),
+                value,
+                "parameter",
+            )?,
+            None => Vec::new(),
+        };
+        let res = self.chain.contract_update(
+            Signer::with_one_key(),
+            sender,
+            Address::Account(sender),
+            Energy::from(step.energy),
+            UpdateContractPayload {
+                address,
+                receive_name: OwnedReceiveName::new(format!("{contract_name}.{}", step.entrypoint))
+                    .map_err(|err| StepFailure::Invalid(err.to_string()))?,
+                message: parameter(param)?,
+                amount: step.amount,
+            },
+        );
+        let mut differences = Vec::new();
+        match (res, step.expect.expects_reject()) {
+            (Ok(success), false) => {
+                if let Some(expected) = &step.expect.return_value {
+                    let ty = self.schema_type(
+                        &module,
+                        |schema| {
+                            schema.get_receive_return_value_schema(&contract_name, &step.entrypoint)
+                        },
+                        "return value",
+                    )?;
+                    let expected_bytes = self.encode_with(&ty, expected, "return value")?;
+                    if expected_bytes != success.return_value {
+                        differences.push(format!(
+                            "Expected the return value\n{expected:#}\nbut it was\n{}",
+                            display(&ty, &success.return_value)
+                        ));
+                    }
+                }
+                if let Some(expected) = &step.expect.events {
+                    let events: Vec<ContractEvent> = success
+                        .events()
+                        .filter(|(event_address, _)| *event_address == address)
+                        .flat_map(|(_, events)| events.iter().cloned())
+                        .collect();
+                    self.check_events(
+                        &module,
+                        &contract_name,
+                        expected,
+                        &events,
+                        &mut differences,
+                    )?;
+                }
+            }
+            (Ok(_), true) => {
+                differences.push("Expected the update to be rejected, but it succeeded.".into());
+            }
+            (Err(error), expects_reject) => match (error.reject_code(), error.return_value()) {
+                (Some(code), Some(return_value)) if expects_reject => {
+                    self.check_reject(
+                        &module,
+                        |schema| schema.get_receive_error_schema(&contract_name, &step.entrypoint),
+                        &step.expect,
+                        code,
+                        return_value,
+                        &mut differences,
+                    )?;
+                }
+                _ => differences.push(format!("The update failed: {}", error.kind)),
+            },
+        }
+        check(differences)
+    }
+
+    /// Check the reject code and error of a rejected transaction.
+    fn check_reject<E: Debug>(
+        &self,
+        module: &str,
+        get_schema: impl FnOnce(&VersionedModuleSchema) -> Result<Type, E>,
+        expect: &Expectation,
+        code: i32,
+        return_value: &[u8],
+        differences: &mut Vec<String>,
+    ) -> Result<(), StepFailure> {
+        if let Some(expected) = expect.reject_code {
+            if expected != code {
+                differences
+                    .push(format!("Expected the reject code {expected}, but it was {code}."));
+            }
+        }
+        if let Some(expected) = &expect.error {
+            let ty = self.schema_type(module, get_schema, "error")?;
+            if self.encode_with(&ty, expected, "error")? != return_value {
+                differences.push(format!(
+                    "Expected the error\n{expected:#}\nbut it was\n{}",
+                    display(&ty, return_value)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the events logged by a contract.
+    fn check_events(
+        &self,
+        module: &str,
+        contract: &str,
+        expected: &[Value],
+        actual: &[ContractEvent],
+        differences: &mut Vec<String>,
+    ) -> Result<(), StepFailure> {
+        let ty = self.schema_type(module, |schema| schema.get_event_schema(contract), "event")?;
+        let expected_bytes = expected
+            .iter()
+            .map(|event| self.encode_with(&ty, event, "event"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let actual_bytes: Vec<&[u8]> = actual.iter().map(|event| event.as_ref()).collect();
+        if expected_bytes.iter().map(Vec::as_slice).ne(actual_bytes.iter().copied()) {
+            let expected: Vec<String> = expected.iter().map(|event| format!("{event:#}")).collect();
+            let actual: Vec<String> =
+                actual_bytes.iter().map(|event| display(&ty, event)).collect();
+            differences.push(format!(
+                "Expected the events\n[{}]\nbut they were\n[{}]",
+                expected.join(",\n"),
+                actual.join(",\n")
+            ));
+        }
+        Ok(())
+    }
+
+    fn account(&self, name: &str) -> Result<AccountAddress, StepFailure> {
+        self.accounts
+            .get(name)
+            .copied()
+            .ok_or_else(|| StepFailure::Invalid(format!("There is no account named '{name}'.")))
+    }
+
+    fn module(&self, name: &str) -> Result<&DeployedModule, StepFailure> {
+        self.modules
+            .get(name)
+            .ok_or_else(|| StepFailure::Invalid(format!("There is no module named '{name}'.")))
+    }
+
+    /// Look up an account or a contract by name.
+    fn resolve(&self, name: &str) -> Result<Address, StepFailure> {
+        if let Some(address) = self.accounts.get(name) {
+            Ok(Address::Account(*address))
+        } else if let Some(contract) = self.contracts.get(name) {
+            Ok(Address::Contract(contract.address))
+        } else {
+            Err(StepFailure::Invalid(format!("There is no account or contract named '{name}'.")))
+        }
+    }
+
+    /// Get a type from the schema embedded in a module. The `what` describes
+    /// the type in error messages.
+    fn schema_type<E: Debug>(
+        &self,
+        module: &str,
+        get_schema: impl FnOnce(&VersionedModuleSchema) -> Result<Type, E>,
+        what: &str,
+    ) -> Result<Type, StepFailure> {
+        let schema = self.module(module)?.schema.as_ref().ok_or_else(|| {
+            StepFailure::Invalid(format!("The module '{module}' does not have an embedded schema."))
+        })?;
+        get_schema(schema).map_err(|err| {
+            StepFailure::Invalid(format!("The module '{module}' has no {what} schema: {err:?}"))
+        })
+    }
+
+    /// Encode a JSON value with a type from the schema embedded in a module.
+    fn encode<E: Debug>(
+        &self,
+        module: &str,
+        get_schema: impl FnOnce(&VersionedModuleSchema) -> Result<Type, E>,
+        value: &Value,
+        what: &str,
+    ) -> Result<Vec<u8>, StepFailure> {
+        let ty = self.schema_type(module, get_schema, what)?;
+        self.encode_with(&ty, value, what)
+    }
+
+    /// Encode a JSON value with a type, after replacing references to accounts
+    /// and contracts by their addresses.
+    fn encode_with(&self, ty: &Type, value: &Value, what: &str) -> Result<Vec<u8>, StepFailure> {
+        let value = self.substitute(value)?;
+        ty.serial_value(&value).map_err(|err| {
+            StepFailure::Invalid(format!("The {what}\n{value:#}\ndoes not match the schema: {err}"))
+        })
+    }
+
+    /// Replace strings of the form `$name` with the address of the account or
+    /// contract with that name.
+    fn substitute(&self, value: &Value) -> Result<Value, StepFailure> {
+        Ok(match value {
+            Value::String(string) => match string.strip_prefix('$') {
+                Some(name) => match self.resolve(name)? {
+                    Address::Account(address) => Value::String(address.to_string()),
+                    Address::Contract(address) => serde_json::json!({
+                        "index": address.index,
+                        "subindex": address.subindex,
+                    }),
+                },
+                None => value.clone(),
+            },
+            Value::Array(values) => {
+                Value::Array(values.iter().map(|v| self.substitute(v)).collect::<Result<_, _>>()?)
+            }
+            Value::Object(entries) => Value::Object(
+                entries
+                    .iter()
+                    .map(|(key, v)| Ok((key.clone(), self.substitute(v)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => value.clone(),
+        })
+    }
+}
+
+/// Turn the differences found in a step into its result.
+fn check(differences: Vec<String>) -> Result<(), StepFailure> {
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(StepFailure::Expectation(differences.join("\n")))
+    }
+}
+
+fn parameter(bytes: Vec<u8>) -> Result<OwnedParameter, StepFailure> {
+    OwnedParameter::try_from(bytes)
+        .map_err(|_| StepFailure::Invalid("The parameter exceeds the maximum size.".into()))
+}
+
+/// Display a value as JSON if it matches the type, and as hex otherwise.
+fn display(ty: &Type, bytes: &[u8]) -> String {
+    ty.to_json_string_pretty(bytes).unwrap_or_else(|_| {
+        let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        format!("0x{hex} (which does not match the schema)")
+    })
+}
+
+fn default_energy() -> u64 { 100_000 }
+
+fn deserialize_ccd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+    let string = String::deserialize(deserializer)?;
+    Amount::from_str(&string)
+        .map_err(|err| serde::de::Error::custom(format!("Invalid amount '{string}' in CCD: {err}")))
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let string = String::deserialize(deserializer)?;
+    Duration::from_str(&string)
+        .map_err(|err| serde::de::Error::custom(format!("Invalid duration '{string}': {err}")))
+}
+
+fn deserialize_balances<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Amount>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, string)| {
+            let amount = Amount::from_str(&string).map_err(|err| {
+                serde::de::Error::custom(format!("Invalid amount '{string}' in CCD: {err}"))
+            })?;
+            Ok((name, amount))
+        })
+        .collect()
+}
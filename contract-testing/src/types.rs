@@ -1,3 +1,6 @@
+use crate::invocation::ReentrancyProbe;
+#[cfg(feature = "native")]
+use crate::NativeModule;
 use concordium_rust_sdk as sdk;
 use concordium_rust_sdk::{
     base::{
@@ -5,9 +8,10 @@ use concordium_rust_sdk::{
         common::types::{CredentialIndex, KeyIndex, Signature},
         constants::ED25519_SIGNATURE_LENGTH,
         contracts_common::{
-            self, AccountAddress, AccountBalance, Address, Amount, ContractAddress, Deserial,
-            EntrypointName, ExchangeRate, ModuleReference, OwnedContractName, OwnedEntrypointName,
-            OwnedPolicy, ParseResult, SlotTime, Timestamp,
+            self, AccountAddress, AccountBalance, AccountThreshold, Address, Amount,
+            ContractAddress, Deserial, EntrypointName, ExchangeRate, ModuleReference,
+            OwnedContractName, OwnedEntrypointName, OwnedPolicy, ParseResult, SignatureThreshold,
+            SlotTime, Timestamp,
         },
         hashes::BlockHash,
         id::types::SchemeId,
@@ -29,7 +33,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     env,
     path::PathBuf,
-    sync::Arc,
+    sync::{Arc, Mutex, RwLock},
 };
 use thiserror::Error;
 
@@ -39,11 +43,55 @@ pub struct ContractModule {
     /// Size of the module in bytes. Used for cost accounting.
     pub size:     u64,
     /// The runnable module.
-    pub artifact: Arc<artifact::Artifact<v1::ProcessedImports, artifact::CompiledFunction>>,
+    pub artifact: Arc<CompiledArtifact>,
 }
 
-/// The chain parameters.
+/// The type of the runnable artifacts that are constructed from Wasm modules.
+pub(crate) type CompiledArtifact =
+    artifact::Artifact<v1::ProcessedImports, artifact::CompiledFunction>;
+
+/// A process-wide cache of compiled smart contract modules.
+///
+/// Validating and compiling a Wasm module into an [`artifact::Artifact`] is
+/// the most expensive part of deploying a module. Since tests usually deploy
+/// the same modules many times, the artifacts are cached by their
+/// [`ModuleReference`] and shared by all the [`Chain`]s in the process,
+/// including those in tests running in parallel. Each module is compiled at
+/// most once per process.
+///
+/// Optionally, the artifacts can also be persisted to a directory, so that
+/// they can be reused across test runs. See
+/// [`ModuleCache::set_persistence_dir`].
+///
+/// The cache is accessed through the [`module_cache`](crate::module_cache)
+/// function.
 #[derive(Debug)]
+pub struct ModuleCache {
+    /// The cached modules. Each entry has its own lock so that a module is
+    /// only compiled once, even if several threads deploy it at the same
+    /// time, without blocking the deployment of other modules.
+    pub(crate) entries:         Mutex<BTreeMap<ModuleCacheKey, Arc<Mutex<Option<CachedModule>>>>>,
+    /// The directory in which the compiled artifacts are persisted, if any.
+    pub(crate) persistence_dir: RwLock<Option<PathBuf>>,
+}
+
+/// The key of a module in the [`ModuleCache`]. Modules deployed with debug
+/// output enabled are validated differently, and are therefore cached
+/// separately.
+pub(crate) type ModuleCacheKey = (ModuleReference, bool);
+
+/// A compiled module in the [`ModuleCache`].
+#[derive(Debug, Clone)]
+pub(crate) struct CachedModule {
+    /// The runnable module.
+    pub(crate) artifact:             Arc<CompiledArtifact>,
+    /// The size of the custom sections in the Wasm module, which does not
+    /// count towards the module size.
+    pub(crate) custom_sections_size: u64,
+}
+
+/// The chain parameters.
+#[derive(Debug, Clone)]
 pub(crate) struct ChainParameters {
     /// The block time viewable inside the smart contracts.
     /// Defaults to `0`.
@@ -88,6 +136,38 @@ pub struct Chain {
     pub(crate) next_contract_index: u64,
     /// An optional connection to an external node.
     pub(crate) external_node_connection: Option<ExternalNodeConnection>,
+    /// Records outgoing calls and injects reentrant calls during
+    /// [`Chain::explore_reentrancy`]. Only set on the copies of the chain used
+    /// for exploration.
+    pub(crate) reentrancy_probe: Option<Mutex<ReentrancyProbe>>,
+    /// The modules with entrypoints that are executed natively. See
+    /// [`Chain::module_register_native`].
+    #[cfg(feature = "native")]
+    pub(crate) native_modules: BTreeMap<ModuleReference, NativeModule>,
+    /// The checkpoints recorded after each transaction, if enabled with
+    /// [`Chain::enable_checkpoints`].
+    pub(crate) checkpoints: Option<Vec<Checkpoint>>,
+}
+
+/// A snapshot of the [`Chain`] after a transaction, which the chain can be
+/// rewound to with [`Chain::rewind_to`].
+///
+/// Checkpoints are recorded when enabled with [`Chain::enable_checkpoints`].
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// The index of the checkpoint. Checkpoint `0` is the state when
+    /// checkpoints were enabled, and checkpoint `n` is the state after the
+    /// `n`th transaction since then.
+    pub index: usize,
+    /// The block time at the checkpoint.
+    pub block_time: SlotTime,
+    /// A description of the transaction that led to the checkpoint.
+    pub description: String,
+    pub(crate) parameters: ChainParameters,
+    pub(crate) accounts: BTreeMap<AccountAddressEq, Account>,
+    pub(crate) modules: BTreeMap<ModuleReference, ContractModule>,
+    pub(crate) contracts: BTreeMap<ContractAddress, Contract>,
+    pub(crate) next_contract_index: u64,
 }
 
 /// A builder for the [`Chain`].
@@ -134,13 +214,61 @@ pub struct Contract {
 /// An account.
 #[derive(Clone, Debug)]
 pub struct Account {
-    pub address: AccountAddress,
+    pub address:          AccountAddress,
     /// The account balance.
-    pub balance: AccountBalance,
+    pub balance:          AccountBalance,
     /// Account policy.
-    pub policy:  OwnedPolicy,
+    pub policy:           OwnedPolicy,
     /// Account's public keys.
-    pub keys:    AccountAccessStructure,
+    pub keys:             AccountAccessStructure,
+    /// Stake that is in cooldown, indexed by the time at which it is removed
+    /// from the staked balance.
+    ///
+    /// See [`Chain::account_remove_stake`].
+    pub stake_cooldowns:  BTreeMap<Timestamp, Amount>,
+    /// Locked amounts, indexed by the time at which they are released.
+    ///
+    /// See [`Chain::account_add_release_schedule`].
+    pub release_schedule: BTreeMap<Timestamp, Amount>,
+}
+
+/// A builder for an [`Account`].
+///
+/// The builder makes it convenient to create accounts with specific account
+/// policies, e.g., identity providers, validity periods, and attributes.
+#[derive(Clone, Debug)]
+pub struct AccountBuilder {
+    /// The address of the account.
+    pub(crate) address: AccountAddress,
+    /// The account balance.
+    pub(crate) balance: AccountBalance,
+    /// The account policy.
+    pub(crate) policy:  OwnedPolicy,
+    /// The account's public keys.
+    pub(crate) keys:    AccountAccessStructure,
+}
+
+/// An account with ed25519 key pairs that are deterministically derived from a
+/// seed.
+///
+/// The same seed always results in the same address and keys, so tests are
+/// reproducible, while the private keys are available for signing messages
+/// with [`TestAccount::sign_message`]. The signatures are checked by the
+/// `check_account_signature` host function once the account is created on the
+/// chain, e.g., with [`TestAccount::account`].
+///
+/// The keys are derived from the seed, not generated securely, so they must
+/// only be used for testing.
+#[derive(Clone, Debug)]
+pub struct TestAccount {
+    /// The address of the account.
+    pub(crate) address:     AccountAddress,
+    /// The seed that the keys are derived from.
+    pub(crate) seed:        [u8; 32],
+    /// The number of credentials that must sign.
+    pub(crate) threshold:   AccountThreshold,
+    /// The credentials with their signature thresholds and key indices.
+    pub(crate) credentials: BTreeMap<CredentialIndex, (SignatureThreshold, Vec<KeyIndex>)>,
 }
 
 /// A signature with account's keys.
@@ -163,6 +291,38 @@ impl From<BTreeMap<CredentialIndex, BTreeMap<KeyIndex, Signature>>> for AccountS
     }
 }
 
+/// Convert to the representation used in `concordium-std`, e.g., for including
+/// the signatures in the parameter of a permit function.
+impl From<AccountSignatures> for contracts_common::AccountSignatures {
+    fn from(value: AccountSignatures) -> Self {
+        let sigs = value
+            .sigs
+            .into_iter()
+            .map(|(cred_index, sigs)| {
+                let sigs = sigs
+                    .into_iter()
+                    .map(|(key_index, sig)| {
+                        let mut bytes = [0u8; ED25519_SIGNATURE_LENGTH];
+                        bytes.copy_from_slice(&sig.sig);
+                        (
+                            key_index.0,
+                            contracts_common::Signature::Ed25519(
+                                contracts_common::SignatureEd25519(bytes),
+                            ),
+                        )
+                    })
+                    .collect();
+                (cred_index.index, contracts_common::CredentialSignatures {
+                    sigs,
+                })
+            })
+            .collect();
+        Self {
+            sigs,
+        }
+    }
+}
+
 impl AccountSignatures {
     /// Return the number of signatures contained in the structure.
     pub fn num_signatures(&self) -> u32 { self.sigs.values().map(|v| v.len() as u32).sum() }
@@ -352,15 +512,156 @@ pub struct ModuleInvalidError(#[from] pub(crate) anyhow::Error);
 #[derive(Debug)]
 pub struct ContractInitSuccess {
     /// The address of the new instance.
-    pub contract_address: ContractAddress,
+    pub contract_address:   ContractAddress,
     /// Contract events (logs) produced during initialization.
-    pub events:           Vec<ContractEvent>,
+    pub events:             Vec<ContractEvent>,
     /// Energy used.
-    pub energy_used:      Energy,
+    pub energy_used:        Energy,
+    /// The amount of [`Energy`] that was used to store the initial state of
+    /// the contract.
+    pub storage_energy:     Energy,
+    /// The amount of [`Energy`] that was charged based on the size of the Wasm
+    /// module.
+    pub module_load_energy: Energy,
     /// Cost of transaction.
-    pub transaction_fee:  Amount,
+    pub transaction_fee:    Amount,
     /// Debug information emitted by the initialization method.
-    pub debug_trace:      DebugTracker,
+    pub debug_trace:        DebugTracker,
+}
+
+/// An estimate of the energy needed for a transaction, as computed by
+/// [`Chain::estimate_update_energy`] and [`Chain::estimate_init_energy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnergyEstimate {
+    /// The least energy that must be reserved for the transaction to succeed.
+    pub energy:             Energy,
+    /// The part of the `energy` used for checking the transaction header,
+    /// which depends on the size of the transaction and the number of
+    /// signatures.
+    pub header_energy:      Energy,
+    /// The part of the `energy` used for executing the contracts.
+    pub execution_energy:   Energy,
+    /// The part of the `energy` used for storing contract state.
+    pub storage_energy:     Energy,
+    /// The part of the `energy` charged based on the sizes of the Wasm modules
+    /// used.
+    pub module_load_energy: Energy,
+}
+
+/// An error that occurred in [`Chain::estimate_update_energy`] or
+/// [`Chain::estimate_init_energy`]. The error `E` is the error of the
+/// transaction.
+#[derive(Debug, Error)]
+pub enum EnergyEstimateError<E> {
+    /// The transaction failed, even with the most energy that the account can
+    /// afford.
+    #[error("{0}")]
+    TransactionFailed(#[from] E),
+    /// The energy used by the transaction is less than the sum of the energy
+    /// used for checking the header, storing state and loading modules. This
+    /// indicates an error in the library.
+    #[error("The energy used ({energy_used:?}) is less than the sum of its parts.")]
+    InconsistentEnergy {
+        /// The energy used by the transaction.
+        energy_used: Energy,
+    },
+}
+
+/// A call made into a contract during [`Chain::explore_reentrancy`], right
+/// before one of its outgoing calls to another contract is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReentrantCall {
+    /// Reenter the contract directly, as if the contract it is calling made
+    /// the call.
+    Reenter {
+        /// The entrypoint of the contract to call.
+        entrypoint: OwnedEntrypointName,
+        /// The parameter to call the entrypoint with.
+        parameter:  OwnedParameter,
+        /// The amount to send from the contract being called.
+        amount:     Amount,
+    },
+    /// Call an attacker contract, as if the contract being called made the
+    /// call. The attacker can then reenter the contract however it likes.
+    ///
+    /// The parameter to the attacker is the address of the contract being
+    /// explored and the name of its entrypoint that made the outgoing call,
+    /// i.e. a `(ContractAddress, EntrypointName)` tuple.
+    Attacker {
+        /// The address of the attacker contract.
+        address:    ContractAddress,
+        /// The entrypoint of the attacker contract to call.
+        entrypoint: OwnedEntrypointName,
+        /// The amount to send from the contract being called.
+        amount:     Amount,
+    },
+}
+
+/// A call from one contract to another, as recorded by
+/// [`Chain::explore_reentrancy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingCall {
+    /// The entrypoint of the explored contract that made the call.
+    pub caller_entrypoint: OwnedEntrypointName,
+    /// The contract called.
+    pub callee:            ContractAddress,
+    /// The entrypoint called.
+    pub callee_entrypoint: OwnedEntrypointName,
+}
+
+/// The result of [`Chain::explore_reentrancy`].
+#[derive(Debug, Clone)]
+pub struct ReentrancyReport {
+    /// The outgoing calls made by the contract when the transaction runs
+    /// without any reentrant calls.
+    pub outgoing_calls: Vec<OutgoingCall>,
+    /// The number of transactions run with a reentrant call injected.
+    pub attempts:       usize,
+    /// The number of attempts in which the contract rejected reentry with the
+    /// [`REENTRANCY_GUARD_REJECT_CODE`](crate::REENTRANCY_GUARD_REJECT_CODE),
+    /// i.e., where a `ReentrancyGuard` from `concordium-std` blocked the
+    /// reentrant call.
+    pub blocked:        usize,
+    /// The problems found.
+    pub findings:       Vec<ReentrancyFinding>,
+}
+
+impl ReentrancyReport {
+    /// Whether no problems were found.
+    pub fn is_safe(&self) -> bool { self.findings.is_empty() }
+
+    /// Whether reentry was blocked by a reentrancy guard in every attempt.
+    /// This is `false` if no attempts were made.
+    pub fn is_guarded(&self) -> bool { self.attempts > 0 && self.blocked == self.attempts }
+}
+
+/// A problem found by [`Chain::explore_reentrancy`].
+#[derive(Debug, Clone)]
+pub struct ReentrancyFinding {
+    /// The index of the outgoing call in
+    /// [`ReentrancyReport::outgoing_calls`] at which the reentrant call was
+    /// injected.
+    pub call_index:     usize,
+    /// The outgoing call at which the reentrant call was injected.
+    pub outgoing_call:  OutgoingCall,
+    /// The reentrant call injected.
+    pub reentrant_call: ReentrantCall,
+    /// The kind of problem.
+    pub kind:           ReentrancyFindingKind,
+}
+
+/// The kinds of problems found by [`Chain::explore_reentrancy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReentrancyFindingKind {
+    /// The transaction succeeded, but the invariant does not hold afterwards.
+    InvariantBroken {
+        /// The message returned by the invariant.
+        message: String,
+    },
+    /// The reentrant call modified the state of the contract, and the contract
+    /// wrote its state again after the outgoing call resumed. The write might
+    /// be based on values read before the outgoing call, which are then stale.
+    StaleStateAfterResume,
 }
 
 /// An error that occurred in [`Chain::contract_init`].
@@ -1309,6 +1610,37 @@ pub enum ChainBuilderError {
     MissingExternalConnection,
 }
 
+/// An error that can occur while changing the stake or the release schedule of
+/// an account.
+#[derive(Debug, Error)]
+pub enum AccountBalanceUpdateError {
+    /// The account does not exist.
+    #[error("{0}")]
+    AccountDoesNotExist(#[from] AccountDoesNotExist),
+    /// The staked amount would exceed the total balance of the account.
+    #[error("The staked amount would exceed the total balance of the account.")]
+    InsufficientBalance,
+    /// The amount to unstake exceeds the stake that is not already in
+    /// cooldown.
+    #[error("The amount to unstake exceeds the stake that is not already in cooldown.")]
+    InsufficientStake,
+    /// The balance of the account overflowed.
+    /// If you are seeing this error, lower the [`Amount`]s used in your tests.
+    #[error("The balance of the account overflowed.")]
+    BalanceOverflow,
+    /// The release time of a cooldown overflowed.
+    #[error("The release time of the cooldown overflowed.")]
+    ReleaseTimeOverflow,
+}
+
+/// The checkpoint passed to [`Chain::rewind_to`] does not exist.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Checkpoint {index} does not exist.")]
+pub struct CheckpointDoesNotExist {
+    /// The index of the checkpoint.
+    pub index: usize,
+}
+
 /// The block time overflowed during a call to `Chain::tick_block_time`.
 #[derive(Debug, Error, PartialEq, Eq)]
 #[error("The block time overflowed during a call to `Chain::tick_block_time`.")]
@@ -0,0 +1,463 @@
+//! Checks of whether a contract can be upgraded from one version of a module to
+//! another without breaking its users or losing access to its state.
+use crate::types::*;
+use concordium_rust_sdk::{
+    base::{
+        base::Energy,
+        contracts_common::{
+            schema::{Type, VersionedModuleSchema},
+            AccountAddress, Address, Amount, ContractAddress, ContractName, ModuleReference,
+            OwnedContractName, OwnedEntrypointName, OwnedParameter, OwnedReceiveName,
+        },
+        smart_contracts::WasmModule,
+        transactions::{InitContractPayload, UpdateContractPayload},
+    },
+    smart_contracts::engine::utils::get_embedded_schema_v1,
+};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// A script that populates the state of the contract before it is upgraded.
+type PopulateScript<'a> = Box<dyn FnMut(&mut Chain, ContractAddress) -> Result<(), String> + 'a>;
+
+/// A check of whether a contract can be upgraded from an old module to a new
+/// module.
+///
+/// The check compares the two modules and then, for each run,
+///  1. deploys both modules and initializes the contract from the old module,
+///  2. populates its state with the populate script,
+///  3. invokes the views,
+///  4. upgrades the contract by calling its upgrade entrypoint, and
+///  5. invokes the views again.
+///
+/// Every run is done on a fresh copy of the chain passed to
+/// [`UpgradeCheck::run`], so the script can make different transactions in
+/// each run, e.g., with a random number generator, to fuzz the state.
+///
+/// The problems found are listed as [`UpgradeFinding`]s in the
+/// [`UpgradeReport`]:
+///  - entrypoints of the contract that the new module removes,
+///  - changes to the parameter, return value, error, and event types in the
+///    embedded schemas,
+///  - upgrades that fail, e.g., because the migration fails, and
+///  - views that fail after the upgrade, or return something else even though
+///    their return value type did not change, which indicates that the new
+///    module cannot read the state of the old module.
+///
+/// ```no_run
+/// # use concordium_smart_contract_testing::*;
+/// # let chain = Chain::new();
+/// # const ACC: AccountAddress = AccountAddress([0; 32]);
+/// let report = UpgradeCheck::new(
+///     module_load_v1("v1.wasm.v1").unwrap(),
+///     module_load_v1("v2.wasm.v1").unwrap(),
+///     ContractName::new_unchecked("init_smart_contract_upgrade"),
+/// )
+/// .populate(|chain, address| {
+///     // Make transactions that write to the state of the contract.
+///     Ok(())
+/// })
+/// .view(OwnedEntrypointName::new_unchecked("view".into()), OwnedParameter::empty())
+/// .run(&chain, ACC)
+/// .unwrap();
+/// assert!(report.is_compatible(), "{:#?}", report.findings);
+/// ```
+pub struct UpgradeCheck<'a> {
+    old_module:         WasmModule,
+    new_module:         WasmModule,
+    contract_name:      OwnedContractName,
+    init_parameter:     OwnedParameter,
+    populate:           Option<PopulateScript<'a>>,
+    upgrade_entrypoint: OwnedEntrypointName,
+    upgrade_parameter:  Box<dyn Fn(ModuleReference) -> OwnedParameter + 'a>,
+    views:              Vec<(OwnedEntrypointName, OwnedParameter)>,
+    energy:             Energy,
+    runs:               usize,
+}
+
+/// The result of [`UpgradeCheck::run`].
+#[derive(Debug, Clone)]
+pub struct UpgradeReport {
+    /// The entrypoints of the contract that only exist in the new module.
+    pub added_entrypoints: Vec<OwnedEntrypointName>,
+    /// The problems found.
+    pub findings:          Vec<UpgradeFinding>,
+}
+
+impl UpgradeReport {
+    /// Whether no problems were found.
+    pub fn is_compatible(&self) -> bool { self.findings.is_empty() }
+}
+
+/// A problem found by [`UpgradeCheck::run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeFinding {
+    /// The new module does not have an entrypoint of the contract in the old
+    /// module.
+    RemovedEntrypoint {
+        /// The entrypoint removed.
+        entrypoint: OwnedEntrypointName,
+    },
+    /// The old module has an embedded schema, but the new module does not.
+    RemovedSchema,
+    /// The type of the parameter of an entrypoint changed.
+    ChangedParameterType {
+        /// The entrypoint.
+        entrypoint: OwnedEntrypointName,
+    },
+    /// The type of the return value of an entrypoint changed.
+    ChangedReturnValueType {
+        /// The entrypoint.
+        entrypoint: OwnedEntrypointName,
+    },
+    /// The type of the errors of an entrypoint changed.
+    ChangedErrorType {
+        /// The entrypoint.
+        entrypoint: OwnedEntrypointName,
+    },
+    /// The type of the events of the contract changed.
+    ChangedEventType,
+    /// The call of the upgrade entrypoint failed.
+    UpgradeFailed {
+        /// The run in which the upgrade failed, starting from 0.
+        run:   usize,
+        /// A description of the error.
+        error: String,
+    },
+    /// A view succeeded before the upgrade, but failed after it.
+    ViewFailed {
+        /// The run in which the view failed, starting from 0.
+        run:        usize,
+        /// The entrypoint of the view.
+        entrypoint: OwnedEntrypointName,
+        /// A description of the error.
+        error:      String,
+    },
+    /// A view returned a different value after the upgrade, even though the
+    /// type of its return value did not change.
+    ViewChanged {
+        /// The run in which the view changed, starting from 0.
+        run:        usize,
+        /// The entrypoint of the view.
+        entrypoint: OwnedEntrypointName,
+        /// The return value before the upgrade.
+        before:     Vec<u8>,
+        /// The return value after the upgrade.
+        after:      Vec<u8>,
+    },
+}
+
+/// An error that prevented [`UpgradeCheck::run`] from checking the upgrade.
+#[derive(Debug, Error)]
+pub enum UpgradeCheckError {
+    /// One of the modules could not be deployed.
+    #[error("Could not deploy a module: {0}")]
+    Deploy(#[from] ModuleDeployError),
+    /// The contract could not be initialized from the old module.
+    #[error("Could not initialize the contract: {0}")]
+    Init(#[from] ContractInitError),
+    /// The populate script failed.
+    #[error("The populate script failed in run {run}: {message}")]
+    Populate {
+        /// The run in which the script failed, starting from 0.
+        run:     usize,
+        /// The error returned by the script.
+        message: String,
+    },
+}
+
+impl<'a> UpgradeCheck<'a> {
+    /// Create a check of upgrading the contract with the `contract_name`,
+    /// e.g., `init_counter`, from the `old_module` to the `new_module`.
+    ///
+    /// By default, the contract is initialized with an empty parameter, the
+    /// upgrade is done by calling the `upgrade` entrypoint with the parameter
+    /// described in [`UpgradeCheck::upgrade_parameter`], and a single run is
+    /// made without populating the state or invoking any views.
+    pub fn new(
+        old_module: WasmModule,
+        new_module: WasmModule,
+        contract_name: ContractName,
+    ) -> Self {
+        Self {
+            old_module,
+            new_module,
+            contract_name: contract_name.to_owned(),
+            init_parameter: OwnedParameter::empty(),
+            populate: None,
+            upgrade_entrypoint: OwnedEntrypointName::new_unchecked("upgrade".into()),
+            upgrade_parameter: Box::new(|module| {
+                // The module reference followed by `None` for the migration, as
+                // in the `smart-contract-upgrade` example.
+                OwnedParameter::from_serial(&(module, 0u8)).expect("Parameter has valid size")
+            }),
+            views: Vec::new(),
+            energy: Energy::from(100_000),
+            runs: 1,
+        }
+    }
+
+    /// Set the parameter for initializing the contract.
+    pub fn init_parameter(mut self, parameter: OwnedParameter) -> Self {
+        self.init_parameter = parameter;
+        self
+    }
+
+    /// Set the script that populates the state of the contract before the
+    /// upgrade. It is given the chain and the address of the contract, and an
+    /// error returned from it stops the check.
+    pub fn populate(
+        mut self,
+        script: impl FnMut(&mut Chain, ContractAddress) -> Result<(), String> + 'a,
+    ) -> Self {
+        self.populate = Some(Box::new(script));
+        self
+    }
+
+    /// Set the entrypoint that upgrades the contract. Defaults to `upgrade`.
+    pub fn upgrade_entrypoint(mut self, entrypoint: OwnedEntrypointName) -> Self {
+        self.upgrade_entrypoint = entrypoint;
+        self
+    }
+
+    /// Set the function that creates the parameter for the upgrade entrypoint
+    /// from the reference of the new module, e.g., to include a migration.
+    ///
+    /// Defaults to the module reference followed by a `0u8`, i.e. the
+    /// serialization of an `UpgradeParams` without a migration from the
+    /// `smart-contract-upgrade` example.
+    pub fn upgrade_parameter(
+        mut self,
+        parameter: impl Fn(ModuleReference) -> OwnedParameter + 'a,
+    ) -> Self {
+        self.upgrade_parameter = Box::new(parameter);
+        self
+    }
+
+    /// Add a view, which is invoked with the parameter before and after the
+    /// upgrade to check that the state can still be read.
+    pub fn view(mut self, entrypoint: OwnedEntrypointName, parameter: OwnedParameter) -> Self {
+        self.views.push((entrypoint, parameter));
+        self
+    }
+
+    /// Set the energy reserved for each transaction and invocation. Defaults to
+    /// 100000.
+    pub fn energy(mut self, energy: Energy) -> Self {
+        self.energy = energy;
+        self
+    }
+
+    /// Set the number of runs. Defaults to 1.
+    pub fn runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    /// Run the check on copies of the `chain`, with `invoker` deploying the
+    /// modules and sending all transactions. Modules that are already deployed
+    /// on the `chain` are reused. The `chain` is not modified.
+    pub fn run(
+        mut self,
+        chain: &Chain,
+        invoker: AccountAddress,
+    ) -> Result<UpgradeReport, UpgradeCheckError> {
+        let mut findings = Vec::new();
+        let mut added_entrypoints = Vec::new();
+
+        // Compare the entrypoints of the contract in the two modules.
+        let mut setup = chain.dry_run_copy();
+        let old_reference = deploy(&mut setup, invoker, &self.old_module)?;
+        let new_reference = deploy(&mut setup, invoker, &self.new_module)?;
+        let old_entrypoints = self.entrypoints(&setup, old_reference);
+        let new_entrypoints = self.entrypoints(&setup, new_reference);
+        for entrypoint in old_entrypoints.difference(&new_entrypoints) {
+            findings.push(UpgradeFinding::RemovedEntrypoint {
+                entrypoint: entrypoint.clone(),
+            });
+        }
+        added_entrypoints.extend(new_entrypoints.difference(&old_entrypoints).cloned());
+
+        // Compare the types of the entrypoints in both modules.
+        let old_schema = get_embedded_schema_v1(self.old_module.source.as_ref()).ok();
+        let new_schema = get_embedded_schema_v1(self.new_module.source.as_ref()).ok();
+        let contract = self.contract_name.as_contract_name().contract_name();
+        let mut unchanged_return_values = BTreeSet::new();
+        match (&old_schema, &new_schema) {
+            (Some(old), Some(new)) => {
+                if old.get_event_schema(contract).ok() != new.get_event_schema(contract).ok() {
+                    findings.push(UpgradeFinding::ChangedEventType);
+                }
+                for entrypoint in old_entrypoints.intersection(&new_entrypoints) {
+                    let name = entrypoint.as_entrypoint_name().to_string();
+                    let changed = |get: fn(&VersionedModuleSchema, &str, &str) -> Option<Type>| {
+                        get(old, contract, &name) != get(new, contract, &name)
+                    };
+                    if changed(|schema, contract, name| {
+                        schema.get_receive_param_schema(contract, name).ok()
+                    }) {
+                        findings.push(UpgradeFinding::ChangedParameterType {
+                            entrypoint: entrypoint.clone(),
+                        });
+                    }
+                    if changed(|schema, contract, name| {
+                        schema.get_receive_return_value_schema(contract, name).ok()
+                    }) {
+                        findings.push(UpgradeFinding::ChangedReturnValueType {
+                            entrypoint: entrypoint.clone(),
+                        });
+                    } else {
+                        unchanged_return_values.insert(entrypoint.clone());
+                    }
+                    if changed(|schema, contract, name| {
+                        schema.get_receive_error_schema(contract, name).ok()
+                    }) {
+                        findings.push(UpgradeFinding::ChangedErrorType {
+                            entrypoint: entrypoint.clone(),
+                        });
+                    }
+                }
+            }
+            (Some(_), None) => findings.push(UpgradeFinding::RemovedSchema),
+            // Without a schema for the old module, there are no types to compare,
+            // and return values are compared as bytes.
+            (None, _) => unchanged_return_values = old_entrypoints.clone(),
+        }
+
+        for run in 0..self.runs {
+            let mut chain = setup.dry_run_copy();
+            let address = chain
+                .contract_init(Signer::with_one_key(), invoker, self.energy, InitContractPayload {
+                    amount:    Amount::zero(),
+                    mod_ref:   old_reference,
+                    init_name: self.contract_name.clone(),
+                    param:     self.init_parameter.clone(),
+                })?
+                .contract_address;
+            if let Some(populate) = &mut self.populate {
+                populate(&mut chain, address).map_err(|message| UpgradeCheckError::Populate {
+                    run,
+                    message,
+                })?;
+            }
+
+            let before = self.invoke_views(&chain, invoker, address);
+            let upgrade = chain.contract_update(
+                Signer::with_one_key(),
+                invoker,
+                Address::Account(invoker),
+                self.energy,
+                UpdateContractPayload {
+                    amount: Amount::zero(),
+                    address,
+                    receive_name: self.receive_name(&self.upgrade_entrypoint),
+                    message: (self.upgrade_parameter)(new_reference),
+                },
+            );
+            if let Err(error) = upgrade {
+                findings.push(UpgradeFinding::UpgradeFailed {
+                    run,
+                    error: error.to_string(),
+                });
+                continue;
+            }
+            let after = self.invoke_views(&chain, invoker, address);
+
+            for ((entrypoint, _), (before, after)) in
+                self.views.iter().zip(before.into_iter().zip(after))
+            {
+                // Views that already fail before the upgrade say nothing about the upgrade.
+                let Ok(before) = before else {
+                    continue;
+                };
+                match after {
+                    Err(error) => findings.push(UpgradeFinding::ViewFailed {
+                        run,
+                        entrypoint: entrypoint.clone(),
+                        error,
+                    }),
+                    Ok(after)
+                        if before != after && unchanged_return_values.contains(entrypoint) =>
+                    {
+                        findings.push(UpgradeFinding::ViewChanged {
+                            run,
+                            entrypoint: entrypoint.clone(),
+                            before,
+                            after,
+                        })
+                    }
+                    Ok(_) => {}
+                }
+            }
+        }
+
+        Ok(UpgradeReport {
+            added_entrypoints,
+            findings,
+        })
+    }
+
+    /// The entrypoints of the contract in a deployed module.
+    fn entrypoints(&self, chain: &Chain, module: ModuleReference) -> BTreeSet<OwnedEntrypointName> {
+        let prefix = format!("{}.", self.contract_name.as_contract_name().contract_name());
+        chain.modules[&module]
+            .artifact
+            .export
+            .keys()
+            .filter_map(|name| {
+                let name: &str = name.as_ref();
+                let entrypoint = name.strip_prefix(&prefix)?;
+                Some(OwnedEntrypointName::new_unchecked(entrypoint.into()))
+            })
+            .collect()
+    }
+
+    fn receive_name(&self, entrypoint: &OwnedEntrypointName) -> OwnedReceiveName {
+        OwnedReceiveName::construct_unchecked(
+            self.contract_name.as_contract_name(),
+            entrypoint.as_entrypoint_name(),
+        )
+    }
+
+    /// Invoke the views and return their return values or errors.
+    fn invoke_views(
+        &self,
+        chain: &Chain,
+        invoker: AccountAddress,
+        address: ContractAddress,
+    ) -> Vec<Result<Vec<u8>, String>> {
+        self.views
+            .iter()
+            .map(|(entrypoint, parameter)| {
+                chain
+                    .contract_invoke(
+                        invoker,
+                        Address::Account(invoker),
+                        self.energy,
+                        UpdateContractPayload {
+                            amount: Amount::zero(),
+                            address,
+                            receive_name: self.receive_name(entrypoint),
+                            message: parameter.clone(),
+                        },
+                    )
+                    .map(|success| success.return_value)
+                    .map_err(|error| error.to_string())
+            })
+            .collect()
+    }
+}
+
+/// Deploy a module on the `chain`, unless it is already deployed, and return
+/// its reference.
+fn deploy(
+    chain: &mut Chain,
+    invoker: AccountAddress,
+    module: &WasmModule,
+) -> Result<ModuleReference, ModuleDeployError> {
+    let reference = module.get_module_ref();
+    if chain.modules.contains_key(&reference) {
+        return Ok(reference);
+    }
+    Ok(chain.module_deploy_v1(Signer::with_one_key(), invoker, module.clone())?.module_reference)
+}
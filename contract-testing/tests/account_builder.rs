@@ -0,0 +1,45 @@
+//! This module contains tests for building accounts with specific policies and
+//! for account aliases.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that the builder sets the policy fields and sorts the attributes by
+/// their tags.
+#[test]
+fn test_account_builder_policy() {
+    let account = Account::builder(helpers::ACC_0)
+        .total_balance(Amount::from_ccd(1000))
+        .identity_provider(7)
+        .created_at(Timestamp::from_timestamp_millis(10))
+        .valid_to(Timestamp::from_timestamp_millis(20))
+        .attribute(attributes::NATIONALITY, *b"SE")
+        .attribute(attributes::COUNTRY_OF_RESIDENCE, *b"NO")
+        // Replaces the previous value.
+        .attribute(attributes::COUNTRY_OF_RESIDENCE, *b"DK")
+        .build();
+
+    assert_eq!(account.balance.available(), Amount::from_ccd(1000));
+    assert_eq!(account.policy.identity_provider, 7);
+    assert_eq!(account.policy.created_at, Timestamp::from_timestamp_millis(10));
+    assert_eq!(account.policy.valid_to, Timestamp::from_timestamp_millis(20));
+    let tags: Vec<u8> = account.policy.items.iter().map(|(tag, _)| tag.0).collect();
+    assert_eq!(tags, [attributes::COUNTRY_OF_RESIDENCE.0, attributes::NATIONALITY.0]);
+    assert_eq!(account.policy.items[0].1.as_ref(), b"DK");
+}
+
+/// Test that all aliases of an account resolve to the same account on the
+/// chain.
+#[test]
+fn test_account_aliases() {
+    let mut chain = Chain::new();
+    let account = Account::builder(helpers::ACC_1).total_balance(Amount::from_ccd(10)).build();
+    let aliases: Vec<AccountAddress> = account.aliases().take(3).collect();
+    chain.create_account(account);
+
+    assert_eq!(aliases.len(), 3);
+    for alias in aliases {
+        assert_eq!(alias.0[..29], helpers::ACC_1.0[..29]);
+        assert_eq!(chain.account_balance_available(alias), Some(Amount::from_ccd(10)));
+    }
+    assert!(chain.get_account(helpers::ACC_1).unwrap().alias(1 << 24).is_none());
+}
@@ -0,0 +1,140 @@
+//! This module tests the assertion helpers for the outcomes of contract
+//! updates.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Deploy and initialize the `transfer` contract and deposit 1000 microCCD in
+/// it.
+fn setup_transfer() -> (Chain, ContractAddress) {
+    let (mut chain, _, contract_address) =
+        helpers::deploy_and_init("transfer.wasm", "init_transfer");
+
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.deposit".into()),
+                message:      OwnedParameter::empty(),
+                amount:       Amount::from_micro_ccd(1000),
+            },
+        )
+        .expect("Updating contract should succeed");
+
+    (chain, contract_address)
+}
+
+/// Invoke `transfer.send` to send 17 microCCD to `ACC_0`.
+fn send(
+    chain: &Chain,
+    contract_address: ContractAddress,
+    energy: Energy,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    chain.contract_invoke(
+        helpers::ACC_0,
+        Address::Account(helpers::ACC_0),
+        energy,
+        UpdateContractPayload {
+            address:      contract_address,
+            receive_name: OwnedReceiveName::new_unchecked("transfer.send".into()),
+            message:      OwnedParameter::from_serial(&(
+                helpers::ACC_0,
+                Amount::from_micro_ccd(17),
+            ))
+            .expect("Parameter has valid size"),
+            amount:       Amount::zero(),
+        },
+    )
+}
+
+#[test]
+fn test_successful_transfer() {
+    let (chain, contract_address) = setup_transfer();
+    let res = send(&chain, contract_address, Energy::from(10000));
+
+    assert_update(&res)
+        .succeeded()
+        .transferred(helpers::ACC_0, Amount::from_micro_ccd(17))
+        .called(contract_address, EntrypointName::new_unchecked("send"))
+        .not_called(contract_address, EntrypointName::new_unchecked("deposit"))
+        .not_rolled_back();
+    // The assertions also work on the unwrapped success.
+    assert_update(res.as_ref().unwrap()).succeeded();
+}
+
+#[test]
+#[should_panic(expected = "Expected a transfer of")]
+fn test_transferred_wrong_amount() {
+    let (chain, contract_address) = setup_transfer();
+    let res = send(&chain, contract_address, Energy::from(10000));
+
+    assert_update(&res).transferred(helpers::ACC_0, Amount::from_micro_ccd(18));
+}
+
+#[test]
+#[should_panic(expected = "Expected entrypoint 'forward'")]
+fn test_not_called() {
+    let (chain, contract_address) = setup_transfer();
+    let res = send(&chain, contract_address, Energy::from(10000));
+
+    assert_update(&res).called(contract_address, EntrypointName::new_unchecked("forward"));
+}
+
+#[test]
+#[should_panic(expected = "Expected some calls to be rolled back")]
+fn test_no_rollbacks() {
+    let (chain, contract_address) = setup_transfer();
+    let res = send(&chain, contract_address, Energy::from(10000));
+
+    assert_update(&res).rolled_back();
+}
+
+#[test]
+fn test_failure() {
+    let (chain, contract_address) = setup_transfer();
+    let res = send(&chain, contract_address, Energy::from(10));
+
+    assert_update(&res).failed();
+}
+
+#[test]
+#[should_panic(expected = "Expected the contract to reject the update")]
+fn test_failure_is_not_rejection() {
+    let (chain, contract_address) = setup_transfer();
+    // Running out of energy is not a rejection by the contract.
+    let res = send(&chain, contract_address, Energy::from(10));
+
+    assert_update(&res).rejected();
+}
+
+#[test]
+#[should_panic(expected = "Expected the update to succeed")]
+fn test_failure_is_not_success() {
+    let (chain, contract_address) = setup_transfer();
+    let res = send(&chain, contract_address, Energy::from(10));
+
+    assert_update(&res).succeeded();
+}
+
+#[test]
+fn test_returned() {
+    let (mut chain, _, contract_address) = helpers::deploy_and_init("fib.wasm", "init_fib");
+
+    let update = chain.contract_update(
+        Signer::with_one_key(),
+        helpers::ACC_0,
+        Address::Account(helpers::ACC_0),
+        Energy::from(100000),
+        UpdateContractPayload {
+            amount:       Amount::zero(),
+            address:      contract_address,
+            receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+            message:      OwnedParameter::from_serial(&6u64).expect("Parameter has valid size"),
+        },
+    );
+
+    assert_update(&update).succeeded().state_changed().returned(13u64);
+}
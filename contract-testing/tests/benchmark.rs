@@ -0,0 +1,88 @@
+//! This module tests the benchmark harness.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+fn setup() -> (Chain, ContractAddress) {
+    let (chain, _, address) = helpers::deploy_and_init("fib.wasm", "init_fib");
+    (chain, address)
+}
+
+fn payload(address: ContractAddress, n: u64) -> UpdateContractPayload {
+    UpdateContractPayload {
+        address,
+        receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+        message: OwnedParameter::from_serial(&n).expect("Parameter has valid size"),
+        amount: Amount::zero(),
+    }
+}
+
+#[test]
+fn test_benchmark_update_and_invoke() {
+    let (chain, address) = setup();
+    let balance = chain.account_balance_available(helpers::ACC_0);
+    let benchmark = Benchmark::new().samples(5).warm_up(1);
+
+    let update = benchmark
+        .update(
+            &chain,
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100_000),
+            payload(address, 6),
+        )
+        .expect("Benchmark should succeed");
+    assert_eq!(update.times.len(), 5);
+    // Every sample starts from the same state, so the energy is the same.
+    let energy = update.execution_energy_summary().expect("There are samples");
+    assert_eq!(energy.min, energy.max);
+    // The chain is not modified.
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), balance);
+
+    let invoke = benchmark
+        .invoke(
+            &chain,
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100_000),
+            payload(address, 6),
+        )
+        .expect("Benchmark should succeed");
+    // The header cost is only charged for the update, but it is not part of the
+    // execution energy.
+    assert!(invoke.energy_used[0] < update.energy_used[0]);
+    assert_eq!(invoke.execution_energy, update.execution_energy);
+}
+
+#[test]
+fn test_scaling_curve() {
+    let (chain, address) = setup();
+    let curve = ScalingCurve::measure([2, 4, 8], |n| {
+        Benchmark::new().samples(2).invoke(
+            &chain,
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100_000),
+            payload(address, n),
+        )
+    })
+    .expect("Benchmarks should succeed");
+
+    let energies: Vec<f64> = curve
+        .points
+        .iter()
+        .map(|(_, result)| result.execution_energy_summary().expect("There are samples").median)
+        .collect();
+    assert!(energies.windows(2).all(|pair| pair[0] < pair[1]), "Energy grows with the input");
+    assert_eq!(curve.to_csv().lines().count(), 4);
+}
+
+#[test]
+fn test_summary() {
+    let summary = Summary::new([4.0, 1.0, 3.0, 2.0]).expect("There are measurements");
+    assert_eq!(summary.min, 1.0);
+    assert_eq!(summary.max, 4.0);
+    assert_eq!(summary.mean, 2.5);
+    assert_eq!(summary.median, 2.5);
+    assert!(Summary::new([]).is_none());
+}
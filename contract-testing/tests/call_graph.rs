@@ -0,0 +1,138 @@
+//! This module tests the reconstruction and rendering of call graphs from the
+//! trace elements of contract invocations.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Uses the call pattern
+/// A
+///  -->  B
+///         --> A
+///         <--
+///       B(trap)
+/// A <--
+/// from the checkpointing tests, where everything done by B is rolled back.
+#[test]
+fn test_call_graph_with_rollback() {
+    let (mut chain, mod_ref, contract_a) = helpers::deploy_and_init("checkpointing.wasm", "init_a");
+    let contract_b = helpers::init(&mut chain, mod_ref, "init_b");
+
+    let forward_parameter = (
+        contract_a,
+        0u16, // length of empty parameter
+        EntrypointName::new_unchecked("a_modify"),
+        Amount::zero(),
+    );
+    let forward_parameter_len = to_bytes(&forward_parameter).len();
+    let parameter = (
+        contract_b,
+        forward_parameter_len as u16,
+        forward_parameter,
+        EntrypointName::new_unchecked("b_forward_crash"),
+        Amount::zero(),
+    );
+
+    let update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      contract_a,
+                receive_name: OwnedReceiveName::new_unchecked("a.a_modify_proxy".into()),
+                message:      OwnedParameter::from_serial(&parameter)
+                    .expect("Parameter has valid size"),
+                amount:       Amount::from_micro_ccd(1),
+            },
+        )
+        .expect("Updating contract should succeed");
+
+    let graph = update.call_graph();
+    let [CallGraphItem::Call(root)] = &graph.roots[..] else {
+        panic!("Expected a single root call, got {graph:#?}");
+    };
+    assert_eq!(root.address, contract_a);
+    assert_eq!(
+        root.entrypoint.as_entrypoint_name(),
+        EntrypointName::new_unchecked("a_modify_proxy")
+    );
+    assert_eq!(root.outcome, CallOutcome::Success);
+    assert!(!root.rolled_back);
+    assert!(root.energy_used.energy > 0);
+
+    let [CallGraphItem::Call(b)] = &root.children[..] else {
+        panic!("Expected a single call from A, got {graph:#?}");
+    };
+    assert_eq!(b.address, contract_b);
+    assert_eq!(b.outcome, CallOutcome::Trapped);
+    assert!(b.rolled_back);
+    assert!(b.energy_used <= root.energy_used, "Nested calls use part of the caller's energy.");
+
+    let [CallGraphItem::Call(inner_a)] = &b.children[..] else {
+        panic!("Expected a single call from B, got {graph:#?}");
+    };
+    assert_eq!(inner_a.address, contract_a);
+    assert_eq!(inner_a.entrypoint.as_entrypoint_name(), EntrypointName::new_unchecked("a_modify"));
+    assert_eq!(inner_a.outcome, CallOutcome::Success);
+    assert!(inner_a.rolled_back, "Calls made by a failed call are rolled back.");
+    assert!(inner_a.children.is_empty());
+
+    let text = graph.to_string();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("<0,0> a_modify_proxy ("));
+    assert!(lines[1].starts_with("  <1,0> b_forward_crash ("));
+    assert!(lines[1].ends_with(", trapped) [rolled back]"));
+    assert!(lines[2].starts_with("    <0,0> a_modify ("));
+    assert!(lines[2].ends_with("[rolled back]"));
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph calls {"));
+    assert!(dot.contains("n0 -> n1;"));
+    assert!(dot.contains("n1 -> n2;"));
+    assert_eq!(dot.matches("style=dashed").count(), 2);
+
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.starts_with("flowchart TD"));
+    assert!(mermaid.contains("n0[\"#lt;0,0#gt; a_modify_proxy"));
+    assert!(mermaid.contains("n0 --> n1"));
+    assert!(mermaid.contains("class n1 rolledBack"));
+    assert!(mermaid.contains("class n2 rolledBack"));
+    assert!(!mermaid.contains("class n0 rolledBack"));
+}
+
+/// Tests that transfers to accounts are included in the call graph.
+#[test]
+fn test_call_graph_with_transfer() {
+    let (mut chain, _, contract_address) =
+        helpers::deploy_and_init("transfer.wasm", "init_transfer");
+
+    let update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("transfer.forward".into()),
+                message:      OwnedParameter::from_serial(&helpers::ACC_0)
+                    .expect("Parameter has valid size"),
+                amount:       Amount::from_micro_ccd(123),
+            },
+        )
+        .expect("Updating contract should succeed");
+
+    let graph = update.call_graph();
+    let [CallGraphItem::Call(root)] = &graph.roots[..] else {
+        panic!("Expected a single root call, got {graph:#?}");
+    };
+    assert_eq!(root.children, [CallGraphItem::Transfer {
+        from:        contract_address,
+        amount:      Amount::from_micro_ccd(123),
+        to:          helpers::ACC_0,
+        rolled_back: false,
+    }]);
+    assert!(root.energy_used.energy > 0);
+    assert!(root.energy_used <= update.energy_used);
+}
@@ -0,0 +1,73 @@
+//! This module tests recording checkpoints and rewinding the chain.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+fn update(chain: &mut Chain, address: ContractAddress) {
+    chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address,
+                receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+                message: OwnedParameter::from_serial(&6u64).expect("Parameter has valid size"),
+                amount: Amount::zero(),
+            },
+        )
+        .expect("Updating valid contract should work");
+}
+
+#[test]
+fn test_rewind() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+    chain.enable_checkpoints();
+
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            module_load_v1_raw(helpers::wasm_test_file("fib.wasm")).expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+    let address = helpers::init(&mut chain, res_deploy.module_reference, "init_fib");
+    let balance_after_init = chain.account_balance_available(helpers::ACC_0);
+
+    chain.tick_block_time(Duration::from_seconds(10)).expect("No overflow");
+    update(&mut chain, address);
+    update(&mut chain, address);
+    assert_eq!(chain.checkpoints().len(), 5);
+    assert_eq!(chain.checkpoints()[3].block_time, Timestamp::from_timestamp_millis(10_000));
+
+    // Go back to the state after the initialization.
+    chain.rewind_to(2).expect("Checkpoint exists");
+    assert_eq!(chain.checkpoints().len(), 3);
+    assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(0));
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), balance_after_init);
+
+    // Try a variation, which is recorded after the rewound checkpoint.
+    update(&mut chain, address);
+    assert_eq!(chain.checkpoints().last().map(|checkpoint| checkpoint.index), Some(3));
+
+    // Go back to before the module was deployed.
+    chain.rewind_to(0).expect("Checkpoint exists");
+    assert!(chain.get_contract(address).is_none());
+    assert!(chain.modules.is_empty());
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(10000)));
+
+    assert_eq!(
+        chain.rewind_to(5),
+        Err(CheckpointDoesNotExist {
+            index: 5,
+        })
+    );
+}
+
+#[test]
+fn test_checkpoints_disabled() {
+    let (mut chain, _) = helpers::deploy("fib.wasm");
+    assert!(chain.checkpoints().is_empty());
+    assert!(chain.rewind_to(0).is_err());
+}
@@ -0,0 +1,42 @@
+//! This module tests the CIS-2 conformance suite on a contract that is not a
+//! token contract.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+#[test]
+fn test_non_token_contract() {
+    let (chain, _, contract) = helpers::deploy_and_init("fib.wasm", "init_fib");
+
+    // The "minting" calls the contract, but the contract has no CIS-2 entrypoints.
+    let mut mints = 0;
+    let report = Cis2Conformance::new(contract, vec![1], |chain, owner| {
+        mints += 1;
+        chain.contract_update(
+            Signer::with_one_key(),
+            owner,
+            Address::Account(owner),
+            Energy::from(10000),
+            UpdateContractPayload {
+                address:      contract,
+                receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+                message:      OwnedParameter::from_serial(&6u64).expect("Parameter has valid size"),
+                amount:       Amount::zero(),
+            },
+        )
+    })
+    .run(&chain)
+    .expect("The contract exists");
+
+    assert!(!report.passed());
+    assert_eq!(report.outcome(Cis2Rule::TransferToContract), Some(&Cis2RuleOutcome::Skipped));
+    assert!(matches!(report.outcome(Cis2Rule::Supports), Some(Cis2RuleOutcome::Failed(_))));
+    // The hook is called once for every rule that is not skipped.
+    assert_eq!(mints, report.results.len() - 1);
+    // The chain is not modified.
+    assert!(chain.contracts.len() == 1 && chain.accounts.len() == 1);
+
+    let err = Cis2Conformance::new(ContractAddress::new(1, 0), vec![1], |_, _| unreachable!())
+        .run(&chain)
+        .expect_err("The contract does not exist");
+    assert_eq!(err.address, ContractAddress::new(1, 0));
+}
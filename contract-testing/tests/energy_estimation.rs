@@ -0,0 +1,131 @@
+//! This module contains tests for estimating the energy needed for contract
+//! initializations and updates.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+fn init_payload(mod_ref: ModuleReference) -> InitContractPayload {
+    InitContractPayload {
+        amount: Amount::zero(),
+        mod_ref,
+        init_name: OwnedContractName::new_unchecked("init_fib".into()),
+        param: OwnedParameter::empty(),
+    }
+}
+
+fn update_payload(address: ContractAddress, entrypoint: &str) -> UpdateContractPayload {
+    UpdateContractPayload {
+        amount: Amount::zero(),
+        address,
+        receive_name: OwnedReceiveName::new_unchecked(format!("fib.{entrypoint}")),
+        message: OwnedParameter::from_serial(&6u64).expect("Parameter has valid size"),
+    }
+}
+
+#[test]
+fn test_estimates_are_minimal() {
+    let (mut chain, mod_ref) = helpers::deploy("fib.wasm");
+
+    // Estimate and check the initialization.
+    let balance_before = chain.account_balance_available(helpers::ACC_0);
+    let init_estimate = chain
+        .estimate_init_energy(Signer::with_one_key(), helpers::ACC_0, init_payload(mod_ref))
+        .expect("Estimating a valid initialization should work");
+    assert_eq!(
+        chain.account_balance_available(helpers::ACC_0),
+        balance_before,
+        "Estimation should not charge the account."
+    );
+    assert!(chain.contracts.is_empty(), "Estimation should not create contracts.");
+    assert_eq!(
+        init_estimate.energy,
+        init_estimate.header_energy
+            + init_estimate.execution_energy
+            + init_estimate.storage_energy
+            + init_estimate.module_load_energy
+    );
+
+    chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            init_estimate.energy - Energy::from(1),
+            init_payload(mod_ref),
+        )
+        .expect_err("One energy less than the estimate should not be sufficient");
+    let init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            init_estimate.energy,
+            init_payload(mod_ref),
+        )
+        .expect("The estimated energy should be sufficient");
+    assert_eq!(init.energy_used, init_estimate.energy);
+
+    // Estimate and check the update.
+    let update_estimate = chain
+        .estimate_update_energy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            update_payload(init.contract_address, "receive"),
+        )
+        .expect("Estimating a valid update should work");
+    assert_eq!(
+        update_estimate.energy,
+        update_estimate.header_energy
+            + update_estimate.execution_energy
+            + update_estimate.storage_energy
+            + update_estimate.module_load_energy
+    );
+    assert!(update_estimate.module_load_energy.energy > 0);
+    assert!(update_estimate.header_energy.energy > 0);
+    // Invoking the entrypoint does not check a transaction header.
+    let invoke = chain
+        .contract_invoke(
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            update_estimate.energy,
+            update_payload(init.contract_address, "receive"),
+        )
+        .expect("Invoking a valid entrypoint should work");
+    assert_eq!(invoke.energy_used, update_estimate.energy - update_estimate.header_energy);
+
+    let err = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            update_estimate.energy - Energy::from(1),
+            update_payload(init.contract_address, "receive"),
+        )
+        .expect_err("One energy less than the estimate should not be sufficient");
+    assert!(matches!(err.kind, ContractInvokeErrorKind::OutOfEnergy { .. }));
+    let update = chain
+        .contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            update_estimate.energy,
+            update_payload(init.contract_address, "receive"),
+        )
+        .expect("The estimated energy should be sufficient");
+    assert_eq!(update.energy_used, update_estimate.energy);
+    assert_eq!(update.storage_energy, update_estimate.storage_energy);
+    assert_eq!(update.module_load_energy, update_estimate.module_load_energy);
+}
+
+#[test]
+fn test_estimate_failure() {
+    let (chain, _, address) = helpers::deploy_and_init("fib.wasm", "init_fib");
+
+    let err = chain
+        .estimate_update_energy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            update_payload(address, "missing"),
+        )
+        .expect_err("Estimating an update of a missing entrypoint should fail");
+    assert!(matches!(err, EnergyEstimateError::TransactionFailed(_)));
+}
@@ -23,3 +23,57 @@ pub(crate) const ACC_1: AccountAddress = AccountAddress([1; 32]);
 pub(crate) fn wasm_test_file(file_name: &str) -> String {
     format!("{WASM_TEST_FOLDER}/{file_name}")
 }
+
+/// Create a chain with `ACC_0` and deploy the module in the wasm test file
+/// `wasm` from it.
+///
+/// Dead code is allowed for the same reason as for `ACC_1`.
+#[allow(dead_code)]
+pub(crate) fn deploy(wasm: &str) -> (Chain, ModuleReference) {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(ACC_0, Amount::from_ccd(10000)));
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            ACC_0,
+            module_load_v1_raw(wasm_test_file(wasm)).expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+    (chain, res_deploy.module_reference)
+}
+
+/// Initialize the contract `init_name` from a deployed module, with `ACC_0`
+/// as the sender, an empty parameter and no amount.
+///
+/// Dead code is allowed for the same reason as for `ACC_1`.
+#[allow(dead_code)]
+pub(crate) fn init(
+    chain: &mut Chain,
+    mod_ref: ModuleReference,
+    init_name: &str,
+) -> ContractAddress {
+    chain
+        .contract_init(Signer::with_one_key(), ACC_0, Energy::from(10000), InitContractPayload {
+            mod_ref,
+            init_name: OwnedContractName::new_unchecked(init_name.into()),
+            param: OwnedParameter::empty(),
+            amount: Amount::zero(),
+        })
+        .expect("Initializing valid contract should work")
+        .contract_address
+}
+
+/// Create a chain with `ACC_0`, deploy the module in the wasm test file `wasm`
+/// and initialize the contract `init_name` from it.
+///
+/// Returns the chain, the module reference and the address of the contract.
+/// Dead code is allowed for the same reason as for `ACC_1`.
+#[allow(dead_code)]
+pub(crate) fn deploy_and_init(
+    wasm: &str,
+    init_name: &str,
+) -> (Chain, ModuleReference, ContractAddress) {
+    let (mut chain, mod_ref) = deploy(wasm);
+    let address = init(&mut chain, mod_ref, init_name);
+    (chain, mod_ref, address)
+}
@@ -0,0 +1,37 @@
+//! This module contains tests for the process-wide cache of compiled modules.
+use concordium_smart_contract_testing::*;
+use std::sync::Arc;
+mod helpers;
+
+/// Test that modules deployed on several chains, also in parallel, are only
+/// compiled once and share the same artifact.
+#[test]
+fn test_module_cache_shared_between_chains() {
+    let deploy = || {
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(1000)));
+        let deployment = chain
+            .module_deploy_v1(
+                Signer::with_one_key(),
+                helpers::ACC_0,
+                module_load_v1_raw(helpers::wasm_test_file("fib.wasm"))
+                    .expect("Module should exist"),
+            )
+            .expect("Deploying valid module should work");
+        let module = chain.get_module(deployment.module_reference).expect("Module is deployed");
+        (deployment, module.artifact.clone(), module.size)
+    };
+
+    let handles: Vec<_> = (0..4).map(|_| std::thread::spawn(deploy)).collect();
+    let results: Vec<_> =
+        handles.into_iter().map(|handle| handle.join().expect("Thread should not panic")).collect();
+
+    let (first_deployment, first_artifact, first_size) = &results[0];
+    assert!(module_cache().contains(first_deployment.module_reference, false));
+    assert!(!module_cache().contains(first_deployment.module_reference, true));
+    for (deployment, artifact, size) in &results[1..] {
+        assert_eq!(deployment, first_deployment);
+        assert_eq!(size, first_size);
+        assert!(Arc::ptr_eq(artifact, first_artifact));
+    }
+}
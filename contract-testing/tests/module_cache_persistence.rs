@@ -0,0 +1,52 @@
+//! This module contains tests for persisting compiled modules to disk. It is
+//! kept separate from the other module cache tests since it clears the
+//! process-wide cache, which would interfere with tests running in parallel.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that modules are persisted to and loaded from the persistence
+/// directory.
+#[test]
+fn test_module_cache_persistence() {
+    let dir = std::env::temp_dir().join(format!("module-cache-test-{}", std::process::id()));
+    module_cache().set_persistence_dir(Some(dir.clone()));
+
+    let module = module_load_v1_raw(helpers::wasm_test_file("checkpointing.wasm"))
+        .expect("Module should exist");
+    let deploy = |module: WasmModule| {
+        let mut chain = Chain::new();
+        chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(1000)));
+        chain
+            .module_deploy_v1_debug(Signer::with_one_key(), helpers::ACC_0, module, true)
+            .expect("Deploying valid module should work")
+    };
+
+    let deployment = deploy(module.clone());
+    let persisted = std::fs::read_dir(&dir).expect("Directory should exist").count();
+    assert_eq!(persisted, 1);
+
+    // Deploying again after clearing the in-memory cache uses the persisted module.
+    module_cache().clear();
+    assert!(!module_cache().contains(deployment.module_reference, true));
+    assert_eq!(deploy(module.clone()), deployment);
+    assert!(module_cache().contains(deployment.module_reference, true));
+
+    // A persisted module in another format is not loaded, but compiled and
+    // persisted again.
+    let path = std::fs::read_dir(&dir)
+        .expect("Directory should exist")
+        .next()
+        .expect("A module should be persisted")
+        .expect("Entry can be read")
+        .path();
+    let mut bytes = std::fs::read(&path).expect("Persisted module can be read");
+    bytes[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(&path, &bytes).expect("Persisted module can be written");
+    module_cache().clear();
+    assert_eq!(deploy(module), deployment);
+    let rewritten = std::fs::read(&path).expect("Persisted module can be read");
+    assert_ne!(rewritten, bytes, "The stale module should be replaced.");
+
+    module_cache().set_persistence_dir(None);
+    std::fs::remove_dir_all(dir).expect("Directory can be removed");
+}
@@ -0,0 +1,69 @@
+//! This module tests inspecting modules.
+use concordium_rust_sdk::base::smart_contracts::ModuleSource;
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+#[test]
+fn test_module_info() {
+    let module =
+        module_load_v1_raw(helpers::wasm_test_file("fib.wasm")).expect("module should exist");
+    let info = ModuleInfo::from_wasm_module(&module).expect("Module should be valid");
+
+    let fib = &info.contracts[&OwnedContractName::new_unchecked("init_fib".into())];
+    assert_eq!(
+        fib.entrypoints,
+        [OwnedEntrypointName::new_unchecked("receive".into())].into_iter().collect()
+    );
+    assert!(!fib.has_fallback);
+    assert!(info.imports.iter().all(|import| import.module == "concordium"));
+    assert!(info.sections.iter().any(|section| section.name == "code" && section.size > 0));
+    assert!(info.schema.is_none());
+    assert!(info.build_info().is_none());
+
+    // The entrypoints of the deployed module are the same.
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10000)));
+    let res_deploy = chain
+        .module_deploy_v1(Signer::with_one_key(), helpers::ACC_0, module)
+        .expect("Deploying valid module should work");
+    assert_eq!(chain.modules[&res_deploy.module_reference].contracts(), info.contracts);
+}
+
+#[test]
+fn test_module_info_fallback() {
+    let module =
+        module_load_v1_raw(helpers::wasm_test_file("fallback.wasm")).expect("module should exist");
+    let info = ModuleInfo::from_wasm_module(&module).expect("Module should be valid");
+
+    // Contract `one` only has a fallback entrypoint, which forwards calls to `two`.
+    let one = &info.contracts[&OwnedContractName::new_unchecked("init_one".into())];
+    assert!(one.has_fallback);
+    assert!(one.entrypoints.is_empty());
+    let two = &info.contracts[&OwnedContractName::new_unchecked("init_two".into())];
+    assert!(!two.has_fallback);
+    assert_eq!(
+        two.entrypoints,
+        [OwnedEntrypointName::new_unchecked("do".into())].into_iter().collect()
+    );
+}
+
+#[test]
+fn test_module_info_malformed() {
+    let module =
+        module_load_v1_raw(helpers::wasm_test_file("fib.wasm")).expect("module should exist");
+    let malformed = |source: &[u8]| WasmModule {
+        version: module.version,
+        source:  ModuleSource::from(source.to_vec()),
+    };
+    let source: &[u8] = module.source.as_ref();
+
+    // A module which is cut off in the middle of a section.
+    ModuleInfo::from_wasm_module(&malformed(&source[..source.len() - 1]))
+        .expect_err("A truncated module should be malformed");
+    // A module without the Wasm magic number.
+    let mut wrong_magic = source.to_vec();
+    wrong_magic[0] = 1;
+    ModuleInfo::from_wasm_module(&malformed(&wrong_magic))
+        .expect_err("A module without the magic number should be malformed");
+    ModuleInfo::from_wasm_module(&malformed(&[])).expect_err("An empty module should be malformed");
+}
@@ -0,0 +1,283 @@
+//! This module tests executing entrypoints natively instead of in Wasm.
+#![cfg(feature = "native")]
+use concordium_smart_contract_testing::*;
+use concordium_std::prims;
+mod helpers;
+
+/// Deploy the fib module and initialize the contract in Wasm.
+fn setup() -> (Chain, ModuleReference, ContractAddress) {
+    helpers::deploy_and_init("fib.wasm", "init_fib")
+}
+
+fn update(
+    chain: &mut Chain,
+    address: ContractAddress,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    chain.contract_update(
+        Signer::with_one_key(),
+        helpers::ACC_0,
+        Address::Account(helpers::ACC_0),
+        Energy::from(10000),
+        UpdateContractPayload {
+            address,
+            receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+            message: OwnedParameter::from_serial(&6u64).expect("Parameter has valid size"),
+            amount: Amount::zero(),
+        },
+    )
+}
+
+/// Returns the parameter via the host functions.
+extern "C" fn echo(_amount: Amount) -> i32 {
+    unsafe {
+        let size = prims::get_parameter_size(0);
+        let mut parameter = vec![0u8; size as usize];
+        prims::get_parameter_section(0, parameter.as_mut_ptr(), size as u32, 0);
+        prims::write_output(parameter.as_ptr(), size as u32, 0);
+    }
+    0
+}
+
+extern "C" fn reject(_amount: Amount) -> i32 { -42 }
+
+extern "C" fn succeed(_amount: Amount) -> i32 { 0 }
+
+#[test]
+fn test_register_errors() {
+    let (mut chain, module_reference, _) = setup();
+
+    let err = chain
+        .module_register_native(module_reference, NativeModule::new().receive("fib.missing", echo))
+        .expect_err("Registering a missing entrypoint should fail");
+    assert!(matches!(err, NativeModuleError::NotExported { name } if name == "fib.missing"));
+
+    let err = chain
+        .module_register_native(ModuleReference::from([0u8; 32]), NativeModule::new())
+        .expect_err("Registering a missing module should fail");
+    assert!(matches!(err, NativeModuleError::ModuleDoesNotExist(_)));
+}
+
+#[test]
+fn test_native_receive() {
+    let (mut chain, module_reference, address) = setup();
+    chain
+        .module_register_native(module_reference, NativeModule::new().receive("fib.receive", echo))
+        .expect("Registering an existing entrypoint should work");
+
+    let res = update(&mut chain, address).expect("Native update should succeed");
+    assert_eq!(res.return_value, to_bytes(&6u64));
+    assert!(!res.state_changed);
+
+    chain
+        .module_register_native(
+            module_reference,
+            NativeModule::new().receive("fib.receive", reject),
+        )
+        .expect("Registering an existing entrypoint should work");
+    assert_update(&update(&mut chain, address)).rejected_with_code(-42);
+}
+
+#[test]
+fn test_native_init() {
+    let (mut chain, module_reference, _) = setup();
+    chain
+        .module_register_native(module_reference, NativeModule::new().init("init_fib", succeed))
+        .expect("Registering an existing entrypoint should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   module_reference,
+                init_name: OwnedContractName::new_unchecked("init_fib".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Native initialization should succeed");
+    assert!(chain.get_contract(res_init.contract_address).is_some());
+}
+
+extern "C" fn trapping(_amount: Amount) -> i32 { concordium_std::trap() }
+
+/// Test that a trap in a native entrypoint fails the update with a runtime
+/// error, as in Wasm, instead of aborting the test.
+#[test]
+fn test_native_trap() {
+    let (mut chain, module_reference, address) = setup();
+    chain
+        .module_register_native(
+            module_reference,
+            NativeModule::new().receive("fib.receive", trapping),
+        )
+        .expect("Registering an existing entrypoint should work");
+
+    let err = update(&mut chain, address).expect_err("Trapping update should fail");
+    match err.kind {
+        ContractInvokeErrorKind::ExecutionError {
+            failure_kind: InvokeFailure::RuntimeError,
+            ..
+        } => (),
+        _ => panic!("Test failed, expected a runtime error."),
+    }
+
+    // The chain can still be used afterwards.
+    chain
+        .module_register_native(module_reference, NativeModule::new().receive("fib.receive", echo))
+        .expect("Registering an existing entrypoint should work");
+    let res = update(&mut chain, address).expect("Native update should succeed");
+    assert_eq!(res.return_value, to_bytes(&6u64));
+}
+
+/// Invokes a transfer with malformed data and rejects unless it fails with a
+/// runtime error.
+extern "C" fn malformed_invoke(_amount: Amount) -> i32 {
+    let data = [0u8; 3];
+    let response = unsafe { prims::invoke(0, data.as_ptr(), data.len() as u32) };
+    if response == 0x06 << 32 {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Test that an operation invoked with malformed data fails with a runtime
+/// error instead of aborting the test.
+#[test]
+fn test_native_malformed_invoke() {
+    let (mut chain, module_reference, address) = setup();
+    chain
+        .module_register_native(
+            module_reference,
+            NativeModule::new().receive("fib.receive", malformed_invoke),
+        )
+        .expect("Registering an existing entrypoint should work");
+
+    update(&mut chain, address).expect("The malformed operation should fail with a runtime error");
+}
+
+extern "C-unwind" fn trapping_unwind(_amount: Amount) -> i32 { concordium_std::trap() }
+
+/// Test that a trap in a native entrypoint with the `"C-unwind"` ABI fails the
+/// update with a runtime error, and that the entrypoint can be executed again
+/// afterwards.
+#[test]
+fn test_native_trap_unwind() {
+    let (mut chain, module_reference, address) = setup();
+    chain
+        .module_register_native(
+            module_reference,
+            NativeModule::new().receive_unwind("fib.receive", trapping_unwind),
+        )
+        .expect("Registering an existing entrypoint should work");
+
+    for _ in 0..2 {
+        let err = update(&mut chain, address).expect_err("Trapping update should fail");
+        assert!(
+            matches!(err.kind, ContractInvokeErrorKind::ExecutionError {
+                failure_kind: InvokeFailure::RuntimeError,
+                ..
+            }),
+            "Test failed, expected a runtime error."
+        );
+    }
+}
+
+/// The fib contract from the examples, written with `#[init]` and `#[receive]`
+/// to be executed natively.
+mod native_fib {
+    use concordium_std::*;
+
+    #[derive(Serialize)]
+    pub struct State {
+        result: u64,
+    }
+
+    #[init(contract = "fib")]
+    fn contract_init(_ctx: &InitContext, _state_builder: &mut StateBuilder) -> InitResult<State> {
+        Ok(State {
+            result: 0,
+        })
+    }
+
+    /// Compute the nth Fibonacci number by calling the contract itself
+    /// recursively. Traps if the result overflows.
+    #[receive(contract = "fib", name = "receive", parameter = "u64", mutable)]
+    fn contract_receive(ctx: &ReceiveContext, host: &mut Host<State>) -> ReceiveResult<u64> {
+        let n: u64 = ctx.parameter_cursor().get()?;
+        if n <= 1 {
+            host.state_mut().result = 1;
+            return Ok(1);
+        }
+        let self_address = ctx.self_address();
+        let mut call = |n: u64| -> u64 {
+            let (state_modified, return_value) = host
+                .invoke_contract_raw(
+                    &self_address,
+                    Parameter::new_unchecked(&n.to_le_bytes()[..]),
+                    EntrypointName::new_unchecked("receive"),
+                    Amount::zero(),
+                )
+                .unwrap_abort();
+            claim!(state_modified, "The call should modify the state.");
+            let result: u64 = return_value.unwrap_abort().get().unwrap_abort();
+            claim_eq!(host.state().result, result, "The state should be reloaded.");
+            result
+        };
+        let n2 = call(n - 2);
+        let n1 = call(n - 1);
+        let result = n1.checked_add(n2).unwrap_abort();
+        host.state_mut().result = result;
+        Ok(result)
+    }
+}
+
+/// Test a contract written with the `#[init]` and `#[receive]` macros, which
+/// calls itself recursively.
+#[test]
+fn test_native_macros() {
+    let (mut chain, module_reference, _) = setup();
+    chain
+        .module_register_native(
+            module_reference,
+            NativeModule::new()
+                .init("init_fib", native_fib::export_contract_init)
+                .receive("fib.receive", native_fib::export_contract_receive),
+        )
+        .expect("Registering existing entrypoints should work");
+
+    let res_init = chain
+        .contract_init(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Energy::from(10000),
+            InitContractPayload {
+                mod_ref:   module_reference,
+                init_name: OwnedContractName::new_unchecked("init_fib".into()),
+                param:     OwnedParameter::empty(),
+                amount:    Amount::zero(),
+            },
+        )
+        .expect("Native initialization should succeed");
+
+    let update_fib = |chain: &mut Chain, n: u64| {
+        chain.contract_update(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            UpdateContractPayload {
+                address:      res_init.contract_address,
+                receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+                message:      OwnedParameter::from_serial(&n).expect("Parameter has valid size"),
+                amount:       Amount::zero(),
+            },
+        )
+    };
+
+    let res = update_fib(&mut chain, 6).expect("Native update should succeed");
+    assert_eq!(res.return_value, to_bytes(&13u64));
+    assert!(res.state_changed);
+}
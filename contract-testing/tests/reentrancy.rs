@@ -0,0 +1,226 @@
+//! This module tests the exploration of reentrant calls into contracts that
+//! call other contracts.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Deploy the checkpointing module and initialize the contracts A and B.
+fn setup() -> (Chain, ContractAddress, ContractAddress) {
+    let (mut chain, mod_ref, contract_a) = helpers::deploy_and_init("checkpointing.wasm", "init_a");
+    let contract_b = helpers::init(&mut chain, mod_ref, "init_b");
+    (chain, contract_a, contract_b)
+}
+
+/// The payload for the call pattern
+/// A
+///   -->  B
+///          --> A (no modification)
+///          <--
+///        B
+/// A <--
+/// where A expects its state to be unchanged by the call.
+fn payload(contract_a: ContractAddress, contract_b: ContractAddress) -> UpdateContractPayload {
+    let forward_parameter = (
+        contract_a,
+        0u16, // length of empty parameter
+        EntrypointName::new_unchecked("a_no_modify"),
+        Amount::zero(),
+    );
+    let forward_parameter_len = to_bytes(&forward_parameter).len();
+    let parameter = (
+        contract_b,
+        forward_parameter_len as u16,
+        forward_parameter,
+        EntrypointName::new_unchecked("b_forward"),
+        Amount::zero(),
+    );
+    UpdateContractPayload {
+        address:      contract_a,
+        receive_name: OwnedReceiveName::new_unchecked("a.a_modify_proxy".into()),
+        message:      OwnedParameter::from_serial(&parameter).expect("Parameter has valid size"),
+        amount:       Amount::zero(),
+    }
+}
+
+fn reenter_no_modify() -> ReentrantCall {
+    ReentrantCall::Reenter {
+        entrypoint: OwnedEntrypointName::new_unchecked("a_no_modify".into()),
+        parameter:  OwnedParameter::empty(),
+        amount:     Amount::zero(),
+    }
+}
+
+#[test]
+fn test_records_outgoing_calls() {
+    let (chain, contract_a, contract_b) = setup();
+    let balance_before = chain.account_balance_available(helpers::ACC_0);
+
+    let report = chain
+        .explore_reentrancy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            payload(contract_a, contract_b),
+            &[reenter_no_modify()],
+            |_| Ok(()),
+        )
+        .expect("Exploring a successful update should work");
+
+    // Only the call made by A is recorded, not the one made by B.
+    assert_eq!(report.outgoing_calls, [OutgoingCall {
+        caller_entrypoint: OwnedEntrypointName::new_unchecked("a_modify_proxy".into()),
+        callee:            contract_b,
+        callee_entrypoint: OwnedEntrypointName::new_unchecked("b_forward".into()),
+    }]);
+    assert_eq!(report.attempts, 1);
+    assert!(report.is_safe(), "Unexpected findings: {:#?}", report.findings);
+    // The contract has no reentrancy guard.
+    assert_eq!(report.blocked, 0);
+    assert!(!report.is_guarded());
+    // The exploration does not charge the invoker.
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), balance_before);
+}
+
+#[test]
+fn test_invariant_broken() {
+    let (chain, contract_a, contract_b) = setup();
+
+    let report = chain
+        .explore_reentrancy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            payload(contract_a, contract_b),
+            &[reenter_no_modify()],
+            |_| Err("always broken".into()),
+        )
+        .expect("Exploring a successful update should work");
+
+    let [finding] = &report.findings[..] else {
+        panic!("Expected a single finding, got {:#?}", report.findings);
+    };
+    assert_eq!(finding.call_index, 0);
+    assert_eq!(finding.outgoing_call.callee, contract_b);
+    assert_eq!(finding.reentrant_call, reenter_no_modify());
+    assert_eq!(finding.kind, ReentrancyFindingKind::InvariantBroken {
+        message: "always broken".into(),
+    });
+}
+
+#[test]
+fn test_missing_attacker() {
+    let (chain, contract_a, contract_b) = setup();
+
+    let report = chain
+        .explore_reentrancy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            payload(contract_a, contract_b),
+            &[ReentrantCall::Attacker {
+                address:    ContractAddress::new(42, 0),
+                entrypoint: OwnedEntrypointName::new_unchecked("attack".into()),
+                amount:     Amount::zero(),
+            }],
+            |_| Err("always broken".into()),
+        )
+        .expect("Exploring a successful update should work");
+
+    // A missing attacker is never called.
+    assert_eq!(report.outgoing_calls.len(), 1);
+    assert_eq!(report.attempts, 0);
+    assert!(report.is_safe());
+    assert!(!report.is_guarded(), "A contract is not guarded if no attempts were made.");
+}
+
+#[test]
+fn test_failing_update() {
+    let (chain, contract_a, contract_b) = setup();
+
+    let mut payload = payload(contract_a, contract_b);
+    payload.receive_name = OwnedReceiveName::new_unchecked("a.missing".into());
+    chain
+        .explore_reentrancy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            payload,
+            &[reenter_no_modify()],
+            |_| Ok(()),
+        )
+        .expect_err("Exploring a failing update should fail");
+}
+
+/// A contract whose entrypoint acquires a reentrancy guard and then calls
+/// itself. It is run natively in place of the entrypoints of the fib module.
+#[cfg(feature = "native")]
+mod guarded {
+    use concordium_std::*;
+
+    #[init(contract = "fib")]
+    fn contract_init(_ctx: &InitContext, _state_builder: &mut StateBuilder) -> InitResult<()> {
+        Ok(())
+    }
+
+    #[receive(contract = "fib", name = "receive", mutable)]
+    fn contract_receive(ctx: &ReceiveContext, host: &mut Host<()>) -> ReceiveResult<()> {
+        let _guard = host.state_builder().enter_non_reentrant()?;
+        let self_address = ctx.self_address();
+        let result = host.invoke_contract_raw(
+            &self_address,
+            Parameter::empty(),
+            EntrypointName::new_unchecked("receive"),
+            Amount::zero(),
+        );
+        claim!(result.is_err(), "The nested call should be blocked by the guard.");
+        Ok(())
+    }
+}
+
+/// Test that reentrant calls rejected by a `ReentrancyGuard` are counted as
+/// blocked.
+#[cfg(feature = "native")]
+#[test]
+fn test_guarded() {
+    // The native entrypoints are registered before the initialization, so
+    // that the contract is initialized by the native init function.
+    let (mut chain, mod_ref) = helpers::deploy("fib.wasm");
+    chain
+        .module_register_native(
+            mod_ref,
+            NativeModule::new()
+                .init("init_fib", guarded::export_contract_init)
+                .receive("fib.receive", guarded::export_contract_receive),
+        )
+        .expect("Registering existing entrypoints should work");
+    let contract = helpers::init(&mut chain, mod_ref, "init_fib");
+
+    let report = chain
+        .explore_reentrancy(
+            Signer::with_one_key(),
+            helpers::ACC_0,
+            Address::Account(helpers::ACC_0),
+            Energy::from(100000),
+            UpdateContractPayload {
+                address:      contract,
+                receive_name: OwnedReceiveName::new_unchecked("fib.receive".into()),
+                message:      OwnedParameter::empty(),
+                amount:       Amount::zero(),
+            },
+            &[ReentrantCall::Reenter {
+                entrypoint: OwnedEntrypointName::new_unchecked("receive".into()),
+                parameter:  OwnedParameter::empty(),
+                amount:     Amount::zero(),
+            }],
+            |_| Ok(()),
+        )
+        .expect("Exploring a successful update should work");
+
+    assert_eq!(report.attempts, 1);
+    assert_eq!(report.blocked, 1);
+    assert!(report.is_guarded());
+    assert!(report.is_safe(), "Unexpected findings: {:#?}", report.findings);
+}
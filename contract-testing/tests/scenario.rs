@@ -0,0 +1,94 @@
+//! This module tests running declarative scenarios.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Parse and run a scenario with the wasm test folder as the base directory.
+fn run(json: &str) -> Result<Chain, ScenarioError> {
+    Scenario::from_json(json).expect("Scenario should parse").run(helpers::WASM_TEST_FOLDER)
+}
+
+#[test]
+fn test_init_tick_and_balances() {
+    let chain = run(r#"{
+        "accounts": [
+            { "name": "alice", "balance": "10000" },
+            { "name": "bob", "balance": "1.5" }
+        ],
+        "modules": [{ "name": "fib", "path": "fib.wasm" }],
+        "steps": [
+            { "init": { "name": "c", "sender": "alice", "module": "fib", "contract": "fib" } },
+            { "tick": "1h" },
+            { "check_balances": { "bob": "1.5", "c": "0" } }
+        ]
+    }"#)
+    .expect("Scenario should succeed");
+
+    assert_eq!(chain.block_time(), Timestamp::from_timestamp_millis(3_600_000));
+    let bob = TestAccount::from_seed("bob").address();
+    assert_eq!(chain.account_balance_available(bob), Some(Amount::from_micro_ccd(1_500_000)));
+}
+
+#[test]
+fn test_failed_expectations() {
+    let err = run(r#"{
+        "accounts": [{ "name": "alice", "balance": "10000" }],
+        "modules": [{ "name": "fib", "path": "fib.wasm" }],
+        "steps": [
+            { "init": { "name": "c", "sender": "alice", "module": "fib", "contract": "fib" } },
+            { "check_balances": { "c": "1" } }
+        ]
+    }"#)
+    .expect_err("Balance check should fail");
+    assert!(matches!(err, ScenarioError::ExpectationFailed {
+        step: 2,
+        ..
+    }));
+
+    let err = run(r#"{
+        "accounts": [{ "name": "alice", "balance": "10000" }],
+        "modules": [{ "name": "fib", "path": "fib.wasm" }],
+        "steps": [
+            { "init": {
+                "name": "c", "sender": "alice", "module": "fib", "contract": "fib",
+                "expect": { "reject": true }
+            } }
+        ]
+    }"#)
+    .expect_err("Initialization should not be rejected");
+    assert!(matches!(err, ScenarioError::ExpectationFailed {
+        step: 1,
+        ..
+    }));
+}
+
+#[test]
+fn test_invalid_scenarios() {
+    let err = Scenario::from_json(r#"{ "steps": [{ "wait": "1h" }] }"#)
+        .expect_err("Unknown steps should be rejected");
+    assert!(matches!(err, ScenarioError::Parse(_)));
+
+    let err = run(r#"{
+        "accounts": [{ "name": "alice", "balance": "10000" }],
+        "steps": [{ "init": { "name": "c", "sender": "bob", "module": "fib", "contract": "fib" } }]
+    }"#)
+    .expect_err("Unknown accounts should be rejected");
+    assert!(matches!(err, ScenarioError::InvalidStep {
+        step: 1,
+        ..
+    }));
+
+    // The test module has no embedded schema, so parameters cannot be encoded.
+    let err = run(r#"{
+        "accounts": [{ "name": "alice", "balance": "10000" }],
+        "modules": [{ "name": "fib", "path": "fib.wasm" }],
+        "steps": [
+            { "init": { "name": "c", "sender": "alice", "module": "fib", "contract": "fib" } },
+            { "update": { "sender": "alice", "contract": "c", "entrypoint": "receive", "parameter": 6 } }
+        ]
+    }"#)
+    .expect_err("Parameters without a schema should be rejected");
+    assert!(matches!(err, ScenarioError::InvalidStep {
+        step: 2,
+        ..
+    }));
+}
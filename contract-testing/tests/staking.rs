@@ -0,0 +1,103 @@
+//! This module contains tests for staking, stake cooldowns and release
+//! schedules on test accounts.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Test that stake is unavailable until the cooldown has passed.
+#[test]
+fn test_stake_cooldown() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(100)));
+
+    chain.account_add_stake(helpers::ACC_0, Amount::from_ccd(40)).expect("Stake is below total");
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(60)));
+
+    chain
+        .account_remove_stake(helpers::ACC_0, Amount::from_ccd(10), Duration::from_millis(100))
+        .expect("Stake is sufficient");
+    chain
+        .account_remove_stake(helpers::ACC_0, Amount::from_ccd(20), Duration::from_millis(200))
+        .expect("Stake is sufficient");
+
+    // Only 10 CCD of stake is not in cooldown.
+    let err = chain
+        .account_remove_stake(helpers::ACC_0, Amount::from_ccd(11), Duration::from_millis(0))
+        .expect_err("Stake not in cooldown is insufficient");
+    assert!(matches!(err, AccountBalanceUpdateError::InsufficientStake));
+
+    chain.tick_block_time(Duration::from_millis(99)).unwrap();
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(60)));
+
+    chain.tick_block_time(Duration::from_millis(1)).unwrap();
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(70)));
+
+    chain.tick_block_time(Duration::from_millis(100)).unwrap();
+    assert_eq!(
+        chain.account_balance(helpers::ACC_0),
+        Some(AccountBalance {
+            total:  Amount::from_ccd(100),
+            staked: Amount::from_ccd(10),
+            locked: Amount::zero(),
+        })
+    );
+}
+
+/// Test that staking more than the total balance fails.
+#[test]
+fn test_stake_exceeding_total() {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(100)));
+
+    let err = chain
+        .account_add_stake(helpers::ACC_0, Amount::from_ccd(101))
+        .expect_err("Stake exceeds the total balance");
+    assert!(matches!(err, AccountBalanceUpdateError::InsufficientBalance));
+
+    let err = chain
+        .account_add_stake(helpers::ACC_1, Amount::from_ccd(1))
+        .expect_err("Account does not exist");
+    assert!(matches!(err, AccountBalanceUpdateError::AccountDoesNotExist(_)));
+}
+
+/// Test that locked amounts are released according to their schedule, and that
+/// locked amounts can be staked.
+#[test]
+fn test_release_schedule() {
+    let mut chain = Chain::new_with_time(Timestamp::from_timestamp_millis(100));
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(10)));
+
+    chain
+        .account_add_release_schedule(helpers::ACC_0, [
+            // Already passed, so it is released immediately.
+            (Timestamp::from_timestamp_millis(50), Amount::from_ccd(1)),
+            (Timestamp::from_timestamp_millis(200), Amount::from_ccd(2)),
+            (Timestamp::from_timestamp_millis(300), Amount::from_ccd(3)),
+        ])
+        .expect("Schedule is valid");
+    assert_eq!(
+        chain.account_balance(helpers::ACC_0),
+        Some(AccountBalance {
+            total:  Amount::from_ccd(16),
+            staked: Amount::zero(),
+            locked: Amount::from_ccd(5),
+        })
+    );
+
+    // Staking overlaps with the locked amount.
+    chain.account_add_stake(helpers::ACC_0, Amount::from_ccd(4)).expect("Stake is below total");
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(11)));
+
+    chain.tick_block_time(Duration::from_millis(100)).unwrap();
+    assert_eq!(chain.account_balance_available(helpers::ACC_0), Some(Amount::from_ccd(12)));
+
+    chain.tick_block_time(Duration::from_millis(100)).unwrap();
+    assert_eq!(
+        chain.account_balance(helpers::ACC_0),
+        Some(AccountBalance {
+            total:  Amount::from_ccd(16),
+            staked: Amount::from_ccd(4),
+            locked: Amount::zero(),
+        })
+    );
+    assert!(chain.get_account(helpers::ACC_0).unwrap().release_schedule.is_empty());
+}
@@ -0,0 +1,133 @@
+//! This module contains tests for test accounts with keys derived from a seed,
+//! and for checking their signatures in contracts.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+/// Deploy and initialize the contract that checks account signatures.
+fn setup(chain: &mut Chain, account: AccountAddress) -> ContractAddress {
+    let res_deploy = chain
+        .module_deploy_v1(
+            Signer::with_one_key(),
+            account,
+            module_load_v1_raw(helpers::wasm_test_file("account-signature-checks.wasm"))
+                .expect("module should exist"),
+        )
+        .expect("Deploying valid module should work");
+
+    chain
+        .contract_init(Signer::with_one_key(), account, Energy::from(10000), InitContractPayload {
+            init_name: OwnedContractName::new_unchecked("init_contract".into()),
+            mod_ref:   res_deploy.module_reference,
+            param:     OwnedParameter::empty(),
+            amount:    Amount::zero(),
+        })
+        .expect("Initializing valid contract should work")
+        .contract_address
+}
+
+/// Check the signatures on the data with the contract and return the result
+/// code, which is 0 if the signatures are valid.
+fn check_signature(
+    chain: &Chain,
+    contract: ContractAddress,
+    account: AccountAddress,
+    data: &[u8],
+    signatures: &AccountSignatures,
+) -> u64 {
+    let res = chain
+        .contract_invoke(
+            account,
+            Address::Account(account),
+            Energy::from(100000),
+            UpdateContractPayload {
+                address:      contract,
+                receive_name: OwnedReceiveName::new_unchecked("contract.check_signature".into()),
+                message:      OwnedParameter::from_serial(&(account, data.to_vec(), signatures))
+                    .expect("Enough space."),
+                amount:       Amount::zero(),
+            },
+        )
+        .expect("Querying contract should work");
+    from_bytes::<u64>(&res.return_value).expect("Return value should be deserializable.")
+}
+
+#[test]
+fn test_deterministic_keys() {
+    let alice = TestAccount::from_seed("alice");
+    let alice_again = TestAccount::from_seed("alice");
+    let bob = TestAccount::from_seed("bob");
+
+    assert_eq!(alice.address(), alice_again.address());
+    assert_eq!(alice.access_structure(), alice_again.access_structure());
+    assert_ne!(alice.address(), bob.address());
+    assert_ne!(alice.access_structure(), bob.access_structure());
+}
+
+#[test]
+fn test_sign_message_single_key() {
+    let mut chain = Chain::new();
+    let alice = TestAccount::from_seed("alice");
+    let bob = TestAccount::from_seed("bob");
+    chain.create_account(alice.account(Amount::from_ccd(1000)));
+    chain.create_account(bob.account(Amount::from_ccd(1000)));
+    let contract = setup(&mut chain, alice.address());
+
+    let data = b"permit message hash";
+    let signatures = alice.sign_message(data);
+    assert_eq!(signatures.num_signatures(), 1);
+    assert_eq!(check_signature(&chain, contract, alice.address(), data, &signatures), 0);
+    // The signatures are not valid for another message or another account.
+    assert_ne!(check_signature(&chain, contract, alice.address(), b"other", &signatures), 0);
+    assert_ne!(check_signature(&chain, contract, bob.address(), data, &signatures), 0);
+}
+
+#[test]
+fn test_sign_message_thresholds() {
+    let mut chain = Chain::new();
+    let alice = TestAccount::from_seed_with_credentials("alice", AccountThreshold::TWO, &[
+        (CredentialIndex::from(0), SignatureThreshold::ONE, &[KeyIndex(0)]),
+        (CredentialIndex::from(3), SignatureThreshold::TWO, &[
+            KeyIndex(1),
+            KeyIndex(4),
+            KeyIndex(7),
+        ]),
+    ]);
+    chain.create_account(alice.account(Amount::from_ccd(1000)));
+    let contract = setup(&mut chain, alice.address());
+
+    let data = b"permit message hash";
+    let signatures = alice.sign_message(data);
+    assert_eq!(signatures.num_signatures(), 4);
+    assert_eq!(check_signature(&chain, contract, alice.address(), data, &signatures), 0);
+
+    // Exactly meeting the thresholds is sufficient.
+    let signatures = alice.sign_message_with_keys(data, &[
+        (CredentialIndex::from(0), KeyIndex(0)),
+        (CredentialIndex::from(3), KeyIndex(1)),
+        (CredentialIndex::from(3), KeyIndex(7)),
+    ]);
+    assert_eq!(signatures.num_signatures(), 3);
+    assert_eq!(check_signature(&chain, contract, alice.address(), data, &signatures), 0);
+
+    // The second credential does not meet its threshold.
+    let signatures = alice.sign_message_with_keys(data, &[
+        (CredentialIndex::from(0), KeyIndex(0)),
+        (CredentialIndex::from(3), KeyIndex(4)),
+    ]);
+    assert_ne!(check_signature(&chain, contract, alice.address(), data, &signatures), 0);
+}
+
+#[test]
+fn test_signatures_in_contracts_common_format() {
+    let alice = TestAccount::from_seed("alice");
+    let signatures = alice.sign_message(b"message");
+    let converted = contracts_common_signatures(signatures.clone());
+    // Both representations have the same serialization.
+    assert_eq!(to_bytes(&signatures), to_bytes(&converted));
+}
+
+fn contracts_common_signatures(
+    signatures: AccountSignatures,
+) -> concordium_rust_sdk::base::contracts_common::AccountSignatures {
+    signatures.into()
+}
@@ -0,0 +1,110 @@
+//! This module tests checking the compatibility of contract upgrades.
+use concordium_smart_contract_testing::*;
+mod helpers;
+
+fn chain() -> Chain {
+    let mut chain = Chain::new();
+    chain.create_account(Account::new(helpers::ACC_0, Amount::from_ccd(1000000)));
+    chain
+}
+
+fn load(file_name: &str) -> WasmModule {
+    module_load_v1_raw(helpers::wasm_test_file(file_name)).expect("module should exist")
+}
+
+/// The parameter of the upgrade entrypoints in the test modules is just the
+/// module reference.
+fn upgrade_parameter(module: ModuleReference) -> OwnedParameter {
+    OwnedParameter::from_serial(&module).expect("Parameter has valid size")
+}
+
+#[test]
+fn test_compatible_upgrade() {
+    let chain = chain();
+    let report = UpgradeCheck::new(
+        load("upgrading_0.wasm"),
+        load("upgrading_1.wasm"),
+        ContractName::new_unchecked("init_a"),
+    )
+    .upgrade_entrypoint(OwnedEntrypointName::new_unchecked("bump".into()))
+    .upgrade_parameter(upgrade_parameter)
+    .runs(2)
+    .run(&chain, helpers::ACC_0)
+    .expect("Check should run");
+
+    assert!(report
+        .added_entrypoints
+        .contains(&OwnedEntrypointName::new_unchecked("newfun".into())));
+    assert!(!report
+        .findings
+        .iter()
+        .any(|finding| matches!(finding, UpgradeFinding::UpgradeFailed { .. })));
+    // The chain passed to the check is not modified.
+    assert!(chain.contracts.is_empty());
+}
+
+#[test]
+fn test_failed_upgrade() {
+    let report = UpgradeCheck::new(
+        load("upgrading-missing-contract0.wasm"),
+        load("upgrading-missing-contract1.wasm"),
+        ContractName::new_unchecked("init_contract"),
+    )
+    .upgrade_parameter(upgrade_parameter)
+    .run(&chain(), helpers::ACC_0)
+    .expect("Check should run");
+
+    assert!(!report.is_compatible());
+    assert!(report.findings.iter().any(|finding| matches!(
+        finding,
+        UpgradeFinding::UpgradeFailed {
+            run: 0,
+            ..
+        }
+    )));
+    assert!(report.findings.contains(&UpgradeFinding::RemovedEntrypoint {
+        entrypoint: OwnedEntrypointName::new_unchecked("upgrade".into()),
+    }));
+}
+
+#[test]
+fn test_populate_error() {
+    let err = UpgradeCheck::new(
+        load("upgrading_0.wasm"),
+        load("upgrading_1.wasm"),
+        ContractName::new_unchecked("init_a"),
+    )
+    .populate(|_, _| Err("no transactions".into()))
+    .run(&chain(), helpers::ACC_0)
+    .expect_err("Populate script should fail");
+    assert!(matches!(err, UpgradeCheckError::Populate {
+        run: 0,
+        ..
+    }));
+}
+
+#[test]
+fn test_modules_already_deployed() {
+    let mut chain = chain();
+    chain
+        .module_deploy_v1(Signer::with_one_key(), helpers::ACC_0, load("upgrading_0.wasm"))
+        .expect("Deploying valid module should succeed");
+    chain
+        .module_deploy_v1(Signer::with_one_key(), helpers::ACC_0, load("upgrading_1.wasm"))
+        .expect("Deploying valid module should succeed");
+
+    let report = UpgradeCheck::new(
+        load("upgrading_0.wasm"),
+        load("upgrading_1.wasm"),
+        ContractName::new_unchecked("init_a"),
+    )
+    .upgrade_entrypoint(OwnedEntrypointName::new_unchecked("bump".into()))
+    .upgrade_parameter(upgrade_parameter)
+    .run(&chain, helpers::ACC_0)
+    .expect("Check should reuse the deployed modules");
+
+    assert!(!report
+        .findings
+        .iter()
+        .any(|finding| matches!(finding, UpgradeFinding::UpgradeFailed { .. })));
+}
@@ -285,6 +285,90 @@ fn test_operator_can_transfer() {
     ]);
 }
 
+/// Test that the contract passes the CIS-2 conformance suite.
+#[test]
+fn test_cis2_conformance() {
+    let (chain, contract_address) = initialize_chain_and_contract();
+
+    // Only `TOKEN_0` is minted, so `TOKEN_1` is unknown to the contract.
+    let token_id = TOKEN_0.0.to_le_bytes().to_vec();
+    let report = Cis2Conformance::new(contract_address, token_id, |chain, owner| {
+        mint(chain, contract_address, Address::Account(owner))
+    })
+    .unknown_token_id(TOKEN_1.0.to_le_bytes().to_vec())
+    .run(&chain)
+    .expect("The contract exists");
+
+    assert!(report.passed(), "{report}");
+    assert_eq!(report.outcome(Cis2Rule::TransferToContract), Some(&Cis2RuleOutcome::Skipped));
+}
+
+/// Test that the CIS-2 conformance suite detects tokens that are minted
+/// without logging mint events. The mint hook mints to Alice, and returns the
+/// transfer to the owner, which does not log a mint event.
+#[test]
+fn test_cis2_conformance_missing_mint_event() {
+    let (chain, contract_address) = initialize_chain_and_contract();
+
+    let token_id = TOKEN_0.0.to_le_bytes().to_vec();
+    let report = Cis2Conformance::new(contract_address, token_id, |chain, owner| {
+        mint(chain, contract_address, ALICE_ADDR)?;
+        transfer_from_alice(chain, contract_address, owner)
+    })
+    .unknown_token_id(TOKEN_1.0.to_le_bytes().to_vec())
+    .run(&chain)
+    .expect("The contract exists");
+
+    assert!(!report.passed());
+    for (rule, outcome) in &report.results {
+        match rule {
+            Cis2Rule::MintEvent => {
+                assert!(matches!(outcome, Cis2RuleOutcome::Failed(_)), "{rule:?}: {outcome:?}")
+            }
+            _ => assert!(!matches!(outcome, Cis2RuleOutcome::Failed(_)), "{rule:?}: {outcome:?}"),
+        }
+    }
+}
+
+/// Helper function that mints `TOKEN_0` to the `owner`.
+fn mint(
+    chain: &mut Chain,
+    contract_address: ContractAddress,
+    owner: Address,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    let mint_params = MintParams {
+        owner,
+        tokens: BTreeSet::from_iter(vec![TOKEN_0]),
+    };
+    chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10000), UpdateContractPayload {
+        amount:       Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("cis2_nft.mint".to_string()),
+        address:      contract_address,
+        message:      OwnedParameter::from_serial(&mint_params).expect("Mint params"),
+    })
+}
+
+/// Helper function that transfers `TOKEN_0` from Alice to the account `to`.
+fn transfer_from_alice(
+    chain: &mut Chain,
+    contract_address: ContractAddress,
+    to: AccountAddress,
+) -> Result<ContractInvokeSuccess, ContractInvokeError> {
+    let transfer_params = TransferParams::from(vec![concordium_cis2::Transfer {
+        from:     ALICE_ADDR,
+        to:       Receiver::Account(to),
+        token_id: TOKEN_0,
+        amount:   TokenAmountU8(1),
+        data:     AdditionalData::empty(),
+    }]);
+    chain.contract_update(SIGNER, ALICE, ALICE_ADDR, Energy::from(10000), UpdateContractPayload {
+        amount:       Amount::zero(),
+        receive_name: OwnedReceiveName::new_unchecked("cis2_nft.transfer".to_string()),
+        address:      contract_address,
+        message:      OwnedParameter::from_serial(&transfer_params).expect("Transfer params"),
+    })
+}
+
 /// Helper function that sets up the contract with two tokens minted to
 /// Alice, `TOKEN_0` and `TOKEN_1`.
 fn initialize_contract_with_alice_tokens() -> (Chain, ContractAddress, ContractInvokeSuccess) {